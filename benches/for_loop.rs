@@ -0,0 +1,21 @@
+use criterion::*;
+use ion_shell::Shell;
+
+const ITERATIONS: u64 = 10_000;
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let script = format!("for i in 0..{}\nend\n", ITERATIONS);
+    let mut shell = Shell::new();
+
+    let mut group = c.benchmark_group("for_loop_throughput");
+
+    group.throughput(Throughput::Elements(ITERATIONS));
+    group.bench_function("range", |b| {
+        b.iter(|| shell.execute_command(script.as_bytes()).unwrap())
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -34,11 +34,15 @@ pub fn builtin(attr: TokenStream, item: TokenStream) -> TokenStream {
     };
 
     let (man, help, name) = build_man_page(&ident, args);
+    let (man_name, man_doc) = man_page_const(&ident);
 
     let result = quote! {
+        #[doc = #man_doc]
+        #vis const #man_name: &str = #man;
+
         #[doc = #help]
         #vis #fn_token #name(#inputs) #output {
-            if ion_shell::builtins::man_pages::check_help(args, #man) {
+            if ion_shell::builtins::man_pages::check_help(args, #man_name) {
                 return ion_shell::builtins::Status::SUCCESS;
             }
             #block
@@ -63,11 +67,15 @@ pub fn builtin_interactive(attr: TokenStream, item: TokenStream) -> TokenStream
     };
 
     let (man, help, name) = build_man_page(&ident, args);
+    let (man_name, man_doc) = man_page_const(&ident);
 
     let result = quote! {
+        #[doc = #man_doc]
+        #vis const #man_name: &str = #man;
+
         #[doc = #help]
         #vis #fn_token #name(#inputs) #output {
-            const HELP_PAGE: &str = #man;
+            const HELP_PAGE: &str = #man_name;
             #block
         }
     };
@@ -75,6 +83,15 @@ pub fn builtin_interactive(attr: TokenStream, item: TokenStream) -> TokenStream
     result.into()
 }
 
+/// The name of the constant holding the man page of the builtin, such as `MAN_ECHO` for `echo`
+/// or `MAN_FN` for `fn_`, with its documentation
+fn man_page_const(ident: &Ident) -> (Ident, String) {
+    let name = ident.to_string();
+    let name = name.trim_end_matches('_');
+    let doc = format!("The man page of the `{}` builtin, printed by `help` and `--help`", name);
+    (quote::format_ident!("MAN_{}", name.to_uppercase(), span = ident.span()), doc)
+}
+
 fn build_man_page(ident: &Ident, args: MacroArgs) -> (String, String, Ident) {
     let name = quote::format_ident!("builtin_{}", &ident, span = ident.span(),);
 
@@ -0,0 +1,101 @@
+/// A range of decimal numbers, such as `0.0..0.25..1.0`. Each value is computed as
+/// `start + index * step` rather than by adding the step repeatedly, and rounded to the number of
+/// decimals written in the bounds and the step, so that rounding errors do not accumulate.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FloatRange {
+    start:     f64,
+    step:      f64,
+    end:       f64,
+    inclusive: bool,
+    decimals:  i32,
+    index:     u32,
+}
+
+impl FloatRange {
+    /// The difference between two values of the range, which may be zero
+    pub const fn step(&self) -> f64 { self.step }
+
+    fn value(&self, index: u32) -> f64 {
+        let scale = 10f64.powi(self.decimals);
+        // Adding zero turns a negative zero into a positive one
+        ((self.start + f64::from(index) * self.step) * scale).round() / scale + 0.0
+    }
+}
+
+impl Iterator for FloatRange {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        if self.step == 0.0 {
+            return None;
+        }
+        let value = self.value(self.index);
+        let in_range = match (self.step > 0.0, self.inclusive) {
+            (true, true) => value <= self.end,
+            (true, false) => value < self.end,
+            (false, true) => value >= self.end,
+            (false, false) => value > self.end,
+        };
+        if in_range {
+            self.index = self.index.checked_add(1)?;
+            Some(format!("{:?}", value))
+        } else {
+            None
+        }
+    }
+}
+
+/// Parses a decimal number made of digits and at most one dot, returning it with the number of
+/// digits after the dot
+fn decimal(input: &str) -> Option<(f64, i32)> {
+    let digits = input.strip_prefix('-').unwrap_or(input);
+    let mut dots = digits.match_indices('.');
+    let decimals = match (dots.next(), dots.next()) {
+        (None, _) => 0,
+        (Some((dot, _)), None) => digits.len() - dot - 1,
+        (Some(_), Some(_)) => return None,
+    };
+    if digits == "." || !digits.bytes().all(|c| c.is_ascii_digit() || c == b'.') {
+        return None;
+    }
+    Some((input.parse().ok()?, decimals as i32))
+}
+
+// A range of decimal numbers uses the same syntax as the integer ranges, where at least one of
+// the numbers has a decimal point:
+//      Exclusive nonstepped: start..end
+//      Inclusive nonstepped: start...end
+//      Exclusive stepped: start..step..end
+//      Inclusive stepped: start..step...end
+// A range whose step goes away from the end is not a range, as for integers.
+pub fn parse_float_range(input: &str) -> Option<FloatRange> {
+    let mut parts = input.split("..").collect::<Vec<_>>();
+    let len = parts.len();
+
+    let inclusive = parts.last()?.starts_with(|c| c == '.' || c == '=');
+    if inclusive {
+        parts[len - 1] = &parts[len - 1][1..];
+    }
+    if !parts.iter().any(|part| part.contains('.')) {
+        return None;
+    }
+
+    let (start, step, end) = match parts.as_slice() {
+        [start, end] => (decimal(start)?, None, decimal(end)?),
+        [start, step, end] => (decimal(start)?, Some(decimal(step)?), decimal(end)?),
+        _ => return None,
+    };
+    let step = step.unwrap_or(if start.0 > end.0 { (-1.0, 0) } else { (1.0, 0) });
+    if (start.0 < end.0 && step.0 < 0.0) || (start.0 > end.0 && step.0 > 0.0) {
+        return None;
+    }
+
+    Some(FloatRange {
+        start: start.0,
+        step: step.0,
+        end: end.0,
+        inclusive,
+        decimals: start.1.max(step.1).max(end.1),
+        index: 0,
+    })
+}
@@ -1,9 +1,10 @@
+mod float;
 mod index;
 mod parse;
 mod range;
 mod select;
 
-pub use self::{index::*, parse::*, range::*, select::*};
+pub use self::{float::*, index::*, parse::*, range::*, select::*};
 
 #[cfg(test)]
 mod tests {
@@ -107,4 +108,40 @@ mod tests {
 
         assert_eq!(actual, expected);
     }
+
+    fn float_range(range: &str) -> Vec<String> { parse_float_range(range).unwrap().collect() }
+
+    #[test]
+    fn float_ranges() {
+        assert_eq!(float_range("0.0..0.25..1.0"), ["0.0", "0.25", "0.5", "0.75"]);
+        assert_eq!(float_range("0.0..0.25...1.0"), ["0.0", "0.25", "0.5", "0.75", "1.0"]);
+        assert_eq!(float_range("0.5..3"), ["0.5", "1.5", "2.5"]);
+        assert_eq!(float_range("1.5..=-0.5"), ["1.5", "0.5", "-0.5"]);
+        assert_eq!(float_range("1.0..-0.5..-1.0"), ["1.0", "0.5", "0.0", "-0.5"]);
+
+        // The boundaries are exact, despite 0.1 not being representable
+        assert_eq!(float_range("0.0..0.1..0.3"), ["0.0", "0.1", "0.2"]);
+        assert_eq!(float_range("0.0..0.1...0.3"), ["0.0", "0.1", "0.2", "0.3"]);
+        assert_eq!(float_range("0..0.1...1").len(), 11);
+        assert_eq!(float_range("0..0.1...1").last().unwrap(), "1.0");
+
+        let zero = parse_float_range("0.0..0.0..1.0").unwrap();
+        assert_eq!(zero.step(), 0.0);
+        assert_eq!(zero.count(), 0);
+
+        for invalid in &["0..3", "0.0..1.0..2.0..3.0", "a.0..1.0", "0.0..0.5..-1.0", "1..2.0.0"] {
+            assert_eq!(parse_float_range(invalid), None);
+        }
+    }
+
+    #[test]
+    fn selections() {
+        assert_eq!("..".parse::<Select<String>>(), Ok(Select::All));
+        assert_eq!("-2".parse::<Select<String>>(), Ok(Select::Index(Index::Backward(1))));
+        assert_eq!("#".parse::<Select<String>>(), Ok(Select::Length));
+        assert_eq!("key".parse::<Select<String>>(), Ok(Select::Key("key".into())));
+
+        let length: Vec<_> = vec![1, 2, 3].into_iter().select(&Select::<String>::Length, 3);
+        assert!(length.is_empty());
+    }
 }
@@ -15,6 +15,8 @@ pub enum Select<K> {
     Range(Range),
     /// Select an element by mapped key
     Key(K),
+    /// Select the number of elements rather than an element: `[#]`
+    Length,
 }
 
 pub trait SelectWithSize {
@@ -35,7 +37,8 @@ where
         O: FromIterator<Self::Item>,
     {
         match s {
-            Select::Key(_) => empty().collect(),
+            // The length is not one of the elements, and must be expanded by the caller
+            Select::Key(_) | Select::Length => empty().collect(),
             Select::All => self.collect(),
             Select::Index(Index::Forward(idx)) => self.nth(*idx).into_iter().collect(),
             Select::Index(Index::Backward(idx)) => self.rev().nth(*idx).into_iter().collect(),
@@ -53,6 +56,8 @@ impl<K: FromStr> FromStr for Select<K> {
     fn from_str(data: &str) -> Result<Self, ()> {
         if data == ".." {
             Ok(Select::All)
+        } else if data == "#" {
+            Ok(Select::Length)
         } else if let Ok(index) = data.parse::<isize>() {
             Ok(Select::Index(Index::new(index)))
         } else if let Some(range) = parse_index_range(data) {
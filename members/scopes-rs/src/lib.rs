@@ -1,6 +1,6 @@
 use std::{
     borrow::Borrow,
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     hash::Hash,
     ops::{Deref, DerefMut},
 };
@@ -24,6 +24,25 @@ pub struct Scope<K: Hash + Eq, V> {
     /// This scope is on a namespace boundary.
     /// Any previous scopes need to be accessed through `super::`.
     namespace: bool,
+    /// The variables of this scope being watched for changes
+    watched:   HashSet<K>,
+}
+
+impl<K: Hash + Eq, V> Scope<K, V> {
+    /// Whether this scope is on a namespace boundary, such as the scope of a function call
+    pub const fn is_namespace(&self) -> bool { self.namespace }
+
+    /// Whether the variable `name` of this scope is watched
+    pub fn is_watched<Q: ?Sized>(&self, name: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq,
+    {
+        self.watched.contains(name)
+    }
+
+    /// The variables watched in this scope
+    pub fn watched(&self) -> impl Iterator<Item = &K> { self.watched.iter() }
 }
 
 impl<K: Hash + Eq, V> Deref for Scope<K, V> {
@@ -39,7 +58,11 @@ impl<K: Hash + Eq, V> DerefMut for Scope<K, V> {
 impl<K: Hash + Eq, V: Clone> Scopes<K, V> {
     pub fn with_capacity(cap: usize) -> Self {
         Self {
-            scopes:  vec![Scope { vars: HashMap::with_capacity(cap), namespace: false }],
+            scopes:  vec![Scope {
+                vars:      HashMap::with_capacity(cap),
+                namespace: false,
+                watched:   HashSet::new(),
+            }],
             current: 0,
         }
     }
@@ -47,14 +70,20 @@ impl<K: Hash + Eq, V: Clone> Scopes<K, V> {
     pub fn new_scope(&mut self, namespace: bool) {
         self.current += 1;
         if self.current >= self.scopes.len() {
-            self.scopes.push(Scope { vars: HashMap::with_capacity(64), namespace });
+            self.scopes.push(Scope {
+                vars: HashMap::with_capacity(64),
+                namespace,
+                watched: HashSet::new(),
+            });
         } else {
             self.scopes[self.current].namespace = namespace;
         }
     }
 
     pub fn pop_scope(&mut self) {
-        self.scopes[self.current].clear();
+        let scope = &mut self.scopes[self.current];
+        scope.vars.clear();
+        scope.watched.clear();
         self.current -= 1;
     }
 
@@ -93,6 +122,63 @@ impl<K: Hash + Eq, V: Clone> Scopes<K, V> {
         None
     }
 
+    /// The index of the scope in which the variable `name` can be modified, which is the nearest
+    /// scope defining it without crossing a namespace boundary
+    fn writable_index<Q: ?Sized>(&self, name: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq,
+    {
+        for (i, scope) in self.scopes[..=self.current].iter().enumerate().rev() {
+            if scope.contains_key(name) {
+                return Some(i);
+            }
+            if scope.namespace {
+                break;
+            }
+        }
+        None
+    }
+
+    /// The scope in which the variable `name` can be modified, if it is defined
+    pub fn writable_scope_mut<Q: ?Sized>(&mut self, name: &Q) -> Option<&mut Scope<K, V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq,
+    {
+        let index = self.writable_index(name)?;
+        Some(&mut self.scopes[index])
+    }
+
+    /// The scope in which the variable `name` is set: the scope in which it can be modified if
+    /// it is defined, or else the current scope
+    pub fn scope_to_set_mut<Q: ?Sized>(&mut self, name: &Q) -> &mut Scope<K, V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq,
+    {
+        let index = self.writable_index(name).unwrap_or(self.current);
+        &mut self.scopes[index]
+    }
+
+    /// Watch the variable `name` in the nearest scope defining it, or in the current scope if it
+    /// is not defined. The watch ends with the scope.
+    pub fn watch(&mut self, name: K) {
+        let index = self.index_scope_for_var(&name).unwrap_or(self.current);
+        self.scopes[index].watched.insert(name);
+    }
+
+    /// Stop watching the variable `name` in every scope
+    pub fn unwatch<Q: ?Sized>(&mut self, name: &Q)
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq,
+    {
+        for scope in self.scopes_mut() {
+            scope.watched.remove(name);
+        }
+    }
+
     pub fn set<T: Into<K>, S: Into<V>>(&mut self, name: T, value: S) -> Option<V> {
         self.scopes[self.current].insert(name.into(), value.into())
     }
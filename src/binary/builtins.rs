@@ -1,4 +1,9 @@
-use ion_shell::{builtin, builtins::Status, types::Str, Shell, Signal};
+use ion_shell::{
+    builtin,
+    builtins::{env_command_environment, Status},
+    types::Str,
+    Shell, Signal,
+};
 use nix::{sys::signal, unistd::Pid};
 use std::{os::unix::process::CommandExt, process::Command};
 
@@ -44,19 +49,19 @@ pub fn debug(args: &[Str], shell: &mut Shell<'_>) -> Status {
     desc = "exit the shell",
     man = "
 SYNOPSIS
-    exit
+    exit [STATUS]
 
 DESCRIPTION
-    Makes ion exit. The exit status will be that of the last command executed."
+    Makes ion exit with STATUS, or with the status of the last command executed. The background
+    jobs are terminated first. When an exit handler is set, such as the one printing the profile
+    of --profile, the execution stops and the handler is called instead."
 )]
 pub fn exit(args: &[Str], shell: &mut Shell<'_>) -> Status {
-    // Kill all active background tasks before exiting the shell.
-    shell.background_send(Signal::SIGTERM).expect("Could not terminate background jobs");
-    let exit_code = args
+    let status = args
         .get(1)
         .and_then(|status| status.parse::<i32>().ok())
-        .unwrap_or_else(|| shell.previous_status().as_os_code());
-    std::process::exit(exit_code);
+        .map_or_else(|| shell.previous_status(), Status::from_exit_code);
+    shell.exit(status)
 }
 
 #[builtin(
@@ -70,6 +75,10 @@ DESCRIPTION
     The <arguments> following the command become the arguments to
     <command>.
 
+    When <command> is env, its options and NAME=VALUE arguments are applied
+    by the shell, which then executes the command given to env directly, as
+    the env builtin would.
+
 OPTIONS
     -c  Execute command with an empty environment."
 )]
@@ -84,12 +93,33 @@ pub fn exec(args: &[Str], _shell: &mut Shell<'_>) -> Status {
         idx += 1;
     }
 
+    let mut environment = None;
+    if args.get(idx).map(Str::as_str) == Some("env") {
+        // An empty environment is the same as `env -i`
+        let mut env_args = vec![Str::from("env")];
+        if clear_env {
+            env_args.push("-i".into());
+        }
+        env_args.extend_from_slice(&args[idx + 1..]);
+        match env_command_environment(&env_args) {
+            // Without a command, the external env prints the environment
+            Ok((_, start)) if start == env_args.len() => (),
+            Ok((variables, start)) => {
+                environment = Some(variables);
+                idx = args.len() - (env_args.len() - start);
+            }
+            Err(status) => return status,
+        }
+    }
+
     match args.get(idx) {
         Some(argument) => {
             let args = if args.len() > idx + 1 { &args[idx + 1..] } else { &[] };
             let mut command = Command::new(argument.as_str());
             command.args(args.iter().map(Str::as_str));
-            if clear_env {
+            if let Some(environment) = environment {
+                command.env_clear().envs(environment);
+            } else if clear_env {
                 command.env_clear();
             }
             Status::error(format!("ion: exec: {}", command.exec().to_string()))
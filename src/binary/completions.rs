@@ -0,0 +1,254 @@
+//! Generates the scripts completing the options of ion in other shells, printed by
+//! `ion --generate-completion SHELL`. They are built from the options documented by `ion --help`
+//! and from the names of the builtins, so that they do not need to be maintained separately.
+
+use super::{manpage, MAN_ION};
+use ion_shell::BuiltinMap;
+use std::{fmt::Write, str::FromStr};
+
+/// A shell for which a completion script can be generated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompletionShell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl FromStr for CompletionShell {
+    type Err = ();
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "bash" => Ok(Self::Bash),
+            "zsh" => Ok(Self::Zsh),
+            "fish" => Ok(Self::Fish),
+            _ => Err(()),
+        }
+    }
+}
+
+/// An option of ion, as documented by `ion --help`
+#[derive(Debug, PartialEq)]
+struct Flag<'a> {
+    /// The names of the option, such as `-f` and `--fake-interactive`
+    names:       Vec<&'a str>,
+    /// The values the option takes, such as `<command>`, of which there are several when the
+    /// option has different meanings
+    values:      Vec<&'a str>,
+    description: String,
+}
+
+/// The completions of a value: some words, and possibly file names
+#[derive(Debug, Default, PartialEq)]
+struct Values<'a> {
+    words: Vec<&'a str>,
+    files: bool,
+}
+
+/// The flags and options of `ion --help`, whose names are separated by commas and followed by the
+/// value they take, if any. The description of an option is separated from it by at least two
+/// spaces, or starts on the next line, and continues on the indented lines. The options
+/// documented twice are merged.
+fn flags() -> Vec<Flag<'static>> {
+    let mut flags: Vec<Flag<'_>> = Vec::new();
+    // Whether the indented lines continue the description of the last option
+    let mut continued = false;
+    let sections = manpage::sections(MAN_ION);
+    let lines = sections
+        .iter()
+        .filter(|(heading, _)| ["FLAGS", "OPTIONS"].contains(heading))
+        .flat_map(|(_, lines)| lines.iter().copied())
+        .filter(|line| !line.trim().is_empty());
+    for line in lines {
+        if line.starts_with(char::is_whitespace) {
+            if let Some(flag) = flags.last_mut().filter(|_| continued) {
+                if !flag.description.is_empty() {
+                    flag.description.push(' ');
+                }
+                flag.description.push_str(line.trim());
+            }
+            continue;
+        }
+        let (term, description) = match line.find("  ") {
+            Some(index) => (&line[..index], line[index..].trim_start()),
+            None => (line, ""),
+        };
+        let mut names = term.split(", ").collect::<Vec<_>>();
+        let value = names.last_mut().and_then(|last| {
+            let (name, value) = last.split_at(last.find(' ')?);
+            *last = name;
+            Some(value.trim_start())
+        });
+        // The description of the first meaning of an option is kept
+        continued = match flags.iter_mut().find(|flag| flag.names == names) {
+            Some(flag) => {
+                flag.values.extend(value);
+                false
+            }
+            None => {
+                let description = description.to_owned();
+                flags.push(Flag { names, values: value.into_iter().collect(), description });
+                true
+            }
+        };
+    }
+    flags
+}
+
+/// How the values of an option are completed. The command given to `-c` is completed with the
+/// names of the builtins.
+fn values<'a>(flag: &Flag<'_>, builtins: &[&'a str]) -> Values<'a> {
+    let mut values = Values::default();
+    for value in &flag.values {
+        match *value {
+            "<key-bindings>" => values.words.extend_from_slice(&["vi", "emacs"]),
+            "<shell>" => values.words.extend_from_slice(&["bash", "zsh", "fish"]),
+            "<command>" => values.words.extend_from_slice(builtins),
+            _ => values.files = true,
+        }
+    }
+    values
+}
+
+fn bash(flags: &[Flag<'_>], builtins: &[&str]) -> String {
+    let mut script = String::from(
+        "_ion() {\n    local cur=\"${COMP_WORDS[COMP_CWORD]}\" \
+         prev=\"${COMP_WORDS[COMP_CWORD-1]}\"\n    case \"$prev\" in\n",
+    );
+    for flag in flags.iter().filter(|flag| !flag.values.is_empty()) {
+        let values = values(flag, builtins);
+        let mut compgen = String::from("compgen");
+        if !values.words.is_empty() {
+            let _ = write!(compgen, " -W '{}'", values.words.join(" "));
+        }
+        if values.files {
+            compgen.push_str(" -f");
+        }
+        let _ = writeln!(
+            script,
+            "        {})\n            COMPREPLY=($({} -- \"$cur\"))\n            return\n            \
+             ;;",
+            flag.names.join("|"),
+            compgen
+        );
+    }
+    let names = flags.iter().flat_map(|flag| flag.names.iter().copied()).collect::<Vec<_>>();
+    let _ = write!(
+        script,
+        "    esac\n    if [[ \"$cur\" == -* ]]; then\n        COMPREPLY=($(compgen -W '{}' -- \
+         \"$cur\"))\n    else\n        COMPREPLY=($(compgen -f -- \"$cur\"))\n    fi\n}}\n\
+         complete -o filenames -F _ion ion\n",
+        names.join(" ")
+    );
+    script
+}
+
+fn zsh(flags: &[Flag<'_>], builtins: &[&str]) -> String {
+    let mut script = String::from("#compdef ion\n\n_ion() {\n    _arguments -s \\\n");
+    for flag in flags {
+        let description =
+            flag.description.replace('\'', "'\\''").replace('[', "\\[").replace(']', "\\]");
+        let names = match flag.names.as_slice() {
+            [name] => format!("'{}", name),
+            names => format!("'({})'{{{}}}'", names.join(" "), names.join(",")),
+        };
+        let action = match flag.values.first() {
+            None => String::new(),
+            Some(value) => {
+                let values = values(flag, builtins);
+                let words = format!("({})", values.words.join(" "));
+                let action = match (values.words.is_empty(), values.files) {
+                    (true, _) => "_files".to_owned(),
+                    (false, false) => words,
+                    (false, true) => {
+                        format!("_alternative \"values:value:{}\" \"files:file:_files\"", words)
+                    }
+                };
+                format!(":{}:{}", value.trim_matches(&['<', '>'][..]), action)
+            }
+        };
+        let _ = writeln!(script, "        {}[{}]{}' \\", names, description, action);
+    }
+    script.push_str("        '*::argument:_files'\n}\n\n_ion \"$@\"\n");
+    script
+}
+
+fn fish(flags: &[Flag<'_>], builtins: &[&str]) -> String {
+    let mut script = String::new();
+    for flag in flags {
+        script.push_str("complete -c ion");
+        for name in &flag.names {
+            match name.strip_prefix("--") {
+                Some(long) => {
+                    let _ = write!(script, " -l {}", long);
+                }
+                None => {
+                    let _ = write!(script, " -s {}", name.trim_start_matches('-'));
+                }
+            }
+        }
+        if !flag.values.is_empty() {
+            let values = values(flag, builtins);
+            script.push_str(if values.files { " -r" } else { " -x" });
+            if !values.words.is_empty() {
+                let _ = write!(script, " -a '{}'", values.words.join(" "));
+            }
+        }
+        let description = flag.description.replace('\\', "\\\\").replace('\'', "\\'");
+        let _ = writeln!(script, " -d '{}'", description);
+    }
+    script
+}
+
+/// The script completing the options of ion for `shell`, with the values they take. The command
+/// given to `-c` is completed with the names of `builtins`.
+pub fn generate(shell: CompletionShell, builtins: &BuiltinMap<'_>) -> String {
+    let mut names = builtins.keys().collect::<Vec<_>>();
+    names.sort_unstable();
+    let flags = flags();
+    match shell {
+        CompletionShell::Bash => bash(&flags, &names),
+        CompletionShell::Zsh => zsh(&flags, &names),
+        CompletionShell::Fish => fish(&flags, &names),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn documented_flags() {
+        let flags = flags();
+        let flag = |name| flags.iter().find(|flag| flag.names.contains(&name)).unwrap();
+        assert_eq!(
+            flag("-f"),
+            &Flag {
+                names:       vec!["-f", "--fake-interactive"],
+                values:      vec![],
+                description: "Use a fake interactive mode, where errors don't exit the shell"
+                    .into(),
+            }
+        );
+        assert_eq!(flag("--compile").values, ["<script>"]);
+        assert_eq!(flag("-o").values, ["<key-bindings>", "<output>"]);
+        assert_eq!(flag("-o").description, "Shortcut layout. Valid options: \"vi\", \"emacs\"");
+        assert_eq!(flags.iter().filter(|flag| flag.names == ["-o"]).count(), 1);
+        assert_eq!(
+            flag("--generate-completion").description,
+            "Print the script completing the options of Ion in the given shell, which is bash, \
+             zsh or fish, then exit"
+        );
+    }
+
+    #[test]
+    fn value_completions() {
+        let flags = flags();
+        let flag = |name| flags.iter().find(|flag| flag.names.contains(&name)).unwrap();
+        assert_eq!(values(flag("-o"), &[]), Values { words: vec!["vi", "emacs"], files: true });
+        assert_eq!(
+            values(flag("-c"), &["cd", "echo"]),
+            Values { words: vec!["cd", "echo"], files: false }
+        );
+    }
+}
@@ -0,0 +1,193 @@
+//! The debugger of `ion --step`, which pauses before each statement of the script to let the
+//! user step through it, print variables and watch them change.
+
+use ion_shell::{
+    flow_control::{ExportAction, LocalAction, Statement},
+    Shell,
+};
+use itertools::Itertools;
+use std::{
+    cell::{Cell, RefCell},
+    io::{self, BufRead, Write},
+};
+
+/// The help of the commands of the debugger, printed by `h`
+const HELP: &str = "commands:
+    <enter>   repeat the last step, s or n
+    s         step to the next statement, into the functions
+    n         step to the next statement, over the functions
+    c         continue without stepping
+    q         quit the script
+    p NAME    print a variable
+    w NAME    print a variable after each step
+    h         print this help";
+
+/// How the execution goes on until the debugger pauses again
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stepping {
+    /// Pause before the next statement, even in a function
+    Into,
+    /// Pause before the next statement executed by a function at most as deep as the given one
+    Over(usize),
+    /// Never pause again
+    Continue,
+}
+
+/// The state of the debugger, shared by the statements of the script
+#[derive(Debug)]
+pub struct Debugger {
+    stepping: Cell<Stepping>,
+    /// The stepping repeated by an empty command
+    last:     Cell<Stepping>,
+    watches:  RefCell<Vec<String>>,
+}
+
+impl Default for Debugger {
+    fn default() -> Self {
+        Self {
+            stepping: Cell::new(Stepping::Into),
+            last:     Cell::new(Stepping::Into),
+            watches:  RefCell::new(Vec::new()),
+        }
+    }
+}
+
+/// The statement as written in the script, approximately: the expressions of the blocks are
+/// shown, but not the statements they contain
+fn describe(statement: &Statement) -> String {
+    let block = |keyword: &str, block: &[Statement]| {
+        format!("{} {}", keyword, block.iter().map(describe).join("; "))
+    };
+    match statement {
+        Statement::Let(LocalAction::List) => "let".into(),
+        Statement::Let(LocalAction::Assign(keys, operator, values)) => {
+            format!("let {} {} {}", keys, operator, values)
+        }
+        Statement::Export(ExportAction::List) => "export".into(),
+        Statement::Export(ExportAction::LocalExport(name)) => format!("export {}", name),
+        Statement::Export(ExportAction::Assign(keys, operator, values)) => {
+            format!("export {} {} {}", keys, operator, values)
+        }
+        Statement::If { expression, .. } => block("if", expression),
+        Statement::ElseIf(else_if) => block("else if", &else_if.expression),
+        Statement::While { expression, .. } => block("while", expression),
+        Statement::DoWhile { .. } => "do".into(),
+        Statement::For { variables, values, .. } => {
+            format!("for {} in {}", variables.iter().join(" "), values.iter().join(" "))
+        }
+        Statement::CStyleFor { init, condition, update, .. } => {
+            format!("for (( {}; {}; {} ))", init, condition, update)
+        }
+        Statement::WithEnv { bindings, .. } => format!(
+            "with {}",
+            bindings.iter().map(|(name, value)| format!("{}={}", name, value)).join(" ")
+        ),
+        Statement::Match { expression, .. } => format!("match {}", expression),
+        Statement::Function { name, .. } => format!("fn {}", name),
+        Statement::Pipeline(pipeline) => {
+            pipeline.items.iter().map(|item| item.job.args.iter().join(" ")).join(" | ")
+        }
+        Statement::Coproc { name, pipeline } => format!(
+            "coproc {} {}",
+            name,
+            pipeline.items.iter().map(|item| item.job.args.iter().join(" ")).join(" | ")
+        ),
+        Statement::Arithmetic(expression) => format!("(( {} ))", expression),
+        Statement::Return(Some(value)) => format!("return {}", value),
+        Statement::Return(None) => "return".into(),
+        Statement::Yield(value) => format!("yield {}", value),
+        Statement::Breakpoint(Some(condition)) => format!("breakpoint --condition {}", condition),
+        Statement::Breakpoint(None) => "breakpoint".into(),
+        Statement::Watch { name, command, .. } => format!("watch {} {{ {} }}", name, command),
+        Statement::Break => "break".into(),
+        Statement::Continue => "continue".into(),
+        Statement::Time(statement) => format!("time {}", describe(statement)),
+        Statement::And(statement) => format!("&& {}", describe(statement)),
+        Statement::Or(statement) => format!("|| {}", describe(statement)),
+        Statement::Not(statement) => format!("not {}", describe(statement)),
+        statement => statement.to_string(),
+    }
+}
+
+/// Print a variable on the standard error, as `p NAME` does
+fn print_variable(shell: &Shell<'_>, name: &str) {
+    match shell.variables().get(name) {
+        Some(value) => eprintln!("{} = {}", name, value),
+        None => eprintln!("{} is not defined", name),
+    }
+}
+
+impl Debugger {
+    /// Called before each statement: pauses when stepping, printing the watched variables and
+    /// the statement, then reads the commands of the user until the execution goes on.
+    pub fn pause(&self, shell: &mut Shell<'_>, statement: &Statement) {
+        // The statements wrapped by these are paused on when they are executed
+        if let Statement::Time(_) | Statement::And(_) | Statement::Or(_) | Statement::Not(_) =
+            statement
+        {
+            return;
+        }
+        let depth = shell.call_stack().len();
+        match self.stepping.get() {
+            Stepping::Into => (),
+            Stepping::Over(over) if depth <= over => (),
+            Stepping::Over(_) | Stepping::Continue => return,
+        }
+
+        for name in self.watches.borrow().iter() {
+            print_variable(shell, name);
+        }
+        match shell.call_stack().last() {
+            Some(function) => eprintln!("-> {} [in {}]", describe(statement), function),
+            None => eprintln!("-> {}", describe(statement)),
+        }
+
+        let stdin = io::stdin();
+        let mut stdin = stdin.lock();
+        let mut line = String::new();
+        loop {
+            eprint!("(step) ");
+            let _ = io::stderr().flush();
+            line.clear();
+            // Without any more commands, the script goes on without stepping
+            if stdin.read_line(&mut line).unwrap_or(0) == 0 {
+                eprintln!();
+                self.stepping.set(Stepping::Continue);
+                return;
+            }
+            let mut words = line.split_whitespace();
+            let stepping = match (words.next(), words.next()) {
+                (None, _) => match self.last.get() {
+                    Stepping::Over(_) => Stepping::Over(depth),
+                    last => last,
+                },
+                (Some("s"), None) => Stepping::Into,
+                (Some("n"), None) => Stepping::Over(depth),
+                (Some("c"), None) => Stepping::Continue,
+                (Some("q"), None) => {
+                    // Exiting as `exit` does runs the exit hooks and terminates the background jobs
+                    let status = shell.previous_status();
+                    shell.exit(status);
+                    return;
+                }
+                (Some("p"), Some(name)) => {
+                    print_variable(shell, name);
+                    continue;
+                }
+                (Some("w"), Some(name)) => {
+                    self.watches.borrow_mut().push(name.into());
+                    continue;
+                }
+                _ => {
+                    eprintln!("{}", HELP);
+                    continue;
+                }
+            };
+            if stepping != Stepping::Continue {
+                self.last.set(stepping);
+            }
+            self.stepping.set(stepping);
+            return;
+        }
+    }
+}
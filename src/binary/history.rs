@@ -22,6 +22,7 @@ NAME
 
 SYNOPSIS
     history [option]
+    history --json
 
 DESCRIPTION
     Manipulates or prints the command history. 
@@ -33,13 +34,15 @@ OPTIONS:
     +shared: Share history between shells using the same history file, implies inc_append.
     -shared: Default, do not share shell history.
     +duplicates: Default, allow duplicates in history.
-    -duplicates: Do not allow duplicates in history."
+    -duplicates: Do not allow duplicates in history.
+    --json: Print the command history as a JSON array of objects with the id and the command of
+            each entry. This is the default when the json-output option is set."
 )]
 //
 pub fn history(
     context_bis: Rc<RefCell<Context>>,
 ) -> impl Fn(&[types::Str], &mut Shell<'_>) -> Status {
-    move |args: &[types::Str], _shell: &mut Shell<'_>| -> Status {
+    move |args: &[types::Str], shell: &mut Shell<'_>| -> Status {
         if man_pages::check_help(args, HELP_PAGE) {
             return Status::SUCCESS;
         }
@@ -65,12 +68,14 @@ pub fn history(
             Some("-duplicates") => {
                 context_bis.borrow_mut().history.load_duplicates = false;
             }
+            Some("--json") => print_json(&context_bis.borrow()),
             Some(_) => {
                 Status::error(
                     "Invalid history option. Choices are [+|-] inc_append, duplicates and share \
                      (implies inc_append).",
                 );
             }
+            None if shell.opts().json_output => print_json(&context_bis.borrow()),
             None => {
                 print!("{}", context_bis.borrow().history.buffers.iter().format("\n"));
             }
@@ -79,6 +84,17 @@ pub fn history(
     }
 }
 
+/// Print the entries of the history as a JSON array of objects with their id and command
+fn print_json(context: &Context) {
+    let entries = context
+        .history
+        .buffers
+        .iter()
+        .enumerate()
+        .map(|(id, buffer)| serde_json::json!({ "id": id, "command": buffer.to_string() }));
+    println!("{}", serde_json::Value::Array(entries.collect()));
+}
+
 #[derive(Debug, Default)]
 pub struct IgnoreSetting {
     // Macro definition fails if last flag has a comment at the end of the line.
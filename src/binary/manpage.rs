@@ -0,0 +1,171 @@
+//! Generates the troff man page of ion, printed by `ion --generate-manpage`. It is built from the
+//! same text as `ion --help` for the options, and as the `help` builtin for the builtins, so that
+//! packagers do not need to maintain a separate documentation.
+
+use super::{history, huponexit, keybindings, MAN_ION};
+use ion_shell::BuiltinMap;
+use std::fmt::Write;
+
+/// The builtins which are only added by the interactive shell, with their man page
+const INTERACTIVE_BUILTINS: [(&str, &str); 3] = [
+    ("history", history::MAN_HISTORY),
+    ("keybindings", keybindings::MAN_KEYBINDINGS),
+    ("huponexit", huponexit::MAN_HUPONEXIT),
+];
+
+/// The sections of the man pages of the builtins which are only written once for the shell
+const SHARED_SECTIONS: [&str; 3] = ["NAME", "BUGS", "AUTHORS"];
+
+/// Escapes text for troff: backslashes and dashes are escaped, and a line starting with a control
+/// character is protected with a zero-width character.
+fn escape(text: &str) -> String {
+    let escaped = text.replace('\\', "\\e").replace('-', "\\-");
+    if escaped.starts_with('.') || escaped.starts_with('\'') {
+        format!("\\&{}", escaped)
+    } else {
+        escaped
+    }
+}
+
+/// Splits a man page in the format of the builtins into its sections. A section starts with an
+/// unindented heading, optionally followed by a colon, and its indented lines follow.
+pub fn sections(page: &str) -> Vec<(&str, Vec<&str>)> {
+    let mut sections: Vec<(&str, Vec<&str>)> = Vec::new();
+    for line in page.lines() {
+        if !line.is_empty() && !line.starts_with(char::is_whitespace) {
+            sections.push((line.trim_end_matches(':'), Vec::new()));
+        } else if let Some((_, lines)) = sections.last_mut() {
+            lines.push(line.strip_prefix("    ").unwrap_or_else(|| line.trim_start()));
+        }
+    }
+    for (_, lines) in &mut sections {
+        while lines.last().map_or(false, |line| line.trim().is_empty()) {
+            lines.pop();
+        }
+    }
+    sections
+}
+
+/// Writes the flags and options of `ion --help` as tagged paragraphs. The description of an
+/// option is separated from it by at least two spaces, or starts on the next line, and continues
+/// on the indented lines.
+fn options(page: &mut String, lines: &[&str]) {
+    for line in lines.iter().filter(|line| !line.trim().is_empty()) {
+        if line.starts_with(char::is_whitespace) {
+            let _ = writeln!(page, "{}", escape(line.trim()));
+            continue;
+        }
+        let _ = match line.find("  ") {
+            Some(index) => write!(
+                page,
+                ".TP\n.B {}\n{}\n",
+                escape(&line[..index]),
+                escape(line[index..].trim_start())
+            ),
+            None => write!(page, ".TP\n.B {}\n", escape(line)),
+        };
+    }
+}
+
+/// Writes the man page of a builtin as a subsection, with its summary followed by its own
+/// sections, whose text is kept as is.
+fn builtin(page: &mut String, name: &str, summary: Option<&str>, man: Option<&str>) {
+    let _ = writeln!(page, ".SS {}", escape(name));
+    let sections = man.map(sections).unwrap_or_default();
+    let description = sections
+        .iter()
+        .find(|(heading, _)| *heading == "NAME")
+        .and_then(|(_, lines)| lines.first())
+        .map(|line| line.trim().to_owned())
+        .or_else(|| summary.map(|summary| format!("{} - {}", name, summary)));
+    if let Some(description) = description {
+        let _ = writeln!(page, "{}", escape(&description));
+    }
+    let own = sections.iter().filter(|(heading, _)| !SHARED_SECTIONS.contains(heading));
+    for (heading, lines) in own {
+        let _ = writeln!(page, ".PP\n.B {}\n.RS\n.nf", escape(heading));
+        for line in lines {
+            let _ = writeln!(page, "{}", escape(line));
+        }
+        page.push_str(".fi\n.RE\n");
+    }
+}
+
+/// The man page of ion in the troff format, documenting its options and the given builtins along
+/// with those of the interactive shell, in alphabetical order
+pub fn generate(builtins: &BuiltinMap<'_>) -> String {
+    let mut page =
+        format!(".TH ION 1 \"\" \"ion {}\" \"User Commands\"\n", env!("CARGO_PKG_VERSION"));
+    let mut help = MAN_ION.lines().skip(1);
+    let description = help.by_ref().take_while(|line| !line.is_empty()).collect::<Vec<_>>();
+    let help = help.collect::<Vec<_>>().join("\n");
+
+    page.push_str(".SH NAME\nion \\- the fast, safe, modern rust shell\n.SH SYNOPSIS\n");
+    for (heading, lines) in sections(&help) {
+        match heading {
+            "USAGE" => {
+                let _ = writeln!(page, "{}", escape(lines.join(" ").trim()));
+                let _ = writeln!(page, ".SH DESCRIPTION\n{}", escape(&description.join(" ")));
+            }
+            _ => {
+                let _ = writeln!(page, ".SH {}", escape(heading));
+                options(&mut page, &lines);
+            }
+        }
+    }
+
+    page.push_str(
+        ".SH BUILTINS\nThe builtins are executed by the shell itself. The page of a builtin is \
+         also printed by\n.B help\nfollowed by its name, or by the builtin with the\n.B \\-\\-help\n\
+         flag.\n",
+    );
+    let mut names = builtins
+        .keys()
+        .chain(INTERACTIVE_BUILTINS.iter().map(|&(name, _)| name))
+        .collect::<Vec<_>>();
+    names.sort_unstable();
+    names.dedup();
+    for name in names {
+        let man = builtins.get_man(name).or_else(|| {
+            INTERACTIVE_BUILTINS
+                .iter()
+                .find(|&&(interactive, _)| interactive == name)
+                .map(|&(_, man)| man)
+        });
+        builtin(&mut page, name, builtins.get_help(name), man);
+    }
+
+    page.push_str(
+        ".SH SEE ALSO\nThe syntax of the shell is described in the ion manual, at\n\
+         .UR https://doc.redox-os.org/ion-manual/\n.UE .\n.SH BUGS\nPlease report all bugs at\n\
+         .UR https://gitlab.redox-os.org/redox-os/ion/issues\n.UE .\n.SH AUTHORS\nThe Ion \
+         developers, under the Redox OS organisation\n",
+    );
+    page
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn troff_escapes() {
+        assert_eq!(escape("path-add --help"), "path\\-add \\-\\-help");
+        assert_eq!(escape(".hidden"), "\\&.hidden");
+        assert_eq!(escape("a\\b"), "a\\eb");
+    }
+
+    #[test]
+    fn man_page_sections() {
+        let page =
+            "NAME\n    cd - change directory\n\nSYNOPSIS\n    cd DIR\n\nOPTIONS:\n    -h\n\n";
+        assert_eq!(
+            sections(page),
+            vec![
+                ("NAME", vec!["cd - change directory"]),
+                ("SYNOPSIS", vec!["cd DIR"]),
+                ("OPTIONS", vec!["-h"]),
+            ]
+        );
+    }
+}
@@ -1,26 +1,30 @@
 //! Contains the binary logic of Ion.
 pub mod builtins;
 mod completer;
+pub mod completions;
+pub mod debugger;
 mod designators;
 mod history;
 mod huponexit;
 mod keybindings;
 mod lexer;
+pub mod manpage;
 mod prompt;
 mod readln;
+pub mod terminal;
 
 use ion_shell::{
     builtins::{BuiltinFunction, Status},
     expansion::Expander,
     parser::Terminator,
     types::{self, array},
-    IonError, PipelineError, Shell, Signal, Value,
+    Hook, IonError, PipelineError, Shell, Signal, Value,
 };
 use itertools::Itertools;
 use liner::{Buffer, Context, KeyBindings};
 use std::{
     cell::{Cell, RefCell},
-    fs::{self, OpenOptions},
+    fs::OpenOptions,
     io::{self, Write},
     os::unix::io::{AsRawFd, IntoRawFd},
     path::Path,
@@ -41,11 +45,24 @@ FLAGS:
     -i, --interactive         Force interactive mode
     -n, --no-execute          Do not execute any commands, perform only syntax checking
     -x                        Print commands before execution
+    --json-output             Make the builtins printing tables, such as jobs, print them as JSON
+    --profile                 Print the calls and the time of each command on stderr once the script completes
+    --step                    Pause before each statement of the script, reading the commands of the debugger
+                              from stdin: s, n, c, q, p NAME, w NAME and h for their help
+    --generate-manpage        Print the man page of Ion and its builtins in the troff format then exit
     -v, --version             Print the version, platform and revision of Ion then exit
 
 OPTIONS:
     -c <command>             Evaluate given commands instead of reading from the commandline
+    --call-graph <script>    Print the calls between the functions of the script in the DOT format of
+                             Graphviz, without executing it, then exit
+    --compile <script>       Compile the script to a bytecode cache loaded by `source`, then exit
+    --generate-completion <shell>
+                             Print the script completing the options of Ion in the given shell, which
+                             is bash, zsh or fish, then exit
     -o <key-bindings>        Shortcut layout. Valid options: "vi", "emacs"
+    -o <output>              With --compile, where to write the cache, the script with the .ionc
+                             extension by default
 
 ARGS:
     <args>...    Script arguments (@args). If the -c option is not specified, the first parameter is taken as a
@@ -148,11 +165,26 @@ impl<'a> InteractiveShell<'a> {
         let mut shell = shell.into_inner();
         shell
             .builtins_mut()
-            .add("history", history, "Prints or manipulates the command history")
-            .add("keybindings", keybindings, "Change the keybindings")
+            .add_with_man(
+                "history",
+                history,
+                "Prints or manipulates the command history",
+                history::MAN_HISTORY,
+            )
+            .add_with_man(
+                "keybindings",
+                keybindings,
+                "Change the keybindings",
+                keybindings::MAN_KEYBINDINGS,
+            )
             .add("exit", exit, "Exits the current session")
             .add("exec", exec, "Replace the shell with the given command.")
-            .add("huponexit", set_huponexit, "Hangup the shell's background jobs on exit");
+            .add_with_man(
+                "huponexit",
+                set_huponexit,
+                "Hangup the shell's background jobs on exit",
+                huponexit::MAN_HUPONEXIT,
+            );
 
         match BaseDirectories::with_prefix("ion") {
             Ok(project_dir) => {
@@ -191,12 +223,9 @@ impl<'a> InteractiveShell<'a> {
     }
 
     fn exec_init_file(project_dir: &BaseDirectories, shell: &mut Shell) {
-        let initrc = project_dir.find_config_file(Self::CONFIG_FILE_NAME);
-        match initrc.and_then(|initrc| fs::File::open(&initrc).ok()) {
-            Some(script) => {
-                if let Err(err) = shell.execute_command(std::io::BufReader::new(script)) {
-                    eprintln!("ion: could not exec initrc: {}", err);
-                }
+        match project_dir.find_config_file(Self::CONFIG_FILE_NAME) {
+            Some(initrc) => {
+                shell.load_rc(initrc);
             }
             None => {
                 if let Err(err) = Self::create_config_file(project_dir) {
@@ -212,6 +241,9 @@ impl<'a> InteractiveShell<'a> {
         self.terminated.set(true);
         {
             let mut shell = self.shell.borrow_mut();
+            if !cmd.trim().is_empty() {
+                shell.run_hook(Hook::Preexec, &[cmd]);
+            }
             match shell.on_command(cmd.bytes(), true) {
                 Ok(_) => (),
                 Err(IonError::PipelineExecutionError(PipelineError::CommandNotFound(command))) => {
@@ -258,6 +290,7 @@ impl<'a> InteractiveShell<'a> {
             if let Err(err) = io::stderr().flush() {
                 println!("ion: failed to flush stderr: {}", err);
             }
+            self.shell.borrow_mut().run_hook(Hook::Precmd, &[]);
             match self.readln(prep_for_exit) {
                 Some(lines) => {
                     for command in lines
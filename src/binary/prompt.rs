@@ -22,7 +22,8 @@ impl<'a> InteractiveShell<'a> {
                             match shell
                                 .variables()
                                 .get_str("PROMPT")
-                                .and_then(|prompt| shell.get_string(&prompt))
+                                .map_err(IonError::from)
+                                .and_then(|prompt| shell.prompt_expand(&prompt))
                             {
                                 Ok(prompt) => prompt.to_string(),
                                 Err(err) => {
@@ -38,6 +38,9 @@ impl<'a> InteractiveShell<'a> {
                 let mut shell = self.shell.borrow_mut();
                 if self.terminated.get() && shell.exit_block().is_err() {
                     prep_for_exit(&mut shell);
+                    let status = shell.previous_status();
+                    shell.run_exit_hook(status);
+                    shell.remove_temporary_paths();
                     std::process::exit(shell.previous_status().as_os_code())
                 }
                 None
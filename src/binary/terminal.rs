@@ -0,0 +1,141 @@
+//! Detection of the terminal capabilities from the environment, so that scripts and prompts can
+//! decide whether to emit ANSI color codes without calling `tput`.
+
+use std::env;
+
+/// The capabilities of the terminal the shell is running in
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Capabilities {
+    /// The terminal emulator or multiplexer, if known
+    program:   Option<&'static str>,
+    /// Whether 24-bit colors are supported
+    truecolor: bool,
+    /// The number of colors supported
+    colors:    u32,
+}
+
+/// Guess the terminal emulator from the variables it sets for its children
+fn guess_program(var: &impl Fn(&str) -> Option<String>) -> Option<&'static str> {
+    let term = var("TERM").unwrap_or_default();
+    if var("TMUX").is_some() || term.starts_with("tmux") {
+        Some("tmux")
+    } else if var("STY").is_some() || term.starts_with("screen") {
+        Some("screen")
+    } else if var("ITERM_SESSION_ID").is_some() {
+        Some("iTerm.app")
+    } else if var("KITTY_WINDOW_ID").is_some() || term == "xterm-kitty" {
+        Some("kitty")
+    } else if var("ALACRITTY_LOG").is_some() || term == "alacritty" {
+        Some("alacritty")
+    } else if var("WT_SESSION").is_some() {
+        Some("WindowsTerminal")
+    } else if var("KONSOLE_VERSION").is_some() {
+        Some("konsole")
+    } else if var("VTE_VERSION").is_some() {
+        Some("gnome-terminal")
+    } else {
+        None
+    }
+}
+
+fn detect(var: impl Fn(&str) -> Option<String>) -> Capabilities {
+    let term = var("TERM").unwrap_or_default();
+    let program = var("TERM_PROGRAM").map_or_else(|| guess_program(&var), |_| None);
+
+    let truecolor = match var("COLORTERM") {
+        Some(colorterm) => colorterm == "truecolor" || colorterm == "24bit",
+        None => {
+            term.ends_with("-direct")
+                || term.contains("truecolor")
+                || term.contains("24bit")
+                // VTE supports 24-bit colors since 0.36
+                || var("VTE_VERSION")
+                    .and_then(|version| version.parse::<u32>().ok())
+                    .map_or(false, |version| version >= 3600)
+                || var("TERM_PROGRAM").map_or(false, |program| {
+                    ["iTerm.app", "WezTerm", "vscode", "Hyper"].contains(&program.as_str())
+                })
+                || ["kitty", "alacritty", "WindowsTerminal", "konsole"]
+                    .iter()
+                    .any(|known| program == Some(*known))
+        }
+    };
+
+    let colors = if truecolor {
+        16_777_216
+    } else if term.contains("256color") {
+        256
+    } else if term.is_empty() || term == "dumb" {
+        0
+    } else {
+        8
+    };
+
+    Capabilities { program, truecolor, colors }
+}
+
+/// Set `$TERM_PROGRAM` and `$COLORTERM` when the terminal did not already, and `$TERM_COLORS` to
+/// the number of colors supported by the terminal.
+pub fn export_capabilities() {
+    let capabilities = detect(|name| env::var(name).ok());
+    if let Some(program) = capabilities.program {
+        env::set_var("TERM_PROGRAM", program);
+    }
+    if capabilities.truecolor && env::var_os("COLORTERM").is_none() {
+        env::set_var("COLORTERM", "truecolor");
+    }
+    env::set_var("TERM_COLORS", capabilities.colors.to_string());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detect_with(vars: &[(&str, &str)]) -> Capabilities {
+        detect(|name| vars.iter().find(|(key, _)| *key == name).map(|(_, value)| value.to_string()))
+    }
+
+    #[test]
+    fn basic_terminals() {
+        assert_eq!(
+            detect_with(&[("TERM", "xterm")]),
+            Capabilities { program: None, truecolor: false, colors: 8 }
+        );
+        assert_eq!(
+            detect_with(&[("TERM", "xterm-256color")]),
+            Capabilities { program: None, truecolor: false, colors: 256 }
+        );
+        assert_eq!(
+            detect_with(&[("TERM", "dumb")]),
+            Capabilities { program: None, truecolor: false, colors: 0 }
+        );
+    }
+
+    #[test]
+    fn truecolor_terminals() {
+        assert_eq!(
+            detect_with(&[("TERM", "xterm-256color"), ("COLORTERM", "truecolor")]),
+            Capabilities { program: None, truecolor: true, colors: 16_777_216 }
+        );
+        assert_eq!(
+            detect_with(&[("TERM", "xterm-256color"), ("VTE_VERSION", "6003")]),
+            Capabilities { program: Some("gnome-terminal"), truecolor: true, colors: 16_777_216 }
+        );
+        assert_eq!(
+            detect_with(&[("TERM", "xterm-256color"), ("TERM_PROGRAM", "iTerm.app")]),
+            Capabilities { program: None, truecolor: true, colors: 16_777_216 }
+        );
+    }
+
+    #[test]
+    fn multiplexers() {
+        assert_eq!(
+            detect_with(&[("TERM", "screen-256color"), ("TMUX", "/tmp/tmux-1000/default,1,0")]),
+            Capabilities { program: Some("tmux"), truecolor: false, colors: 256 }
+        );
+        assert_eq!(
+            detect_with(&[("TERM", "screen")]),
+            Capabilities { program: Some("screen"), truecolor: false, colors: 8 }
+        );
+    }
+}
@@ -112,24 +112,28 @@ pub fn value_check<E: Expander>(
             _ => Err(TypeError::BadValue(expected.clone()).into()),
         }
     } else {
-        let mut extracted = shell.get_string(value)?;
+        let extracted = shell.get_string(value)?;
         match expected {
-            Primitive::Str => Ok(Value::Str(extracted)),
-            Primitive::Boolean => {
-                if is_boolean(&mut extracted) {
-                    Ok(Value::Str(extracted))
-                } else {
-                    Err(TypeError::BadValue(expected.clone()).into())
-                }
-            }
-            Primitive::Integer if extracted.parse::<i64>().is_ok() => Ok(Value::Str(extracted)),
-            Primitive::Float if extracted.parse::<f64>().is_ok() => Ok(Value::Str(extracted)),
             Primitive::Indexed(_, ref kind) => value_check(shell, value, kind),
-            _ => Err(TypeError::BadValue(expected.clone()).into()),
+            _ => string_check(extracted, expected).map_err(Into::into),
         }
     }
 }
 
+/// Checks that an already expanded string is a valid value of the type `expected`
+pub fn string_check(
+    mut value: types::Str,
+    expected: &Primitive,
+) -> Result<Value<Rc<types::Function>>, TypeError> {
+    match expected {
+        Primitive::Str => Ok(Value::Str(value)),
+        Primitive::Boolean if is_boolean(&mut value) => Ok(Value::Str(value)),
+        Primitive::Integer if value.parse::<i64>().is_ok() => Ok(Value::Str(value)),
+        Primitive::Float if value.parse::<f64>().is_ok() => Ok(Value::Str(value)),
+        _ => Err(TypeError::BadValue(expected.clone())),
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
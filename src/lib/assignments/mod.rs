@@ -1,6 +1,6 @@
 mod actions;
 mod checker;
 pub use self::{
-    actions::{Action, AssignmentActions},
-    checker::{is_array, value_check},
+    actions::{Action, AssignmentActions, AssignmentError},
+    checker::{is_array, string_check, value_check},
 };
@@ -0,0 +1,87 @@
+use super::Status;
+use crate as ion_shell;
+use crate::{
+    shell::{Colors, IonError},
+    types, Shell,
+};
+use builtins_proc::builtin;
+use std::io::{self, BufWriter, Write};
+
+#[builtin(
+    desc = "print text in color",
+    man = "
+SYNOPSIS
+    color [ -h | --help ] [-n] [--no-color] COLOR [STRING]...
+
+DESCRIPTION
+    Print the STRING(s) to standard output in the given COLOR, then reset the color. If no STRING
+    is given, only the escape sequence of the COLOR is printed.
+
+    COLOR is a comma-separated list of the same values as the ${c::COLOR} namespace:
+        a color name                red, light_blue, yellowbg, ...
+        an attribute                bold, dim, underlined, blink, reverse, hidden
+        one of 256 colors           256:202, 0xCA, 202
+        a 24-bit color              rgb:255:128:0, 0xFF8000
+        reset                       reset all colors and attributes
+    Suffix a color with 'bg' to set the background color instead.
+
+    When standard output is not a terminal, the text is printed without any escape sequence.
+
+OPTIONS
+    -n
+        do not output the trailing newline
+    --no-color
+        print the text without any escape sequence
+
+EXAMPLES
+    color red 'error:' file not found
+    color bold,256:202 warning
+    color rgb:255:128:0,blackbg orange
+    color reset"
+)]
+pub fn color(args: &[types::Str], _: &mut Shell<'_>) -> Status {
+    let mut use_colors = atty::is(atty::Stream::Stdout);
+    let mut newline = true;
+    let mut args = args[1..].iter().peekable();
+
+    while let Some(arg) = args.peek() {
+        match arg.as_str() {
+            "--no-color" => use_colors = false,
+            "-n" => newline = false,
+            _ => break,
+        }
+        args.next();
+    }
+
+    let colors = match args.next() {
+        Some(spec) => match Colors::collect::<IonError>(spec) {
+            Ok(colors) => colors,
+            Err(why) => return Status::error(format!("color: {}", why)),
+        },
+        None => return Status::bad_argument("color: no color given"),
+    };
+    let text = args.map(types::Str::as_str).collect::<Vec<_>>().join(" ");
+
+    let stdout = io::stdout();
+    let mut buffer = BufWriter::new(stdout.lock());
+    let mut inner = || -> io::Result<()> {
+        if text.is_empty() {
+            if use_colors {
+                write!(buffer, "{}", colors)?;
+            }
+            return Ok(());
+        }
+
+        if use_colors {
+            write!(buffer, "{}{}\x1b[0m", colors, text)?;
+        } else {
+            buffer.write_all(text.as_bytes())?;
+        }
+        if newline {
+            buffer.write_all(b"\n")?;
+        }
+        Ok(())
+    };
+
+    inner().and_then(|_| buffer.flush()).into()
+}
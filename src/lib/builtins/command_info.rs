@@ -1,32 +1,170 @@
-use super::Status;
+use super::{json::print_json, Status};
 use crate as ion_shell;
 use crate::{
     shell::{Shell, Value},
     types,
 };
 use builtins_proc::builtin;
+use serde_json::{json, Value as Json};
 
-use std::{borrow::Cow, env};
+use std::{borrow::Cow, env, os::unix::fs::PermissionsExt, path::Path};
+
+/// What `which` looks for, and whether it stops at the first match
+#[derive(Debug, Default, Clone, Copy)]
+struct Which {
+    all:            bool,
+    skip_alias:     bool,
+    skip_functions: bool,
+}
+
+impl Which {
+    /// Describes what `command` refers to, in the order the shell would try them
+    fn matches(self, command: &str, shell: &Shell<'_>) -> Vec<String> {
+        let mut matches = Vec::new();
+        if command.contains('/') {
+            if is_executable(Path::new(command)) {
+                matches.push(command.to_owned());
+            }
+            return matches;
+        }
+
+        match shell.variables().get(command) {
+            Some(Value::Alias(alias)) if !self.skip_alias => {
+                matches.push(format!("alias {}='{}'", command, &**alias))
+            }
+            Some(Value::Function(_)) if !self.skip_functions => {
+                matches.push(format!("{} is a shell function", command))
+            }
+            _ => (),
+        }
+        if shell.builtins().contains(command) {
+            matches.push(format!("{} is a shell builtin", command));
+        }
+        if !self.all && !matches.is_empty() {
+            matches.truncate(1);
+            return matches;
+        }
+
+        if let Some(paths) = env::var_os("PATH") {
+            for path in env::split_paths(&paths) {
+                let executable = path.join(command);
+                if is_executable(&executable) {
+                    matches.push(executable.display().to_string());
+                    if !self.all {
+                        break;
+                    }
+                }
+            }
+        }
+        matches
+    }
+}
+
+/// Whether `path` is a file which has any of its execute permission bits set
+fn is_executable(path: &Path) -> bool {
+    path.metadata()
+        .map_or(false, |metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
 
 #[builtin(
-    names = "which, type",
-    desc = "locate a program file in the current user's path",
+    desc = "locate a command",
     man = "
 SYNOPSIS
-    which PROGRAM
+    which [ -h | --help ] [ -a | --all ] [--skip-alias] [--skip-functions] COMMAND...
 
 DESCRIPTION
-    The which utility takes a list of command names and searches for the
-    alias/builtin/function/executable that would be executed if you ran that command."
+    Prints what the shell would run for each COMMAND, trying them in the order the shell does:
+
+    alias     printed as alias COMMAND='EXPANSION'
+    function  printed as COMMAND is a shell function
+    builtin   printed as COMMAND is a shell builtin
+    program   the full path of the first executable named COMMAND in $PATH
+
+    A COMMAND which contains a / is printed if it is an executable file. The exit status is 1 if
+    any of the commands was not found.
+
+OPTIONS
+    -a, --all
+        print every match rather than the first one, including each executable in $PATH
+    --skip-alias
+        ignore aliases
+    --skip-functions
+        ignore functions
+
+EXAMPLES
+    which -a ls
+    which --skip-alias --skip-functions git"
 )]
 pub fn which(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
-    if args.len() == 1 {
-        return Status::bad_argument("which: Expected at least 1 args, got only 0");
+    let mut which = Which::default();
+    let mut index = 1;
+    while let Some(option) = args.get(index).filter(|arg| arg.starts_with('-') && arg.len() > 1) {
+        index += 1;
+        match option.as_str() {
+            "-a" | "--all" => which.all = true,
+            "--skip-alias" => which.skip_alias = true,
+            "--skip-functions" => which.skip_functions = true,
+            "--" => break,
+            _ => return Status::bad_argument(format!("which: invalid option '{}'", option)),
+        }
+    }
+    if index == args.len() {
+        return Status::bad_argument("which: no command given");
     }
 
     let mut result = Status::SUCCESS;
-    for command in &args[1..] {
+    for command in &args[index..] {
+        let matches = which.matches(command, shell);
+        if matches.is_empty() {
+            result = Status::FALSE;
+        }
+        for description in matches {
+            println!("{}", description);
+        }
+    }
+    result
+}
+
+#[builtin(
+    names = "type",
+    desc = "describe what would be called for a command",
+    man = "
+SYNOPSIS
+    type [--json] PROGRAM...
+
+DESCRIPTION
+    Takes a list of command names and describes the alias, builtin, function or executable that
+    would be executed if you ran that command.
+
+OPTIONS
+    --json
+        print a JSON array with an object for each command found, whose fields are name, kind
+        (alias, function, builtin or file) and value (the expansion of an alias or the path of a
+        file, null otherwise). This is the default when the json-output option is set."
+)]
+pub fn type_(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
+    let option = args.get(1).map_or(false, |arg| arg == "--json");
+    let commands = &args[if option { 2 } else { 1 }..];
+    if commands.is_empty() {
+        return Status::bad_argument("type: Expected at least 1 args, got only 0");
+    }
+    let json = option || shell.opts().json_output;
+
+    let mut result = Status::SUCCESS;
+    let mut objects = Vec::new();
+    for command in commands {
         match get_command_info(command, shell) {
+            Ok(c_type) if json => {
+                let (kind, value) = match c_type.as_ref() {
+                    "alias" => match shell.variables().get(&**command) {
+                        Some(Value::Alias(ref alias)) => ("alias", Json::from(alias.as_str())),
+                        _ => ("alias", Json::Null),
+                    },
+                    "function" | "builtin" => (c_type.as_ref(), Json::Null),
+                    path => ("file", Json::from(path)),
+                };
+                objects.push(json!({ "name": command.as_str(), "kind": kind, "value": value }));
+            }
             Ok(c_type) => match c_type.as_ref() {
                 "alias" => {
                     if let Some(Value::Alias(ref alias)) = shell.variables().get(&**command) {
@@ -40,6 +178,12 @@ pub fn which(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
             Err(_) => result = Status::from_exit_code(1),
         }
     }
+    if json {
+        let printed = print_json("type", &Json::Array(objects), false);
+        if printed.is_failure() {
+            return printed;
+        }
+    }
     result
 }
 
@@ -48,3 +48,73 @@ SYNOPSIS
 DESCRIPTION
     Returns 0 if the first argument contains any other argument, else returns 0"
 )], contains);
+
+#[builtin(
+    names = "element-of",
+    desc = "check if values are elements of an array",
+    man = "
+SYNOPSIS
+    element-of [ -h | --help ] [-i] [--any | --all] [--] NEEDLE HAYSTACK...
+    element-of [ -h | --help ] [-i] [--any | --all] [--] NEEDLE... -- HAYSTACK...
+
+DESCRIPTION
+    Returns 0 if NEEDLE is one of the HAYSTACK arguments, else returns 1, which is always the case
+    when there are none. Several needles can be tested by separating them from the haystack with
+    --, which should also be given when the haystack may contain --.
+
+    The options end at the first argument which is not an option, or at a first --. A needle
+    which may start with -, such as the value of a variable, must be preceded by -- so that it is
+    not taken as an option.
+
+    Unlike contains, which looks for substrings of a string, the needles are compared with whole
+    elements, which makes it suited to test arrays.
+
+OPTIONS
+    -i, --ignore-case
+        compare the needles and the elements case-insensitively
+    --any
+        succeed if any of the needles is found, which is the default
+    --all
+        succeed only if all the needles are found
+
+EXAMPLES
+    element-of -- $answer @choices || echo 'invalid choice'
+    element-of --all -i rust ion -- @tags"
+)]
+pub fn element_of(args: &[small::String], _shell: &mut crate::Shell<'_>) -> Status {
+    let mut ignore_case = false;
+    let mut all = false;
+    let mut args = &args[1..];
+    while let Some(option) = args.first() {
+        match option.as_str() {
+            "-i" | "--ignore-case" => ignore_case = true,
+            "--any" => all = false,
+            "--all" => all = true,
+            "--" => {
+                args = &args[1..];
+                break;
+            }
+            _ => break,
+        }
+        args = &args[1..];
+    }
+
+    let (needles, haystack) = match args.iter().position(|arg| arg == "--") {
+        Some(separator) => (&args[..separator], &args[separator + 1..]),
+        None if args.is_empty() => (args, args),
+        None => args.split_at(1),
+    };
+    if needles.is_empty() {
+        return Status::bad_argument("ion: element-of: no value to look for was supplied");
+    }
+
+    let found = |needle: &small::String| {
+        if ignore_case {
+            let needle = needle.to_lowercase();
+            haystack.iter().any(|element| element.to_lowercase() == needle)
+        } else {
+            haystack.contains(needle)
+        }
+    };
+    Status::from(if all { needles.iter().all(found) } else { needles.iter().any(found) })
+}
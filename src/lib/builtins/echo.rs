@@ -9,10 +9,11 @@ use std::io::{self, BufWriter, Write};
     desc = "display text",
     man = "
 SYNOPSIS
-    echo [ -h | --help ] [-e] [-n] [-s] [STRING]...
+    echo [ -h | --help ] [-e] [-n] [-s] [--] [STRING]...
 
 DESCRIPTION
-    Print the STRING(s) to standard output.
+    Print the STRING(s) to standard output. The arguments following -- are printed as they are,
+    even if they look like options.
 
 OPTIONS
     -e
@@ -39,10 +40,13 @@ pub fn echo(args: &[types::Str], _: &mut Shell<'_>) -> Status {
     let mut escape = false;
     let mut newline = true;
     let mut spaces = true;
+    let mut options = true;
     let mut data: SmallVec<[&str; 16]> = SmallVec::with_capacity(16);
 
     for arg in args {
         match &**arg {
+            _ if !options => data.push(arg),
+            "--" => options = false,
             "--escape" => escape = true,
             "--no-newline" => newline = false,
             "--no-spaces" => spaces = false,
@@ -0,0 +1,236 @@
+use super::Status;
+use crate as ion_shell;
+use crate::{
+    expansion::pipelines::{PipeItem, Pipeline, RedirectFrom},
+    shell::{IonError, Job, PipelineError},
+    types, Shell,
+};
+use builtins_proc::builtin;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    env,
+    ffi::OsString,
+    io::{self, Write},
+};
+
+/// Quote an argument so that it is passed as is to the command when the pipeline is expanded
+pub(super) fn quote(arg: &str) -> types::Str {
+    let plain = |c: char| c.is_alphanumeric() || ['_', '-', '.', '/'].contains(&c);
+    if !arg.is_empty() && arg.chars().all(plain) {
+        arg.into()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\"'\"'")).into()
+    }
+}
+
+/// Replace the environment of the shell process, returning the previous one
+fn replace_environment(
+    variables: impl IntoIterator<Item = (OsString, OsString)>,
+) -> Vec<(OsString, OsString)> {
+    let previous = env::vars_os().collect::<Vec<_>>();
+    for (name, _) in &previous {
+        env::remove_var(name);
+    }
+    for (name, value) in variables {
+        env::set_var(name, value);
+    }
+    previous
+}
+
+/// The environment which `env` gives to its command: the exported variables, or none with `-i`,
+/// without the variables removed with `-u`, and overridden by each `NAME=VALUE` argument. The
+/// arguments start with the name of the builtin. Returns the environment along with the index of
+/// the first argument of the command, which is `args.len()` when there is none, or the failed
+/// status after printing an error.
+pub fn env_command_environment(
+    args: &[types::Str],
+) -> Result<(BTreeMap<OsString, OsString>, usize), Status> {
+    let mut ignore_environment = false;
+    let mut unset = Vec::new();
+    let mut index = 1;
+    while let Some(arg) = args.get(index).map(types::Str::as_str) {
+        match arg {
+            "-i" | "--ignore-environment" => ignore_environment = true,
+            "-u" | "--unset" => {
+                index += 1;
+                match args.get(index) {
+                    Some(name) => unset.push(name.as_str()),
+                    None => {
+                        return Err(Status::bad_argument(format!("env: {} requires a name", arg)))
+                    }
+                }
+            }
+            "--" => {
+                index += 1;
+                break;
+            }
+            _ if arg.starts_with('-') && arg.len() > 1 => {
+                return Err(Status::bad_argument(format!("env: invalid option '{}'", arg)))
+            }
+            _ => break,
+        }
+        index += 1;
+    }
+
+    let mut environment = if ignore_environment {
+        BTreeMap::new()
+    } else {
+        env::vars_os().collect::<BTreeMap<_, _>>()
+    };
+    for name in unset {
+        environment.remove(&OsString::from(name));
+    }
+    while let Some((name, value)) = args.get(index).and_then(|arg| arg.split_once('=')) {
+        if name.is_empty() {
+            return Err(Status::bad_argument(format!(
+                "env: invalid variable name in '={}'",
+                value
+            )));
+        }
+        environment.insert(name.into(), value.into());
+        index += 1;
+    }
+    Ok((environment, index))
+}
+
+#[builtin(
+    desc = "run a command in a modified environment",
+    man = "
+SYNOPSIS
+    env [ -h | --help ] [-i] [-u NAME]... [NAME=VALUE]... [COMMAND [ARGS]...]
+
+DESCRIPTION
+    Runs COMMAND with the exported variables of the shell, overridden by each NAME=VALUE. The
+    environment of the shell itself is left untouched.
+
+    Without a command, the resulting environment is printed instead, as NAME=VALUE lines sorted by
+    name.
+
+OPTIONS
+    -i, --ignore-environment
+        start from an empty environment instead of the exported variables
+    -u NAME, --unset NAME
+        remove NAME from the environment
+
+EXAMPLES
+    env LANG=C sort words.txt
+    env -i PATH=/bin sh -c 'echo $PATH'"
+)]
+pub fn env(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
+    let (environment, index) = match env_command_environment(args) {
+        Ok(parsed) => parsed,
+        Err(status) => return status,
+    };
+
+    let command = args[index..].iter().map(|arg| quote(arg.as_str())).collect::<types::Args>();
+    if command.is_empty() {
+        let stdout = io::stdout();
+        let mut stdout = stdout.lock();
+        let result = environment.iter().try_for_each(|(name, value)| {
+            writeln!(stdout, "{}={}", name.to_string_lossy(), value.to_string_lossy())
+        });
+        return result.into();
+    }
+
+    let mut pipeline = Pipeline::new();
+    pipeline.items.push(PipeItem {
+        job:     Job::new(command, RedirectFrom::None),
+        outputs: Vec::new(),
+        inputs:  Vec::new(),
+    });
+
+    let previous = replace_environment(environment);
+    let result = shell.run_pipeline(&pipeline);
+    replace_environment(previous);
+
+    match result {
+        Ok(status) => status,
+        Err(IonError::PipelineExecutionError(PipelineError::CommandNotFound(command))) => {
+            eprintln!("ion: env: command not found: {}", command);
+            Status::from_exit_code(127)
+        }
+        Err(why) => Status::error(format!("ion: env: {}", why)),
+    }
+}
+
+/// The lines printed by `env-diff` for the variables added, removed or changed in `current`
+/// since `initial`, sorted by name
+fn environment_diff(
+    initial: &BTreeMap<OsString, OsString>,
+    current: &BTreeMap<OsString, OsString>,
+) -> Vec<String> {
+    let names = initial.keys().chain(current.keys()).collect::<BTreeSet<_>>();
+    names
+        .into_iter()
+        .filter_map(|name| {
+            let name_lossy = name.to_string_lossy();
+            match (initial.get(name), current.get(name)) {
+                (None, Some(value)) => {
+                    Some(format!("+ {}={}", name_lossy, value.to_string_lossy()))
+                }
+                (Some(_), None) => Some(format!("- {}", name_lossy)),
+                (Some(old), Some(new)) if old != new => Some(format!(
+                    "~ {}={} → {}",
+                    name_lossy,
+                    old.to_string_lossy(),
+                    new.to_string_lossy()
+                )),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+#[builtin(
+    names = "env-diff",
+    desc = "print the changes of the environment since the shell started",
+    man = "
+SYNOPSIS
+    env-diff [ -h | --help ]
+
+DESCRIPTION
+    Compares the exported variables with the environment the shell was started with, and prints
+    a line for each variable which differs, sorted by name:
+
+    + NAME=VALUE          the variable was exported since
+    - NAME                the variable was removed since
+    ~ NAME=OLD → NEW      the value of the variable changed
+
+    This shows what an init script or a sourced file exported.
+
+EXAMPLES
+    source ~/.config/ion/extra.ion
+    env-diff"
+)]
+pub fn env_diff(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
+    if args.len() > 1 {
+        return Status::bad_argument("env-diff: takes no arguments");
+    }
+    let current = env::vars_os().collect::<BTreeMap<_, _>>();
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    let result = environment_diff(shell.initial_environment(), &current)
+        .iter()
+        .try_for_each(|line| writeln!(stdout, "{}", line));
+    result.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn differences() {
+        let environment = |variables: &[(&str, &str)]| {
+            variables
+                .iter()
+                .map(|&(name, value)| (name.into(), value.into()))
+                .collect::<BTreeMap<OsString, OsString>>()
+        };
+        let initial = environment(&[("HOME", "/home/me"), ("LANG", "C"), ("OLD", "1")]);
+        let current = environment(&[("HOME", "/home/me"), ("LANG", "en_US"), ("NEW", "2")]);
+        let diff = environment_diff(&initial, &current);
+        assert_eq!(diff, ["~ LANG=C → en_US", "+ NEW=2", "- OLD"]);
+        assert!(environment_diff(&initial, &initial).is_empty());
+    }
+}
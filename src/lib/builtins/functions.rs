@@ -1,20 +1,44 @@
 use super::Status;
 use crate as ion_shell;
-use crate::{types, Shell};
+use crate::{shell::Value, types, Shell};
 use builtins_proc::builtin;
 use std::io::{self, Write};
 
+/// Memoize each of the given functions
+fn memoize(names: &[types::Str], shell: &mut Shell<'_>) -> Status {
+    if names.is_empty() {
+        return Status::bad_argument("fn: --memo requires a function name");
+    }
+    for name in names {
+        if let Some(Value::Function(_)) = shell.variables().get(name) {
+            shell.memoize(name);
+        } else {
+            return Status::error(format!("ion: fn: `{}` is not a function", name));
+        }
+    }
+    Status::SUCCESS
+}
+
 #[builtin(
     names = "fn",
     desc = "print a short description of every defined function",
     man = "
 SYNOPSIS
-    fn [ -h | --help ]
+    fn [ -h | --help ] [--memo NAME...]
 
 DESCRIPTION
-    Prints all the defined functions along with their help, if provided"
+    Prints all the defined functions along with their help, if provided
+
+OPTIONS
+    --memo NAME...
+        memoize the given functions, like `@cache NAME`: the output and the exit status of each
+        successful call are cached for its arguments, and replayed instead of calling the function
+        again with the same arguments. Redefining the function clears its cache."
 )]
 pub fn fn_(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
+    if args.get(1).map(types::Str::as_str) == Some("--memo") {
+        return memoize(&args[2..], shell);
+    }
     let stdout = io::stdout();
     let stdout = &mut stdout.lock();
     let _ = writeln!(stdout, "# Functions");
@@ -27,3 +51,62 @@ pub fn fn_(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
     }
     Status::SUCCESS
 }
+
+#[builtin(
+    names = "cache-clear",
+    desc = "clear the cache of memoized functions",
+    man = "
+SYNOPSIS
+    cache-clear [ -h | --help ] [NAME...]
+
+DESCRIPTION
+    Forgets the results cached for the given memoized functions, or for all of them if no name is
+    given. The functions stay memoized."
+)]
+pub fn cache_clear(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
+    if args.len() == 1 {
+        let names = shell.memos().map(|(name, _)| name.clone()).collect::<Vec<_>>();
+        for name in names {
+            shell.clear_memo(&name);
+        }
+        return Status::SUCCESS;
+    }
+    for name in &args[1..] {
+        if !shell.clear_memo(name) {
+            return Status::error(format!("ion: cache-clear: `{}` is not memoized", name));
+        }
+    }
+    Status::SUCCESS
+}
+
+#[builtin(
+    names = "cache-stats",
+    desc = "print the statistics of the memoized functions",
+    man = "
+SYNOPSIS
+    cache-stats [ -h | --help ]
+
+DESCRIPTION
+    Prints, for each memoized function, the number of calls answered from the cache (hits), the
+    number of calls which executed the function (misses), and the number of cached results."
+)]
+pub fn cache_stats(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
+    let mut memos = shell.memos().collect::<Vec<_>>();
+    memos.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    memos
+        .into_iter()
+        .try_for_each(|(name, memo)| {
+            writeln!(
+                stdout,
+                "{}: {} hits, {} misses, {} cached",
+                name,
+                memo.hits(),
+                memo.misses(),
+                memo.entries()
+            )
+        })
+        .into()
+}
@@ -0,0 +1,87 @@
+use super::Status;
+use crate as ion_shell;
+use crate::{
+    shell::{Hook, Value},
+    types, Shell,
+};
+use builtins_proc::builtin;
+use std::io::{self, Write};
+
+/// Print the functions registered for each of the hooks, as `hook add` commands
+fn list(shell: &Shell<'_>, hooks: &[Hook]) -> Status {
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    let result = hooks.iter().try_for_each(|&hook| {
+        shell.hooks(hook).iter().try_for_each(|name| writeln!(stdout, "hook add {} {}", hook, name))
+    });
+    result.into()
+}
+
+#[builtin(
+    desc = "call functions at some points of the execution of the shell",
+    man = "
+SYNOPSIS
+    hook [ -h | --help ] [list [HOOK]]
+    hook add HOOK FUNCTION
+    hook remove HOOK FUNCTION
+
+DESCRIPTION
+    Registers functions to be called by the shell at the following hooks:
+
+    precmd   before the prompt is displayed
+    preexec  before a command line is executed, with the command line as argument
+    chpwd    after the current directory is changed by cd, pushd or popd
+    exit     before the shell exits, with the exit status as argument
+
+    The functions of a hook are called in the order they were added, and the status of the
+    previous command is preserved across them. A function is only added once to each hook.
+    Without a subcommand, or with list, the registered functions are printed as hook add
+    commands.
+
+EXAMPLES
+    fn show_directory
+        echo now in $PWD
+    end
+    hook add chpwd show_directory
+
+    fn log_command command
+        echo $command >> ~/.commands
+    end
+    hook add preexec log_command"
+)]
+pub fn hook(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
+    let subcommand = args.get(1).map(types::Str::as_str);
+    if let Some(subcommand) = subcommand.filter(|name| !["list", "add", "remove"].contains(name)) {
+        return Status::bad_argument(format!("hook: invalid subcommand '{}'", subcommand));
+    }
+    let hook = match args.get(2).map(|name| name.parse::<Hook>()) {
+        Some(Ok(hook)) => Some(hook),
+        Some(Err(why)) => return Status::bad_argument(format!("hook: {}", why)),
+        None => None,
+    };
+
+    match (subcommand, hook, args.get(3)) {
+        (None, ..) | (Some("list"), None, None) => list(shell, &Hook::ALL),
+        (Some("list"), Some(hook), None) => list(shell, &[hook]),
+        (Some("list"), ..) => Status::bad_argument("hook: list takes at most a hook"),
+        (Some("add"), Some(hook), Some(name)) if args.len() == 4 => {
+            let defined = matches!(shell.variables().get(name), Some(Value::Function(_)));
+            if defined {
+                shell.add_hook(hook, name);
+                Status::SUCCESS
+            } else {
+                Status::error(format!("hook: function `{}` does not exist", name))
+            }
+        }
+        (Some("remove"), Some(hook), Some(name)) if args.len() == 4 => {
+            if shell.remove_hook(hook, name) {
+                Status::SUCCESS
+            } else {
+                Status::error(format!("hook: function `{}` is not registered for {}", name, hook))
+            }
+        }
+        (Some(subcommand), ..) => {
+            Status::bad_argument(format!("hook: {} requires a hook and a function", subcommand))
+        }
+    }
+}
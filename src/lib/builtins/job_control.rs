@@ -1,12 +1,18 @@
 //! Contains the `jobs`, `disown`, `bg`, and `fg` commands that manage job
 //! control in the shell.
 
-use super::Status;
+use super::{json::print_json, Status};
 use crate::{
     shell::{BackgroundProcess, Shell},
     types,
 };
+use nix::{
+    sys::signal::{self, Signal},
+    unistd::Pid,
+};
+use serde_json::{json, Value as Json};
 use smallvec::SmallVec;
+use std::convert::TryFrom;
 
 /// Disowns given process job IDs, and optionally marks jobs to not receive SIGHUP signals.
 /// The `-a` flag selects all jobs, `-r` selects all running jobs, and `-h` specifies to mark
@@ -64,13 +70,27 @@ pub fn disown(shell: &mut Shell<'_>, args: &[types::Str]) -> Result<(), String>
     Ok(())
 }
 
-/// Display a list of all jobs running in the background.
-pub fn jobs(shell: &mut Shell<'_>) {
-    for (id, process) in shell.background_jobs().iter().enumerate() {
-        if process.exists() {
+/// Display a list of all jobs running in the background, or print them as a JSON array of
+/// objects with their id, pid, status and command.
+pub fn jobs(shell: &mut Shell<'_>, json: bool) -> Status {
+    let jobs = shell.background_jobs();
+    let jobs = jobs.iter().enumerate().filter(|(_, process)| process.exists());
+    if !json {
+        for (id, process) in jobs {
             eprintln!("[{}] {}", id, process);
         }
+        return Status::SUCCESS;
     }
+    let jobs = jobs.map(|(id, process)| {
+        json!({
+            "id": id,
+            "pid": process.pid().as_raw(),
+            "status": if process.is_running() { "running" } else { "stopped" },
+            "command": process.name(),
+        })
+    });
+    let jobs = Json::Array(jobs.collect());
+    print_json("jobs", &jobs, false)
 }
 
 /// Hands control of the foreground process to the specified jobs, recording their exit status.
@@ -147,3 +167,75 @@ pub fn bg(shell: &mut Shell<'_>, args: &[types::Str]) -> Status {
         Status::SUCCESS
     }
 }
+
+/// Parse a signal given by name, with or without the `SIG` prefix, or by number. The signal 0,
+/// parsed as `Some(None)`, only checks that the processes exist.
+fn parse_signal(name: &str) -> Option<Option<Signal>> {
+    if let Ok(number) = name.parse::<i32>() {
+        return if number == 0 { Some(None) } else { Signal::try_from(number).ok().map(Some) };
+    }
+    let name = name.to_ascii_uppercase();
+    let signal =
+        if name.starts_with("SIG") { name.parse() } else { format!("SIG{}", name).parse() };
+    signal.ok().map(Some)
+}
+
+/// Sends a signal to the given `%job` specs and process IDs. The signal defaults to SIGTERM, and
+/// can be given by name or by number: `-TERM`, `-SIGTERM`, `-15` or `-s TERM`. `-l` lists the
+/// names of the signals.
+pub fn kill(shell: &mut Shell<'_>, args: &[types::Str]) -> Status {
+    let mut signal = Some(Signal::SIGTERM);
+    let mut args = args.iter().peekable();
+
+    match args.peek().copied().map(types::Str::as_str) {
+        Some("-l") => {
+            let names = Signal::iterator().map(|signal| &signal.as_str()[3..]).collect::<Vec<_>>();
+            println!("{}", names.join(" "));
+            return Status::SUCCESS;
+        }
+        Some("-s") => {
+            args.next();
+            match args.next().and_then(|name| parse_signal(name.as_str())) {
+                Some(parsed) => signal = parsed,
+                None => return Status::bad_argument("ion: kill: -s requires a valid signal"),
+            }
+        }
+        Some(arg) if arg.starts_with('-') && arg.len() > 1 => match parse_signal(&arg[1..]) {
+            Some(parsed) => {
+                signal = parsed;
+                args.next();
+            }
+            None => {
+                return Status::bad_argument(format!("ion: kill: unknown signal: {}", &arg[1..]))
+            }
+        },
+        _ => (),
+    }
+
+    if args.peek().is_none() {
+        return Status::bad_argument("ion: kill: must provide a job spec or a process ID");
+    }
+
+    let mut status = Status::SUCCESS;
+    for arg in args {
+        let result = if let Some(jobspec) = arg.strip_prefix('%') {
+            let pid = jobspec.parse::<usize>().ok().and_then(|njob| {
+                shell.background_jobs().get(njob).filter(|p| p.exists()).map(|p| p.pid())
+            });
+            match pid {
+                Some(pid) => signal::killpg(pid, signal).map_err(|why| why.to_string()),
+                None => Err(format!("job {} does not exist", arg)),
+            }
+        } else {
+            match arg.parse::<i32>() {
+                Ok(pid) => signal::kill(Pid::from_raw(pid), signal).map_err(|why| why.to_string()),
+                Err(_) => Err(format!("invalid job spec or process ID: '{}'", arg)),
+            }
+        };
+
+        if let Err(why) = result {
+            status = Status::error(format!("ion: kill: {}: {}", arg, why));
+        }
+    }
+    status
+}
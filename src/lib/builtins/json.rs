@@ -0,0 +1,190 @@
+use super::Status;
+use crate as ion_shell;
+use crate::{
+    shell::{variables::Variables, Value},
+    types::{self, Function},
+    Shell,
+};
+use builtins_proc::builtin;
+use serde_json::{Map, Value as Json};
+use std::{
+    io::{self, Read, Write},
+    rc::Rc,
+};
+
+/// Convert a variable to JSON. Functions and aliases have no JSON equivalent.
+pub(super) fn json_of(value: &Value<Rc<Function>>) -> Option<Json> {
+    match value {
+        Value::Str(string) => Some(Json::String(string.to_string())),
+        Value::Array(array) => array.iter().map(json_of).collect::<Option<_>>().map(Json::Array),
+        Value::HashMap(map) => entries_to_json(map.iter()),
+        Value::BTreeMap(map) => entries_to_json(map.iter()),
+        Value::Alias(_) | Value::Function(_) | Value::None => None,
+    }
+}
+
+fn entries_to_json<'a>(
+    entries: impl Iterator<Item = (&'a types::Str, &'a Value<Rc<Function>>)>,
+) -> Option<Json> {
+    entries
+        .map(|(key, value)| json_of(value).map(|value| (key.to_string(), value)))
+        .collect::<Option<Map<_, _>>>()
+        .map(Json::Object)
+}
+
+/// Print JSON on the standard output, followed by a newline, for the builtin `name`
+pub(super) fn print_json(name: &str, json: &Json, pretty: bool) -> Status {
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    let result = if pretty {
+        serde_json::to_writer_pretty(&mut stdout, json)
+    } else {
+        serde_json::to_writer(&mut stdout, json)
+    };
+    match result.map_err(io::Error::from).and_then(|()| writeln!(stdout)) {
+        Ok(()) => Status::SUCCESS,
+        Err(why) => Status::error(format!("ion: {}: {}", name, why)),
+    }
+}
+
+/// Convert JSON to a variable: objects become hmaps, and scalars become strings, `null` being an
+/// empty string.
+fn value_of(json: Json) -> Value<Rc<Function>> {
+    match json {
+        Json::Null => Value::Str(types::Str::new()),
+        Json::Bool(boolean) => Value::Str(boolean.to_string().into()),
+        Json::Number(number) => Value::Str(number.to_string().into()),
+        Json::String(string) => Value::Str(string.into()),
+        Json::Array(array) => Value::Array(array.into_iter().map(value_of).collect()),
+        Json::Object(map) => Value::HashMap(
+            map.into_iter().map(|(key, value)| (key.into(), value_of(value))).collect(),
+        ),
+    }
+}
+
+#[builtin(
+    names = "to-json",
+    desc = "print variables as JSON",
+    man = "
+SYNOPSIS
+    to-json [ -h | --help ] [-p] NAME
+    to-json [ -h | --help ] [-p] -a VALUES...
+
+DESCRIPTION
+    Prints the variable NAME as JSON: strings are printed as JSON strings, arrays as JSON arrays
+    and maps as JSON objects, with their keys sorted. Nested arrays and maps are converted as well.
+    Functions and aliases can not be converted.
+
+    With -a, the given values are printed as a JSON array of strings instead, which allows
+    converting an expression such as @array or @split(string).
+
+OPTIONS
+    -a, --array
+        print the values as a JSON array instead of a variable
+    -p, --pretty
+        indent the JSON over several lines
+
+EXAMPLES
+    let colors = [red green]
+    to-json colors          # prints [\"red\",\"green\"]
+    to-json -a @colors blue # prints [\"red\",\"green\",\"blue\"]"
+)]
+pub fn to_json(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
+    let mut array = false;
+    let mut pretty = false;
+    let mut args = args[1..].iter().map(types::Str::as_str).peekable();
+    while let Some(&arg) = args.peek().filter(|arg| !array && arg.starts_with('-')) {
+        match arg {
+            "-a" | "--array" => array = true,
+            "-p" | "--pretty" => pretty = true,
+            _ => return Status::bad_argument(format!("to-json: invalid option '{}'", arg)),
+        }
+        args.next();
+    }
+
+    let json = if array {
+        Json::Array(args.map(|value| Json::String(value.into())).collect())
+    } else {
+        let name = match (args.next(), args.next()) {
+            (Some(name), None) => name,
+            _ => return Status::bad_argument("to-json: expected a single variable name"),
+        };
+        match shell.variables().get(name) {
+            Some(value) => match json_of(value) {
+                Some(json) => json,
+                None => {
+                    return Status::error(format!("ion: to-json: `{}` can not be converted", name))
+                }
+            },
+            None => return Status::error(format!("ion: to-json: `{}` is not defined", name)),
+        }
+    };
+
+    print_json("to-json", &json, pretty)
+}
+
+#[builtin(
+    names = "from-json",
+    desc = "set a variable from JSON",
+    man = "
+SYNOPSIS
+    from-json [ -h | --help ] NAME [JSON]
+
+DESCRIPTION
+    Parses JSON, given as an argument or read from the standard input, and sets the variable NAME
+    to its value: objects become hmaps, arrays become arrays and other values become strings,
+    null being an empty string. Nested arrays and objects are converted as well. Malformed JSON is
+    an error, and leaves the variable untouched.
+
+EXAMPLES
+    from-json config '{\"name\": \"ion\", \"tags\": [\"shell\"]}'
+    echo @config[name]
+    curl -s https://example.com/api | from-json response"
+)]
+pub fn from_json(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
+    let (name, text) = match args {
+        [_, name] => {
+            let mut text = String::new();
+            if let Err(why) = io::stdin().read_to_string(&mut text) {
+                return Status::error(format!("ion: from-json: could not read the input: {}", why));
+            }
+            (name, text)
+        }
+        [_, name, text] => (name, text.to_string()),
+        _ => return Status::bad_argument("from-json: expected a variable name and some JSON"),
+    };
+
+    let name = name.as_str();
+    if !Variables::is_valid_name(name) {
+        return Status::bad_argument(format!("from-json: invalid variable name `{}`", name));
+    }
+    if shell.variables().is_readonly(name) {
+        return Status::error(format!("ion: cannot modify readonly variable `{}`", name));
+    }
+    match serde_json::from_str(&text) {
+        Ok(json) => {
+            shell.variables_mut().set(name, value_of(json));
+            Status::SUCCESS
+        }
+        Err(why) => Status::error(format!("ion: from-json: invalid JSON: {}", why)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_round_trip() {
+        let text = r#"{"name":"ion","tags":["shell",{"fast":true}],"v":1.5,"none":null}"#;
+        let value = value_of(serde_json::from_str(text).unwrap());
+        assert!(matches!(value, Value::HashMap(_)));
+        let expected = r#"{"name":"ion","none":"","tags":["shell",{"fast":"true"}],"v":"1.5"}"#;
+        assert_eq!(json_of(&value).unwrap().to_string(), expected);
+    }
+
+    #[test]
+    fn functions_are_not_converted() {
+        assert!(json_of(&Value::Array(vec![Value::Str("a".into()), Value::None])).is_none());
+    }
+}
@@ -0,0 +1,159 @@
+use super::{env::quote, Status};
+use crate as ion_shell;
+use crate::{
+    expansion::pipelines::{PipeItem, Pipeline, RedirectFrom},
+    shell::{IonError, Job, PipelineError},
+    types, Shell,
+};
+use builtins_proc::builtin;
+use nix::{
+    errno::Errno,
+    fcntl::{flock, FlockArg},
+};
+use std::{
+    fs::{File, OpenOptions},
+    io,
+    os::unix::io::AsRawFd,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// The delay between two attempts to take a lock before a timeout
+const RETRY_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How long to wait for the lock
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Wait {
+    Forever,
+    Timeout(Duration),
+    Never,
+}
+
+/// Opens the lock file, creating it if needed. Files which can not be written, such as
+/// directories, are opened for reading instead, which is enough to lock them.
+fn open(path: &str) -> io::Result<File> {
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .open(path)
+        .or_else(|why| File::open(path).map_err(|_| why))
+}
+
+/// Takes an exclusive lock on the file, returning `Ok(false)` if it is held by someone else for
+/// longer than allowed
+fn acquire(file: &File, wait: Wait) -> nix::Result<bool> {
+    let fd = file.as_raw_fd();
+    let deadline = match wait {
+        Wait::Forever => return flock(fd, FlockArg::LockExclusive).map(|_| true),
+        Wait::Timeout(timeout) => Instant::now() + timeout,
+        Wait::Never => Instant::now(),
+    };
+    loop {
+        match flock(fd, FlockArg::LockExclusiveNonblock) {
+            Ok(()) => return Ok(true),
+            Err(Errno::EWOULDBLOCK) if Instant::now() < deadline => {
+                thread::sleep(RETRY_INTERVAL.min(deadline - Instant::now()))
+            }
+            Err(Errno::EWOULDBLOCK) => return Ok(false),
+            Err(why) => return Err(why),
+        }
+    }
+}
+
+#[builtin(
+    desc = "run a command while holding a lock on a file",
+    man = "
+SYNOPSIS
+    lock [ -h | --help ] [ -n | --nonblocking | -w SECONDS | --timeout SECONDS ] FILE COMMAND...
+
+DESCRIPTION
+    Takes an exclusive lock on FILE with flock(2), runs COMMAND, then releases the lock. If
+    another process holds the lock, lock waits until it is released. FILE is created if it does
+    not exist. The exit status is the one of the command, or 1 if the lock could not be taken.
+
+    As the lock is advisory, it only protects against the processes which also lock the file,
+    such as other invocations of lock or flock(1).
+
+OPTIONS
+    -n, --nonblocking
+        fail immediately if the lock is held by another process
+    -w SECONDS, --timeout SECONDS
+        fail if the lock could not be taken within SECONDS, which may be fractional
+
+EXAMPLES
+    lock /var/lock/myapp.lock ./migrate-database
+    lock --timeout 30 ~/.config/app.lock ion update-config.ion"
+)]
+pub fn lock(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
+    let mut wait = Wait::Forever;
+    let mut args = args[1..].iter().map(types::Str::as_str);
+    let path = loop {
+        match args.next() {
+            Some("-n") | Some("--nonblocking") => wait = Wait::Never,
+            Some(option @ "-w") | Some(option @ "--timeout") => {
+                let seconds = match args.next() {
+                    Some(seconds) => seconds,
+                    None => {
+                        return Status::bad_argument(format!("lock: {} requires a value", option))
+                    }
+                };
+                match seconds.parse::<f64>() {
+                    // Timeouts longer than a century are as good as waiting forever
+                    Ok(seconds) if seconds > f64::from(u32::MAX) => wait = Wait::Forever,
+                    Ok(seconds) if seconds >= 0.0 => {
+                        wait = Wait::Timeout(Duration::from_secs_f64(seconds))
+                    }
+                    _ => {
+                        return Status::bad_argument(format!(
+                            "lock: invalid timeout '{}': expected a number of seconds",
+                            seconds
+                        ))
+                    }
+                }
+            }
+            Some("--") => match args.next() {
+                Some(path) => break path,
+                None => return Status::bad_argument("lock: no file given"),
+            },
+            Some(option) if option.starts_with('-') && option.len() > 1 => {
+                return Status::bad_argument(format!("lock: invalid option '{}'", option))
+            }
+            Some(path) => break path,
+            None => return Status::bad_argument("lock: no file given"),
+        }
+    };
+    let command = args.map(quote).collect::<types::Args>();
+    if command.is_empty() {
+        return Status::bad_argument("lock: no command given");
+    }
+
+    let file = match open(path) {
+        Ok(file) => file,
+        Err(why) => return Status::error(format!("lock: could not open '{}': {}", path, why)),
+    };
+    match acquire(&file, wait) {
+        Ok(true) => (),
+        Ok(false) => return Status::error(format!("lock: '{}' is locked", path)),
+        Err(why) => return Status::error(format!("lock: could not lock '{}': {}", path, why)),
+    }
+
+    let mut pipeline = Pipeline::new();
+    pipeline.items.push(PipeItem {
+        job:     Job::new(command, RedirectFrom::None),
+        outputs: Vec::new(),
+        inputs:  Vec::new(),
+    });
+    let result = shell.run_pipeline(&pipeline);
+    // Unlock explicitly, as forked children may still hold a copy of the descriptor
+    let _ = flock(file.as_raw_fd(), FlockArg::Unlock);
+
+    match result {
+        Ok(status) => status,
+        Err(IonError::PipelineExecutionError(PipelineError::CommandNotFound(command))) => {
+            eprintln!("ion: lock: command not found: {}", command);
+            Status::from_exit_code(127)
+        }
+        Err(why) => Status::error(format!("ion: lock: {}", why)),
+    }
+}
@@ -2,7 +2,7 @@ use crate::types;
 
 /// Print the given help if the -h or --help argument are found
 pub fn check_help(args: &[types::Str], man_page: &'static str) -> bool {
-    for arg in args {
+    for arg in args.iter().take_while(|arg| *arg != "--") {
         if arg == "-h" || arg == "--help" {
             println!("{}", man_page);
             return true;
@@ -2,41 +2,86 @@
 /// helpers for creating help
 pub mod man_pages;
 
+mod color;
 mod command_info;
 mod conditionals;
 mod echo;
+mod env;
 mod exists;
 mod functions;
 mod helpers;
+mod hook;
 mod is;
+mod json;
+mod lock;
 mod job_control;
 mod math;
+mod notify;
+mod path;
+mod progress;
 mod random;
+mod seq;
 mod set;
 mod source;
+mod spinner;
 mod status;
+mod temporary;
+mod terminal;
 mod test;
 mod variables;
+mod watch;
 
 pub use self::{
-    command_info::builtin_which,
-    conditionals::{builtin_contains, builtin_ends_with, builtin_starts_with},
-    echo::builtin_echo,
-    exists::builtin_exists,
-    functions::builtin_fn_,
+    color::{builtin_color, MAN_COLOR},
+    command_info::{builtin_type_, builtin_which, MAN_TYPE, MAN_WHICH},
+    conditionals::{
+        builtin_contains, builtin_element_of, builtin_ends_with, builtin_starts_with, MAN_CONTAINS,
+        MAN_ELEMENT_OF, MAN_ENDS_WITH, MAN_STARTS_WITH,
+    },
+    echo::{builtin_echo, MAN_ECHO},
+    env::{builtin_env, builtin_env_diff, env_command_environment, MAN_ENV, MAN_ENV_DIFF},
+    exists::{builtin_exists, MAN_EXISTS},
+    functions::{
+        builtin_cache_clear, builtin_cache_stats, builtin_fn_, MAN_CACHE_CLEAR, MAN_CACHE_STATS,
+        MAN_FN,
+    },
     helpers::Status,
-    is::builtin_is,
+    hook::{builtin_hook, MAN_HOOK},
+    is::{builtin_is, MAN_IS},
+    json::{builtin_from_json, builtin_to_json, MAN_FROM_JSON, MAN_TO_JSON},
+    lock::{builtin_lock, MAN_LOCK},
     man_pages::check_help,
-    math::builtin_math,
-    set::builtin_set,
-    source::builtin_source,
-    status::builtin_status,
-    test::builtin_test,
-    variables::{builtin_alias, builtin_drop, builtin_unalias},
+    math::{builtin_math, MAN_MATH},
+    notify::{builtin_notify, MAN_NOTIFY},
+    path::{
+        builtin_path_add, builtin_path_clean, builtin_path_list, builtin_path_prepend,
+        builtin_path_remove, MAN_PATH_ADD, MAN_PATH_CLEAN, MAN_PATH_LIST, MAN_PATH_PREPEND,
+        MAN_PATH_REMOVE,
+    },
+    progress::{builtin_progress, MAN_PROGRESS},
+    seq::{builtin_seq, MAN_SEQ},
+    set::{builtin_set, MAN_SET},
+    source::{builtin_source, MAN_SOURCE},
+    spinner::{builtin_spinner, MAN_SPINNER},
+    status::{builtin_status, MAN_STATUS},
+    temporary::{builtin_tmpdir, builtin_tmpfile, MAN_TMPDIR, MAN_TMPFILE},
+    terminal::{
+        builtin_term_bold, builtin_term_clear, builtin_term_cols, builtin_term_hide_cursor,
+        builtin_term_move, builtin_term_reset, builtin_term_restore_cursor, builtin_term_rows,
+        builtin_term_save_cursor, builtin_term_show_cursor, MAN_TERM_BOLD, MAN_TERM_CLEAR,
+        MAN_TERM_COLS, MAN_TERM_HIDE_CURSOR, MAN_TERM_MOVE, MAN_TERM_RESET,
+        MAN_TERM_RESTORE_CURSOR, MAN_TERM_ROWS, MAN_TERM_SAVE_CURSOR, MAN_TERM_SHOW_CURSOR,
+    },
+    test::{builtin_test, MAN_TEST},
+    variables::{
+        builtin_alias, builtin_declare, builtin_drop, builtin_scope, builtin_unalias, MAN_ALIAS,
+        MAN_DECLARE, MAN_DROP, MAN_SCOPE, MAN_UNALIAS,
+    },
+    watch::{builtin_unwatch, builtin_watches, MAN_UNWATCH, MAN_WATCHES},
 };
 use crate as ion_shell;
 use crate::{
-    shell::{Shell, Value},
+    shell::{Hook, Shell, Value},
     types,
 };
 use builtins_proc::builtin;
@@ -47,13 +92,13 @@ use std::{
     borrow::Cow,
     collections::HashMap,
     fs::File,
-    io::{self, BufRead, BufReader},
+    io::{BufRead, BufReader},
     path::{Path, PathBuf},
     process::{Command, Stdio},
 };
 
 const HELP_DESC: &str = "Display helpful information about a given command or list commands if \
-                         none specified\n    help <command>";
+                         none specified\n    help [-s] <command>";
 
 const SOURCE_DESC: &str = "Evaluate the file following the command or re-initialize the init file";
 
@@ -102,6 +147,7 @@ fn parse_numeric_arg(arg: &str) -> Option<(bool, usize)> {
 pub struct BuiltinMap<'a> {
     fcts: HashMap<&'static str, BuiltinFunction<'a>>,
     help: HashMap<&'static str, &'static str>,
+    man:  HashMap<&'static str, &'static str>,
 }
 
 impl<'a> Default for BuiltinMap<'a> {
@@ -112,7 +158,8 @@ impl<'a> Default for BuiltinMap<'a> {
             .with_variables()
             .with_process_control()
             .with_values_tests()
-            .with_files_and_directory();
+            .with_files_and_directory()
+            .with_terminal();
         builtins
     }
 }
@@ -127,11 +174,17 @@ impl<'a> BuiltinMap<'a> {
     /// Create a new, blank builtin map
     ///
     /// If you have a hint over the number of builtins, with_capacity is probably better
-    pub fn new() -> Self { BuiltinMap { fcts: HashMap::new(), help: HashMap::new() } }
+    pub fn new() -> Self {
+        BuiltinMap { fcts: HashMap::new(), help: HashMap::new(), man: HashMap::new() }
+    }
 
     /// Create a new, blank builtin map with a given capacity
     pub fn with_capacity(cap: usize) -> Self {
-        BuiltinMap { fcts: HashMap::with_capacity(cap), help: HashMap::with_capacity(cap) }
+        BuiltinMap {
+            fcts: HashMap::with_capacity(cap),
+            help: HashMap::with_capacity(cap),
+            man:  HashMap::with_capacity(cap),
+        }
     }
 
     /// Check if the given builtin exists
@@ -143,10 +196,14 @@ impl<'a> BuiltinMap<'a> {
     /// Get the provided help for a given builtin
     pub fn get_help(&self, func: &str) -> Option<&str> { self.help.get(func).copied() }
 
+    /// Get the man page of a given builtin, if it was added with one
+    pub fn get_man(&self, func: &str) -> Option<&str> { self.man.get(func).copied() }
+
     /// Get the function of a given builtin
     pub fn get(&self, func: &str) -> Option<BuiltinFunction<'a>> { self.fcts.get(func).cloned() }
 
-    /// Add a new builtin
+    /// Add a new builtin. A man page it was previously added with is kept, so that a builtin can
+    /// be wrapped in another function without losing its documentation.
     pub fn add(
         &mut self,
         name: &'static str,
@@ -158,99 +215,354 @@ impl<'a> BuiltinMap<'a> {
         self
     }
 
+    /// Add a new builtin with its man page, as printed by `help`. The builtins defined with the
+    /// `builtin` attribute have theirs in a `MAN_` constant, such as `MAN_ECHO` for `echo`.
+    pub fn add_with_man(
+        &mut self,
+        name: &'static str,
+        func: BuiltinFunction<'a>,
+        help: &'static str,
+        man: &'static str,
+    ) -> &mut Self {
+        self.man.insert(name, man);
+        self.add(name, func, help)
+    }
+
     /// Create and control variables
     ///
-    /// Contains `fn`, `alias`, `unalias`, `drop`, `read`
+    /// Contains `fn`, `alias`, `unalias`, `drop`, `read`, `declare`, `typeset`, `scope`,
+    /// `cache-clear`, `cache-stats`, `to-json`, `from-json`, `path-add`, `path-prepend`,
+    /// `path-remove`, `path-clean`, `path-list`, `env-diff`
     pub fn with_variables(&mut self) -> &mut Self {
-        self.add("fn", &builtin_fn_, "Print list of functions")
-            .add("alias", &builtin_alias, "View, set or unset aliases")
-            .add("unalias", &builtin_unalias, "Delete an alias")
-            .add("drop", &builtin_drop, "Delete a variable")
-            .add("read", &builtin_read, "Read some variables\n    read <variable>")
+        self.add_with_man("fn", &builtin_fn_, "Print list of functions", MAN_FN)
+            .add_with_man(
+                "cache-clear",
+                &builtin_cache_clear,
+                "Clear the cache of memoized functions",
+                MAN_CACHE_CLEAR,
+            )
+            .add_with_man(
+                "cache-stats",
+                &builtin_cache_stats,
+                "Print the statistics of memoized functions",
+                MAN_CACHE_STATS,
+            )
+            .add_with_man("alias", &builtin_alias, "View, set or unset aliases", MAN_ALIAS)
+            .add_with_man("unalias", &builtin_unalias, "Delete an alias", MAN_UNALIAS)
+            .add_with_man("drop", &builtin_drop, "Delete a variable", MAN_DROP)
+            .add_with_man(
+                "declare",
+                &builtin_declare,
+                "Declare variables with attributes",
+                MAN_DECLARE,
+            )
+            .add_with_man(
+                "typeset",
+                &builtin_declare,
+                "Declare variables with attributes",
+                MAN_DECLARE,
+            )
+            .add_with_man(
+                "scope",
+                &builtin_scope,
+                "Print the variables of the current scope",
+                MAN_SCOPE,
+            )
+            .add_with_man(
+                "read",
+                &builtin_read,
+                "Read some variables\n    read <variable>",
+                MAN_READ,
+            )
+            .add_with_man("to-json", &builtin_to_json, "Print a variable as JSON", MAN_TO_JSON)
+            .add_with_man(
+                "from-json",
+                &builtin_from_json,
+                "Set a variable from JSON",
+                MAN_FROM_JSON,
+            )
+            .add_with_man(
+                "path-add",
+                &builtin_path_add,
+                "Append directories to $PATH",
+                MAN_PATH_ADD,
+            )
+            .add_with_man(
+                "path-prepend",
+                &builtin_path_prepend,
+                "Prepend directories to $PATH",
+                MAN_PATH_PREPEND,
+            )
+            .add_with_man(
+                "path-remove",
+                &builtin_path_remove,
+                "Remove directories from $PATH",
+                MAN_PATH_REMOVE,
+            )
+            .add_with_man(
+                "path-clean",
+                &builtin_path_clean,
+                "Remove missing and duplicate $PATH entries",
+                MAN_PATH_CLEAN,
+            )
+            .add_with_man(
+                "path-list",
+                &builtin_path_list,
+                "Print the directories of $PATH",
+                MAN_PATH_LIST,
+            )
+            .add_with_man(
+                "env-diff",
+                &builtin_env_diff,
+                "Print the changes of the environment since the shell started",
+                MAN_ENV_DIFF,
+            )
     }
 
     /// Control subrpocesses states
     ///
-    /// Contains `disown`, `bg`, `fg`, `wait`, `isatty`, `jobs`
+    /// Contains `disown`, `bg`, `fg`, `wait`, `isatty`, `jobs`, `kill`, `lock`
     pub fn with_process_control(&mut self) -> &mut Self {
-        self.add("disown", &builtin_disown, DISOWN_DESC)
-            .add("bg", &builtin_bg, "Resumes a stopped background process")
-            .add("fg", &builtin_fg, "Resumes and sets a background process as the active process")
-            .add(
+        self.add_with_man("disown", &builtin_disown, DISOWN_DESC, MAN_DISOWN)
+            .add_with_man("bg", &builtin_bg, "Resumes a stopped background process", MAN_BG)
+            .add_with_man(
+                "fg",
+                &builtin_fg,
+                "Resumes and sets a background process as the active process",
+                MAN_FG,
+            )
+            .add_with_man(
                 "wait",
                 &builtin_wait,
                 "Waits until all running background processes have completed",
+                MAN_WAIT,
+            )
+            .add_with_man(
+                "isatty",
+                &builtin_isatty,
+                "Returns 0 exit status if the supplied FD is a tty",
+                MAN_ISATTY,
+            )
+            .add_with_man(
+                "jobs",
+                &builtin_jobs,
+                "Displays all jobs that are attached to the background",
+                MAN_JOBS,
+            )
+            .add_with_man("kill", &builtin_kill, "Sends a signal to jobs and processes", MAN_KILL)
+            .add_with_man(
+                "lock",
+                &builtin_lock,
+                "Run a command while holding a lock on a file",
+                MAN_LOCK,
             )
-            .add("isatty", &builtin_isatty, "Returns 0 exit status if the supplied FD is a tty")
-            .add("jobs", &builtin_jobs, "Displays all jobs that are attached to the background")
     }
 
     /// Utilities concerning the filesystem
     ///
-    /// Contains `test`, `exists`, `popd`, `pushd`, `dirs`, `cd`
+    /// Contains `test`, `exists`, `popd`, `pushd`, `dirs`, `cd`, `tmpfile`, `tmpdir`
     pub fn with_files_and_directory(&mut self) -> &mut Self {
-        self.add("test", &builtin_test, "Performs tests on files and text")
-            .add("exists", &builtin_exists, "Performs tests on files and text")
-            .add("popd", &builtin_popd, "Pop a directory from the stack")
-            .add("pushd", &builtin_pushd, "Push a directory to the stack")
-            .add("dirs", &builtin_dirs, "Display the current directory stack")
-            .add("cd", &builtin_cd, "Change the current directory\n    cd <path>")
-            .add("dir_depth", &builtin_dir_depth, "Set the maximum directory depth")
+        self.add_with_man("test", &builtin_test, "Performs tests on files and text", MAN_TEST)
+            .add_with_man("exists", &builtin_exists, "Performs tests on files and text", MAN_EXISTS)
+            .add_with_man("popd", &builtin_popd, "Pop a directory from the stack", MAN_POPD)
+            .add_with_man("pushd", &builtin_pushd, "Push a directory to the stack", MAN_PUSHD)
+            .add_with_man("dirs", &builtin_dirs, "Display the current directory stack", MAN_DIRS)
+            .add_with_man("cd", &builtin_cd, "Change the current directory\n    cd <path>", MAN_CD)
+            .add_with_man(
+                "dir_depth",
+                &builtin_dir_depth,
+                "Set the maximum directory depth",
+                MAN_DIR_DEPTH,
+            )
+            .add_with_man(
+                "tmpfile",
+                &builtin_tmpfile,
+                "Create a temporary file removed on exit",
+                MAN_TMPFILE,
+            )
+            .add_with_man(
+                "tmpdir",
+                &builtin_tmpdir,
+                "Create a temporary directory removed on exit",
+                MAN_TMPDIR,
+            )
     }
 
     /// Utilities to test values
     ///
     /// Contains `bool`, `math`, `eq`, `is`, `true`, `false`, `starts-with`, `ends-with`,
-    /// `contains`, `matches`, `random`
+    /// `contains`, `element-of`, `matches`, `random`, `seq`
     pub fn with_values_tests(&mut self) -> &mut Self {
-        self.add("bool", &builtin_bool, "If the value is '1' or 'true', return 0 exit status")
-            .add("math", &builtin_math, "Calculate a mathematical expression")
-            .add("eq", &builtin_is, "Simple alternative to == and !=")
-            .add("is", &builtin_is, "Simple alternative to == and !=")
-            .add("true", &builtin_true_, "Do nothing, successfully")
-            .add("false", &builtin_false_, "Do nothing, unsuccessfully")
-            .add(
-                "starts-with",
-                &builtin_starts_with,
-                "Evaluates if the supplied argument starts with a given string",
+        self.add_with_man(
+            "bool",
+            &builtin_bool,
+            "If the value is '1' or 'true', return 0 exit status",
+            MAN_BOOL,
+        )
+        .add_with_man("math", &builtin_math, "Calculate a mathematical expression", MAN_MATH)
+        .add_with_man("eq", &builtin_is, "Simple alternative to == and !=", MAN_IS)
+        .add_with_man("is", &builtin_is, "Simple alternative to == and !=", MAN_IS)
+        .add_with_man("true", &builtin_true_, "Do nothing, successfully", MAN_TRUE)
+        .add_with_man("false", &builtin_false_, "Do nothing, unsuccessfully", MAN_FALSE)
+        .add_with_man(
+            "starts-with",
+            &builtin_starts_with,
+            "Evaluates if the supplied argument starts with a given string",
+            MAN_STARTS_WITH,
+        )
+        .add_with_man(
+            "ends-with",
+            &builtin_ends_with,
+            "Evaluates if the supplied argument ends with a given string",
+            MAN_ENDS_WITH,
+        )
+        .add_with_man(
+            "contains",
+            &builtin_contains,
+            "Evaluates if the supplied argument contains a given string",
+            MAN_CONTAINS,
+        )
+        .add_with_man(
+            "element-of",
+            &builtin_element_of,
+            "Evaluates if the supplied values are elements of an array",
+            MAN_ELEMENT_OF,
+        )
+        .add_with_man(
+            "matches",
+            &builtin_matches,
+            "Checks if a string matches a given regex",
+            MAN_MATCHES,
+        )
+        .add_with_man("random", &builtin_random, "Outputs a random u64", MAN_RANDOM)
+        .add_with_man("seq", &builtin_seq, "Print a sequence of numbers", MAN_SEQ)
+    }
+
+    /// Basic utilities for any ion embedded library
+    ///
+    /// Contains `help`, `source`, `status`, `echo`, `color`, `progress`, `notify`, `type`,
+    /// `which`, `hook`, `watches`, `unwatch`
+    pub fn with_basic(&mut self) -> &mut Self {
+        self.add_with_man("help", &builtin_help, HELP_DESC, MAN_HELP)
+            .add_with_man("source", &builtin_source, SOURCE_DESC, MAN_SOURCE)
+            .add_with_man(
+                "status",
+                &builtin_status,
+                "Evaluates the current runtime status",
+                MAN_STATUS,
+            )
+            .add_with_man("echo", &builtin_echo, "Display a line of text", MAN_ECHO)
+            .add_with_man("color", &builtin_color, "Display a line of text in color", MAN_COLOR)
+            .add_with_man("progress", &builtin_progress, "Display a progress bar", MAN_PROGRESS)
+            .add_with_man("notify", &builtin_notify, "Send a desktop notification", MAN_NOTIFY)
+            .add_with_man(
+                "which",
+                &builtin_which,
+                "Locate what would be called for a given command",
+                MAN_WHICH,
             )
-            .add(
-                "ends-with",
-                &builtin_ends_with,
-                "Evaluates if the supplied argument ends with a given string",
+            .add_with_man(
+                "type",
+                &builtin_type_,
+                "indicates what would be called for a given command",
+                MAN_TYPE,
             )
-            .add(
-                "contains",
-                &builtin_contains,
-                "Evaluates if the supplied argument contains a given string",
+            .add_with_man(
+                "hook",
+                &builtin_hook,
+                "Call functions at some points of the shell's execution",
+                MAN_HOOK,
             )
-            .add("matches", &builtin_matches, "Checks if a string matches a given regex")
-            .add("random", &builtin_random, "Outputs a random u64")
+            .add_with_man("watches", &builtin_watches, "List the watched variables", MAN_WATCHES)
+            .add_with_man("unwatch", &builtin_unwatch, "Stop watching variables", MAN_UNWATCH)
     }
 
-    /// Basic utilities for any ion embedded library
+    /// Query and control the terminal, without forking `tput`
     ///
-    /// Contains `help`, `source`, `status`, `echo`, `type`, `which`
-    pub fn with_basic(&mut self) -> &mut Self {
-        self.add("help", &builtin_help, HELP_DESC)
-            .add("source", &builtin_source, SOURCE_DESC)
-            .add("status", &builtin_status, "Evaluates the current runtime status")
-            .add("echo", &builtin_echo, "Display a line of text")
-            .add("which", &builtin_which, "indicates what would be called for a given command")
-            .add("type", &builtin_which, "indicates what would be called for a given command")
+    /// Contains `term-cols`, `term-rows`, `term-clear`, `term-move`, `term-hide-cursor`,
+    /// `term-show-cursor`, `term-save-cursor`, `term-restore-cursor`, `term-bold`, `term-reset`
+    pub fn with_terminal(&mut self) -> &mut Self {
+        self.add_with_man(
+            "term-cols",
+            &builtin_term_cols,
+            "Print the number of columns of the terminal",
+            MAN_TERM_COLS,
+        )
+        .add_with_man(
+            "term-rows",
+            &builtin_term_rows,
+            "Print the number of rows of the terminal",
+            MAN_TERM_ROWS,
+        )
+        .add_with_man("term-clear", &builtin_term_clear, "Clear the terminal", MAN_TERM_CLEAR)
+        .add_with_man(
+            "term-move",
+            &builtin_term_move,
+            "Move the cursor\n    term-move <row> <col>",
+            MAN_TERM_MOVE,
+        )
+        .add_with_man(
+            "term-hide-cursor",
+            &builtin_term_hide_cursor,
+            "Hide the cursor",
+            MAN_TERM_HIDE_CURSOR,
+        )
+        .add_with_man(
+            "term-show-cursor",
+            &builtin_term_show_cursor,
+            "Show the cursor",
+            MAN_TERM_SHOW_CURSOR,
+        )
+        .add_with_man(
+            "term-save-cursor",
+            &builtin_term_save_cursor,
+            "Save the position of the cursor",
+            MAN_TERM_SAVE_CURSOR,
+        )
+        .add_with_man(
+            "term-restore-cursor",
+            &builtin_term_restore_cursor,
+            "Restore the position of the cursor",
+            MAN_TERM_RESTORE_CURSOR,
+        )
+        .add_with_man(
+            "term-bold",
+            &builtin_term_bold,
+            "Print the following text in bold",
+            MAN_TERM_BOLD,
+        )
+        .add_with_man(
+            "term-reset",
+            &builtin_term_reset,
+            "Reset the attributes of the text",
+            MAN_TERM_RESET,
+        )
     }
 
     /// Utilities that may be a security risk. Not included by default
     ///
-    /// Contains `eval`, `set`
+    /// Contains `eval`, `set`, `source-sh`, `spinner`, `env`
     pub fn with_unsafe(&mut self) -> &mut Self {
-        self.add("eval", &builtin_eval, "Evaluates the evaluated expression")
-            .add(
+        self.add_with_man("eval", &builtin_eval, "Evaluates the evaluated expression", MAN_EVAL)
+            .add_with_man(
                 "set",
                 &builtin_set,
                 "Set or unset values of shell options and positional parameters.",
+                MAN_SET,
+            )
+            .add_with_man(
+                "source-sh",
+                &builtin_source_sh,
+                "Execute a sh script and load the env diff",
+                MAN_SOURCE_SH,
+            )
+            .add_with_man(
+                "spinner",
+                &builtin_spinner,
+                "Display a spinner while a command runs",
+                MAN_SPINNER,
             )
-            .add("source-sh", &builtin_source_sh, "Execute a sh script and load the env diff")
+            .add_with_man("env", &builtin_env, "Run a command in a modified environment", MAN_ENV)
     }
 }
 
@@ -343,38 +655,39 @@ pub fn dir_depth(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
     man = "
 SYNOPSIS
     cd DIRECTORY
+    cd -
+    cd -N
 
 DESCRIPTION
     Without arguments cd changes the working directory to your home directory.
     With arguments cd changes the working directory to the directory you provided.
+    With - as argument, cd changes to the previous working directory ($OLDPWD) and prints it.
+    With -N as argument, cd changes to the directory N entries back in the directory stack, as
+    listed by dirs, and prints it: cd -1 is the previous directory visited by cd or pushd.
 "
 )]
 pub fn cd(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
-    let err = match args.get(1) {
-        Some(dir) => {
+    let err = match (args.get(1), args.get(1).and_then(|arg| parse_numeric_arg(arg))) {
+        (Some(dir), _) if dir == "-" => shell.dir_stack_mut().switch_to_previous_directory(),
+        (_, Some((false, index))) => shell.dir_stack_mut().switch_to_stacked_directory(index),
+        (Some(dir), _) => {
             let dir = dir.as_str();
             if let Some(Value::Array(cdpath)) = shell.variables().get("CDPATH").cloned() {
-                if dir == "-" {
-                    shell.dir_stack_mut().switch_to_previous_directory()
-                } else {
-                    let check_cdpath_first = cdpath
-                        .iter()
-                        .map(|path| {
-                            let path_dir = Path::new(&path.to_string()).join(dir);
-                            shell.dir_stack_mut().change_and_push_dir(&path_dir)
-                        })
-                        .find(Result::is_ok)
-                        .unwrap_or_else(|| {
-                            shell.dir_stack_mut().change_and_push_dir(Path::new(dir))
-                        });
-                    shell.dir_stack_mut().popd(1);
-                    check_cdpath_first
-                }
+                let check_cdpath_first = cdpath
+                    .iter()
+                    .map(|path| {
+                        let path_dir = Path::new(&path.to_string()).join(dir);
+                        shell.dir_stack_mut().change_and_push_dir(&path_dir)
+                    })
+                    .find(Result::is_ok)
+                    .unwrap_or_else(|| shell.dir_stack_mut().change_and_push_dir(Path::new(dir)));
+                shell.dir_stack_mut().popd(1);
+                check_cdpath_first
             } else {
                 shell.dir_stack_mut().change_and_push_dir(Path::new(dir))
             }
         }
-        None => shell.dir_stack_mut().switch_to_home_directory(),
+        (None, _) => shell.dir_stack_mut().switch_to_home_directory(),
     };
 
     match err {
@@ -382,6 +695,7 @@ pub fn cd(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
             if let Some(Value::Function(function)) = shell.variables().get("CD_CHANGE").cloned() {
                 let _ = shell.execute_function(&function, &["ion"]);
             }
+            shell.run_hook(Hook::Chpwd, &[]);
             Status::SUCCESS
         }
         Err(why) => Status::error(format!("{}", why)),
@@ -559,6 +873,9 @@ pub fn pushd(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
             .map(|dir| dir.to_str().unwrap_or("ion: no directory found"))
             .format(" ")
     );
+    if !keep_front {
+        shell.run_hook(Hook::Chpwd, &[]);
+    }
     Status::SUCCESS
 }
 
@@ -618,6 +935,9 @@ pub fn popd(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
             return Status::error(format!("ion: popd: {}", err));
         }
         println!("{}", shell.dir_stack().dirs().map(|dir| dir.display()).format(" "));
+        if index == 0 {
+            shell.run_hook(Hook::Chpwd, &[]);
+        }
         Status::SUCCESS
     } else {
         Status::error(format!("ion: popd: {}: directory stack index out of range", index))
@@ -634,16 +954,25 @@ impl Completer for EmptyCompleter {
     desc = "read a line of input into some variables",
     man = "
 SYNOPSIS
-    read VARIABLES...
+    read [--] VARIABLES...
 
 DESCRIPTION
     For each variable reads from standard input and stores the results in the variable.
+    Fails once the end of the input is reached, so that a file can be read line by line with
+    `while read line < FILE`, which opens the file once for the whole loop.
 "
 )]
 pub fn read(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
+    let names = match &args[1..] {
+        [separator, names @ ..] if separator == "--" => names,
+        names => names,
+    };
+    if let Some(arg) = names.iter().find(|arg| shell.variables().is_readonly(arg)) {
+        return Status::error(format!("ion: cannot modify readonly variable `{}`", arg));
+    }
     if atty::is(atty::Stream::Stdin) {
         let mut con = Context::new();
-        for arg in args.iter().skip(1) {
+        for arg in names.iter() {
             match con.read_line(Prompt::from(format!("{}=", arg.trim())), None, &mut EmptyCompleter)
             {
                 Ok(buffer) => {
@@ -653,18 +982,49 @@ pub fn read(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
             }
         }
     } else {
-        let stdin = io::stdin();
-        let handle = stdin.lock();
-        let mut lines = handle.lines();
-        for arg in args.iter().skip(1) {
-            if let Some(Ok(line)) = lines.next() {
-                shell.variables_mut().set(arg.as_ref(), line.trim());
+        for arg in names.iter() {
+            match read_line_unbuffered() {
+                Some(line) => {
+                    shell.variables_mut().set(arg.as_ref(), line.trim());
+                }
+                None => return Status::FALSE,
             }
         }
     }
     Status::SUCCESS
 }
 
+/// Reads a line from the standard input without consuming anything past its end, so that the
+/// rest of the input is left to the next reader, such as the next iteration of
+/// `while read line < file`. Regular files are read in chunks, seeking back to the end of the
+/// line, and other inputs one byte at a time. `None` is returned at the end of the input.
+fn read_line_unbuffered() -> Option<String> {
+    use nix::unistd::{self, Whence};
+    const CHUNK: usize = 4096;
+
+    let fd = nix::libc::STDIN_FILENO;
+    let size = if unistd::lseek(fd, 0, Whence::SeekCur).is_ok() { CHUNK } else { 1 };
+    let mut buffer = [0; CHUNK];
+    let mut line = Vec::new();
+    loop {
+        let read = match unistd::read(fd, &mut buffer[..size]) {
+            Ok(0) | Err(_) if line.is_empty() => return None,
+            Ok(0) | Err(_) => break,
+            Ok(read) => read,
+        };
+        if let Some(end) = buffer[..read].iter().position(|&byte| byte == b'\n') {
+            line.extend_from_slice(&buffer[..end]);
+            let unread = (read - end - 1) as nix::libc::off_t;
+            if unread != 0 {
+                let _ = unistd::lseek(fd, -unread, Whence::SeekCur);
+            }
+            break;
+        }
+        line.extend_from_slice(&buffer[..read]);
+    }
+    Some(String::from_utf8_lossy(&line).into_owned())
+}
+
 #[builtin(
     desc = "evaluates the specified commands",
     man = "
@@ -746,14 +1106,23 @@ pub fn wait(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
     desc = "list all jobs running in the background",
     man = "
 SYNOPSIS
-    jobs
+    jobs [--json]
 
 DESCRIPTION
-    Prints a list of all jobs running in the background."
+    Prints a list of all jobs running in the background.
+
+OPTIONS
+    --json
+        print a JSON array with an object for each job, whose fields are id, pid, status
+        (running or stopped) and command. This is the default when the json-output option is set."
 )]
 pub fn jobs(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
-    job_control::jobs(shell);
-    Status::SUCCESS
+    let json = match args.get(1).map(types::Str::as_str) {
+        None => shell.opts().json_output,
+        Some("--json") if args.len() == 2 => true,
+        Some(arg) => return Status::bad_argument(format!("jobs: invalid argument '{}'", arg)),
+    };
+    job_control::jobs(shell, json)
 }
 
 #[builtin(
@@ -782,6 +1151,27 @@ pub fn fg(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
     job_control::fg(shell, &args[1..])
 }
 
+#[builtin(
+    desc = "send a signal to jobs and processes",
+    man = "
+SYNOPSIS
+    kill [ --help ] [-SIGNAL | -s SIGNAL] [%JOB | PID]...
+    kill -l
+
+DESCRIPTION
+    kill sends a signal to the given jobs and processes. Jobs are designated by their number in
+    the job table, prefixed with '%', as listed by the jobs builtin. The signal defaults to TERM.
+
+OPTIONS
+    -SIGNAL, -s SIGNAL
+        the signal to send, by name with or without the SIG prefix, or by number: -TERM,
+        -SIGKILL, -9
+    -l  list the names of the signals"
+)]
+pub fn kill(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
+    job_control::kill(shell, &args[1..])
+}
+
 #[builtin(
     desc = "disown processes",
     man = "
@@ -804,26 +1194,77 @@ pub fn disown(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
     }
 }
 
+/// The documentation of a builtin: its man page, or the first line of the SYNOPSIS section of it
+/// if `short` is set. Builtins added without man page are described by their short help.
+fn documentation(builtins: &BuiltinMap<'_>, name: &str, short: bool) -> Option<String> {
+    let help = builtins.get_help(name)?;
+    let described = || format!("{} - {}", name, help.lines().next().unwrap_or_default());
+    Some(match builtins.get_man(name) {
+        Some(man) if !short => man.to_owned(),
+        Some(man) => man
+            .lines()
+            .skip_while(|line| !matches!(line.trim(), "SYNOPSIS" | "SYNOPSYS"))
+            .skip(1)
+            .map(str::trim)
+            .find(|line| !line.is_empty())
+            .map_or_else(described, Into::into),
+        None if short => described(),
+        None => format!("{} - {}", name, help),
+    })
+}
+
 #[builtin(
     desc = "get help for builtins",
     man = "
 SYNOPSIS
-    help [BUILTIN]
+    help [ -h | --help ] [ -s | --short ] [BUILTIN...]
+    help --all
 
 DESCRIPTION
-    Get the short description for BUILTIN. If no argument is provided, list all the builtins"
+    Prints the documentation of each BUILTIN, which is its man page, or its short description if
+    it has none. If no argument is provided, list all the builtins.
+
+OPTIONS
+    -s, --short
+        print a one-line synopsis of each BUILTIN instead
+    --all
+        print the documentation of every builtin
+
+EXAMPLES
+    help echo
+    help -s cd pushd popd"
 )]
 pub fn help(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
-    if let Some(command) = args.get(1) {
-        if let Some(help) = shell.builtins().get_help(command) {
-            println!("{}", help);
-        } else {
-            println!("Command helper not found [run 'help']...");
+    let builtins = shell.builtins();
+    let (short, names) = match args.get(1).map(types::Str::as_str) {
+        None => {
+            println!("{}", builtins.keys().sorted().format("\n"));
+            return Status::SUCCESS;
+        }
+        Some("--all") if args.len() == 2 => {
+            let pages =
+                builtins.keys().sorted().filter_map(|name| documentation(builtins, name, false));
+            println!("{}", pages.format("\n\n"));
+            return Status::SUCCESS;
+        }
+        Some("--all") => return Status::bad_argument("help: --all does not take builtins"),
+        Some("-s") | Some("--short") if args.len() == 2 => {
+            return Status::bad_argument("help: no builtin given")
+        }
+        Some("-s") | Some("--short") => (true, &args[2..]),
+        Some(_) => (false, &args[1..]),
+    };
+
+    let mut status = Status::SUCCESS;
+    for name in names {
+        match documentation(builtins, name, short) {
+            Some(documentation) => println!("{}", documentation),
+            None => {
+                status = Status::error(format!("help: no builtin named '{}' [run 'help']", name))
+            }
         }
-    } else {
-        println!("{}", shell.builtins().keys().sorted().format("\n"));
     }
-    Status::SUCCESS
+    status
 }
 
 use regex::Regex;
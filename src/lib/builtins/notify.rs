@@ -0,0 +1,136 @@
+use super::Status;
+use crate as ion_shell;
+use crate::{types, Shell};
+use builtins_proc::builtin;
+use std::process::{Command, Stdio};
+
+/// The options of a notification
+#[cfg_attr(target_os = "macos", allow(dead_code))]
+struct Notification<'a> {
+    title:       &'a str,
+    message:     &'a str,
+    urgency:     Option<&'a str>,
+    icon:        Option<&'a str>,
+    expire_time: Option<u32>,
+}
+
+impl<'a> Notification<'a> {
+    /// The command sending the notification with the native mechanism of the platform
+    #[cfg(target_os = "macos")]
+    fn command(&self) -> Command {
+        let escape = |text: &str| text.replace('\\', "\\\\").replace('"', "\\\"");
+        let mut command = Command::new("osascript");
+        command.arg("-e").arg(format!(
+            "display notification \"{}\" with title \"{}\"",
+            escape(self.message),
+            escape(self.title)
+        ));
+        command
+    }
+
+    /// The command sending the notification with the native mechanism of the platform
+    #[cfg(not(target_os = "macos"))]
+    fn command(&self) -> Command {
+        let mut command = Command::new("notify-send");
+        if let Some(urgency) = self.urgency {
+            command.arg("--urgency").arg(urgency);
+        }
+        if let Some(icon) = self.icon {
+            command.arg("--icon").arg(icon);
+        }
+        if let Some(expire_time) = self.expire_time {
+            command.arg("--expire-time").arg(expire_time.to_string());
+        }
+        command.arg(self.title).arg(self.message);
+        command
+    }
+
+    /// Send the notification, falling back to stderr if the native mechanism is unavailable
+    fn send(&self) {
+        let sent = self
+            .command()
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .map_or(false, |status| status.success());
+        if !sent {
+            if self.message.is_empty() {
+                eprintln!("{}", self.title);
+            } else {
+                eprintln!("{}: {}", self.title, self.message);
+            }
+        }
+    }
+}
+
+#[builtin(
+    desc = "send a desktop notification",
+    man = "
+SYNOPSIS
+    notify [ -h | --help ] [--urgency low|normal|critical] [--icon ICON] [--expire-time MS] TITLE
+        [MESSAGE]
+
+DESCRIPTION
+    Sends a desktop notification, using notify-send on Linux and the BSDs, and osascript on macOS.
+    When the notification can not be sent, it is printed to the standard error instead.
+
+OPTIONS
+    --urgency low|normal|critical
+        the urgency of the notification
+    --icon ICON
+        the name or the path of the icon to display
+    --expire-time MS
+        the time after which the notification is closed, in milliseconds
+
+EXAMPLES
+    cargo build; notify --urgency low 'Build finished' \"status: $?\""
+)]
+pub fn notify(args: &[types::Str], _: &mut Shell<'_>) -> Status {
+    let (mut urgency, mut icon, mut expire_time) = (None, None, None);
+    let mut positionals = Vec::with_capacity(2);
+
+    let mut args = args[1..].iter();
+    while let Some(arg) = args.next() {
+        let option = match arg.as_str() {
+            "--urgency" | "--icon" | "--expire-time" => arg.as_str(),
+            _ => {
+                positionals.push(arg.as_str());
+                continue;
+            }
+        };
+        let value = match args.next() {
+            Some(value) => value.as_str(),
+            None => return Status::bad_argument(format!("notify: {} requires a value", option)),
+        };
+        match option {
+            "--urgency" if ["low", "normal", "critical"].contains(&value) => urgency = Some(value),
+            "--urgency" => {
+                return Status::bad_argument(format!(
+                    "notify: invalid urgency '{}': expected low, normal or critical",
+                    value
+                ))
+            }
+            "--icon" => icon = Some(value),
+            _ => match value.parse::<u32>() {
+                Ok(value) => expire_time = Some(value),
+                Err(_) => {
+                    return Status::bad_argument(format!(
+                        "notify: invalid expire time '{}': expected milliseconds",
+                        value
+                    ))
+                }
+            },
+        }
+    }
+
+    let (title, message) = match positionals.as_slice() {
+        [title] => (*title, ""),
+        [title, message] => (*title, *message),
+        [] => return Status::bad_argument("notify: no title given"),
+        _ => return Status::bad_argument("notify: too many arguments"),
+    };
+
+    Notification { title, message, urgency, icon, expire_time }.send();
+    Status::SUCCESS
+}
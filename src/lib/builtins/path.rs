@@ -0,0 +1,201 @@
+use super::Status;
+use crate as ion_shell;
+use crate::{types, Shell};
+use builtins_proc::builtin;
+use std::{
+    env,
+    ffi::OsStr,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+/// The directories of `$PATH`, in order. The empty entries, which stand for the current
+/// directory, are left out.
+fn directories() -> Vec<PathBuf> { env::var_os("PATH").map_or_else(Vec::new, |path| split(&path)) }
+
+/// Split a value of `$PATH` into its non-empty directories
+fn split(path: &OsStr) -> Vec<PathBuf> {
+    env::split_paths(path).filter(|directory| !directory.as_os_str().is_empty()).collect()
+}
+
+/// Export the directories as the new `$PATH`
+fn set_directories(name: &str, directories: &[PathBuf]) -> Status {
+    match env::join_paths(directories) {
+        Ok(path) => {
+            env::set_var("PATH", path);
+            Status::SUCCESS
+        }
+        Err(why) => Status::error(format!("{}: {}", name, why)),
+    }
+}
+
+/// Whether two directories of `$PATH` are the same, ignoring trailing slashes
+fn same(first: &Path, second: &Path) -> bool { first == second }
+
+/// Check the directories given to a builtin, which can not be empty nor contain a `:`
+fn arguments<'a>(args: &'a [types::Str]) -> Result<Vec<&'a Path>, Status> {
+    if args.len() < 2 {
+        return Err(Status::bad_argument(format!("{}: no directory given", args[0])));
+    }
+    match args[1..].iter().find(|directory| directory.is_empty() || directory.contains(':')) {
+        Some(directory) => Err(Status::bad_argument(format!(
+            "{}: invalid directory '{}': it must not be empty nor contain ':'",
+            args[0], directory
+        ))),
+        None => Ok(args[1..].iter().map(|directory| Path::new(directory.as_str())).collect()),
+    }
+}
+
+#[builtin(
+    names = "path-add",
+    desc = "append directories to $PATH",
+    man = "
+SYNOPSIS
+    path-add [ -h | --help ] DIRECTORY...
+
+DESCRIPTION
+    Appends each DIRECTORY to the exported $PATH, unless it is already in it. Trailing slashes are
+    ignored when comparing the directories.
+
+EXAMPLES
+    path-add ~/.cargo/bin ~/.local/bin"
+)]
+pub fn path_add(args: &[types::Str], _: &mut Shell<'_>) -> Status {
+    let new = match arguments(args) {
+        Ok(new) => new,
+        Err(status) => return status,
+    };
+    let mut directories = directories();
+    for directory in new {
+        if !directories.iter().any(|existing| same(existing, directory)) {
+            directories.push(directory.to_path_buf());
+        }
+    }
+    set_directories(&args[0], &directories)
+}
+
+#[builtin(
+    names = "path-prepend",
+    desc = "prepend directories to $PATH",
+    man = "
+SYNOPSIS
+    path-prepend [ -h | --help ] DIRECTORY...
+
+DESCRIPTION
+    Puts the DIRECTORY arguments at the start of the exported $PATH, in the given order, so that
+    their commands take precedence. A directory which is already in $PATH is moved instead of
+    being added twice.
+
+EXAMPLES
+    path-prepend /usr/local/bin"
+)]
+pub fn path_prepend(args: &[types::Str], _: &mut Shell<'_>) -> Status {
+    let new = match arguments(args) {
+        Ok(new) => new,
+        Err(status) => return status,
+    };
+    let mut directories = directories();
+    directories.retain(|existing| !new.iter().any(|directory| same(existing, directory)));
+    let mut prepended = Vec::with_capacity(new.len() + directories.len());
+    for directory in new {
+        if !prepended.iter().any(|existing: &PathBuf| same(existing, directory)) {
+            prepended.push(directory.to_path_buf());
+        }
+    }
+    prepended.append(&mut directories);
+    set_directories(&args[0], &prepended)
+}
+
+#[builtin(
+    names = "path-remove",
+    desc = "remove directories from $PATH",
+    man = "
+SYNOPSIS
+    path-remove [ -h | --help ] DIRECTORY...
+
+DESCRIPTION
+    Removes every occurrence of each DIRECTORY from the exported $PATH. Trailing slashes are
+    ignored when comparing the directories.
+
+EXAMPLES
+    path-remove /opt/old/bin"
+)]
+pub fn path_remove(args: &[types::Str], _: &mut Shell<'_>) -> Status {
+    let removed = match arguments(args) {
+        Ok(removed) => removed,
+        Err(status) => return status,
+    };
+    let mut directories = directories();
+    directories.retain(|existing| !removed.iter().any(|directory| same(existing, directory)));
+    set_directories(&args[0], &directories)
+}
+
+#[builtin(
+    names = "path-clean",
+    desc = "remove missing and duplicate directories from $PATH",
+    man = "
+SYNOPSIS
+    path-clean [ -h | --help ]
+
+DESCRIPTION
+    Removes the entries of the exported $PATH which are empty, which are not existing directories,
+    or which appeared earlier in $PATH."
+)]
+pub fn path_clean(args: &[types::Str], _: &mut Shell<'_>) -> Status {
+    if args.len() > 1 {
+        return Status::bad_argument("path-clean: no argument is expected");
+    }
+    let mut cleaned: Vec<PathBuf> = Vec::new();
+    for directory in directories() {
+        if directory.is_dir() && !cleaned.iter().any(|existing| same(existing, &directory)) {
+            cleaned.push(directory);
+        }
+    }
+    set_directories("path-clean", &cleaned)
+}
+
+#[builtin(
+    names = "path-list",
+    desc = "print the directories of $PATH",
+    man = "
+SYNOPSIS
+    path-list [ -h | --help ]
+
+DESCRIPTION
+    Prints each directory of the exported $PATH on its own line, in order."
+)]
+pub fn path_list(args: &[types::Str], _: &mut Shell<'_>) -> Status {
+    if args.len() > 1 {
+        return Status::bad_argument("path-list: no argument is expected");
+    }
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    directories()
+        .iter()
+        .try_for_each(|directory| writeln!(stdout, "{}", directory.display()))
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_directories() {
+        let same = |first: &str, second: &str| same(first.as_ref(), second.as_ref());
+        assert!(same("/usr/bin", "/usr/bin/"));
+        assert!(same("/", "//"));
+        assert!(same("bin", "bin/"));
+        assert!(!same("/usr/bin", "/usr/local/bin"));
+        assert!(!same("/", "."));
+    }
+
+    #[test]
+    fn empty_entries_are_ignored() {
+        assert!(split("".as_ref()).is_empty());
+        assert_eq!(
+            split("/usr/bin::/bin:".as_ref()),
+            vec![PathBuf::from("/usr/bin"), PathBuf::from("/bin")]
+        );
+    }
+}
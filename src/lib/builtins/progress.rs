@@ -0,0 +1,130 @@
+use super::Status;
+use crate as ion_shell;
+use crate::{types, Shell};
+use builtins_proc::builtin;
+use std::io::{self, Write};
+
+const DEFAULT_FORMAT: &str = "[{bar}] {percent}% {message}";
+const DEFAULT_WIDTH: usize = 40;
+
+fn render(format: &str, width: usize, current: u64, total: u64, message: &str) -> String {
+    let current = current.min(total);
+    // Computed on 128 bits, as the products overflow for large numbers of steps
+    let ratio = |scale: u128| u128::from(current) * scale / u128::from(total);
+    let filled = ratio(width as u128) as usize;
+    let bar = "█".repeat(filled) + &"░".repeat(width - filled);
+    format
+        .replace("{bar}", &bar)
+        .replace("{percent}", &ratio(100).to_string())
+        .replace("{current}", &current.to_string())
+        .replace("{total}", &total.to_string())
+        .replace("{message}", message)
+}
+
+#[builtin(
+    desc = "display a progress bar",
+    man = "
+SYNOPSIS
+    progress [ -h | --help ] --total N --current M [--width W] [--format FORMAT] [MESSAGE]...
+
+DESCRIPTION
+    Draws a progress bar for M out of N steps, overwriting the bar previously drawn on the line.
+    Once M reaches N, the line is terminated so that the following output is printed below it.
+
+    Nothing is printed when standard output is not a terminal.
+
+OPTIONS
+    --total N
+        the number of steps to complete
+    --current M
+        the number of steps completed
+    --width W
+        the width of the bar, in characters. Defaults to 40.
+    --format FORMAT
+        the format of the line, where {bar}, {percent}, {current}, {total} and {message} are
+        replaced by their value. Defaults to '[{bar}] {percent}% {message}'.
+
+EXAMPLES
+    for i in 1..=$len(@items)
+        process_item @items[$((i - 1))]
+        progress --total $len(@items) --current $i Processing
+    end"
+)]
+pub fn progress(args: &[types::Str], _: &mut Shell<'_>) -> Status {
+    let mut total = None;
+    let mut current = None;
+    let mut width = DEFAULT_WIDTH;
+    let mut format = DEFAULT_FORMAT;
+    let mut message = Vec::new();
+
+    let mut args = args[1..].iter();
+    while let Some(arg) = args.next() {
+        let option = match arg.as_str() {
+            "--total" | "--current" | "--width" | "--format" => arg.as_str(),
+            _ => {
+                message.push(arg.as_str());
+                continue;
+            }
+        };
+
+        let value = match args.next() {
+            Some(value) => value.as_str(),
+            None => return Status::bad_argument(format!("progress: {} requires a value", option)),
+        };
+        if option == "--format" {
+            format = value;
+            continue;
+        }
+
+        let value = match value.parse::<u64>() {
+            Ok(value) => value,
+            Err(_) => {
+                return Status::bad_argument(format!(
+                    "progress: {} expects a number, found '{}'",
+                    option, value
+                ))
+            }
+        };
+        match option {
+            "--total" => total = Some(value),
+            "--current" => current = Some(value),
+            _ => width = value as usize,
+        }
+    }
+
+    let (total, current) = match (total, current) {
+        (Some(total), Some(current)) if total != 0 => (total, current),
+        (Some(_), Some(_)) => return Status::bad_argument("progress: the total can not be 0"),
+        _ => return Status::bad_argument("progress: --total and --current are required"),
+    };
+
+    if !atty::is(atty::Stream::Stdout) {
+        return Status::SUCCESS;
+    }
+
+    let line = render(format, width, current, total, &message.join(" "));
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    let result = write!(stdout, "\r\x1b[2K{}", line)
+        .and_then(|_| if current >= total { writeln!(stdout) } else { Ok(()) })
+        .and_then(|_| stdout.flush());
+    result.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_bar() {
+        assert_eq!(render(DEFAULT_FORMAT, 4, 1, 2, "Processing"), "[██░░] 50% Processing");
+        assert_eq!(render(DEFAULT_FORMAT, 4, 3, 2, ""), "[████] 100% ");
+        assert_eq!(render("{current}/{total} {bar}", 3, 0, 5, ""), "0/5 ░░░");
+    }
+
+    #[test]
+    fn render_huge_total() {
+        assert_eq!(render(DEFAULT_FORMAT, 4, u64::MAX / 2, u64::MAX, ""), "[█░░░] 49% ");
+        assert_eq!(render(DEFAULT_FORMAT, 4, u64::MAX, u64::MAX, ""), "[████] 100% ");
+    }
+}
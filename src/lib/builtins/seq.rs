@@ -0,0 +1,361 @@
+use super::Status;
+use crate as ion_shell;
+use crate::{types, Shell};
+use builtins_proc::builtin;
+use std::{
+    fmt::Display,
+    io::{self, BufWriter, Write},
+    iter::{self, Peekable},
+    str::CharIndices,
+};
+
+/// A printf-style format with a single floating-point conversion, as given to `-f`
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Format {
+    prefix:     String,
+    suffix:     String,
+    /// Pad on the right rather than on the left, with the `-` flag
+    left:       bool,
+    /// Pad with zeros rather than spaces, with the `0` flag
+    zero:       bool,
+    /// Keep the trailing zeros of `%g`, with the `#` flag
+    alternate:  bool,
+    /// The sign printed before the positive numbers, given by the `+` and ` ` flags
+    sign:       &'static str,
+    width:      usize,
+    precision:  Option<usize>,
+    /// One of `f`, `e` and `g`, or their uppercase variants
+    conversion: char,
+}
+
+impl Default for Format {
+    /// The format of `%g`
+    fn default() -> Self {
+        Self {
+            prefix:     String::new(),
+            suffix:     String::new(),
+            left:       false,
+            zero:       false,
+            alternate:  false,
+            sign:       "",
+            width:      0,
+            precision:  None,
+            conversion: 'g',
+        }
+    }
+}
+
+/// The text of a format around the conversion, with `%%` standing for `%`. `None` if it contains
+/// another conversion.
+fn literal(text: &str) -> Option<String> {
+    let mut literal = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(character) = chars.next() {
+        if character == '%' && chars.next() != Some('%') {
+            return None;
+        }
+        literal.push(character);
+    }
+    Some(literal)
+}
+
+/// Parses the digits at the start of a conversion, such as its width
+fn digits(chars: &mut Peekable<CharIndices<'_>>) -> Option<usize> {
+    let mut number = None;
+    while let Some(digit) = chars.peek().and_then(|&(_, digit)| digit.to_digit(10)) {
+        number = Some(number.unwrap_or(0) * 10 + digit as usize);
+        chars.next();
+    }
+    number
+}
+
+impl Format {
+    /// Parses a format such as `%05.2f` or `value: %g`
+    fn parse(format: &str) -> Option<Self> {
+        // The first `%` which is not followed by another one starts the conversion
+        let mut from = 0;
+        let start = loop {
+            let position = from + format[from..].find('%')?;
+            if format[position + 1..].starts_with('%') {
+                from = position + 2;
+            } else {
+                break position;
+            }
+        };
+
+        let prefix = literal(&format[..start])?;
+        let mut parsed =
+            Self { prefix, ..Self::default() }.parse_conversion(&format[start + 1..])?;
+        parsed.suffix = literal(&parsed.suffix)?;
+        Some(parsed)
+    }
+
+    /// Parses the flags, width, precision and type of the conversion, keeping the rest of the
+    /// format as the suffix
+    fn parse_conversion(mut self, spec: &str) -> Option<Self> {
+        let mut chars = spec.char_indices().peekable();
+        while let Some(&(_, flag)) = chars.peek() {
+            match flag {
+                '-' => self.left = true,
+                '0' => self.zero = true,
+                '#' => self.alternate = true,
+                '+' => self.sign = "+",
+                ' ' if self.sign.is_empty() => self.sign = " ",
+                ' ' => (),
+                _ => break,
+            }
+            chars.next();
+        }
+
+        self.width = digits(&mut chars).unwrap_or(0);
+        if chars.peek().map(|&(_, dot)| dot) == Some('.') {
+            chars.next();
+            self.precision = Some(digits(&mut chars).unwrap_or(0));
+        }
+
+        match chars.next() {
+            Some((position, conversion)) if "feg".contains(conversion.to_ascii_lowercase()) => {
+                self.conversion = conversion;
+                self.suffix = spec[position + conversion.len_utf8()..].into();
+                Some(self)
+            }
+            _ => None,
+        }
+    }
+
+    fn format(&self, value: f64) -> String {
+        let precision = self.precision.unwrap_or(6);
+        let mut digits = match self.conversion.to_ascii_lowercase() {
+            'f' => format!("{:.*}", precision, value.abs()),
+            'e' => exponential(value.abs(), precision),
+            _ => general(value.abs(), precision, self.alternate),
+        };
+        if self.conversion.is_ascii_uppercase() {
+            digits.make_ascii_uppercase();
+        }
+
+        let sign = if value < 0.0 { "-" } else { self.sign };
+        let padding = self.width.saturating_sub(sign.len() + digits.len());
+        let number = if self.left {
+            format!("{}{}{}", sign, digits, " ".repeat(padding))
+        } else if self.zero {
+            format!("{}{}{}", sign, "0".repeat(padding), digits)
+        } else {
+            format!("{}{}{}", " ".repeat(padding), sign, digits)
+        };
+        format!("{}{}{}", self.prefix, number, self.suffix)
+    }
+}
+
+/// A positive `value` in the exponential notation of C, such as `1.500000e+02`
+fn exponential(value: f64, precision: usize) -> String {
+    let formatted = format!("{:.*e}", precision, value);
+    let (mantissa, exponent) = formatted.split_once('e').unwrap_or((&formatted, "0"));
+    let exponent = exponent.parse::<i32>().unwrap_or(0);
+    format!("{}e{}{:02}", mantissa, if exponent < 0 { '-' } else { '+' }, exponent.abs())
+}
+
+/// A positive `value` with `precision` significant digits, in the fixed notation unless its
+/// exponent is too small or too large, like `%g` in C. The trailing zeros of the fraction are
+/// removed, unless `alternate`.
+fn general(value: f64, precision: usize, alternate: bool) -> String {
+    let precision = precision.max(1);
+    let scientific = exponential(value, precision - 1);
+    let exponent = scientific.rsplit('e').next().and_then(|e| e.parse::<i64>().ok()).unwrap_or(0);
+    let formatted = if exponent < -4 || exponent >= precision as i64 {
+        scientific
+    } else {
+        format!("{:.*}", (precision as i64 - 1 - exponent) as usize, value)
+    };
+    if alternate {
+        return formatted;
+    }
+
+    let trim = |number: &str| {
+        if number.contains('.') {
+            number.trim_end_matches('0').trim_end_matches('.').to_owned()
+        } else {
+            number.to_owned()
+        }
+    };
+    match formatted.find('e') {
+        Some(position) => format!("{}{}", trim(&formatted[..position]), &formatted[position..]),
+        None => trim(&formatted),
+    }
+}
+
+/// Parses a finite number, returning the argument if it is not one
+fn number(arg: &str) -> Result<f64, &str> {
+    arg.parse::<f64>().ok().filter(|value| value.is_finite()).ok_or(arg)
+}
+
+/// The number of digits after the decimal point of a number as written
+fn decimals(number: &str) -> usize {
+    let mantissa = number.split(|c| c == 'e' || c == 'E').next().unwrap_or(number);
+    mantissa.split_once('.').map_or(0, |(_, fraction)| fraction.len())
+}
+
+/// The integers from `first` to `last` by `step`, stopping before an overflow
+fn integers(first: i64, step: i64, last: i64) -> impl Iterator<Item = i64> {
+    iter::successors(Some(first), move |number| number.checked_add(step))
+        .take_while(move |&number| if step > 0 { number <= last } else { number >= last })
+}
+
+/// The numbers from `first` to `last` by `step`. Each one is computed from its index rather than
+/// by adding the step to the previous one, which would accumulate the rounding errors.
+fn floats(first: f64, step: f64, last: f64) -> impl Iterator<Item = f64> {
+    let tolerance = step.abs() * 1e-9;
+    (0_u32..).map(move |index| first + f64::from(index) * step).take_while(move |&number| {
+        if step > 0.0 {
+            number <= last + tolerance
+        } else {
+            number >= last - tolerance
+        }
+    })
+}
+
+/// Print the values separated by `separator`, followed by a newline unless there is none
+fn print(values: impl Iterator<Item = impl Display>, separator: &str) -> io::Result<()> {
+    let stdout = io::stdout();
+    let mut stdout = BufWriter::new(stdout.lock());
+    let mut empty = true;
+    for value in values {
+        if !empty {
+            stdout.write_all(separator.as_bytes())?;
+        }
+        empty = false;
+        write!(stdout, "{}", value)?;
+    }
+    if !empty {
+        writeln!(stdout)?;
+    }
+    stdout.flush()
+}
+
+#[builtin(
+    desc = "print a sequence of numbers",
+    man = "
+SYNOPSIS
+    seq [ -h | --help ] [-f FORMAT] [-s SEPARATOR] [FIRST [STEP]] LAST
+
+DESCRIPTION
+    Prints the numbers from FIRST to LAST by STEP, one per line. FIRST and STEP default to 1, and
+    a negative STEP prints a descending sequence. Nothing is printed if LAST is beyond FIRST in
+    the direction of STEP.
+
+    The numbers may be integers or floating-point numbers. Without a format, the numbers are
+    printed with as many decimals as FIRST and STEP have.
+
+OPTIONS
+    -f, --format FORMAT
+        print each number with the printf-style FORMAT, which must contain a single %f, %e or
+        %g conversion, such as %05.2f. %% prints a %.
+
+    -s, --sep SEPARATOR
+        separate the numbers with SEPARATOR instead of a newline. The sequence still ends with a
+        newline.
+
+EXAMPLES
+    seq 3
+    seq 10 -2 0
+    seq --sep , 0 0.25 1
+    seq -f 'part-%02g' 5
+    for i in @(seq 10 -2 0)
+        echo $i
+    end"
+)]
+pub fn seq(args: &[types::Str], _: &mut Shell<'_>) -> Status {
+    let mut format = None;
+    let mut separator = "\n";
+    let mut numbers = Vec::with_capacity(3);
+    let mut args = args[1..].iter().map(types::Str::as_str);
+    while let Some(arg) = args.next() {
+        match arg {
+            "-f" | "--format" => match args.next().map(|spec| (spec, Format::parse(spec))) {
+                Some((_, Some(spec))) => format = Some(spec),
+                Some((spec, None)) => {
+                    return Status::bad_argument(format!(
+                        "seq: invalid format '{}': expected a single %f, %e or %g conversion",
+                        spec
+                    ))
+                }
+                None => return Status::bad_argument("seq: -f requires a format"),
+            },
+            "-s" | "--sep" => match args.next() {
+                Some(arg) => separator = arg,
+                None => return Status::bad_argument("seq: -s requires a separator"),
+            },
+            _ if arg.starts_with('-') && arg.parse::<f64>().is_err() => {
+                return Status::bad_argument(format!("seq: invalid option '{}'", arg))
+            }
+            _ => numbers.push(arg),
+        }
+    }
+
+    let (first, step, last) = match numbers[..] {
+        [last] => ("1", "1", last),
+        [first, last] => (first, "1", last),
+        [first, step, last] => (first, step, last),
+        _ => return Status::bad_argument("seq: expected [FIRST [STEP]] LAST"),
+    };
+    let (start, increment, end) =
+        match number(first).and_then(|start| Ok((start, number(step)?, number(last)?))) {
+            Ok(bounds) => bounds,
+            Err(arg) => return Status::bad_argument(format!("seq: invalid number '{}'", arg)),
+        };
+    if increment == 0.0 {
+        return Status::bad_argument("seq: the step must not be zero");
+    }
+
+    let result = match (format, first.parse::<i64>(), step.parse::<i64>(), last.parse::<i64>()) {
+        (None, Ok(first), Ok(step), Ok(last)) => print(integers(first, step, last), separator),
+        (format, ..) => {
+            let format = format.unwrap_or_else(|| Format {
+                precision:  Some(decimals(first).max(decimals(step))),
+                conversion: 'f',
+                ..Format::default()
+            });
+            print(floats(start, increment, end).map(|number| format.format(number)), separator)
+        }
+    };
+    result.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(spec: &str, value: f64) -> String { Format::parse(spec).unwrap().format(value) }
+
+    #[test]
+    fn formats() {
+        assert_eq!(format("%g", 1.5), "1.5");
+        assert_eq!(format("%g", 100_000.0), "100000");
+        assert_eq!(format("%g", 1_000_000.0), "1e+06");
+        assert_eq!(format("%g", 0.0001), "0.0001");
+        assert_eq!(format("%G", 0.000_01), "1E-05");
+        assert_eq!(format("%#g", 2.0), "2.00000");
+        assert_eq!(format("%.2f", -1.2345), "-1.23");
+        assert_eq!(format("%e", 150.0), "1.500000e+02");
+        assert_eq!(format("%05.1f", -2.5), "-02.5");
+        assert_eq!(format("%-5g|", 3.0), "3    |");
+        assert_eq!(format("%+4g", 3.0), "  +3");
+        assert_eq!(format("100%% of %g%%", 7.0), "100% of 7%");
+
+        assert_eq!(Format::parse("%d"), None);
+        assert_eq!(Format::parse("no conversion"), None);
+        assert_eq!(Format::parse("%g and %g"), None);
+    }
+
+    #[test]
+    fn sequences() {
+        assert_eq!(integers(1, 2, 8).collect::<Vec<_>>(), [1, 3, 5, 7]);
+        assert_eq!(integers(3, -1, 1).collect::<Vec<_>>(), [3, 2, 1]);
+        assert_eq!(integers(3, 1, 1).count(), 0);
+        assert_eq!(integers(i64::MAX - 1, 1, i64::MAX).count(), 2);
+        assert_eq!(floats(0.0, 0.1, 0.3).count(), 4);
+        assert_eq!(floats(1.0, -0.5, 0.0).collect::<Vec<_>>(), [1.0, 0.5, 0.0]);
+        assert_eq!(decimals("1.250"), 3);
+        assert_eq!(decimals("10"), 0);
+    }
+}
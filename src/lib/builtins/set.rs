@@ -1,11 +1,14 @@
 use super::Status;
 use crate as ion_shell;
 use crate::{
-    shell::{variables::Value, Shell},
+    shell::{variables::Value, Options, Shell},
     types,
 };
 use builtins_proc::builtin;
-use std::iter;
+use std::{
+    io::{self, Write},
+    iter,
+};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 enum PositionalArgs {
@@ -13,23 +16,86 @@ enum PositionalArgs {
     RetainIfNone,
 }
 
+/// Print the named options: with their state when `listing`, or as the commands restoring them
+fn print_options(options: &Options, listing: bool) -> Status {
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    Options::NAMES
+        .iter()
+        .try_for_each(|name| {
+            let set = options.get(name) == Some(true);
+            match (listing, set) {
+                (true, _) => writeln!(stdout, "{:<28}{}", name, if set { "on" } else { "off" }),
+                (false, true) => writeln!(stdout, "set -o {}", name),
+                (false, false) => writeln!(stdout, "set +o {}", name),
+            }
+        })
+        .into()
+}
+
 #[builtin(
     desc = "Set or unset values of shell options and positional parameters.",
     man = "
 SYNOPSIS
-    set [ --help ] [-e | +e] [-p | +p] [- | --] [STRING]...
+    set [ --help ] [-e | +e] [-E | +E] [-c | +c] [-p | +p] [-S | +S] [-R | +R] [-J | +J]
+        [--pad-assignment | --no-pad-assignment] [--lenient-indices | --no-lenient-indices]
+        [-o NAME | +o NAME] [- | --] [STRING]...
+    set -o
+    set +o
 
 DESCRIPTION
     Shell options may be set using the '-' character, and unset using the '+' character.
 
 OPTIONS
-    -e  Exit immediately if a command exits with a non-zero status.
+    -e  Exit immediately if a command exits with a non-zero status. Commands inside of functions,
+        and the status returned by a function, are exempt unless -E is set as well. Conditions
+        are always exempt: the expressions of if, while and for where, and the commands on the
+        left of && and ||.
+
+    -E, --exit-on-error-in-functions
+        Let -e apply inside of functions and to the status returned by functions, like any
+        other command. +E and --no-exit-on-error-in-functions unset it.
+
+    -c  Exit immediately if a command can not be found, even if the `command_not_found_handler`
+        function was called for it.
     
     -p  If any command in pipe exits with an non-zero code then pipe returns this non-zero code 
         instead of error code of the last command.
         It can be combined with the option -e to let a script fail 
         if an errors occures in a pipe
         
+    -S, --strict-assignment
+        Make `let a b = VALUES` fail when there are not as many values as keys, instead of
+        distributing the words of the values over the keys. +S and --no-strict-assignment unset
+        it.
+
+    --pad-assignment
+        When the words of the values are distributed over the keys of `let a b c = $(cmd)`, set
+        the keys left without a word to an empty string instead of failing.
+        --no-pad-assignment unsets it.
+
+    -R, --recover-panics
+        Make a statement which panics, because of a bug in the shell, fail with a diagnostic
+        instead of aborting the shell. The function calls and scopes that the statement entered
+        are exited, but other changes that it made are kept. +R and --no-recover-panics unset it.
+
+    -J, --json-output
+        Make the builtins printing tables, such as `jobs`, `type`, `declare -p` and `history`,
+        print them as JSON as if they were given --json. +J and --no-json-output unset it.
+
+    --lenient-indices
+        Expand an index out of the bounds of an array, such as @array[5] for an array of two
+        elements, to nothing instead of failing. --no-lenient-indices unsets it.
+
+    -o NAME
+        Set the option called NAME, and +o NAME unsets it. The options are errexit (-e),
+        exit-on-error-in-functions (-E), exit-on-command-not-found (-c), pipefail (-p),
+        strict-assignment (-S), pad-assignment, recover-panics (-R), json-output (-J) and
+        lenient-indices.
+
+        Without a name, -o lists the options with their state, and +o prints the set commands
+        restoring the current state, which can be saved and evaluated later.
+
     --  Following arguments will be set as positional arguments in the shell.
         If no argument are supplied, arguments will be unset.
 
@@ -57,8 +123,32 @@ pub fn set(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
             }
             "-e" => shell.opts_mut().err_exit = true,
             "+e" => shell.opts_mut().err_exit = false,
+            "-E" | "--exit-on-error-in-functions" => shell.opts_mut().err_trace = true,
+            "+E" | "--no-exit-on-error-in-functions" => shell.opts_mut().err_trace = false,
+            "-c" => shell.opts_mut().not_found_exit = true,
+            "+c" => shell.opts_mut().not_found_exit = false,
             "-p" => shell.opts_mut().pipe_fail = true,
             "+p" => shell.opts_mut().pipe_fail = false,
+            "-S" | "--strict-assignment" => shell.opts_mut().strict_assignment = true,
+            "+S" | "--no-strict-assignment" => shell.opts_mut().strict_assignment = false,
+            "--pad-assignment" => shell.opts_mut().pad_assignment = true,
+            "--no-pad-assignment" => shell.opts_mut().pad_assignment = false,
+            "-R" | "--recover-panics" => shell.opts_mut().recover_panics = true,
+            "+R" | "--no-recover-panics" => shell.opts_mut().recover_panics = false,
+            "-J" | "--json-output" => shell.opts_mut().json_output = true,
+            "+J" | "--no-json-output" => shell.opts_mut().json_output = false,
+            "--lenient-indices" => shell.opts_mut().lenient_indices = true,
+            "--no-lenient-indices" => shell.opts_mut().lenient_indices = false,
+            "-o" | "+o" => {
+                let value = arg.starts_with('-');
+                match args_iter.next() {
+                    Some(name) if shell.opts_mut().set(name.as_str(), value) => (),
+                    Some(name) => {
+                        return Status::bad_argument(format!("set: unknown option '{}'", name))
+                    }
+                    None => return print_options(shell.opts(), value),
+                }
+            }
             _ => {
                 return Status::bad_argument(format!(
                     "set: argument '{}' is not recognized. Try adding `--` before it to pass it \
@@ -1,6 +1,9 @@
 use super::Status;
 use crate as ion_shell;
-use crate::{shell::Shell, types};
+use crate::{
+    shell::{IonError, Shell},
+    types,
+};
 use builtins_proc::builtin;
 use std::fs::File;
 
@@ -8,23 +11,43 @@ use std::fs::File;
     desc = "evaluates given file",
     man = "
 SYNOPSIS
-    source FILEPATH
+    source [--once] FILEPATH
 
 DESCRIPTION
     Evaluates the commands in a specified file in the current shell. All changes in shell
-    variables will affect the current shell because of this."
+    variables will affect the current shell because of this.
+    While the file is evaluated, its absolute path is stored in $ION_SOURCE.
+
+    If the file has a bytecode cache compiled with `ion --compile`, the same path with the .ionc
+    extension, which is newer than the file, the cached statements are executed instead of
+    parsing the file again.
+
+OPTIONS
+    --once
+        do nothing if the file was already evaluated by the shell, so that a library sourced by
+        several scripts only defines its functions once. Files reached through symbolic links or
+        relative paths are recognized as the same file."
 )]
 pub fn source(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
-    match args.get(1) {
+    let (once, path) = match args.get(1).map(types::Str::as_str) {
+        Some("--once") => (true, args.get(2)),
+        _ => (false, args.get(1)),
+    };
+    match path {
+        Some(argument) if once && shell.has_executed_file(argument.as_str()) => Status::SUCCESS,
         Some(argument) => {
-            if let Ok(file) = File::open(argument.as_str()) {
-                if let Err(why) = shell.execute_command(file) {
-                    Status::error(format!("ion: {}", why))
-                } else {
-                    Status::SUCCESS
-                }
-            } else {
-                Status::error(format!("ion: failed to open {}\n", argument))
+            let result = match shell.execute_cached_file(argument.as_str()) {
+                Some(result) => result,
+                None => match File::open(argument.as_str()) {
+                    Ok(file) => shell.execute_file(argument.as_str(), file),
+                    Err(_) => return Status::error(format!("ion: failed to open {}\n", argument)),
+                },
+            };
+            match result {
+                // The exit keeps unwinding once the source builtin returns
+                Err(IonError::Exit(status)) => status,
+                Err(why) => Status::error(format!("ion: {}", why)),
+                Ok(_) => Status::SUCCESS,
             }
         }
         None => Status::error("an argument is required for source"),
@@ -0,0 +1,128 @@
+use super::Status;
+use crate as ion_shell;
+use crate::{types, Shell};
+use builtins_proc::builtin;
+use std::{
+    io::{self, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+const FRAMES: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+const INTERVAL: Duration = Duration::from_millis(80);
+const DEFAULT_FORMAT: &str = "{spinner} {message}";
+
+fn render(format: &str, spinner: char, message: &str, elapsed: Duration) -> String {
+    format
+        .replace("{spinner}", spinner.encode_utf8(&mut [0; 4]))
+        .replace("{message}", message)
+        .replace("{elapsed}", &format!("{:.1}s", elapsed.as_secs_f32()))
+}
+
+#[builtin(
+    desc = "display a spinner while a command runs",
+    man = "
+SYNOPSIS
+    spinner [ -h | --help ] [--format FORMAT] MESSAGE COMMAND...
+
+DESCRIPTION
+    Evaluates COMMAND, the same way as eval, while an animated spinner and the MESSAGE are
+    displayed. Once the command completes, the spinner is replaced with ✓ if it succeeded, or
+    with ✗ if it failed. The exit status is the one of the command.
+
+    When standard output is not a terminal, the command is evaluated without any display.
+
+OPTIONS
+    --format FORMAT
+        the format of the line, where {spinner}, {message} and {elapsed} are replaced by the
+        spinner, the message and the time elapsed since the command started. Defaults to
+        '{spinner} {message}'.
+
+EXAMPLES
+    spinner 'Downloading...' curl -sO https://example.com/archive.tar.gz
+    spinner --format '{spinner} {message} ({elapsed})' 'Building' cargo build"
+)]
+pub fn spinner(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
+    let mut format = DEFAULT_FORMAT;
+    let mut args = args[1..].iter();
+    let message = loop {
+        match args.next().map(types::Str::as_str) {
+            Some("--format") => match args.next() {
+                Some(value) => format = value,
+                None => return Status::bad_argument("spinner: --format requires a value"),
+            },
+            Some(message) => break message,
+            None => return Status::bad_argument("spinner: no message given"),
+        }
+    };
+    let command = args.map(types::Str::as_str).collect::<Vec<_>>().join(" ");
+    if command.is_empty() {
+        return Status::bad_argument("spinner: no command given");
+    }
+
+    let evaluate = |shell: &mut Shell<'_>| {
+        shell
+            .execute_command(command.as_bytes())
+            .unwrap_or_else(|why| Status::error(format!("ion: spinner: {}", why)))
+    };
+
+    if !atty::is(atty::Stream::Stdout) {
+        return evaluate(shell);
+    }
+
+    let start = Instant::now();
+    let done = Arc::new(AtomicBool::new(false));
+    let animation = {
+        let done = done.clone();
+        let (format, message) = (format.to_owned(), message.to_owned());
+        thread::spawn(move || {
+            let stdout = io::stdout();
+            for &frame in FRAMES.iter().cycle() {
+                if done.load(Ordering::SeqCst) {
+                    break;
+                }
+                let line = render(&format, frame, &message, start.elapsed());
+                {
+                    let mut stdout = stdout.lock();
+                    let _ = write!(stdout, "\r\x1b[2K{}", line);
+                    let _ = stdout.flush();
+                }
+                thread::sleep(INTERVAL);
+            }
+        })
+    };
+
+    let status = evaluate(shell);
+
+    done.store(true, Ordering::SeqCst);
+    let _ = animation.join();
+
+    let mark = if status.is_success() { '✓' } else { '✗' };
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    let _ = writeln!(stdout, "\r\x1b[2K{}", render(format, mark, message, start.elapsed()));
+    let _ = stdout.flush();
+
+    status
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_format() {
+        assert_eq!(
+            render(DEFAULT_FORMAT, '✓', "Downloading", Duration::from_secs(2)),
+            "✓ Downloading"
+        );
+        assert_eq!(
+            render("{message} {spinner} ({elapsed})", '⠋', "Building", Duration::from_millis(1500)),
+            "Building ⠋ (1.5s)"
+        );
+    }
+}
@@ -0,0 +1,132 @@
+use super::Status;
+use crate as ion_shell;
+use crate::{types, Shell};
+use builtins_proc::builtin;
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use std::{
+    env,
+    fs::{DirBuilder, OpenOptions},
+    io,
+    os::unix::fs::{DirBuilderExt, OpenOptionsExt},
+    path::PathBuf,
+};
+
+/// The number of random names tried before giving up
+const ATTEMPTS: usize = 100;
+
+/// What a temporary path is created as
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    File,
+    Directory,
+}
+
+/// Creates a file or directory with a random name in `$TMPDIR`, or `/tmp` if it is not set. As
+/// with `mkstemp`, the path is created exclusively, and is only accessible by the user.
+fn create(kind: Kind, suffix: &str) -> io::Result<PathBuf> {
+    let dir = env::var_os("TMPDIR")
+        .filter(|dir| !dir.is_empty())
+        .map_or_else(|| PathBuf::from("/tmp"), PathBuf::from);
+    for _ in 0..ATTEMPTS {
+        let name: String = thread_rng().sample_iter(Alphanumeric).take(10).collect();
+        let path = dir.join(format!("ion.{}{}", name, suffix));
+        let created = match kind {
+            Kind::File => {
+                OpenOptions::new().write(true).create_new(true).mode(0o600).open(&path).map(drop)
+            }
+            Kind::Directory => DirBuilder::new().mode(0o700).create(&path),
+        };
+        match created {
+            Ok(()) => return Ok(path),
+            Err(ref why) if why.kind() == io::ErrorKind::AlreadyExists => (),
+            Err(why) => return Err(why),
+        }
+    }
+    Err(io::Error::new(io::ErrorKind::AlreadyExists, "no unused name was found"))
+}
+
+/// Creates a temporary path, printing it, and removes it when the shell exits unless `--keep`
+/// is given
+fn temporary(args: &[types::Str], shell: &mut Shell<'_>, kind: Kind) -> Status {
+    let (mut keep, mut suffix) = (false, "");
+    let mut options = args[1..].iter();
+    while let Some(option) = options.next() {
+        match option.as_str() {
+            "--keep" => keep = true,
+            "--suffix" => match options.next() {
+                Some(value) if !value.contains('/') => suffix = value.as_str(),
+                Some(value) => {
+                    return Status::bad_argument(format!(
+                        "{}: invalid suffix '{}': it can not contain '/'",
+                        args[0], value
+                    ))
+                }
+                None => {
+                    return Status::bad_argument(format!("{}: --suffix requires a value", args[0]))
+                }
+            },
+            _ => return Status::bad_argument(format!("{}: invalid option '{}'", args[0], option)),
+        }
+    }
+
+    match create(kind, suffix) {
+        Ok(path) => {
+            println!("{}", path.display());
+            if !keep {
+                shell.remove_on_exit(path);
+            }
+            Status::SUCCESS
+        }
+        Err(why) => {
+            Status::error(format!("{}: could not create a temporary path: {}", args[0], why))
+        }
+    }
+}
+
+#[builtin(
+    desc = "create a temporary file",
+    man = "
+SYNOPSIS
+    tmpfile [ -h | --help ] [--keep] [--suffix SUFFIX]
+
+DESCRIPTION
+    Creates an empty file with a unique name in $TMPDIR, or in /tmp if it is not set, and prints
+    its path. The file is only readable and writable by the user. It is removed when the shell
+    exits, including when a script is interrupted.
+
+OPTIONS
+    --keep
+        do not remove the file when the shell exits
+    --suffix SUFFIX
+        end the name of the file with SUFFIX, such as an extension
+
+EXAMPLES
+    let config = $(tmpfile --suffix .json)"
+)]
+pub fn tmpfile(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
+    temporary(args, shell, Kind::File)
+}
+
+#[builtin(
+    desc = "create a temporary directory",
+    man = "
+SYNOPSIS
+    tmpdir [ -h | --help ] [--keep] [--suffix SUFFIX]
+
+DESCRIPTION
+    Creates an empty directory with a unique name in $TMPDIR, or in /tmp if it is not set, and
+    prints its path. The directory is only accessible by the user. It is removed with its contents
+    when the shell exits, including when a script is interrupted.
+
+OPTIONS
+    --keep
+        do not remove the directory when the shell exits
+    --suffix SUFFIX
+        end the name of the directory with SUFFIX
+
+EXAMPLES
+    let workdir = $(tmpdir)"
+)]
+pub fn tmpdir(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
+    temporary(args, shell, Kind::Directory)
+}
@@ -0,0 +1,180 @@
+//! Builtins wrapping the most common `tput` operations, without forking the external binary.
+
+use super::Status;
+use crate as ion_shell;
+use crate::{types, Shell};
+use builtins_proc::builtin;
+use nix::libc;
+use std::{
+    env,
+    io::{self, Write},
+    mem,
+};
+
+/// Query the size of the terminal from the first of stdin, stdout and stderr which is a terminal,
+/// falling back to `$COLUMNS` and `$LINES`, and then to 80x24 like `tput`.
+fn size() -> (u16, u16) {
+    for &fd in &[libc::STDIN_FILENO, libc::STDOUT_FILENO, libc::STDERR_FILENO] {
+        let mut winsize: libc::winsize = unsafe { mem::zeroed() };
+        let result = unsafe { libc::ioctl(fd, libc::TIOCGWINSZ, &mut winsize) };
+        if result == 0 && winsize.ws_col != 0 {
+            return (winsize.ws_col, winsize.ws_row);
+        }
+    }
+    let from_env = |name: &str, default: u16| {
+        env::var(name).ok().and_then(|value| value.parse().ok()).unwrap_or(default)
+    };
+    (from_env("COLUMNS", 80), from_env("LINES", 24))
+}
+
+/// Write an escape sequence to stdout, if it is a terminal
+fn emit(sequence: &str) -> Status {
+    if !atty::is(atty::Stream::Stdout) {
+        return Status::SUCCESS;
+    }
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    stdout.write_all(sequence.as_bytes()).and_then(|_| stdout.flush()).into()
+}
+
+macro_rules! sequence_builtin {
+    (#[$outer:meta], $name:ident, $sequence:expr) => {
+        #[$outer]
+        pub fn $name(args: &[types::Str], _: &mut Shell<'_>) -> Status { emit($sequence) }
+    };
+}
+
+#[builtin(
+    desc = "print the number of columns of the terminal",
+    names = "term-cols",
+    man = "
+SYNOPSIS
+    term-cols
+
+DESCRIPTION
+    Prints the number of columns of the terminal, like `tput cols`."
+)]
+pub fn term_cols(args: &[types::Str], _: &mut Shell<'_>) -> Status {
+    println!("{}", size().0);
+    Status::SUCCESS
+}
+
+#[builtin(
+    desc = "print the number of rows of the terminal",
+    names = "term-rows",
+    man = "
+SYNOPSIS
+    term-rows
+
+DESCRIPTION
+    Prints the number of rows of the terminal, like `tput lines`."
+)]
+pub fn term_rows(args: &[types::Str], _: &mut Shell<'_>) -> Status {
+    println!("{}", size().1);
+    Status::SUCCESS
+}
+
+#[builtin(
+    desc = "move the cursor of the terminal",
+    names = "term-move",
+    man = "
+SYNOPSIS
+    term-move ROW COL
+
+DESCRIPTION
+    Moves the cursor to the given row and column, counted from 0, like `tput cup ROW COL`.
+    Nothing is printed when standard output is not a terminal."
+)]
+pub fn term_move(args: &[types::Str], _: &mut Shell<'_>) -> Status {
+    match (args.get(1).map(|row| row.parse::<u16>()), args.get(2).map(|col| col.parse::<u16>())) {
+        (Some(Ok(row)), Some(Ok(col))) if args.len() == 3 => {
+            emit(&format!("\x1b[{};{}H", u32::from(row) + 1, u32::from(col) + 1))
+        }
+        _ => Status::bad_argument("term-move: expected a row and a column"),
+    }
+}
+
+sequence_builtin!(
+#[builtin(
+    desc = "clear the terminal",
+    names = "term-clear",
+    man = "
+SYNOPSIS
+    term-clear
+
+DESCRIPTION
+    Clears the terminal and moves the cursor to its top left corner, like `tput clear`.
+    Nothing is printed when standard output is not a terminal."
+)], term_clear, "\x1b[H\x1b[2J");
+sequence_builtin!(
+#[builtin(
+    desc = "hide the cursor of the terminal",
+    names = "term-hide-cursor",
+    man = "
+SYNOPSIS
+    term-hide-cursor
+
+DESCRIPTION
+    Hides the cursor, like `tput civis`.
+    Nothing is printed when standard output is not a terminal."
+)], term_hide_cursor, "\x1b[?25l");
+sequence_builtin!(
+#[builtin(
+    desc = "show the cursor of the terminal",
+    names = "term-show-cursor",
+    man = "
+SYNOPSIS
+    term-show-cursor
+
+DESCRIPTION
+    Shows the cursor, like `tput cnorm`.
+    Nothing is printed when standard output is not a terminal."
+)], term_show_cursor, "\x1b[?25h");
+sequence_builtin!(
+#[builtin(
+    desc = "save the position of the cursor",
+    names = "term-save-cursor",
+    man = "
+SYNOPSIS
+    term-save-cursor
+
+DESCRIPTION
+    Saves the position of the cursor, like `tput sc`.
+    Nothing is printed when standard output is not a terminal."
+)], term_save_cursor, "\x1b7");
+sequence_builtin!(
+#[builtin(
+    desc = "restore the position of the cursor",
+    names = "term-restore-cursor",
+    man = "
+SYNOPSIS
+    term-restore-cursor
+
+DESCRIPTION
+    Moves the cursor back to the position saved by term-save-cursor, like `tput rc`.
+    Nothing is printed when standard output is not a terminal."
+)], term_restore_cursor, "\x1b8");
+sequence_builtin!(
+#[builtin(
+    desc = "print the following text in bold",
+    names = "term-bold",
+    man = "
+SYNOPSIS
+    term-bold
+
+DESCRIPTION
+    Prints the following text in bold, until term-reset, like `tput bold`.
+    Nothing is printed when standard output is not a terminal."
+)], term_bold, "\x1b[1m");
+sequence_builtin!(
+#[builtin(
+    desc = "reset the attributes of the text",
+    names = "term-reset",
+    man = "
+SYNOPSIS
+    term-reset
+
+DESCRIPTION
+    Resets the attributes and colors of the following text, like `tput sgr0`.
+    Nothing is printed when standard output is not a terminal."
+)], term_reset, "\x1b[0m");
@@ -16,10 +16,11 @@ Try 'test --help' for more information."#;
     desc = "perform tests on files and text",
     man = r#"
 SYNOPSIS
-    test [EXPRESSION]
+    test [--] [EXPRESSION]
 
 DESCRIPTION
-    Tests the expressions given and returns an exit status of 0 if true, else 1.
+    Tests the expressions given and returns an exit status of 0 if true, else 1. A leading --
+    ends the options, so that the EXPRESSION may start with a string such as -n.
 
 OPTIONS
     --help
@@ -129,6 +130,8 @@ pub fn test(args: &[types::Str], _: &mut Shell<'_>) -> Status {
 
 fn evaluate_arguments(arguments: &[types::Str]) -> Result<bool, types::Str> {
     match arguments.first() {
+        // The arguments following `--` are strings, even if they look like flags
+        Some(s) if s == "--" && arguments.len() > 1 => evaluate_operands(&arguments[1..]),
         Some(s) if s.starts_with('-') && s[1..].starts_with(char::is_alphabetic) => {
             // Access the second character in the flag string: this will be type of the
             // flag. If no flag was given, return `SUCCESS`
@@ -141,19 +144,7 @@ fn evaluate_arguments(arguments: &[types::Str]) -> Result<bool, types::Str> {
                 })
             })
         }
-        Some(arg) => {
-            // If there is no operator, check if the first argument is non-zero
-            arguments.get(1).map_or_else(
-                || Ok(!arg.is_empty()),
-                |operator| {
-                    // If there is no right hand argument, a condition was expected
-                    let right_arg = arguments
-                        .get(2)
-                        .ok_or_else(|| types::Str::from("parse error: condition expected"))?;
-                    evaluate_expression(arg, operator, right_arg)
-                },
-            )
-        }
+        Some(_) => evaluate_operands(arguments),
         None => {
             println!("{}", QUICK_GUIDE);
             Ok(false)
@@ -161,6 +152,22 @@ fn evaluate_arguments(arguments: &[types::Str]) -> Result<bool, types::Str> {
     }
 }
 
+/// Evaluate a single string, or an expression comparing two operands
+fn evaluate_operands(arguments: &[types::Str]) -> Result<bool, types::Str> {
+    let arg = &arguments[0];
+    // If there is no operator, check if the first argument is non-zero
+    arguments.get(1).map_or_else(
+        || Ok(!arg.is_empty()),
+        |operator| {
+            // If there is no right hand argument, a condition was expected
+            let right_arg = arguments
+                .get(2)
+                .ok_or_else(|| types::Str::from("parse error: condition expected"))?;
+            evaluate_expression(arg, operator, right_arg)
+        },
+    )
+}
+
 fn evaluate_expression(first: &str, operator: &str, second: &str) -> Result<bool, types::Str> {
     match operator {
         "=" | "==" => Ok(first == second),
@@ -365,6 +372,18 @@ fn test_empty_str() {
     assert_eq!(eval(vec!["c".into(), "=".into(), "".into()]), Ok(false));
 }
 
+#[test]
+fn test_end_of_options() {
+    let eval = |args: &[&str]| {
+        evaluate_arguments(&args.iter().map(|&arg| arg.into()).collect::<Vec<types::Str>>())
+    };
+    assert_eq!(eval(&["--", "-n"]), Ok(true));
+    assert_eq!(eval(&["--", ""]), Ok(false));
+    assert_eq!(eval(&["--", "-n", "=", "-n"]), Ok(true));
+    assert_eq!(eval(&["--", "-n", "=", "-z"]), Ok(false));
+    assert_eq!(eval(&["--"]), Ok(true));
+}
+
 #[test]
 fn test_integers_arguments() {
     fn vec_string(args: &[&str]) -> Vec<types::Str> { args.iter().map(|s| (*s).into()).collect() }
@@ -1,11 +1,27 @@
 // TODO: Move into grammar
 
-use std::io::{self, Write};
+use std::{
+    collections::BTreeMap,
+    env,
+    io::{self, Write},
+    rc::Rc,
+};
 
-use super::Status;
+use super::{
+    json::{json_of, print_json},
+    Status,
+};
 use crate as ion_shell;
-use crate::{shell::variables::Variables, types, Shell};
+use crate::{
+    shell::{
+        variables::{Attributes, Variables},
+        Value,
+    },
+    types::{self, Function},
+    Shell,
+};
 use builtins_proc::builtin;
+use serde_json::{json, Value as Json};
 
 fn print_list(vars: &Variables) {
     let stdout = io::stdout();
@@ -140,6 +156,9 @@ pub fn drop(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
     }
 
     for variable in args.iter().skip(1) {
+        if shell.variables().is_readonly(variable) {
+            return Status::error(format!("ion: cannot modify readonly variable `{}`", variable));
+        }
         if shell.variables_mut().remove(variable.as_ref()).is_none() {
             return Status::error(format!("ion: undefined variable: {}", variable));
         }
@@ -148,6 +167,290 @@ pub fn drop(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
     Status::SUCCESS
 }
 
+/// The variables having all the given attributes, sorted by name, restricted to `names` unless
+/// it is empty
+fn declarations<'a>(
+    vars: &'a Variables,
+    variables: impl Iterator<Item = (&'a types::Str, &'a Value<Rc<Function>>)>,
+    filter: Attributes,
+    names: &[&str],
+) -> Vec<(&'a types::Str, &'a Value<Rc<Function>>)> {
+    // Inner scopes come last, so that they shadow the outer ones
+    let variables = variables.collect::<BTreeMap<_, _>>();
+    variables
+        .into_iter()
+        .filter(|(name, _)| names.is_empty() || names.contains(&name.as_str()))
+        // The special variables managed by the shell, such as `?`, can not be declared
+        .filter(|(name, _)| Variables::is_valid_name(name))
+        .filter(|(name, _)| vars.attributes(name).contains(filter))
+        .filter(|(_, value)| !matches!(value, Value::Alias(_) | Value::Function(_) | Value::None))
+        .collect()
+}
+
+/// Print the variables, as they would be declared
+fn print_declarations(
+    vars: &Variables,
+    declarations: &[(&types::Str, &Value<Rc<Function>>)],
+) -> io::Result<()> {
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    for &(name, value) in declarations {
+        let attributes = vars.attributes(name);
+        write!(stdout, "declare ")?;
+        if !attributes.is_empty() {
+            write!(stdout, "-{} ", attributes.flags())?;
+        }
+        match value {
+            Value::Str(value) => writeln!(stdout, "{} = {}", name, value)?,
+            Value::Array(_) => writeln!(stdout, "{} = [{}]", name, value)?,
+            Value::HashMap(map) => {
+                let map = map.iter().collect::<BTreeMap<_, _>>();
+                let entries = map.iter().map(|(key, value)| format!("{}={}", key, value));
+                writeln!(stdout, "{} = [{}]", name, entries.collect::<Vec<_>>().join(" "))?
+            }
+            Value::BTreeMap(map) => {
+                let entries = map.iter().map(|(key, value)| format!("{}={}", key, value));
+                writeln!(stdout, "{} = [{}]", name, entries.collect::<Vec<_>>().join(" "))?
+            }
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+/// The variables as a JSON array of objects, with their name, the names of their attributes and
+/// their value
+fn json_declarations(
+    vars: &Variables,
+    declarations: &[(&types::Str, &Value<Rc<Function>>)],
+) -> Json {
+    let objects = declarations.iter().map(|&(name, value)| {
+        let attributes = vars.attributes(name);
+        let attributes = [
+            (attributes.integer, "integer"),
+            (attributes.array, "array"),
+            (attributes.readonly, "readonly"),
+            (attributes.exported, "exported"),
+        ];
+        let attributes =
+            attributes.iter().filter(|(set, _)| *set).map(|(_, name)| *name).collect::<Vec<_>>();
+        json!({ "name": name.as_str(), "attributes": attributes, "value": json_of(value) })
+    });
+    Json::Array(objects.collect())
+}
+
+#[builtin(
+    desc = "declare variables with attributes",
+    names = "declare, typeset",
+    man = "
+SYNOPSIS
+    declare [ -h | --help ] [-i] [-a] [-r] [-x] [NAME [= VALUE...]]
+    declare [ -h | --help ] [-i] [-a] [-r] [-x] -p [--json] [NAME...]
+
+DESCRIPTION
+    Sets the variable NAME to VALUE, and gives it the attributes of the options. Without a value,
+    the attributes are given to the existing variable, or to a new empty variable.
+
+    Attributes are kept until the variable is dropped: integer variables can only be assigned
+    integers, readonly variables can no longer be modified nor dropped, and exported variables
+    update the environment of the children processes whenever they are assigned.
+
+    Without a name, the variables having all the attributes of the options are listed. With -p,
+    the given variables are listed instead of being declared.
+
+OPTIONS
+    -i  the variable holds an integer
+    -a  the variable holds an array, of which each value is an element
+    -r  the variable is readonly
+    -x  the variable is exported to the environment of the children processes
+    -p  list the given variables, or all of them, as they would be declared
+    --json
+        list the variables as a JSON array with an object for each variable, whose fields are
+        name, attributes (an array of integer, array, readonly and exported) and value. This is
+        the default when the json-output option is set.
+
+EXAMPLES
+    declare -i count = 0
+    declare -a items = one two three
+    declare -r PI = 3.14
+    declare -x PATH
+    declare -p --json count items"
+)]
+pub fn declare(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
+    let mut attributes = Attributes::default();
+    let mut print = false;
+    let mut json = shell.opts().json_output;
+    let mut args = args[1..].iter().map(types::Str::as_str).peekable();
+    while let Some(flags) = args.peek().copied().and_then(|arg| arg.strip_prefix('-')) {
+        if flags == "-json" {
+            print = true;
+            json = true;
+            args.next();
+            continue;
+        }
+        for flag in flags.chars() {
+            match flag {
+                'i' => attributes.integer = true,
+                'a' => attributes.array = true,
+                'r' => attributes.readonly = true,
+                'x' => attributes.exported = true,
+                'p' => print = true,
+                _ => return Status::bad_argument(format!("declare: invalid option '-{}'", flag)),
+            }
+        }
+        args.next();
+    }
+
+    let name = match args.next() {
+        Some(name) if !print => name,
+        first => {
+            let names = first.into_iter().chain(args).collect::<Vec<_>>();
+            let vars = shell.variables();
+            if let Some(name) = names.iter().find(|name| vars.get(name).is_none()) {
+                return Status::error(format!("ion: declare: `{}` is not defined", name));
+            }
+            let listed = declarations(vars, vars.variables(), attributes, &names);
+            return if json {
+                print_json("declare", &json_declarations(vars, &listed), false)
+            } else {
+                print_declarations(vars, &listed).into()
+            };
+        }
+    };
+    if !Variables::is_valid_name(name) {
+        return Status::bad_argument(format!("declare: invalid variable name '{}'", name));
+    }
+    let values = match args.next() {
+        Some("=") => Some(args.collect::<Vec<_>>()),
+        Some(arg) => {
+            return Status::bad_argument(format!("declare: expected '=', found '{}'", arg))
+        }
+        None => None,
+    };
+
+    let variables = shell.variables_mut();
+    if values.is_some() && variables.is_readonly(name) {
+        return Status::error(format!("ion: cannot modify readonly variable `{}`", name));
+    }
+    let attributes = attributes.union(variables.attributes(name));
+
+    let value: Value<Rc<Function>> = match values {
+        Some(values) if attributes.array => {
+            values.into_iter().map(|value| Value::Str(value.into())).collect()
+        }
+        Some(values) => Value::Str(values.join(" ").into()),
+        None => match variables.get(name) {
+            Some(value) => value.clone(),
+            None if attributes.array => Value::Array(types::Array::new()),
+            None if attributes.integer => Value::Str("0".into()),
+            // Declaring an environment variable makes it a shell variable
+            None => Value::Str(env::var(name).unwrap_or_default().into()),
+        },
+    };
+
+    let is_integer = |value: &types::Str| value.parse::<i64>().is_ok();
+    let integers = match &value {
+        Value::Str(value) => is_integer(value),
+        Value::Array(values) => {
+            values.iter().all(|value| matches!(value, Value::Str(value) if is_integer(value)))
+        }
+        _ => false,
+    };
+    if attributes.integer && !integers {
+        return Status::error(format!("ion: declare: `{}` must be an integer", name));
+    }
+
+    if attributes.exported {
+        env::set_var(name, value.to_string());
+    }
+    variables.set(name, value);
+    variables.set_attributes(name, attributes);
+    Status::SUCCESS
+}
+
+/// Print the functions, sorted by name, with their description
+fn print_functions(vars: &Variables) -> io::Result<()> {
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    for (name, function) in vars.functions().collect::<BTreeMap<_, _>>() {
+        match function.description() {
+            Some(description) => writeln!(stdout, "fn {} -- {}", name, description)?,
+            None => writeln!(stdout, "fn {}", name)?,
+        }
+    }
+    Ok(())
+}
+
+/// Print the aliases, sorted by name, as `alias` prints them
+fn print_aliases(vars: &Variables) -> io::Result<()> {
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    for (name, alias) in vars.aliases().collect::<BTreeMap<_, _>>() {
+        writeln!(stdout, "alias {}='{}'", name, alias)?;
+    }
+    Ok(())
+}
+
+/// Print the environment variables, sorted by name, as exported variables are declared
+fn print_exports() -> io::Result<()> {
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    for (name, value) in env::vars().collect::<BTreeMap<_, _>>() {
+        writeln!(stdout, "declare -x {} = {}", name, value)?;
+    }
+    Ok(())
+}
+
+#[builtin(
+    desc = "print the variables of the current scope",
+    man = "
+SYNOPSIS
+    scope [ -h | --help ] [--all | --functions | --aliases | --exports]
+
+DESCRIPTION
+    Prints the variables defined in the current scope, sorted by name, as declare -p does. In a
+    function, these are the local variables of the function, including its arguments and @argv,
+    but not the global variables it can read. Outside of any function, every variable is printed.
+
+OPTIONS
+    --all
+        print every variable, including those inherited from the outer scopes
+
+    --functions
+        print the defined functions, with their description
+
+    --aliases
+        print the defined aliases
+
+    --exports
+        print the variables exported to the environment of the commands
+
+EXAMPLES
+    fn greet name
+        let greeting = hello
+        scope
+    end"
+)]
+pub fn scope(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
+    let vars = shell.variables();
+    let result = match args.get(1).map(types::Str::as_str) {
+        _ if args.len() > 2 => return Status::bad_argument("scope: takes at most one option"),
+        None => print_declarations(
+            vars,
+            &declarations(vars, vars.local_variables(), Attributes::default(), &[]),
+        ),
+        Some("--all") => print_declarations(
+            vars,
+            &declarations(vars, vars.variables(), Attributes::default(), &[]),
+        ),
+        Some("--functions") => print_functions(vars),
+        Some("--aliases") => print_aliases(vars),
+        Some("--exports") => print_exports(),
+        Some(option) => return Status::bad_argument(format!("scope: invalid option '{}'", option)),
+    };
+    result.into()
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -155,6 +458,27 @@ mod test {
 
     fn vec_string(args: &[&str]) -> Vec<types::Str> { args.iter().map(|s| (*s).into()).collect() }
 
+    #[test]
+    fn declare_readonly_variable() {
+        let mut shell = Shell::default();
+        let args = vec_string(&["declare", "-r", "PI", "=", "3.14"]);
+        assert!(builtin_declare(&args, &mut shell).is_success());
+        assert_eq!(shell.expand_string("$PI").unwrap().join(""), "3.14");
+        let args = vec_string(&["declare", "PI", "=", "3"]);
+        assert!(builtin_declare(&args, &mut shell).is_failure());
+        assert!(builtin_drop(&vec_string(&["drop", "PI"]), &mut shell).is_failure());
+    }
+
+    #[test]
+    fn declare_integer_variable() {
+        let mut shell = Shell::default();
+        let args = vec_string(&["declare", "-i", "count", "=", "zero"]);
+        assert!(builtin_declare(&args, &mut shell).is_failure());
+        let args = vec_string(&["declare", "-i", "count"]);
+        assert!(builtin_declare(&args, &mut shell).is_success());
+        assert_eq!(shell.expand_string("$count").unwrap().join(""), "0");
+    }
+
     #[test]
     fn drop_deletes_variable() {
         let mut shell = Shell::default();
@@ -0,0 +1,60 @@
+use super::Status;
+use crate as ion_shell;
+use crate::{types, Shell};
+use builtins_proc::builtin;
+use std::io::{self, Write};
+
+#[builtin(
+    desc = "list the variables watched with watch",
+    man = "
+SYNOPSIS
+    watches [ -h | --help ]
+
+DESCRIPTION
+    Prints the variables watched with the watch statement, in the order they were watched, as the
+    watch statements which registered them.
+
+    A watch executes its statement each time the value of the variable changes, including when it
+    is defined or dropped. The values are compared after each statement. The status of the
+    previous command is preserved across the statement of a watch, and the changes it makes do
+    not trigger the watches.
+
+EXAMPLES
+    watch count { echo count is now $count }
+    let count = 1
+    watches"
+)]
+pub fn watches(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
+    if args.len() > 1 {
+        return Status::bad_argument("watches: takes no arguments");
+    }
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    let result = shell
+        .watches()
+        .try_for_each(|(name, command)| writeln!(stdout, "watch {} {{ {} }}", name, command));
+    result.into()
+}
+
+#[builtin(
+    desc = "stop watching variables",
+    man = "
+SYNOPSIS
+    unwatch [ -h | --help ] NAME...
+
+DESCRIPTION
+    Removes the watches of the given variables, registered with the watch statement. Fails if one
+    of the variables was not watched, after removing the others."
+)]
+pub fn unwatch(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
+    if args.len() < 2 {
+        return Status::bad_argument("unwatch: requires at least one variable name");
+    }
+    let mut status = Status::SUCCESS;
+    for name in &args[1..] {
+        if !shell.unwatch(name) {
+            status = Status::error(format!("unwatch: variable `{}` is not watched", name));
+        }
+    }
+    status
+}
@@ -0,0 +1,570 @@
+//! Arithmetic expansions: `$((a + b * 2))`.
+//!
+//! Expressions follow the operators and the precedence of C, with `**` for powers and the `²`
+//! and `³` postfix operators. Numbers are 64 bits integers, promoted to floating point numbers as
+//! soon as one of the operands is a float. Variables may be referenced with or without `$`.
+
+use crate::types;
+use std::{cmp::Ordering, convert::TryFrom, fmt, iter::Peekable, str::Chars};
+use thiserror::Error;
+
+/// An error while evaluating an arithmetic expression
+#[derive(Debug, Error, Clone, PartialEq)]
+pub enum ArithmeticError {
+    /// The expression is empty
+    #[error("arithmetic: empty expression")]
+    Empty,
+    /// A character which is not part of any token was found
+    #[error("arithmetic: unexpected character '{0}'")]
+    UnexpectedCharacter(char),
+    /// A token is misplaced
+    #[error("arithmetic: unexpected '{0}'")]
+    UnexpectedToken(String),
+    /// The expression ended while an operand or a closing parenthesis was expected
+    #[error("arithmetic: unexpected end of expression")]
+    UnexpectedEnd,
+    /// A variable is not set
+    #[error("arithmetic: variable '{0}' is not set")]
+    UnknownVariable(String),
+    /// The value of a variable is not a number
+    #[error("arithmetic: value '{1}' of variable '{0}' is not a number")]
+    NotANumber(String, String),
+    /// A division or a modulus by zero
+    #[error("arithmetic: division by zero")]
+    DivideByZero,
+    /// The result does not fit in a 64 bits integer
+    #[error("arithmetic: integer overflow")]
+    Overflow,
+    /// A bitwise operator was applied to a float
+    #[error("arithmetic: operator '{0}' requires integer operands")]
+    IntegerRequired(&'static str),
+}
+
+type Result<T> = std::result::Result<T, ArithmeticError>;
+
+/// The result of an arithmetic expression
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Number {
+    /// A 64 bits integer
+    Int(i64),
+    /// A floating point number
+    Float(f64),
+}
+
+impl Number {
+    fn as_float(self) -> f64 {
+        match self {
+            Self::Int(int) => int as f64,
+            Self::Float(float) => float,
+        }
+    }
+
+    pub(crate) fn is_true(self) -> bool {
+        match self {
+            Self::Int(int) => int != 0,
+            Self::Float(float) => float != 0.,
+        }
+    }
+
+    const fn int(self, operator: &'static str) -> Result<i64> {
+        match self {
+            Self::Int(int) => Ok(int),
+            Self::Float(_) => Err(ArithmeticError::IntegerRequired(operator)),
+        }
+    }
+
+    fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+            i64::from_str_radix(hex, 16).ok().map(Self::Int)
+        } else if let Ok(int) = text.parse::<i64>() {
+            Some(Self::Int(int))
+        } else {
+            text.parse::<f64>().ok().filter(|float| float.is_finite()).map(Self::Float)
+        }
+    }
+}
+
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Int(int) => write!(f, "{}", int),
+            Self::Float(float) => write!(f, "{}", float),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(Number),
+    Variable(String),
+    Operator(&'static str),
+    Open,
+    Close,
+}
+
+/// The operators, longest first so that `**` is not read as two `*`
+const OPERATORS: [&str; 25] = [
+    "**", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "+", "-", "*", "/", "%", "&", "|", "^",
+    "~", "!", "<", ">", "?", ":", "²", "³",
+];
+
+fn tokenize(input: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+    while let Some(&character) = chars.peek() {
+        match character {
+            _ if character.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::Open);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::Close);
+            }
+            '0'..='9' | '.' => tokens.push(Token::Number(number(&mut chars)?)),
+            '$' => {
+                chars.next();
+                let braced = chars.peek() == Some(&'{');
+                if braced {
+                    chars.next();
+                }
+                let name = identifier(&mut chars);
+                if name.is_empty() || (braced && chars.next() != Some('}')) {
+                    return Err(ArithmeticError::UnexpectedCharacter('$'));
+                }
+                tokens.push(Token::Variable(name));
+            }
+            _ if character.is_alphabetic() || character == '_' => {
+                tokens.push(Token::Variable(identifier(&mut chars)));
+            }
+            _ => {
+                let rest = chars.clone().collect::<String>();
+                let operator = OPERATORS
+                    .iter()
+                    .copied()
+                    .find(|operator| rest.starts_with(operator))
+                    .ok_or(ArithmeticError::UnexpectedCharacter(character))?;
+                for _ in 0..operator.chars().count() {
+                    chars.next();
+                }
+                tokens.push(Token::Operator(operator));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+fn identifier(chars: &mut Peekable<Chars<'_>>) -> String {
+    let mut name = String::new();
+    while let Some(&character) = chars.peek() {
+        // `²` and `³` are numeric, but are operators rather than part of a name
+        if !character.is_alphabetic() && !character.is_ascii_digit() && character != '_' {
+            break;
+        }
+        name.push(character);
+        chars.next();
+    }
+    name
+}
+
+fn number(chars: &mut Peekable<Chars<'_>>) -> Result<Number> {
+    let mut text = String::new();
+    while let Some(&character) = chars.peek() {
+        let exponent_sign = (character == '-' || character == '+')
+            && text.ends_with(|last| last == 'e' || last == 'E')
+            && !text.contains(|c| c == 'x' || c == 'X');
+        if !character.is_ascii_alphanumeric() && character != '.' && !exponent_sign {
+            break;
+        }
+        text.push(character);
+        chars.next();
+    }
+    Number::parse(&text).ok_or(ArithmeticError::UnexpectedToken(text))
+}
+
+/// A parsed expression, evaluated lazily so that `&&`, `||` and `?:` short-circuit
+#[derive(Debug)]
+enum Expression {
+    Number(Number),
+    Variable(String),
+    Unary(&'static str, Box<Expression>),
+    Binary(&'static str, Box<Expression>, Box<Expression>),
+    Ternary(Box<Expression>, Box<Expression>, Box<Expression>),
+}
+
+/// The binding power of the binary operators, from the loosest to the tightest
+fn precedence(operator: &str) -> Option<u8> {
+    Some(match operator {
+        "||" => 1,
+        "&&" => 2,
+        "|" => 3,
+        "^" => 4,
+        "&" => 5,
+        "==" | "!=" => 6,
+        "<" | "<=" | ">" | ">=" => 7,
+        "<<" | ">>" => 8,
+        "+" | "-" => 9,
+        "*" | "/" | "%" => 10,
+        _ => return None,
+    })
+}
+
+struct Parser {
+    tokens:   Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> { self.tokens.get(self.position) }
+
+    fn advance(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: &Token) -> Result<()> {
+        match self.advance() {
+            Some(ref token) if token == expected => Ok(()),
+            Some(token) => Err(unexpected(&token)),
+            None => Err(ArithmeticError::UnexpectedEnd),
+        }
+    }
+
+    /// `condition ? then : otherwise`, which is right associative
+    fn ternary(&mut self) -> Result<Expression> {
+        let condition = self.binary(1)?;
+        if self.peek() != Some(&Token::Operator("?")) {
+            return Ok(condition);
+        }
+        self.advance();
+        let then = self.ternary()?;
+        self.expect(&Token::Operator(":"))?;
+        let otherwise = self.ternary()?;
+        Ok(Expression::Ternary(Box::new(condition), Box::new(then), Box::new(otherwise)))
+    }
+
+    /// Binary operators binding at least as tightly as `min_precedence`
+    fn binary(&mut self, min_precedence: u8) -> Result<Expression> {
+        let mut lhs = self.unary()?;
+        while let Some(&Token::Operator(operator)) = self.peek() {
+            match precedence(operator) {
+                Some(precedence) if precedence >= min_precedence => {
+                    self.advance();
+                    let rhs = self.binary(precedence + 1)?;
+                    lhs = Expression::Binary(operator, Box::new(lhs), Box::new(rhs));
+                }
+                _ => break,
+            }
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Expression> {
+        match self.peek() {
+            Some(&Token::Operator(operator)) if ["-", "+", "!", "~"].contains(&operator) => {
+                self.advance();
+                Ok(Expression::Unary(operator, Box::new(self.unary()?)))
+            }
+            _ => self.power(),
+        }
+    }
+
+    /// `a ** b`, which binds tighter than unary operators on its left, and is right associative
+    fn power(&mut self) -> Result<Expression> {
+        let base = self.postfix()?;
+        if self.peek() == Some(&Token::Operator("**")) {
+            self.advance();
+            Ok(Expression::Binary("**", Box::new(base), Box::new(self.unary()?)))
+        } else {
+            Ok(base)
+        }
+    }
+
+    /// The `²` and `³` operators
+    fn postfix(&mut self) -> Result<Expression> {
+        let mut operand = self.primary()?;
+        while let Some(&Token::Operator(operator)) = self.peek() {
+            let exponent = match operator {
+                "²" => 2,
+                "³" => 3,
+                _ => break,
+            };
+            self.advance();
+            let exponent = Box::new(Expression::Number(Number::Int(exponent)));
+            operand = Expression::Binary("**", Box::new(operand), exponent);
+        }
+        Ok(operand)
+    }
+
+    fn primary(&mut self) -> Result<Expression> {
+        match self.advance() {
+            Some(Token::Number(number)) => Ok(Expression::Number(number)),
+            Some(Token::Variable(name)) => Ok(Expression::Variable(name)),
+            Some(Token::Open) => {
+                let expression = self.ternary()?;
+                self.expect(&Token::Close)?;
+                Ok(expression)
+            }
+            Some(token) => Err(unexpected(&token)),
+            None => Err(ArithmeticError::UnexpectedEnd),
+        }
+    }
+}
+
+fn unexpected(token: &Token) -> ArithmeticError {
+    ArithmeticError::UnexpectedToken(match token {
+        Token::Number(number) => number.to_string(),
+        Token::Variable(name) => name.clone(),
+        Token::Operator(operator) => (*operator).to_string(),
+        Token::Open => "(".into(),
+        Token::Close => ")".into(),
+    })
+}
+
+const fn boolean(value: bool) -> Number { Number::Int(value as i64) }
+
+/// Apply an operator on integers, or on floats if either operand is a float
+fn arithmetic(
+    lhs: Number,
+    rhs: Number,
+    int: impl FnOnce(i64, i64) -> Option<i64>,
+    float: impl FnOnce(f64, f64) -> f64,
+) -> Result<Number> {
+    match (lhs, rhs) {
+        (Number::Int(lhs), Number::Int(rhs)) => {
+            int(lhs, rhs).map(Number::Int).ok_or(ArithmeticError::Overflow)
+        }
+        _ => Ok(Number::Float(float(lhs.as_float(), rhs.as_float()))),
+    }
+}
+
+fn shift(lhs: Number, rhs: Number, operator: &'static str) -> Result<Number> {
+    let (lhs, rhs) = (lhs.int(operator)?, rhs.int(operator)?);
+    let rhs = u32::try_from(rhs).ok().filter(|&rhs| rhs < 64).ok_or(ArithmeticError::Overflow)?;
+    Ok(Number::Int(if operator == "<<" { lhs << rhs } else { lhs >> rhs }))
+}
+
+fn power(base: Number, exponent: Number) -> Result<Number> {
+    match (base, exponent) {
+        (Number::Int(base), Number::Int(exponent)) if exponent >= 0 => u32::try_from(exponent)
+            .ok()
+            .and_then(|exponent| base.checked_pow(exponent))
+            .map(Number::Int)
+            .ok_or(ArithmeticError::Overflow),
+        _ => Ok(Number::Float(base.as_float().powf(exponent.as_float()))),
+    }
+}
+
+impl Expression {
+    fn evaluate(
+        &self,
+        variable: &mut impl FnMut(&str) -> Option<types::Str>,
+    ) -> Result<Number> {
+        match self {
+            Self::Number(number) => Ok(*number),
+            Self::Variable(name) => {
+                let value = variable(name)
+                    .ok_or_else(|| ArithmeticError::UnknownVariable(name.clone()))?;
+                Number::parse(&value)
+                    .ok_or_else(|| ArithmeticError::NotANumber(name.clone(), value.to_string()))
+            }
+            Self::Unary(operator, operand) => {
+                let operand = operand.evaluate(variable)?;
+                match *operator {
+                    "-" => match operand {
+                        Number::Int(int) => {
+                            int.checked_neg().map(Number::Int).ok_or(ArithmeticError::Overflow)
+                        }
+                        Number::Float(float) => Ok(Number::Float(-float)),
+                    },
+                    "!" => Ok(boolean(!operand.is_true())),
+                    "~" => Ok(Number::Int(!operand.int("~")?)),
+                    _ => Ok(operand),
+                }
+            }
+            Self::Ternary(condition, then, otherwise) => {
+                if condition.evaluate(variable)?.is_true() {
+                    then.evaluate(variable)
+                } else {
+                    otherwise.evaluate(variable)
+                }
+            }
+            Self::Binary("&&", lhs, rhs) => {
+                Ok(boolean(lhs.evaluate(variable)?.is_true() && rhs.evaluate(variable)?.is_true()))
+            }
+            Self::Binary("||", lhs, rhs) => {
+                Ok(boolean(lhs.evaluate(variable)?.is_true() || rhs.evaluate(variable)?.is_true()))
+            }
+            Self::Binary(operator, lhs, rhs) => {
+                let (lhs, rhs) = (lhs.evaluate(variable)?, rhs.evaluate(variable)?);
+                let compare = || lhs.as_float().partial_cmp(&rhs.as_float());
+                match *operator {
+                    "+" => arithmetic(lhs, rhs, i64::checked_add, |a, b| a + b),
+                    "-" => arithmetic(lhs, rhs, i64::checked_sub, |a, b| a - b),
+                    "*" => arithmetic(lhs, rhs, i64::checked_mul, |a, b| a * b),
+                    "/" | "%" if !rhs.is_true() => Err(ArithmeticError::DivideByZero),
+                    "/" => arithmetic(lhs, rhs, i64::checked_div, |a, b| a / b),
+                    "%" => arithmetic(lhs, rhs, i64::checked_rem, |a, b| a % b),
+                    "**" => power(lhs, rhs),
+                    "<<" | ">>" => shift(lhs, rhs, *operator),
+                    "&" => Ok(Number::Int(lhs.int("&")? & rhs.int("&")?)),
+                    "|" => Ok(Number::Int(lhs.int("|")? | rhs.int("|")?)),
+                    "^" => Ok(Number::Int(lhs.int("^")? ^ rhs.int("^")?)),
+                    "==" => Ok(boolean(compare() == Some(Ordering::Equal))),
+                    "!=" => Ok(boolean(compare() != Some(Ordering::Equal))),
+                    "<" => Ok(boolean(compare() == Some(Ordering::Less))),
+                    ">" => Ok(boolean(compare() == Some(Ordering::Greater))),
+                    "<=" => Ok(boolean(compare().map_or(false, Ordering::is_le))),
+                    ">=" => Ok(boolean(compare().map_or(false, Ordering::is_ge))),
+                    _ => unreachable!("unknown binary operator {}", operator),
+                }
+            }
+        }
+    }
+}
+
+/// Evaluate an arithmetic expression, looking up the value of its variables with `variable`
+pub(crate) fn evaluate(
+    input: &str,
+    mut variable: impl FnMut(&str) -> Option<types::Str>,
+) -> Result<Number> {
+    let mut parser = Parser { tokens: tokenize(input)?, position: 0 };
+    if parser.tokens.is_empty() {
+        return Err(ArithmeticError::Empty);
+    }
+    let expression = parser.ternary()?;
+    match parser.advance() {
+        Some(token) => Err(unexpected(&token)),
+        None => expression.evaluate(&mut variable),
+    }
+}
+
+fn is_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// Split an assignment, such as `i = 0`, `i += 2` or `i++`, into the name of the assigned
+/// variable and the expression of its new value. Returns `None` if the clause is a plain
+/// expression. Used by the clauses of C-style for loops.
+pub(crate) fn assignment(clause: &str) -> Option<(&str, String)> {
+    let clause = clause.trim();
+    for &(prefix, operator) in &[("++", "+"), ("--", "-")] {
+        if let Some(name) = clause.strip_prefix(prefix).map(str::trim).filter(|name| is_name(name))
+        {
+            return Some((name, format!("{} {} 1", name, operator)));
+        }
+    }
+
+    let end = clause.find(|c: char| !c.is_alphanumeric() && c != '_').unwrap_or(clause.len());
+    let (name, rest) = clause.split_at(end);
+    if !is_name(name) {
+        return None;
+    }
+    match rest.trim_start() {
+        "++" => Some((name, format!("{} + 1", name))),
+        "--" => Some((name, format!("{} - 1", name))),
+        rest if rest.starts_with("==") => None,
+        rest => {
+            let position = rest.find('=')?;
+            let (operator, value) = (&rest[..position], rest[position + 1..].trim());
+            match operator {
+                "" => Some((name, value.to_string())),
+                "+" | "-" | "*" | "/" | "%" | "**" | "<<" | ">>" | "&" | "|" | "^" => {
+                    Some((name, format!("{} {} ({})", name, operator, value)))
+                }
+                _ => None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(input: &str) -> Result<Number> {
+        evaluate(input, |name| match name {
+            "a" => Some("6".into()),
+            "b" => Some("4".into()),
+            "half" => Some("0.5".into()),
+            "word" => Some("foo".into()),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn precedence_and_associativity() {
+        assert_eq!(eval("1 + 2 * 3"), Ok(Number::Int(7)));
+        assert_eq!(eval("(1 + 2) * 3"), Ok(Number::Int(9)));
+        assert_eq!(eval("10 - 4 - 3"), Ok(Number::Int(3)));
+        assert_eq!(eval("2 ** 3 ** 2"), Ok(Number::Int(512)));
+        assert_eq!(eval("-2 ** 2"), Ok(Number::Int(-4)));
+        assert_eq!(eval("1 << 2 + 1"), Ok(Number::Int(8)));
+        assert_eq!(eval("6 & 3 | 8 ^ 1"), Ok(Number::Int(11)));
+        assert_eq!(eval("a²+b³"), Ok(Number::Int(100)));
+    }
+
+    #[test]
+    fn variables() {
+        assert_eq!(eval("a * b"), Ok(Number::Int(24)));
+        assert_eq!(eval("$a - ${b}"), Ok(Number::Int(2)));
+        assert_eq!(eval("a * half"), Ok(Number::Float(3.)));
+        assert_eq!(eval("missing + 1"), Err(ArithmeticError::UnknownVariable("missing".into())));
+        assert_eq!(eval("word"), Err(ArithmeticError::NotANumber("word".into(), "foo".into())));
+    }
+
+    #[test]
+    fn integers_and_floats() {
+        assert_eq!(eval("7 / 2"), Ok(Number::Int(3)));
+        assert_eq!(eval("7.0 / 2"), Ok(Number::Float(3.5)));
+        assert_eq!(eval("-7 % 3"), Ok(Number::Int(-1)));
+        assert_eq!(eval("2 ** -1"), Ok(Number::Float(0.5)));
+        assert_eq!(eval("0x1f + 1e2"), Ok(Number::Float(131.)));
+        assert_eq!(eval("1.5 & 1"), Err(ArithmeticError::IntegerRequired("&")));
+        assert_eq!(Number::Float(2.).to_string(), "2");
+    }
+
+    #[test]
+    fn logic_and_comparisons() {
+        assert_eq!(eval("a > b && b >= 4"), Ok(Number::Int(1)));
+        assert_eq!(eval("a == b || !a"), Ok(Number::Int(0)));
+        assert_eq!(eval("~0"), Ok(Number::Int(-1)));
+        assert_eq!(eval("a < b ? 1 : b < 5 ? 2 : 3"), Ok(Number::Int(2)));
+        // The branches which are not taken are not evaluated
+        assert_eq!(eval("0 && 1 / 0"), Ok(Number::Int(0)));
+        assert_eq!(eval("1 ? 2 : missing"), Ok(Number::Int(2)));
+    }
+
+    #[test]
+    fn assignments() {
+        assert_eq!(assignment("i = 0"), Some(("i", "0".into())));
+        assert_eq!(assignment("i=a*2"), Some(("i", "a*2".into())));
+        assert_eq!(assignment("i += 2"), Some(("i", "i + (2)".into())));
+        assert_eq!(assignment("i <<= 1"), Some(("i", "i << (1)".into())));
+        assert_eq!(assignment("i++"), Some(("i", "i + 1".into())));
+        assert_eq!(assignment("--i"), Some(("i", "i - 1".into())));
+        assert_eq!(assignment("i == 0"), None);
+        assert_eq!(assignment("i <= 0"), None);
+        assert_eq!(assignment("i != 0"), None);
+        assert_eq!(assignment("1 + 2"), None);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(eval("1 / 0"), Err(ArithmeticError::DivideByZero));
+        assert_eq!(eval("1 % (a - 6)"), Err(ArithmeticError::DivideByZero));
+        assert_eq!(eval("1.5 / 0.0"), Err(ArithmeticError::DivideByZero));
+        assert_eq!(eval("9223372036854775807 + 1"), Err(ArithmeticError::Overflow));
+        assert_eq!(eval(""), Err(ArithmeticError::Empty));
+        assert_eq!(eval("(1 + 2"), Err(ArithmeticError::UnexpectedEnd));
+        assert_eq!(eval("1 2"), Err(ArithmeticError::UnexpectedToken("2".into())));
+        assert_eq!(eval("1 # 2"), Err(ArithmeticError::UnexpectedCharacter('#')));
+    }
+}
@@ -1,5 +1,34 @@
-use super::{Expander, Result};
-use crate::{ranges, types};
+use super::{Error, Expander, Result};
+use crate::{assignments::is_array, ranges, types};
+
+/// Whether a word is written as a range, such as `0..$end`: it contains `..` outside of the
+/// expansions and the brackets it contains
+fn is_range(word: &str) -> bool {
+    let mut depth = 0_usize;
+    let mut bytes = word.bytes().peekable();
+    while let Some(byte) = bytes.next() {
+        match byte {
+            b'(' | b'{' | b'[' => depth += 1,
+            b')' | b'}' | b']' => depth = depth.saturating_sub(1),
+            b'.' if depth == 0 && bytes.peek() == Some(&b'.') => return true,
+            _ => (),
+        }
+    }
+    false
+}
+
+/// The first bound of `range` which is not a number, when it was expanded from an expression
+/// written as a range with expansions, such as `0..$(echo end)`. A word without expansions which
+/// is not a range is a single value.
+fn invalid_bound<'a>(expression: &[types::Str], range: &'a str) -> Option<&'a str> {
+    match expression {
+        [word] if word.contains('$') && is_range(word) => range
+            .split("..")
+            .map(|bound| bound.trim_start_matches(|c| c == '.' || c == '='))
+            .find(|bound| bound.parse::<f64>().is_err()),
+        _ => None,
+    }
+}
 
 /// The expression given to a for loop as the value to iterate upon.
 pub enum ForValueExpression {
@@ -7,29 +36,82 @@ pub enum ForValueExpression {
     Multiple(Vec<types::Str>),
     /// A single value
     Normal(types::Str),
-    /// A range of numbers
+    /// A range of numbers, integers or decimals
     Range(Box<dyn Iterator<Item = types::Str> + 'static>),
+    /// The keys of a map, in sorted order: `%{map}`
+    HashMapKeys(Vec<types::Str>),
+    /// The key-value pairs of a map, sorted by key: `%-{map}`
+    HashMapPairs(Vec<(types::Str, types::Str)>),
 }
 
 impl ForValueExpression {
     /// Parse the arguments for the for loop
+    ///
+    /// Array expansions, such as `@array`, `@{array}` or `[a b c]`, always yield their elements
+    /// as separate values, so that a single element is neither word-split nor mistaken for a
+    /// range.
     pub fn new<E: Expander>(
         expression: &[types::Str],
         expanders: &mut E,
     ) -> Result<Self, E::Error> {
+        if let [word] = expression {
+            if let Some(name) = word.strip_prefix("%{").and_then(|w| w.strip_suffix('}')) {
+                let mut keys: Vec<_> = expanders.map_keys(name)?.into_iter().collect();
+                keys.sort();
+                return Ok(Self::HashMapKeys(keys));
+            } else if let Some(name) = word.strip_prefix("%-{").and_then(|w| w.strip_suffix('}'))
+            {
+                let keys = expanders.map_keys(name)?;
+                let values = expanders.map_values(name)?;
+                let mut pairs: Vec<_> = keys.into_iter().zip(values).collect();
+                pairs.sort();
+                return Ok(Self::HashMapPairs(pairs));
+            }
+        }
+
         let mut output = Vec::new();
+        let mut arrays = false;
         for exp in expression {
+            arrays |= exp.starts_with('@') || is_array(exp);
             output.extend(expanders.expand_string(exp)?);
         }
 
-        Ok(if output.is_empty() {
+        Ok(if output.is_empty() || arrays {
             Self::Multiple(output)
         } else if let (Some(range), true) = (ranges::parse_range(&output[0]), output.len() == 1) {
             Self::Range(range)
+        } else if let (Some(range), true) =
+            (ranges::parse_float_range(&output[0]), output.len() == 1)
+        {
+            if range.step() == 0.0 {
+                return Err(Error::ZeroStep(output[0].to_string()));
+            }
+            Self::Range(Box::new(range.map(Into::into)))
         } else if output.len() > 1 {
             Self::Multiple(output)
+        } else if let Some(bound) = invalid_bound(expression, &output[0]) {
+            return Err(Error::InvalidRangeBound(output[0].to_string(), bound.into()));
         } else {
             Self::Normal(output[0].clone())
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_bounds() {
+        let expression = |word: &str| [types::Str::from(word)];
+        assert_eq!(invalid_bound(&expression("0..$end"), "0..notanumber"), Some("notanumber"));
+        assert_eq!(invalid_bound(&expression("$(echo x)..=3"), "x..=3"), Some("x"));
+        assert_eq!(invalid_bound(&expression("0..$step...$end"), "0..2...a"), Some("a"));
+        assert_eq!(invalid_bound(&expression("0..$(echo)"), "0.."), Some(""));
+        assert_eq!(invalid_bound(&expression("0..$end"), "0..-1.5"), None);
+        // Not written as ranges
+        assert_eq!(invalid_bound(&expression("$path"), "../file"), None);
+        assert_eq!(invalid_bound(&expression("$(echo a..b)"), "a..b"), None);
+        assert_eq!(invalid_bound(&expression("file..txt"), "file..txt"), None);
+    }
+}
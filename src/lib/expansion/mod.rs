@@ -1,13 +1,16 @@
 // TODO: Handle Runtime Errors
+pub(crate) mod arithmetic;
 mod braces;
 mod loops;
 mod methods;
 /// Expand pipelines
 pub mod pipelines;
+mod ternary;
 mod words;
 
 use self::braces::BraceToken;
 pub use self::{
+    arithmetic::ArithmeticError,
     loops::ForValueExpression,
     methods::MethodError,
     words::{unescape, Select, SelectWithSize, WordIterator, WordToken},
@@ -35,6 +38,9 @@ pub enum Error<T: fmt::Debug + error::Error + fmt::Display + 'static> {
     /// Error during method expansion
     #[error("{0}")]
     MethodError(#[source] MethodError),
+    /// Error during arithmetic expansion
+    #[error("{0}")]
+    ArithmeticError(#[source] ArithmeticError),
     /// Wrong type was given
     #[error("{0}")]
     TypeError(#[source] TypeError),
@@ -103,6 +109,14 @@ pub enum Error<T: fmt::Debug + error::Error + fmt::Display + 'static> {
     /// Mixed types between maps and scalar/array value
     #[error("variable '{0}' is not a map-like value")]
     NotAMap(String),
+
+    /// A range of decimal numbers would never reach its end
+    #[error("the step of the range '{0}' is zero")]
+    ZeroStep(String),
+
+    /// A bound of a range given to a for loop was expanded to something else than a number
+    #[error("the bound '{1}' of the range '{0}' is not a number")]
+    InvalidRangeBound(String, String),
 }
 
 impl<T: fmt::Display + fmt::Debug + error::Error> From<TypeError> for Error<T> {
@@ -113,6 +127,10 @@ impl<T: fmt::Display + fmt::Debug + error::Error> From<MethodError> for Error<T>
     fn from(cause: MethodError) -> Self { Self::MethodError(cause) }
 }
 
+impl<T: fmt::Display + fmt::Debug + error::Error> From<ArithmeticError> for Error<T> {
+    fn from(cause: ArithmeticError) -> Self { Self::ArithmeticError(cause) }
+}
+
 /// The result of expansion with a given expander
 pub type Result<T, E> = std::result::Result<T, Error<E>>;
 
@@ -146,10 +164,22 @@ pub trait Expander: Sized {
         _command: &str,
         _set_cmd_duration: bool,
     ) -> Result<types::Str, Self::Error>;
+    /// Expand a subshell expression to its standard error: `$^(command)`.
+    fn command_stderr(&mut self, _command: &str) -> Result<types::Str, Self::Error>;
     /// Iterating upon key-value maps.
     fn map_keys(&self, _name: &str) -> Result<Args, Self::Error>;
     /// Iterating upon key-value maps.
     fn map_values(&self, _name: &str) -> Result<Args, Self::Error>;
+    /// Evaluate the condition of a conditional expansion: `$(condition ? then : otherwise)`. By
+    /// default, the condition is true if it expands to a non-empty value.
+    fn condition(&mut self, condition: &str) -> Result<bool, Self::Error> {
+        Ok(self.expand_string(condition)?.iter().any(|word| !word.is_empty()))
+    }
+
+    /// Whether an index out of the bounds of an array expands to nothing instead of failing. By
+    /// default, it fails.
+    fn lenient_indices(&self) -> bool { false }
+
     /// Get a string that exists in the shell.
     fn get_string(&mut self, value: &str) -> Result<types::Str, Self::Error> {
         Ok(self.expand_string(value)?.join(" ").into())
@@ -190,10 +220,36 @@ trait ExpanderInternal: Expander {
         command: &str,
         selection: &Option<&'a str>,
     ) -> Result<(), Self::Error> {
-        let result = self.command(command, true)?;
+        let result = self.conditional(command)?;
+        self.slice(current, result.trim_end_matches('\n'), selection)
+    }
+
+    fn expand_stderr_process<'a>(
+        &mut self,
+        current: &mut types::Str,
+        command: &str,
+        selection: &Option<&'a str>,
+    ) -> Result<(), Self::Error> {
+        let result = self.command_stderr(command)?;
         self.slice(current, result.trim_end_matches('\n'), selection)
     }
 
+    /// Expand the branch selected by the condition if the command is a conditional expression,
+    /// or else the output of the command.
+    fn conditional(&mut self, command: &str) -> Result<types::Str, Self::Error> {
+        match ternary::split(command) {
+            Some((condition, then, otherwise)) => {
+                let branch = if self.condition(condition)? { then } else { otherwise };
+                if ternary::split(branch).is_some() {
+                    self.conditional(branch)
+                } else {
+                    self.get_string(branch)
+                }
+            }
+            None => self.command(command, true),
+        }
+    }
+
     fn expand_brace(
         &mut self,
         current: &mut types::Str,
@@ -243,9 +299,19 @@ trait ExpanderInternal: Expander {
                 }
                 Ok(collected)
             }
-            Select::Index(index) => self.array_nth(elements, index).map(|el| args![el]),
+            Select::Index(index) => match self.array_nth(elements, index) {
+                Err(Error::OutOfBound { .. }) if self.lenient_indices() => Ok(Args::new()),
+                element => element.map(|el| args![el]),
+            },
             Select::Range(range) => self.array_range(elements, range),
             Select::Key(key) => Err(Error::KeyOnArray(key.to_string())),
+            Select::Length => {
+                let mut length = 0;
+                for element in elements {
+                    length += self.expand_string(element)?.len();
+                }
+                Ok(args![length.to_string()])
+            }
         }
     }
 
@@ -297,6 +363,9 @@ trait ExpanderInternal: Expander {
                 value.parse::<Select<types::Str>>().map_err(|_| Error::IndexParsingError(value))?;
             let expanded: Vec<_> = expanded.collect();
             let len = expanded.len();
+            if selection == Select::Length {
+                return Ok(args![len.to_string()]);
+            }
             Ok(expanded.into_iter().map(Into::into).select(&selection, len))
         } else {
             Ok(expanded.map(Into::into).collect())
@@ -338,6 +407,10 @@ trait ExpanderInternal: Expander {
                     }
                 }
                 Select::Key(_) => (),
+                Select::Length => {
+                    let length = UnicodeSegmentation::graphemes(expanded.as_ref(), true).count();
+                    output.push_str(&length.to_string());
+                }
             }
         } else {
             output.push_str(expanded.as_ref())
@@ -453,10 +526,13 @@ trait ExpanderInternal: Expander {
             WordToken::Process(command, ref index) => {
                 self.expand_process(&mut output, command, index)?
             }
+            WordToken::ProcessStderr(command, ref index) => {
+                self.expand_stderr_process(&mut output, command, index)?
+            }
             WordToken::Variable(text, ref index) => {
                 self.slice(&mut output, self.string(text)?, index)?;
             }
-            WordToken::Arithmetic(s) => self.expand_arithmetic(&mut output, s),
+            WordToken::Arithmetic(s) => self.expand_arithmetic(&mut output, s)?,
             _ => unreachable!(),
         }
 
@@ -572,6 +648,9 @@ trait ExpanderInternal: Expander {
                 | WordToken::Process(command, ref index) => {
                     self.expand_process(&mut output, command, index)?;
                 }
+                WordToken::ProcessStderr(command, ref index) => {
+                    self.expand_stderr_process(&mut output, command, index)?;
+                }
                 WordToken::ArrayMethod(ref method, _) => {
                     method.handle(&mut output, self)?;
                 }
@@ -596,7 +675,7 @@ trait ExpanderInternal: Expander {
                 WordToken::Variable(text, ref index) => {
                     self.slice(&mut output, self.string(text)?, index)?;
                 }
-                WordToken::Arithmetic(s) => self.expand_arithmetic(&mut output, s),
+                WordToken::Arithmetic(s) => self.expand_arithmetic(&mut output, s)?,
             }
         }
 
@@ -646,38 +725,10 @@ trait ExpanderInternal: Expander {
     /// x * 5 + y => 22
     /// ```
     /// if `x=5` and `y=7`
-    fn expand_arithmetic(&self, output: &mut types::Str, input: &str) {
-        crate::IonPool::string(|intermediate| {
-            crate::IonPool::string(|varbuf| {
-                let flush = |var: &mut types::Str, out: &mut types::Str| {
-                    if !var.is_empty() {
-                        // We have reached the end of a potential variable, so we expand it and push
-                        // it onto the result
-                        out.push_str(self.string(var).as_ref().unwrap_or(var));
-                    }
-                };
-
-                for c in input.bytes() {
-                    match c {
-                        b'0'..=b'9' | b'A'..=b'Z' | b'_' | b'a'..=b'z' => {
-                            varbuf.push(c as char);
-                        }
-                        _ => {
-                            flush(varbuf, intermediate);
-                            varbuf.clear();
-                            intermediate.push(c as char);
-                        }
-                    }
-                }
-
-                flush(varbuf, intermediate);
-
-                output.push_str(&match calc::eval(intermediate) {
-                    Ok(s) => s.to_string(),
-                    Err(e) => e.to_string(),
-                });
-            });
-        });
+    fn expand_arithmetic(&self, output: &mut types::Str, input: &str) -> Result<(), Self::Error> {
+        let value = arithmetic::evaluate(input, |name| self.string(name).ok())?;
+        output.push_str(&value.to_string());
+        Ok(())
     }
 }
 
@@ -760,6 +811,10 @@ pub(crate) mod test {
             Ok(cmd.into())
         }
 
+        fn command_stderr(&mut self, cmd: &str) -> Result<types::Str, Self::Error> {
+            Ok(cmd.into())
+        }
+
         fn tilde(&self, input: &str) -> Result<types::Str, Self::Error> { Ok(input.into()) }
 
         fn map_keys<'a>(&'a self, name: &str) -> Result<Args, Self::Error> {
@@ -4,10 +4,11 @@ use crate::{
     types,
 };
 use itertools::Itertools;
+use serde::{Deserialize, Serialize};
 use std::fmt;
 
 /// What to redirect to the next command
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
 pub enum RedirectFrom {
     /// Stdout (`|`)
     Stdout,
@@ -19,26 +20,42 @@ pub enum RedirectFrom {
     None,
 }
 
+/// How the output of a command is stored in a variable
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub enum Capture {
+    /// As a string, without its trailing newlines (`>$var`)
+    String,
+    /// As an array of its lines (`>@{var}`)
+    Array,
+}
+
 /// An output redirection for a command
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct Redirection {
     /// What to redirect
-    pub from:   RedirectFrom,
-    /// Where to redirect
-    pub file:   types::Str,
+    pub from:    RedirectFrom,
+    /// Where to redirect: a file, or the name of the variable to capture the output in
+    #[serde(with = "crate::shell::compile::string")]
+    pub file:    types::Str,
     /// Should the file be overridden
-    pub append: bool,
+    pub append:  bool,
+    /// Capture the output in a variable instead of writing it to a file
+    pub capture: Option<Capture>,
+    /// Should the captured output also be passed along as if it was not redirected (`>&$var`)
+    pub tee:     bool,
 }
 
 /// Represents input that a process could initially receive from `stdin`
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum Input {
     /// A file; the contents of said file will be written to the `stdin` of a
     /// process
-    File(types::Str),
+    File(#[serde(with = "crate::shell::compile::string")] types::Str),
     /// A string literal that is written to the `stdin` of a process.
     /// If there is a second string, that second string is the EOF phrase for the heredoc.
-    HereString(types::Str),
+    HereString(#[serde(with = "crate::shell::compile::string")] types::Str),
+    /// A variable, as in `<$var`, whose contents are written as-is to the `stdin` of a process.
+    Variable(#[serde(with = "crate::shell::compile::string")] types::Str),
 }
 
 impl<'a> fmt::Display for RedirectFrom {
@@ -61,6 +78,7 @@ impl<'a> fmt::Display for Input {
         match self {
             Input::File(ref file) => write!(f, "< {}", file),
             Input::HereString(ref string) => write!(f, "<<< '{}'", string),
+            Input::Variable(ref variable) => write!(f, "<{}", variable),
         }
     }
 }
@@ -69,7 +87,7 @@ impl<'a> fmt::Display for Redirection {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "{}>{} {}",
+            "{}>{}{}{}{}",
             match self.from {
                 RedirectFrom::Stdout => "",
                 RedirectFrom::Stderr => "^",
@@ -77,12 +95,18 @@ impl<'a> fmt::Display for Redirection {
                 RedirectFrom::None => unreachable!(),
             },
             if self.append { ">" } else { "" },
+            if self.tee { "&" } else { "" },
+            match self.capture {
+                None => " ",
+                Some(Capture::String) => "$",
+                Some(Capture::Array) => "@",
+            },
             self.file,
         )
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, Serialize, Deserialize)]
 /// Where should the pipeline be run
 pub enum PipeType {
     /// In the foreground
@@ -97,7 +121,7 @@ impl Default for PipeType {
     fn default() -> Self { Self::Normal }
 }
 
-#[derive(Default, Debug, PartialEq, Clone)]
+#[derive(Default, Debug, PartialEq, Clone, Serialize, Deserialize)]
 /// A pipeline
 ///
 /// Ex: `cat <<< input > output | cat &| cat &`
@@ -112,7 +136,7 @@ pub struct Pipeline<T> {
 ///
 /// For example `cat <<< input > output` is a pipeitem, with its own redirections, but representing
 /// a single executable to run
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct PipeItem<T> {
     /// The command to spawn
     pub job:     T,
@@ -141,6 +165,7 @@ impl<'a> PipeItem<Job> {
             .map(|input| match input {
                 Input::File(ref s) => shell.get_string(s).map(Input::File),
                 Input::HereString(ref s) => shell.get_string(s).map(Input::HereString),
+                Input::Variable(ref s) => shell.get_string(s).map(Input::Variable),
             })
             .collect::<Result<_, _>>()?;
 
@@ -148,6 +173,10 @@ impl<'a> PipeItem<Job> {
             .outputs
             .iter()
             .map(|output| {
+                // The name of a captured variable is not expanded
+                if output.capture.is_some() {
+                    return Ok(output.clone());
+                }
                 shell.get_string(output.file.as_str()).map(|file| {
                     let mut output = output.clone();
                     output.file = file;
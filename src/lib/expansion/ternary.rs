@@ -0,0 +1,84 @@
+//! Conditional expressions in process expansions: `$(condition ? then : otherwise)`.
+
+/// Find the byte offset of the first `?` or `:` token of `input` which is neither quoted nor
+/// nested in parentheses, braces or brackets. Tokens must be surrounded by whitespace, so that
+/// globs and URLs are not mistaken for ternaries. When looking for a `:`, the `?` of nested
+/// ternaries must first be matched.
+fn find_token(input: &str, token: u8) -> Option<usize> {
+    let bytes = input.as_bytes();
+    let (mut quote, mut depth, mut nested) = (None, 0_usize, 0_usize);
+    let mut skip_next = false;
+    for (i, &byte) in bytes.iter().enumerate() {
+        if skip_next {
+            skip_next = false;
+            continue;
+        }
+        match (quote, byte) {
+            (Some(b'"'), b'\\') => skip_next = true,
+            (Some(open), _) if open == byte => quote = None,
+            (Some(_), _) => (),
+            (None, b'\\') => skip_next = true,
+            (None, b'\'') | (None, b'"') => quote = Some(byte),
+            (None, b'(') | (None, b'{') | (None, b'[') => depth += 1,
+            (None, b')') | (None, b'}') | (None, b']') => depth = depth.saturating_sub(1),
+            (None, b'?') | (None, b':')
+                if depth == 0
+                    && (i == 0 || bytes[i - 1].is_ascii_whitespace())
+                    && bytes.get(i + 1).map_or(true, u8::is_ascii_whitespace) =>
+            {
+                if byte == token && nested == 0 {
+                    return Some(i);
+                } else if byte == b'?' {
+                    nested += 1;
+                } else {
+                    nested = nested.saturating_sub(1);
+                }
+            }
+            _ => (),
+        }
+    }
+    None
+}
+
+/// Split a conditional expression into its condition and its two branches. Ternaries are right
+/// associative, so the last branch may itself be a ternary.
+pub(crate) fn split(input: &str) -> Option<(&str, &str, &str)> {
+    let question = find_token(input, b'?')?;
+    let (condition, rest) = (input[..question].trim(), &input[question + 1..]);
+    let colon = find_token(rest, b':')?;
+    let (then, otherwise) = (rest[..colon].trim(), rest[colon + 1..].trim());
+    if condition.is_empty() {
+        None
+    } else {
+        Some((condition, then, otherwise))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn simple_ternary() {
+        assert_eq!(
+            split(r#"$name ? "hi $name" : "hi stranger""#),
+            Some(("$name", r#""hi $name""#, r#""hi stranger""#))
+        );
+        assert_eq!(split("test -f file ? yes : no"), Some(("test -f file", "yes", "no")));
+    }
+
+    #[test]
+    fn not_a_ternary() {
+        assert_eq!(split("ls file?.txt"), None);
+        assert_eq!(split("echo https://example.com"), None);
+        assert_eq!(split("echo '?' ':'"), None);
+        assert_eq!(split("echo $(a ? b : c)"), None);
+        assert_eq!(split("? a : b"), None);
+    }
+
+    #[test]
+    fn nested_ternaries() {
+        assert_eq!(split("$a ? x : $b ? y : z"), Some(("$a", "x", "$b ? y : z")));
+        assert_eq!(split("$a ? $b ? x : y : z"), Some(("$a", "$b ? x : y", "z")));
+    }
+}
@@ -110,6 +110,8 @@ pub enum WordToken<'a> {
     ArrayProcess(&'a str, bool, Option<&'a str>),
     /// A process that expands to a scalar value
     Process(&'a str, Option<&'a str>),
+    /// A process whose standard error expands to a scalar value
+    ProcessStderr(&'a str, Option<&'a str>),
     /// A method on a scalar value
     StringMethod(StringMethod<'a>),
     /// A method on a array value
@@ -333,8 +335,9 @@ impl<'a> WordIterator<'a> {
         panic!("ion: fatal error with syntax validation: unterminated array process");
     }
 
-    /// Contains the logic for parsing subshell syntax.
-    fn process<I>(&mut self, iterator: &mut I) -> WordToken<'a>
+    /// Contains the logic for parsing subshell syntax, capturing its standard error instead of its
+    /// standard output for `$^(..)`.
+    fn process<I>(&mut self, iterator: &mut I, stderr: bool) -> WordToken<'a>
     where
         I: Iterator<Item = u8>,
     {
@@ -354,6 +357,12 @@ impl<'a> WordIterator<'a> {
                         iterator.next();
                         self.read += 1;
                         level += 1;
+                    } else if self.data.as_bytes()[self.read + 1..].starts_with(b"^(") {
+                        // Pop out the '^(' chars
+                        iterator.next();
+                        iterator.next();
+                        self.read += 2;
+                        level += 1;
                     }
                 }
                 b'@' if self.quotes != Quotes::Single => {
@@ -368,11 +377,16 @@ impl<'a> WordIterator<'a> {
                     if level == 0 {
                         let output = &self.data[start..self.read];
                         self.read += 1;
-                        return if let Some(&b'[') = self.data.as_bytes().get(self.read) {
+                        let selection = if let Some(&b'[') = self.data.as_bytes().get(self.read) {
                             let _ = iterator.next();
-                            WordToken::Process(output, Some(self.read_selection(iterator)))
+                            Some(self.read_selection(iterator))
                         } else {
-                            WordToken::Process(output, None)
+                            None
+                        };
+                        return if stderr {
+                            WordToken::ProcessStderr(output, selection)
+                        } else {
+                            WordToken::Process(output, selection)
                         };
                     } else {
                         level -= 1;
@@ -814,9 +828,17 @@ impl<'a> Iterator for WordIterator<'a> {
                                         self.read += 1;
                                         return Some(self.arithmetic_expression(&mut iterator));
                                     } else {
-                                        return Some(self.process(&mut iterator));
+                                        return Some(self.process(&mut iterator, false));
                                     }
                                 }
+                                Some(b'^')
+                                    if self.data.as_bytes().get(self.read + 1) == Some(&b'(') =>
+                                {
+                                    let _ = iterator.next();
+                                    let _ = iterator.next();
+                                    self.read += 2;
+                                    return Some(self.process(&mut iterator, true));
+                                }
                                 Some(b'{') => {
                                     self.read += 1;
                                     return Some(self.braced_variable(&mut iterator));
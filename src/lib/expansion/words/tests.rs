@@ -134,6 +134,21 @@ fn nested_processes() {
     compare(input, expected);
 }
 
+#[test]
+fn stderr_processes() {
+    let input = "echo $^(cargo build) $^(echo $^(echo one))[0] \"$^(ls)\"";
+    let expected = &[
+        WordToken::Normal("echo".into(), false, false),
+        WordToken::Whitespace(" "),
+        WordToken::ProcessStderr("cargo build", None),
+        WordToken::Whitespace(" "),
+        WordToken::ProcessStderr("echo $^(echo one)", Some("0")),
+        WordToken::Whitespace(" "),
+        WordToken::ProcessStderr("ls", None),
+    ];
+    compare(input, expected);
+}
+
 #[test]
 fn words_process_with_quotes() {
     let input = "echo $(git branch | rg '[*]' | awk '{print $2}')";
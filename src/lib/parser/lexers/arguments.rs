@@ -125,6 +125,10 @@ impl<'a> ArgumentSplitter<'a> {
         }
     }
 
+    /// The data that was not split yet
+    #[must_use]
+    pub fn remainder(&self) -> &'a str { &self.data[self.read..] }
+
     fn scan_singlequotes<B: Iterator<Item = u8>>(&mut self, bytes: &mut B) {
         while let Some(character) = bytes.next() {
             match character {
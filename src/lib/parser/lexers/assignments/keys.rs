@@ -1,4 +1,5 @@
 use super::Primitive;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 /// Keys are used in assignments to define which variable will be set, and whether the correct
@@ -13,7 +14,7 @@ pub struct Key<'a> {
 
 /// Functions require that their keys to have a longer lifetime, and that is made possible
 /// by eliminating the lifetime requirements via allocating a `String`.
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct KeyBuf {
     /// What type should the literal be
     pub kind: Primitive,
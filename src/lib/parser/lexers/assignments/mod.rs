@@ -119,6 +119,18 @@ mod tests {
             assignment_lexer("abc def ?= 123 456"),
             (Some("abc def"), Some(Operator::OptionalEqual), Some("123 456"))
         );
+
+        assert_eq!(assignment_lexer("a := b"), (Some("a"), Some(Operator::Equal), Some("b")));
+
+        assert_eq!(
+            assignment_lexer("a:int:=$b"),
+            (Some("a:int"), Some(Operator::Equal), Some("$b"))
+        );
+
+        assert_eq!(
+            assignment_lexer("a ::= b"),
+            (Some("a"), Some(Operator::ConcatenateHead), Some("b"))
+        );
     }
 
     #[test]
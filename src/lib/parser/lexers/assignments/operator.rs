@@ -1,7 +1,8 @@
+use serde::{Deserialize, Serialize};
 use std::fmt::{self, Display, Formatter};
 
 /// An operation to do on a value
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
 pub enum Operator {
     /// Addition (only works on numeric types)
     Add,
@@ -35,6 +36,8 @@ impl Operator {
             b'/' => Some(Self::Divide),
             b'*' => Some(Self::Multiply),
             b'?' => Some(Self::OptionalEqual),
+            // `:=` always assigns the expanded value, like `=`
+            b':' => Some(Self::Equal),
             _ => None,
         }
     }
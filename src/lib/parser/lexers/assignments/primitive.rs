@@ -1,7 +1,8 @@
+use serde::{Deserialize, Serialize};
 use std::fmt::{self, Display, Formatter};
 
 /// A primitive defines the type that a requested value should satisfy.
-#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Serialize, Deserialize)]
 pub enum Primitive {
     /// A plain string (ex: `"a string"`)
     Str,
@@ -15,6 +15,7 @@ pub use self::{
     statement::{parse_and_validate, Error, StatementSplitter},
     terminator::Terminator,
 };
+pub(crate) use self::statement::StatementVariant;
 
 #[cfg(fuzzing)]
 pub mod fuzzing {
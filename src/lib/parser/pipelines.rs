@@ -2,14 +2,32 @@ use std::iter::Peekable;
 use thiserror::Error;
 
 use crate::{
-    expansion::pipelines::{Input, PipeItem, PipeType, Pipeline, RedirectFrom, Redirection},
+    expansion::pipelines::{
+        Capture, Input, PipeItem, PipeType, Pipeline, RedirectFrom, Redirection,
+    },
     parser::lexers::arguments::{Field, Levels, LevelsError},
-    shell::Job,
+    shell::{variables::Variables, Job},
     types::*,
 };
 
 const ARG_DEFAULT_SIZE: usize = 10;
 
+/// The variable in which an output redirection immediately followed by it, as in `>$var` or
+/// `>@{var}`, captures the output
+fn capture(target: &str) -> Option<(Capture, &str)> {
+    let (capture, name) = match target.as_bytes().first()? {
+        b'$' => (Capture::String, &target[1..]),
+        b'@' => (Capture::Array, &target[1..]),
+        _ => return None,
+    };
+    let name = name.strip_prefix('{').and_then(|name| name.strip_suffix('}')).unwrap_or(name);
+    if Variables::is_valid_name(name) {
+        Some((capture, name))
+    } else {
+        None
+    }
+}
+
 /// An error produced during pipeline parsing
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Error)]
 pub enum PipelineParsingError {
@@ -26,6 +44,9 @@ pub enum PipelineParsingError {
     /// No file was provided after the input redirection
     #[error("expected file argument after redirection for input")]
     NoRedirectionArg,
+    /// No variable was provided after a tee redirection
+    #[error("expected variable after '>&'")]
+    NoTeeVariable,
 
     // quotes
     /// Unterminated double quotes
@@ -107,9 +128,22 @@ impl<'a> Collector<'a> {
         } else {
             false
         };
-        self.arg(bytes)?
-            .ok_or(PipelineParsingError::NoRedirection)
-            .map(|file| outputs.push(Redirection { from, file: file.into(), append }))
+        let tee = match bytes.peek() {
+            Some(&(i, b'&')) if matches!(self.peek(i + 1), Some(b'$') | Some(b'@')) => {
+                bytes.next();
+                true
+            }
+            _ => false,
+        };
+        let adjacent = matches!(bytes.peek(), Some(&(_, b'$')) | Some(&(_, b'@')));
+        let file = self.arg(bytes)?.ok_or(PipelineParsingError::NoRedirection)?;
+        let (file, capture) = match capture(file) {
+            Some((capture, name)) if adjacent => (name, Some(capture)),
+            _ if tee => return Err(PipelineParsingError::NoTeeVariable),
+            _ => (file, None),
+        };
+        outputs.push(Redirection { from, file: file.into(), append, capture, tee });
+        Ok(())
     }
 
     fn parse(&self) -> Result<Pipeline<Job>, PipelineParsingError> {
@@ -210,6 +244,11 @@ impl<'a> Collector<'a> {
                         } else {
                             return Err(PipelineParsingError::HeredocsDeprecated);
                         }
+                    } else if Some(b'$') == self.peek(i + 1) {
+                        // A variable right after the arrow feeds its contents to stdin
+                        if let Some(variable) = self.arg(&mut bytes)? {
+                            inputs.push(Input::Variable(variable.into()));
+                        }
                     } else if let Some(file) = self.arg(&mut bytes)? {
                         // Otherwise interpret it as stdin redirection
                         inputs.push(Input::File(file.into()));
@@ -415,7 +454,9 @@ impl<'a> Collector<'a> {
 mod tests {
     use crate::{
         parser::{
-            pipelines::{Input, PipeItem, PipeType, Pipeline, RedirectFrom, Redirection},
+            pipelines::{
+                Capture, Input, PipeItem, PipeType, Pipeline, RedirectFrom, Redirection,
+            },
             statement::parse,
         },
         shell::{flow_control::Statement, Job, Shell},
@@ -432,9 +473,11 @@ mod tests {
             assert_eq!("HEAD", &pipeline.items[0].job.args[3]);
 
             let expected = vec![Redirection {
-                from:   RedirectFrom::Stderr,
-                file:   "/dev/null".into(),
-                append: false,
+                from:    RedirectFrom::Stderr,
+                file:    "/dev/null".into(),
+                append:  false,
+                capture: None,
+                tee:     false,
             }];
 
             assert_eq!(expected, pipeline.items[0].outputs);
@@ -809,19 +852,25 @@ mod tests {
                     inputs:  Vec::new(),
                     outputs: vec![
                         Redirection {
-                            from:   RedirectFrom::Stderr,
-                            file:   "err".into(),
-                            append: true,
+                            from:    RedirectFrom::Stderr,
+                            file:    "err".into(),
+                            append:  true,
+                            capture: None,
+                            tee:     false,
                         },
                         Redirection {
-                            from:   RedirectFrom::Both,
-                            file:   "both".into(),
-                            append: false,
+                            from:    RedirectFrom::Both,
+                            file:    "both".into(),
+                            append:  false,
+                            capture: None,
+                            tee:     false,
                         },
                         Redirection {
-                            from:   RedirectFrom::Stdout,
-                            file:   "out".into(),
-                            append: false,
+                            from:    RedirectFrom::Stdout,
+                            file:    "out".into(),
+                            append:  false,
+                            capture: None,
+                            tee:     false,
                         },
                     ],
                 },
@@ -852,9 +901,11 @@ mod tests {
                     job:     Job::new(args!["cat"], RedirectFrom::None),
                     inputs:  vec![Input::File("stuff".into())],
                     outputs: vec![Redirection {
-                        from:   RedirectFrom::Stderr,
-                        file:   "other".into(),
-                        append: true,
+                        from:    RedirectFrom::Stderr,
+                        file:    "other".into(),
+                        append:  true,
+                        capture: None,
+                        tee:     false,
                     }],
                 },
             ],
@@ -887,9 +938,11 @@ mod tests {
 
                     inputs:  vec![Input::File("stuff".into())],
                     outputs: vec![Redirection {
-                        from:   RedirectFrom::Both,
-                        file:   "other".into(),
-                        append: true,
+                        from:    RedirectFrom::Both,
+                        file:    "other".into(),
+                        append:  true,
+                        capture: None,
+                        tee:     false,
                     }],
                 },
             ],
@@ -968,9 +1021,11 @@ mod tests {
 
                     inputs:  vec![Input::HereString("$VAR".into())],
                     outputs: vec![Redirection {
-                        from:   RedirectFrom::Stdout,
-                        file:   "out.log".into(),
-                        append: false,
+                        from:    RedirectFrom::Stdout,
+                        file:    "out.log".into(),
+                        append:  false,
+                        capture: None,
+                        tee:     false,
                     }],
                 },
             ],
@@ -979,6 +1034,70 @@ mod tests {
         assert_eq!(Statement::Pipeline(expected), parse(input).unwrap());
     }
 
+    #[test]
+    fn capture_to_variable() {
+        if let Statement::Pipeline(pipeline) = parse("ls >$out ^>>@{errors} > $file").unwrap() {
+            assert_eq!(pipeline.items[0].outputs, vec![
+                Redirection {
+                    from:    RedirectFrom::Stdout,
+                    file:    "out".into(),
+                    append:  false,
+                    capture: Some(Capture::String),
+                    tee:     false,
+                },
+                Redirection {
+                    from:    RedirectFrom::Stderr,
+                    file:    "errors".into(),
+                    append:  true,
+                    capture: Some(Capture::Array),
+                    tee:     false,
+                },
+                Redirection {
+                    from:    RedirectFrom::Stdout,
+                    file:    "$file".into(),
+                    append:  false,
+                    capture: None,
+                    tee:     false,
+                },
+            ]);
+        } else {
+            panic!();
+        }
+    }
+
+    #[test]
+    fn tee_to_variable() {
+        if let Statement::Pipeline(pipeline) = parse("ls >&$out | wc -l ^>>&@{errors}").unwrap() {
+            assert_eq!(pipeline.items[0].outputs, vec![Redirection {
+                from:    RedirectFrom::Stdout,
+                file:    "out".into(),
+                append:  false,
+                capture: Some(Capture::String),
+                tee:     true,
+            }]);
+            assert_eq!(pipeline.items[1].outputs, vec![Redirection {
+                from:    RedirectFrom::Stderr,
+                file:    "errors".into(),
+                append:  true,
+                capture: Some(Capture::Array),
+                tee:     true,
+            }]);
+        } else {
+            panic!();
+        }
+        assert!(parse("ls >&$1").is_err());
+    }
+
+    #[test]
+    fn variable_as_stdin() {
+        if let Statement::Pipeline(pipeline) = parse("cat <$VAR | wc -c < $FILE").unwrap() {
+            assert_eq!(vec![Input::Variable("$VAR".into())], pipeline.items[0].inputs);
+            assert_eq!(vec![Input::File("$FILE".into())], pipeline.items[1].inputs);
+        } else {
+            panic!();
+        }
+    }
+
     #[test]
     fn awk_tests() {
         if let Statement::Pipeline(pipeline) =
@@ -1004,9 +1123,11 @@ mod tests {
 
                 inputs:  Vec::new(),
                 outputs: vec![Redirection {
-                    from:   RedirectFrom::Stdout,
-                    file:   "foo\\'bar".into(),
-                    append: true,
+                    from:    RedirectFrom::Stdout,
+                    file:    "foo\\'bar".into(),
+                    append:  true,
+                    capture: None,
+                    tee:     false,
                 }],
             }],
             pipe:  PipeType::Normal,
@@ -10,8 +10,6 @@ pub enum Error {
     NoConditional,
     #[error("extra value, '{0}', was given to bind")]
     ExtraBind(String),
-    #[error("extra variable, '{0}', was given to case")]
-    ExtraVar(String),
 }
 
 impl FromStr for Case {
@@ -23,7 +21,7 @@ impl FromStr for Case {
         }
         let mut splitter = ArgumentSplitter::new(data);
         // let argument = splitter.next().ok_or(CaseError::Empty)?;
-        let mut argument = None;
+        let mut arguments = Vec::new();
         let mut binding = None;
         let mut conditional = None;
         loop {
@@ -65,15 +63,14 @@ impl FromStr for Case {
                     }
                     conditional = Some(string);
                 }
-                Some(inner) if argument.is_none() => {
-                    argument = Some(inner);
+                Some(inner) => {
+                    arguments.push(Some(inner).filter(|&val| val != "_").map(Into::into));
                     continue;
                 }
-                Some(inner) => return Err(Error::ExtraVar(inner.into())),
                 None => (),
             }
             return Ok(Self {
-                value: argument.filter(|&val| val != "_").map(Into::into),
+                values: arguments,
                 binding: binding.map(Into::into),
                 conditional,
                 statements: Vec::new(),
@@ -90,7 +87,7 @@ mod tests {
     fn case_parsing() {
         assert_eq!(
             Ok(Case {
-                value:       Some("test".into()),
+                values:      vec![Some("test".into())],
                 binding:     Some("test".into()),
                 conditional: Some("exists".into()),
                 statements:  Vec::new(),
@@ -99,7 +96,7 @@ mod tests {
         );
         assert_eq!(
             Ok(Case {
-                value:       Some("test".into()),
+                values:      vec![Some("test".into())],
                 binding:     Some("test".into()),
                 conditional: None,
                 statements:  Vec::new(),
@@ -108,12 +105,21 @@ mod tests {
         );
         assert_eq!(
             Ok(Case {
-                value:       Some("test".into()),
+                values:      vec![Some("test".into())],
                 binding:     None,
                 conditional: None,
                 statements:  Vec::new(),
             }),
             "test".parse::<Case>()
         );
+        assert_eq!(
+            Ok(Case {
+                values:      vec![Some("x".into()), None],
+                binding:     Some("both".into()),
+                conditional: None,
+                statements:  Vec::new(),
+            }),
+            "x _ @ both".parse::<Case>()
+        );
     }
 }
@@ -68,6 +68,12 @@ pub enum Error {
     /// No value given for iteration in a for loop
     #[error("no value supplied for iteration in for loop")]
     NoInKeyword,
+    /// The `where` clause of a for loop lacked a condition
+    #[error("no condition supplied after `where` in for loop")]
+    NoWhereCondition,
+    /// A C-style for loop did not have three clauses in double parentheses
+    #[error("expected three clauses in C-style for loop: for (( INIT; CONDITION; UPDATE ))")]
+    InvalidCStyleFor,
     /// Error with match statements
     #[error("case error: {0}")]
     Case(#[source] CaseError),
@@ -77,6 +83,18 @@ pub enum Error {
         Function names may only contain alphanumeric characters"
     )]
     InvalidFunctionName(String),
+    /// A with-env block was not given any binding, or one of them was not a `NAME=VALUE`
+    #[error("with-env: expected NAME=VALUE bindings before the block: with-env NAME=VALUE...")]
+    InvalidWithEnv,
+    /// A coproc statement was not given a name and a command in braces
+    #[error("coproc: expected a name followed by a command in braces: coproc NAME {{ COMMAND }}")]
+    InvalidCoproc,
+    /// A breakpoint was given something else than a condition
+    #[error("breakpoint: expected a condition after --condition: breakpoint [--condition VALUE]")]
+    InvalidBreakpoint,
+    /// A watch statement was given a block or no command to execute
+    #[error("watch: expected a name followed by a statement in braces: watch NAME {{ COMMAND }}")]
+    InvalidWatch,
     /// The arguments did not match the function's signature
     #[error("function argument error: {0}")]
     InvalidFunctionArgument(#[source] FunctionParseError),
@@ -4,7 +4,7 @@ use super::{
     Error,
 };
 use crate::{
-    parser::lexers::{assignment_lexer, ArgumentSplitter},
+    parser::lexers::{assignment_lexer, ArgumentSplitter, Operator},
     shell::{
         flow_control::{Case, ElseIf, ExportAction, IfMode, LocalAction, Statement},
         variables::Variables,
@@ -13,6 +13,32 @@ use crate::{
 };
 use std::char;
 
+/// Parse the definition of the function `name`, given the rest of its signature
+fn function(name: &str, signature: &str) -> super::Result {
+    if !Variables::is_valid_name(name) {
+        return Err(Error::InvalidFunctionName(name.into()));
+    }
+
+    let (args, description) = parse_function(signature);
+    Ok(Statement::Function {
+        description: description.map(types::Str::from),
+        name:        name.into(),
+        args:        collect_arguments(args)?,
+        statements:  Vec::new(),
+    })
+}
+
+/// The signature of a function literal, `fn(args)`, given as the value of a `let`. The
+/// parentheses keep literal strings such as `fn` or `fn x` apart from function literals.
+fn function_literal(value: &str) -> Option<&str> {
+    value.strip_prefix("fn(").and_then(|signature| signature.strip_suffix(')'))
+}
+
+/// The expression of an arithmetic condition in double parentheses: `(( i < 10 ))`
+fn arithmetic(condition: &str) -> Option<&str> {
+    condition.strip_prefix("((").and_then(|inner| inner.strip_suffix("))")).map(str::trim)
+}
+
 pub fn parse(code: &str) -> super::Result {
     let cmd = code.trim();
     match cmd {
@@ -20,8 +46,27 @@ pub fn parse(code: &str) -> super::Result {
         _ if cmd.starts_with("return ") => {
             Ok(Statement::Return(Some(cmd[7..].trim_start().into())))
         }
+        _ if cmd.starts_with("yield ") => Ok(Statement::Yield(cmd[6..].trim_start().into())),
         "end" => Ok(Statement::End),
+        _ if cmd.starts_with("end ") => match cmd[4..].trim_start().strip_prefix("while") {
+            Some(condition) if condition.starts_with(char::is_whitespace) => {
+                let pipeline = pipelines::Collector::run(condition.trim_start())?;
+                Ok(Statement::EndWhile(vec![Statement::Pipeline(pipeline)]))
+            }
+            Some("") => Err(Error::IncompleteFlowControl),
+            _ => Ok(Statement::Pipeline(pipelines::Collector::run(cmd)?)),
+        },
+        "do" => Ok(Statement::DoWhile { statements: Vec::new(), expression: Vec::new() }),
         "break" => Ok(Statement::Break),
+        "breakpoint" => Ok(Statement::Breakpoint(None)),
+        _ if cmd.starts_with("breakpoint ") => {
+            match cmd[11..].trim_start().strip_prefix("--condition") {
+                Some(condition) if condition.starts_with(char::is_whitespace) => {
+                    Ok(Statement::Breakpoint(Some(condition.trim_start().into())))
+                }
+                _ => Err(Error::InvalidBreakpoint),
+            }
+        }
         "continue" => Ok(Statement::Continue),
         "for" | "match" | "case" => Err(Error::IncompleteFlowControl),
         "let" => Ok(Statement::Let(LocalAction::List)),
@@ -29,6 +74,11 @@ pub fn parse(code: &str) -> super::Result {
             // Split the let expression and ensure that the statement is valid.
             let (keys, op, vals) = assignment_lexer(cmd[4..].trim_start());
             match vals {
+                // A function literal defines a function named after the variable, which must be
+                // a single key without a type
+                Some(vals) if op == Some(Operator::Equal) && function_literal(vals).is_some() => {
+                    function(keys.unwrap().trim(), function_literal(vals).unwrap())
+                }
                 Some(vals) => {
                     // If the values exist, then the keys and operator also exists.
                     Ok(Statement::Let(LocalAction::Assign(
@@ -78,11 +128,29 @@ pub fn parse(code: &str) -> super::Result {
             }
         }
         _ if cmd.starts_with("while ") => {
-            let pipeline = pipelines::Collector::run(cmd[6..].trim_start())?;
-            Ok(Statement::While {
-                expression: vec![Statement::Pipeline(pipeline)],
-                statements: Vec::new(),
-            })
+            let condition = cmd[6..].trim_start();
+            let expression = match arithmetic(condition) {
+                Some(expression) => Statement::Arithmetic(expression.into()),
+                None => Statement::Pipeline(pipelines::Collector::run(condition)?),
+            };
+            Ok(Statement::While { expression: vec![expression], statements: Vec::new() })
+        }
+        _ if cmd.starts_with("for") && cmd[3..].trim_start().starts_with("((") => {
+            let clauses = cmd[3..]
+                .trim_start()
+                .strip_prefix("((")
+                .and_then(|clauses| clauses.strip_suffix("))"))
+                .ok_or(Error::InvalidCStyleFor)?;
+            let mut clauses = clauses.split(';').map(|clause| types::Str::from(clause.trim()));
+            match (clauses.next(), clauses.next(), clauses.next(), clauses.next()) {
+                (Some(init), Some(condition), Some(update), None) => Ok(Statement::CStyleFor {
+                    init,
+                    condition,
+                    update,
+                    statements: Vec::new(),
+                }),
+                _ => Err(Error::InvalidCStyleFor),
+            }
         }
         _ if cmd.starts_with("for ") => {
             let cmd = cmd[4..].trim_start();
@@ -90,14 +158,22 @@ pub fn parse(code: &str) -> super::Result {
             let variables = parts.next().unwrap().split_whitespace().map(Into::into).collect();
             let cmd = parts.next();
 
-            match cmd {
-                Some(cmd) => Ok(Statement::For {
-                    variables,
-                    values: ArgumentSplitter::new(cmd.trim()).map(types::Str::from).collect(),
-                    statements: Vec::new(),
-                }),
-                None => Err(Error::NoInKeyword),
+            let mut words = ArgumentSplitter::new(cmd.ok_or(Error::NoInKeyword)?.trim());
+            let mut values = Vec::new();
+            let mut filter = Vec::new();
+            while let Some(word) = words.next() {
+                if word == "where" {
+                    let condition = words.remainder().trim();
+                    if condition.is_empty() {
+                        return Err(Error::NoWhereCondition);
+                    }
+                    filter.push(parse(condition)?);
+                    break;
+                }
+                values.push(word.into());
             }
+
+            Ok(Statement::For { variables, values, filter, statements: Vec::new() })
         }
         _ if cmd.starts_with("case ") => {
             Ok(Statement::Case(cmd[5..].trim_start().parse::<Case>()?))
@@ -106,22 +182,82 @@ pub fn parse(code: &str) -> super::Result {
             expression: cmd[6..].trim_start().into(),
             cases:      Vec::new(),
         }),
+        // Memoizing functions is a call to the `fn` builtin rather than a definition
+        _ if cmd.starts_with("@cache ") => {
+            let command = format!("fn --memo {}", cmd[7..].trim_start());
+            Ok(Statement::Pipeline(pipelines::Collector::run(&command)?))
+        }
+        _ if cmd.starts_with("fn --memo") => {
+            Ok(Statement::Pipeline(pipelines::Collector::run(cmd)?))
+        }
         _ if cmd.starts_with("fn ") => {
             let cmd = cmd[3..].trim_start();
             let pos = cmd.find(char::is_whitespace).unwrap_or_else(|| cmd.len());
+            function(&cmd[..pos], &cmd[pos..])
+        }
+        _ if cmd.starts_with("coproc ") => {
+            let cmd = cmd[7..].trim_start();
+            let pos = cmd.find(char::is_whitespace).unwrap_or_else(|| cmd.len());
             let name = &cmd[..pos];
             if !Variables::is_valid_name(name) {
-                return Err(Error::InvalidFunctionName(name.into()));
+                return Err(Error::InvalidCoproc);
             }
 
-            let (args, description) = parse_function(&cmd[pos..]);
-            Ok(Statement::Function {
-                description: description.map(types::Str::from),
-                name:        name.into(),
-                args:        collect_arguments(args)?,
-                statements:  Vec::new(),
+            let command = cmd[pos..]
+                .trim()
+                .strip_prefix('{')
+                .and_then(|command| command.strip_suffix('}'))
+                .map(str::trim)
+                .filter(|command| !command.is_empty())
+                .ok_or(Error::InvalidCoproc)?;
+            Ok(Statement::Coproc {
+                name:     name.into(),
+                pipeline: pipelines::Collector::run(command)?,
             })
         }
+        _ if cmd.starts_with("watch ") => {
+            let rest = cmd[6..].trim_start();
+            let pos = rest.find(char::is_whitespace).unwrap_or_else(|| rest.len());
+            let name = &rest[..pos];
+            let command = rest[pos..]
+                .trim()
+                .strip_prefix('{')
+                .and_then(|command| command.strip_suffix('}'))
+                .map(str::trim);
+            match command {
+                Some(command) if Variables::is_valid_name(name) => {
+                    // An empty command is parsed as the default statement
+                    let body = parse(command)?;
+                    if body.is_block()
+                        || matches!(
+                            body,
+                            Statement::Default | Statement::End | Statement::EndWhile(_)
+                        )
+                    {
+                        return Err(Error::InvalidWatch);
+                    }
+                    Ok(Statement::Watch {
+                        name:    name.into(),
+                        command: command.into(),
+                        body:    Box::new(body),
+                    })
+                }
+                // The `watch` command of the system, such as `watch -n 1 ls`
+                _ => Ok(Statement::Pipeline(pipelines::Collector::run(cmd)?)),
+            }
+        }
+        "with-env" => Err(Error::InvalidWithEnv),
+        _ if cmd.starts_with("with-env ") => {
+            let bindings = ArgumentSplitter::new(cmd[9..].trim_start())
+                .map(|binding| match binding.split_once('=') {
+                    Some((name, value)) if Variables::is_valid_name(name) => {
+                        Ok((name.to_owned(), value.to_owned()))
+                    }
+                    _ => Err(Error::InvalidWithEnv),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(Statement::WithEnv { bindings, statements: Vec::new() })
+        }
         _ if cmd.starts_with("time ") => {
             // Ignore embedded time calls
             let mut timed = cmd[4..].trim_start();
@@ -229,6 +365,7 @@ mod tests {
             Statement::For {
                 variables:  vec!["x", "y", "z"].into_iter().map(Into::into).collect(),
                 values:     vec!["1..=10"].into_iter().map(Into::into).collect(),
+                filter:     Vec::new(),
                 statements: Vec::new(),
             }
         );
@@ -238,9 +375,95 @@ mod tests {
             Statement::For {
                 variables:  vec!["x"].into_iter().map(Into::into).collect(),
                 values:     vec!["{1..=10}", "{1..=10}"].into_iter().map(Into::into).collect(),
+                filter:     Vec::new(),
                 statements: Vec::new(),
             }
         );
+
+        assert_eq!(
+            parse("for (( i = 0; i < 10; i++ ))").unwrap(),
+            Statement::CStyleFor {
+                init:       "i = 0".into(),
+                condition:  "i < 10".into(),
+                update:     "i++".into(),
+                statements: Vec::new(),
+            }
+        );
+        assert_eq!(parse("for ((;;))").unwrap(), Statement::CStyleFor {
+            init:       "".into(),
+            condition:  "".into(),
+            update:     "".into(),
+            statements: Vec::new(),
+        });
+        assert_eq!(parse("for (( i = 0; i < 10 ))"), Err(Error::InvalidCStyleFor));
+
+        match parse("for x in @list where test $x -gt 0").unwrap() {
+            Statement::For { values, filter, .. } => {
+                assert_eq!(values, vec![types::Str::from("@list")]);
+                assert_eq!(filter.len(), 1);
+                assert!(matches!(filter[0], Statement::Pipeline(_)));
+            }
+            statement => panic!("expected a for loop, found {}", statement),
+        }
+
+        assert!(matches!(parse("for x in @list where "), Err(Error::NoWhereCondition)));
+    }
+
+    #[test]
+    fn parsing_arithmetic_while() {
+        assert_eq!(parse("while (( i < 10 ))").unwrap(), Statement::While {
+            expression: vec![Statement::Arithmetic("i < 10".into())],
+            statements: Vec::new(),
+        });
+    }
+
+    #[test]
+    fn parsing_coproc() {
+        match parse("coproc server { cat | tr a b }").unwrap() {
+            Statement::Coproc { name, pipeline } => {
+                assert_eq!(name.as_str(), "server");
+                assert_eq!(pipeline.items.len(), 2);
+            }
+            statement => panic!("expected a coproc, found {}", statement),
+        }
+
+        assert_eq!(parse("coproc server cat"), Err(Error::InvalidCoproc));
+        assert_eq!(parse("coproc { cat }"), Err(Error::InvalidCoproc));
+        assert_eq!(parse("coproc server {}"), Err(Error::InvalidCoproc));
+    }
+
+    #[test]
+    fn parsing_watch() {
+        match parse("watch count { echo $count }").unwrap() {
+            Statement::Watch { name, command, body } => {
+                assert_eq!((name.as_str(), command.as_str()), ("count", "echo $count"));
+                assert!(matches!(*body, Statement::Pipeline(_)));
+            }
+            statement => panic!("expected a watch, found {}", statement),
+        }
+
+        assert!(matches!(parse("watch -n 1 ls").unwrap(), Statement::Pipeline(_)));
+        assert_eq!(parse("watch count {}"), Err(Error::InvalidWatch));
+        assert_eq!(parse("watch count { if true }"), Err(Error::InvalidWatch));
+    }
+
+    #[test]
+    fn parsing_with_env() {
+        assert_eq!(
+            parse("with-env DEBUG=1 LOG='very verbose' EMPTY=").unwrap(),
+            Statement::WithEnv {
+                bindings:   vec![
+                    ("DEBUG".into(), "1".into()),
+                    ("LOG".into(), "'very verbose'".into()),
+                    ("EMPTY".into(), "".into()),
+                ],
+                statements: Vec::new(),
+            }
+        );
+
+        assert_eq!(parse("with-env"), Err(Error::InvalidWithEnv));
+        assert_eq!(parse("with-env DEBUG"), Err(Error::InvalidWithEnv));
+        assert_eq!(parse("with-env =1"), Err(Error::InvalidWithEnv));
     }
 
     #[test]
@@ -307,6 +530,25 @@ mod tests {
         assert_eq!(correct_parse, parsed_if);
     }
 
+    #[test]
+    fn parsing_do_while() {
+        let correct_parse = Statement::DoWhile { statements: Vec::new(), expression: Vec::new() };
+        assert_eq!(correct_parse, parse("  do  ").unwrap());
+
+        match parse("end while test $x -lt 3").unwrap() {
+            Statement::EndWhile(expression) => assert_eq!(expression.len(), 1),
+            statement => panic!("expected end while, found {}", statement),
+        }
+        assert_eq!(parse("end while"), Err(Error::IncompleteFlowControl));
+    }
+
+    #[test]
+    fn parsing_memoization() {
+        let expected = Statement::Pipeline(pipelines::Collector::run("fn --memo fib").unwrap());
+        assert_eq!(parse("@cache fib").unwrap(), expected);
+        assert_eq!(parse("fn --memo fib").unwrap(), expected);
+    }
+
     #[test]
     fn parsing_functions() {
         // Default case where spaced normally
@@ -360,4 +602,33 @@ mod tests {
         let parsed_if = parse("fn bob a b      --bob is a nice function").unwrap();
         assert_eq!(correct_parse, parsed_if);
     }
+
+    #[test]
+    fn parsing_function_literals() {
+        let correct_parse = Statement::Function {
+            description: None,
+            name:        "bob".into(),
+            args:        vec![KeyBuf { name: "a".into(), kind: Primitive::Str }],
+            statements:  Vec::default(),
+        };
+        assert_eq!(parse("let bob = fn(a)").unwrap(), correct_parse);
+        assert_eq!(parse("let bob = fn(a)").unwrap(), parse("fn bob a").unwrap());
+        assert_eq!(parse("let bob = fn()").unwrap(), parse("fn bob").unwrap());
+        assert!(matches!(parse("let bob = fnord").unwrap(), Statement::Let(_)));
+        assert!(matches!(parse("let bob = fn").unwrap(), Statement::Let(_)));
+        assert!(matches!(parse("let bob = fn a").unwrap(), Statement::Let(_)));
+        assert!(parse("let a b = fn()").is_err());
+        assert!(parse("let bob:str = fn()").is_err());
+    }
+
+    #[test]
+    fn parsing_breakpoints() {
+        assert_eq!(parse("breakpoint").unwrap(), Statement::Breakpoint(None));
+        assert_eq!(
+            parse("breakpoint --condition \"$DEBUG\"").unwrap(),
+            Statement::Breakpoint(Some("\"$DEBUG\"".into()))
+        );
+        assert_eq!(parse("breakpoint --condition").unwrap_err(), Error::InvalidBreakpoint);
+        assert_eq!(parse("breakpoint now").unwrap_err(), Error::InvalidBreakpoint);
+    }
 }
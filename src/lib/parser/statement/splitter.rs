@@ -116,6 +116,8 @@ impl<'a> Iterator for StatementSplitter<'a> {
                 }
                 // Array expansion
                 b'@' | b'$' => self.variable = true,
+                // The standard error of a process: `$^(..)`
+                b'^' if self.variable && last == Some(b'$') => (),
                 b'{' if [Some(b'$'), Some(b'@')].contains(&last) => self.vbrace = true,
                 b'(' if self.math_paren_level > 0 => self.math_paren_level += 1,
                 b'(' if self.variable && last == Some(b'(') => {
@@ -123,9 +125,19 @@ impl<'a> Iterator for StatementSplitter<'a> {
                     self.paren_level -= 1;
                 }
                 b'(' if self.variable => self.paren_level += 1,
+                // The clauses of a C-style for loop are split by `;`, like an arithmetic
+                // expansion, and the condition of an arithmetic while loop may contain `&&`
+                b'(' if !self.inside_quotes()
+                    && bytes.peek().map(|&(_, next)| next) == Some(b'(')
+                    && ["for", "while"].contains(&self.data[start..i].trim()) =>
+                {
+                    self.math_paren_level = 1;
+                    skip = true;
+                }
                 b'(' if error.is_none() && !self.inside_quotes() => {
                     error = Some(Error::InvalidCharacter(character as char, i + 1))
                 }
+                b')' if self.math_paren_level > 1 => self.math_paren_level -= 1,
                 b')' if self.math_paren_level == 1 => match bytes.peek() {
                     Some(&(_, b')')) => {
                         self.math_paren_level = 0;
@@ -160,7 +172,7 @@ impl<'a> Iterator for StatementSplitter<'a> {
                         self.brace_level -= 1;
                     }
                 }
-                b';' if self.paren_level == 0 => {
+                b';' if self.paren_level == 0 && self.math_paren_level == 0 => {
                     self.read = i + 1;
                     if start == i {
                         return Some(Err(Error::ExpectedCommandButFound(";")));
@@ -173,7 +185,11 @@ impl<'a> Iterator for StatementSplitter<'a> {
                     };
                 }
                 // Detecting if there is a 2nd `&` character
-                b'&' | b'|' if self.paren_level == 0 && last == Some(character) => {
+                b'&' | b'|'
+                    if self.paren_level == 0
+                        && self.math_paren_level == 0
+                        && last == Some(character) =>
+                {
                     self.read = i + 1;
                     if start == i - 1 {
                         return {
@@ -367,6 +383,22 @@ fn arithmetic() {
     let results = StatementSplitter::new(command).collect::<Vec<_>>();
     assert_eq!(results[0], Ok(StatementVariant::Default("$((3 + 3))")));
     assert_eq!(results.len(), 1);
+
+    let command = "echo $(((a + 1) * 2 && b || c)); echo done";
+    let results = StatementSplitter::new(command).collect::<Vec<_>>();
+    assert_eq!(results[0], Ok(StatementVariant::Default("echo $(((a + 1) * 2 && b || c))")));
+    assert_eq!(results[1], Ok(StatementVariant::Default("echo done")));
+    assert_eq!(results.len(), 2);
+}
+
+#[test]
+fn c_style_for() {
+    let command = "for (( i = 0; (i < 10) && j; i++ )); echo $i; end";
+    let results = StatementSplitter::new(command).collect::<Vec<_>>();
+    assert_eq!(results[0], Ok(StatementVariant::Default("for (( i = 0; (i < 10) && j; i++ ))")));
+    assert_eq!(results[1], Ok(StatementVariant::Default("echo $i")));
+    assert_eq!(results[2], Ok(StatementVariant::Default("end")));
+    assert_eq!(results.len(), 3);
 }
 
 #[test]
@@ -497,3 +529,13 @@ fn variants() {
     assert_eq!(results[4], Ok(StatementVariant::Default(r#"echo "Have a good day!""#)));
     assert_eq!(results.len(), 5);
 }
+
+#[test]
+fn arithmetic_while() {
+    let command = "while (( i < 10 && (j || k) )); let i += 1; end";
+    let results = StatementSplitter::new(command).collect::<Vec<_>>();
+    assert_eq!(results[0], Ok(StatementVariant::Default("while (( i < 10 && (j || k) ))")));
+    assert_eq!(results[1], Ok(StatementVariant::Default("let i += 1")));
+    assert_eq!(results[2], Ok(StatementVariant::Default("end")));
+    assert_eq!(results.len(), 3);
+}
@@ -146,7 +146,8 @@ impl<I: Iterator<Item = u8>> Terminator<I> {
                 self.quotes = Quotes::Double;
                 Some(b'"')
             }
-            b'(' if self.inner.prev() == Some(&b'$') || self.inner.prev() == Some(&b'@') => {
+            // `^(` may only start the stderr of a process, `$^(`
+            b'(' if matches!(self.inner.prev(), Some(b'$') | Some(b'@') | Some(b'^')) => {
                 self.subshell += 1;
                 Some(b'(')
             }
@@ -1,12 +1,17 @@
 use super::{
     flow_control::{ExportAction, LocalAction},
-    Shell,
+    IonError, Shell,
 };
 use crate::{
     assignments::*,
     builtins::Status,
-    parser::lexers::assignments::{Key, Operator, Primitive},
+    expansion::Expander,
+    parser::lexers::{
+        assignments::{Key, KeyIterator, Operator, Primitive},
+        ArgumentSplitter,
+    },
     shell::{flow_control::Function, Value, Variables},
+    types,
 };
 use std::{
     env,
@@ -15,6 +20,23 @@ use std::{
 };
 use types_rs::{EuclDiv, Modifications, OpError, Pow};
 
+/// The variables which are managed by the shell, and thus can not be set
+const RESERVED_VARIABLES: [&str; 13] = [
+    "HOME",
+    "HOST",
+    "PWD",
+    "OLDPWD",
+    "MWD",
+    "SWD",
+    "EPOCHSECONDS",
+    "EPOCHREALTIME",
+    "FUNCNAME",
+    "ION_CALL_DEPTH",
+    "0",
+    "?",
+    "argv",
+];
+
 fn list_vars(shell: &Shell<'_>) -> Result<(), io::Error> {
     let stdout = io::stdout();
     let mut buffer = BufWriter::new(stdout.lock());
@@ -65,11 +87,17 @@ impl<'b> Shell<'b> {
     pub fn export(&mut self, action: &ExportAction) -> Status {
         match action {
             ExportAction::Assign(ref keys, op, ref vals) => {
+                if let Err(status) = self.check_readonly(keys) {
+                    return status;
+                }
                 let actions = AssignmentActions::new(keys, *op, vals);
 
                 for action in actions {
                     let err = action.map_err(|e| e.to_string()).and_then(|act| {
                         let Action(key, operator, expression) = act;
+                        if operator == Operator::OptionalEqual && env::var_os(key.name).is_some() {
+                            return Ok(());
+                        }
                         value_check(self, expression, &key.kind)
                             .map_err(|e| format!("{}: {}", key.name, e))
                             // TODO: handle operators here in the same way as local
@@ -127,53 +155,216 @@ impl<'b> Shell<'b> {
         let mut backup: Vec<_> = Vec::with_capacity(4);
         for action in actions {
             let Action(key, operator, expression) = action.map_err(|e| e.to_string())?;
+            if !self.should_assign(&key, operator)? {
+                continue;
+            }
+
+            let rhs = value_check(self, expression, &key.kind)
+                .map_err(|why| format!("{}: {}", key.name, why))?;
+            let value = self.updated_value(&key, operator, rhs)?;
+            backup.push((key, value));
+        }
+        Ok(backup)
+    }
+
+    /// Check that the variable `key` may be set, returning whether the assignment must be
+    /// performed: `?=` does not modify existing variables.
+    fn should_assign(&self, key: &Key<'_>, operator: Operator) -> Result<bool, String> {
+        // sanitize variable names
+        if RESERVED_VARIABLES.contains(&key.name) {
+            return Err(format!("not allowed to set `{}`", key.name));
+        }
+
+        if !Variables::is_valid_name(key.name) {
+            return Err("invalid variable name: only alphanumerical characters and underscores \
+                        are supported"
+                .to_string());
+        }
 
-            // sanitize variable names
-            if ["HOME", "HOST", "PWD", "MWD", "SWD", "?"].contains(&key.name) {
-                return Err(format!("not allowed to set `{}`", key.name));
+        Ok(operator != Operator::OptionalEqual || self.variables.get(key.name).is_none())
+    }
+
+    /// Apply the operator of the assignment to the current value of `key` and to `rhs`
+    fn updated_value(
+        &self,
+        key: &Key<'_>,
+        operator: Operator,
+        rhs: Value<Rc<Function>>,
+    ) -> Result<Value<Rc<Function>>, String> {
+        let value = match (&rhs, &key.kind) {
+            (Value::HashMap(_), Primitive::Indexed(..)) => {
+                return Err("cannot insert hmap into index".to_string())
+            }
+            (Value::BTreeMap(_), Primitive::Indexed(..)) => {
+                return Err("cannot insert bmap into index".to_string())
+            }
+            (Value::Array(_), Primitive::Indexed(..)) => {
+                return Err("multi-dimensional arrays are not yet supported".to_string())
             }
+            _ if [Operator::Equal, Operator::OptionalEqual].contains(&operator) => rhs,
+            _ => {
+                let lhs = self.variables.get(key.name).ok_or_else(|| {
+                    format!("cannot update non existing variable `{}`", key.name)
+                })?;
+                apply(operator, lhs, rhs).map_err(|_| {
+                    format!(
+                        "type error: variable `{}` of type `{}` does not support operator",
+                        key.name, key.kind
+                    )
+                })?
+            }
+        };
+
+        let integer = key.name == "SECONDS" || self.variables.attributes(key.name).integer;
+        if integer && !is_integer(&value) {
+            return Err(format!("type error: `{}` must be an integer", key.name));
+        }
+        Ok(value)
+    }
+
+    /// Distribute the words of the values over the keys, when there are not as many values as
+    /// keys, such as in `let a b = $(cmd)`. The output of an unquoted command substitution is
+    /// split on whitespace, while the other values are kept whole, then each key receives a word,
+    /// the last one receiving all the remaining words. Keys left without a word are an error,
+    /// unless `set -o pad-assignment` sets them to an empty string. Returns `None` when each key
+    /// has its own value, when a key is not a plain string or number, or with `set -S`, which
+    /// makes any mismatch an error.
+    fn distribute_values<'b>(
+        &mut self,
+        keys: &'b str,
+        operator: Operator,
+        vals: &str,
+    ) -> Option<Result<Vec<(Key<'b>, Value<Rc<Function>>)>, String>> {
+        let keys = KeyIterator::new(keys).collect::<Result<Vec<_>, _>>().ok()?;
+        let scalar = |key: &Key<'_>| {
+            matches!(
+                key.kind,
+                Primitive::Str | Primitive::Boolean | Primitive::Integer | Primitive::Float
+            )
+        };
+        if self.opts.strict_assignment
+            || keys.is_empty()
+            || !keys.iter().all(scalar)
+            || ArgumentSplitter::new(vals).count() == keys.len()
+        {
+            return None;
+        }
 
-            if !Variables::is_valid_name(key.name) {
-                return Err("invalid variable name: only alphanumerical characters and \
-                            underscores are supported"
-                    .to_string());
+        let mut words: Vec<types::Str> = Vec::new();
+        for value in ArgumentSplitter::new(vals) {
+            let expanded = match self.expand_string(value) {
+                Ok(expanded) => expanded,
+                Err(why) => return Some(Err(why.to_string())),
+            };
+            if value.starts_with("$(") && value.ends_with(')') {
+                words.extend(
+                    expanded.iter().flat_map(|word| word.split_whitespace()).map(Into::into),
+                );
+            } else {
+                words.extend(expanded);
             }
+        }
+        if words.len() < keys.len() && !self.opts.pad_assignment {
+            let (key, word) = match words.last() {
+                Some(word) => (keys[words.len() - 1].name, word.as_str()),
+                None => (keys[0].name, ""),
+            };
+            return Some(Err(AssignmentError::ExtraKeys(key, word).to_string()));
+        }
+        let remainder = words.split_off(words.len().min(keys.len() - 1));
+        words.push(remainder.join(" ").into());
 
-            if operator == Operator::OptionalEqual && self.variables.get(key.name).is_some() {
-                continue;
+        // All the values are expanded before any of the keys is assigned
+        let mut updates = Vec::with_capacity(keys.len());
+        let mut words = words.into_iter();
+        for key in keys {
+            let word = words.next().unwrap_or_else(types::Str::new);
+            match self.should_assign(&key, operator) {
+                Ok(true) => (),
+                Ok(false) => continue,
+                Err(why) => return Some(Err(why)),
             }
+            let value = string_check(word, &key.kind)
+                .map_err(|why| format!("{}: {}", key.name, why))
+                .and_then(|rhs| self.updated_value(&key, operator, rhs));
+            match value {
+                Ok(value) => updates.push((key, value)),
+                Err(why) => return Some(Err(why)),
+            }
+        }
+        Some(Ok(updates))
+    }
 
-            let rhs = value_check(self, expression, &key.kind)
-                .map_err(|why| format!("{}: {}", key.name, why))?;
+    /// Assign the value returned by a function with `return --value`, when the value of a single
+    /// key assignment is a call to that function written as `@name(args)`, as a generator is
+    /// called in a for loop: `let result = @myfn(args)`. Returns `None` if the value is not a
+    /// function call.
+    fn assign_returned_value(&mut self, keys: &str, op: Operator, vals: &str) -> Option<Status> {
+        let mut words = ArgumentSplitter::new(vals);
+        let call = match (words.next(), words.next()) {
+            (Some(call), None) => call,
+            _ => return None,
+        };
+        let (function, args) = match self.function_call(call) {
+            Ok(Some(call)) => call,
+            Ok(None) => return None,
+            Err(why) => return Some(Status::error(format!("ion: assignment error: {}", why))),
+        };
+        let mut keys = KeyIterator::new(keys);
+        let key = match (keys.next(), keys.next()) {
+            (Some(Ok(key)), None) if op == Operator::Equal => key,
+            _ => {
+                return Some(Status::error(
+                    "ion: assignment error: a function call can only be assigned to a single \
+                     variable with `=`",
+                ))
+            }
+        };
 
-            match (&rhs, &key.kind) {
-                (Value::HashMap(_), Primitive::Indexed(..)) => {
-                    return Err("cannot insert hmap into index".to_string())
-                }
-                (Value::BTreeMap(_), Primitive::Indexed(..)) => {
-                    return Err("cannot insert bmap into index".to_string())
-                }
-                (Value::Array(_), Primitive::Indexed(..)) => {
-                    return Err("multi-dimensional arrays are not yet supported".to_string())
-                }
-                _ if [Operator::Equal, Operator::OptionalEqual].contains(&operator) => {
-                    backup.push((key, rhs))
-                }
-                _ => {
-                    let lhs = self.variables.get(key.name).ok_or_else(|| {
-                        format!("cannot update non existing variable `{}`", key.name)
-                    })?;
-                    let val = apply(operator, lhs, rhs).map_err(|_| {
-                        format!(
-                            "type error: variable `{}` of type `{}` does not support operator",
-                            key.name, key.kind
-                        )
-                    })?;
-                    backup.push((key, val));
-                }
+        if !Variables::is_valid_name(key.name) {
+            return Some(Status::error(format!(
+                "ion: assignment error: invalid variable name `{}`",
+                key.name
+            )));
+        }
+
+        self.return_value = None;
+        let result = function.execute(self, &args);
+        let value = self.return_value.take();
+        if let Err(why) = result {
+            return Some(Status::error(format!("ion: assignment error: {}", why)));
+        }
+
+        Some(match value.map(|value| self.assign(&key, value)) {
+            Some(Ok(())) => Status::SUCCESS,
+            Some(Err(why)) => Status::error(format!("ion: assignment error: {}", why)),
+            None => Status::error(format!(
+                "ion: assignment error: function `{}` did not return a value",
+                function.name()
+            )),
+        })
+    }
+
+    /// Refuse to bind a variable which was declared readonly, such as the variable of a loop
+    pub(crate) fn ensure_writable(&self, name: &str) -> Result<(), IonError> {
+        if self.variables.is_readonly(name) {
+            Err(IonError::ReadonlyVariable(name.into()))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Refuse to modify the variables which were declared readonly
+    fn check_readonly(&self, keys: &str) -> Result<(), Status> {
+        match KeyIterator::new(keys)
+            .filter_map(Result::ok)
+            .find(|key| self.variables.is_readonly(key.name))
+        {
+            Some(key) => {
+                Err(Status::error(format!("ion: cannot modify readonly variable `{}`", key.name)))
             }
+            None => Ok(()),
         }
-        Ok(backup)
     }
 
     /// Set a local variable given a binding
@@ -184,8 +375,19 @@ impl<'b> Shell<'b> {
                 Status::SUCCESS
             }
             LocalAction::Assign(ref keys, op, ref vals) => {
-                let actions = AssignmentActions::new(keys, *op, vals);
-                if let Err(why) = self.calculate(actions).and_then(|apply| {
+                if let Err(status) = self.check_readonly(keys) {
+                    return status;
+                }
+                self.capture_status = None;
+                if let Some(status) = self.assign_returned_value(keys, *op, vals) {
+                    return status;
+                }
+
+                let updates = match self.distribute_values(keys, *op, vals) {
+                    Some(updates) => updates,
+                    None => self.calculate(AssignmentActions::new(keys, *op, vals)),
+                };
+                if let Err(why) = updates.and_then(|apply| {
                     for (key, value) in apply {
                         self.assign(&key, value)?
                     }
@@ -193,13 +395,18 @@ impl<'b> Shell<'b> {
                 }) {
                     Status::error(format!("ion: assignment error: {}", why))
                 } else {
-                    Status::SUCCESS
+                    // Like in POSIX shells, an assignment has the status of its last substitution
+                    self.capture_status.take().unwrap_or(Status::SUCCESS)
                 }
             }
         }
     }
 }
 
+fn is_integer(value: &Value<Rc<Function>>) -> bool {
+    matches!(value, Value::Str(value) if value.parse::<i64>().is_ok())
+}
+
 // This should logically be a method over operator, but Value is only accessible in the main repo
 // TODO: too much allocations occur over here. We need to expand variables before they get
 // parsed
@@ -0,0 +1,63 @@
+//! The `breakpoint` statement, which pauses a script with an interactive session in the scope
+//! of the statement, where the variables can be inspected and modified, and the functions called.
+//! `continue` or `exit`, or the end of the input, resume the script.
+
+use super::{IonError, Shell};
+use crate::expansion::Expander;
+use std::io;
+
+/// Whether the expanded condition of a breakpoint is true: it must not be empty, `0` or `false`
+fn is_true(condition: &str) -> bool { !["", "0", "false"].contains(&condition) }
+
+impl<'a> Shell<'a> {
+    /// Executes a breakpoint, reading the commands of the session from the standard input until
+    /// the script is resumed. The commands do not exit the shell on failure, even with `set -e`,
+    /// and the errors are printed without stopping the session. The status of the statement
+    /// before the breakpoint is kept, so that the session does not change `$?` for the script.
+    pub(super) fn execute_breakpoint(&mut self, condition: Option<&str>) -> Result<(), IonError> {
+        if let Some(condition) = condition {
+            if !is_true(&self.expand_string(condition)?.join(" ")) {
+                return Ok(());
+            }
+        }
+
+        let status = self.previous_status;
+        let mut line = String::new();
+        loop {
+            let prompt = if self.flow_control.is_empty() { "breakpoint> " } else { "> " };
+            let _ = self.write_error(format_args!("{}", prompt));
+            line.clear();
+            if io::stdin().read_line(&mut line).unwrap_or(0) == 0 {
+                let _ = self.write_error(format_args!("\n"));
+                break;
+            }
+            // In a block, such as a loop, `continue` is a statement of the block
+            if self.flow_control.is_empty() && ["continue", "exit"].contains(&line.trim()) {
+                break;
+            }
+            match self.without_err_exit(|shell| shell.on_command(line.bytes(), false)) {
+                Err(IonError::Exit(status)) => return Err(IonError::Exit(status)),
+                Err(why) => {
+                    let _ = self.write_error(format_args!("ion: {}\n", why));
+                }
+                Ok(()) => (),
+            }
+        }
+
+        // A block left open by the session is discarded
+        self.reset_flow();
+        self.previous_status = status;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conditions() {
+        assert!(is_true("1") && is_true("yes") && is_true("true"));
+        assert!(!is_true("") && !is_true("0") && !is_true("false"));
+    }
+}
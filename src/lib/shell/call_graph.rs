@@ -0,0 +1,154 @@
+//! The static call graph of a script, printed by `ion --call-graph` in the DOT format of
+//! Graphviz. The script is parsed without being executed, so that only the calls written as the
+//! command of a pipeline are found: those through aliases, `eval` or command substitutions are
+//! not.
+
+use super::{compile, flow_control::Statement, IonError, Shell};
+use crate::{expansion::pipelines::Pipeline, shell::Job, types};
+use std::{fmt, io::Read};
+
+/// The functions defined by a script and the calls between them, in the order they appear
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CallGraph {
+    functions: Vec<types::Str>,
+    calls:     Vec<(types::Str, types::Str)>,
+}
+
+impl CallGraph {
+    /// The names of the functions defined by the script, including those defined in other
+    /// functions. A function defined several times is listed once.
+    #[must_use]
+    pub fn functions(&self) -> &[types::Str] { &self.functions }
+
+    /// The calls from a function to another, as the names of the caller and of the callee. The
+    /// calls made outside of any function are not included.
+    #[must_use]
+    pub fn calls(&self) -> &[(types::Str, types::Str)] { &self.calls }
+
+    /// Records the functions defined in `statements` and the commands they execute. The
+    /// commands which are not functions are removed once the whole script was visited.
+    fn visit(&mut self, caller: Option<&types::Str>, statements: &[Statement]) {
+        for statement in statements {
+            self.visit_statement(caller, statement);
+        }
+    }
+
+    fn visit_statement(&mut self, caller: Option<&types::Str>, statement: &Statement) {
+        match statement {
+            Statement::Function { name, statements, .. } => {
+                if !self.functions.contains(name) {
+                    self.functions.push(name.clone());
+                }
+                self.visit(Some(name), statements);
+            }
+            Statement::If { expression, success, else_if, failure, .. } => {
+                self.visit(caller, expression);
+                self.visit(caller, success);
+                for block in else_if {
+                    self.visit(caller, &block.expression);
+                    self.visit(caller, &block.success);
+                }
+                self.visit(caller, failure);
+            }
+            Statement::ElseIf(block) => {
+                self.visit(caller, &block.expression);
+                self.visit(caller, &block.success);
+            }
+            Statement::For { filter, statements, .. } => {
+                self.visit(caller, filter);
+                self.visit(caller, statements);
+            }
+            Statement::While { expression, statements }
+            | Statement::DoWhile { statements, expression } => {
+                self.visit(caller, expression);
+                self.visit(caller, statements);
+            }
+            Statement::CStyleFor { statements, .. }
+            | Statement::WithEnv { statements, .. }
+            | Statement::EndWhile(statements) => self.visit(caller, statements),
+            Statement::Case(case) => self.visit(caller, &case.statements),
+            Statement::Match { cases, .. } => {
+                for case in cases {
+                    self.visit(caller, &case.statements);
+                }
+            }
+            Statement::Pipeline(pipeline) | Statement::Coproc { pipeline, .. } => {
+                if let Some(caller) = caller {
+                    self.visit_pipeline(caller, pipeline);
+                }
+            }
+            Statement::Time(statement)
+            | Statement::And(statement)
+            | Statement::Or(statement)
+            | Statement::Not(statement)
+            | Statement::Watch { body: statement, .. } => self.visit_statement(caller, statement),
+            _ => (),
+        }
+    }
+
+    fn visit_pipeline(&mut self, caller: &types::Str, pipeline: &Pipeline<Job>) {
+        for command in pipeline.items.iter().filter_map(|item| item.job.args.first()) {
+            let call = (caller.clone(), command.clone());
+            if !self.calls.contains(&call) {
+                self.calls.push(call);
+            }
+        }
+    }
+}
+
+/// A name quoted as a DOT identifier
+fn quoted(name: &str) -> String {
+    format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+impl fmt::Display for CallGraph {
+    /// The graph in the DOT format, with a node for each function and an edge for each call
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "digraph calls {{")?;
+        for function in &self.functions {
+            writeln!(f, "    {};", quoted(function))?;
+        }
+        for (caller, callee) in &self.calls {
+            writeln!(f, "    {} -> {};", quoted(caller), quoted(callee))?;
+        }
+        writeln!(f, "}}")
+    }
+}
+
+impl<'a> Shell<'a> {
+    /// The functions defined by a script and the calls between them, found without executing
+    /// the script. The whole script is parsed first, so that a function may be called before
+    /// its definition.
+    pub fn call_graph<T: Read>(script: T) -> Result<CallGraph, IonError> {
+        let mut graph = CallGraph::default();
+        graph.visit(None, &compile::parse_script(script)?);
+        let CallGraph { functions, calls } = &mut graph;
+        calls.retain(|(_, callee)| functions.contains(callee));
+        Ok(graph)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn calls_between_functions() {
+        let script = "fn a\n    b | c\n    if not b\n        echo no\n    end\nend\nfn b\n    fn \
+                      c\n        a\n    end\nend\nfn d\nend\na\n";
+        let graph = Shell::call_graph(script.as_bytes()).unwrap();
+        let functions = graph.functions().iter().map(types::Str::as_str).collect::<Vec<_>>();
+        assert_eq!(functions, ["a", "b", "c", "d"]);
+        let calls = graph
+            .calls()
+            .iter()
+            .map(|(caller, callee)| (caller.as_str(), callee.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(calls, [("a", "b"), ("a", "c"), ("c", "a")]);
+        assert_eq!(
+            graph.to_string(),
+            "digraph calls {\n    \"a\";\n    \"b\";\n    \"c\";\n    \"d\";\n    \"a\" -> \
+             \"b\";\n    \"a\" -> \"c\";\n    \"c\" -> \"a\";\n}\n"
+        );
+    }
+}
@@ -121,7 +121,7 @@ impl fmt::Display for Colors {
 impl Colors {
     /// If no matches were made, then this will attempt to parse the variable as either a
     /// 24-bit true color color, or one of 256 colors. It supports both hexadecimal and
-    /// decimals.
+    /// decimals, as well as the explicit `256:N` and `rgb:R:G:B` forms.
     fn parse_colors(&mut self, variable: &str) -> Result<(), ()> {
         // First, determine which field we will write to.
         let (field, variable) = if let Some(varsuffixstrip) = variable.strip_suffix("bg") {
@@ -132,7 +132,21 @@ impl Colors {
 
         // Then, check if the value is a hexadecimal value
         //
-        if let Some(varprefstrip) = variable.strip_prefix("0x") {
+        if let Some(value) = variable.strip_prefix("256:") {
+            if let Ok(value) = value.parse::<u8>() {
+                *field = Some(Mode::Range256(value));
+                return Ok(());
+            }
+        } else if let Some(rgb) = variable.strip_prefix("rgb:") {
+            // 24-bit Color rgb:R:G:B
+            let mut values = rgb.split(':').map(str::parse::<u8>);
+            if let (Some(Ok(red)), Some(Ok(green)), Some(Ok(blue)), None) =
+                (values.next(), values.next(), values.next(), values.next())
+            {
+                *field = Some(Mode::TrueColor(red, green, blue));
+                return Ok(());
+            }
+        } else if let Some(varprefstrip) = variable.strip_prefix("0x") {
             let variable = varprefstrip;
 
             match variable.len() {
@@ -303,4 +317,18 @@ mod test {
         let actual = Colors::collect::<IonError>("0x00FF00,0xFF0000bg").unwrap();
         assert_eq!(expected, actual);
     }
+
+    #[test]
+    fn explicit_colors() {
+        let expected = Colors {
+            attributes: Vec::default(),
+            background: Some(Mode::Range256(202)),
+            foreground: Some(Mode::TrueColor(255, 128, 0)),
+        };
+        let actual = Colors::collect::<IonError>("rgb:255:128:0,256:202bg").unwrap();
+        assert_eq!(expected, actual);
+        assert_eq!("\x1b[38;2;255;128;0;48;5;202m", &actual.to_string());
+        assert!(Colors::collect::<IonError>("rgb:255:128").is_err());
+        assert!(Colors::collect::<IonError>("256:300").is_err());
+    }
 }
@@ -0,0 +1,103 @@
+//! The log of the executed commands, written to the file named by `$ION_DEBUG_COMMANDS`. Like
+//! `set -x`, it records each command as it was expanded, but in a file for offline analysis
+//! rather than interleaved with the output on the standard error.
+
+use super::{variables::Value, Shell};
+use crate::{builtins::Status, types};
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// The variable naming the log file
+const LOG_VARIABLE: &str = "ION_DEBUG_COMMANDS";
+
+/// The log file currently named by `$ION_DEBUG_COMMANDS`
+#[derive(Debug)]
+pub(super) struct CommandLog {
+    path: types::Str,
+    /// `None` when the file could not be opened, so that it is not tried again for each command
+    file: Option<File>,
+}
+
+/// A line of the log: the time since the Unix epoch in seconds, with milliseconds, the status
+/// and the command, separated by tabs
+fn entry(time: SystemTime, status: Status, command: &str) -> String {
+    let time = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    format!(
+        "{}.{:03}\t{}\t{}\n",
+        time.as_secs(),
+        time.subsec_millis(),
+        status.as_os_code(),
+        command
+    )
+}
+
+impl<'a> Shell<'a> {
+    /// Opens the log when `$ION_DEBUG_COMMANDS` is set to a new path, and closes it once the
+    /// variable is unset. Called after each statement, so that the log follows the assignments.
+    pub(super) fn update_command_log(&mut self) {
+        let path = match self.variables.get(LOG_VARIABLE) {
+            Some(Value::Str(path)) if !path.is_empty() => path,
+            _ => {
+                self.command_log = None;
+                return;
+            }
+        };
+        if self.command_log.as_ref().map_or(false, |log| &log.path == path) {
+            return;
+        }
+
+        let file = match OpenOptions::new().create(true).append(true).open(path.as_str()) {
+            Ok(file) => Some(file),
+            Err(why) => {
+                eprintln!("ion: could not open ${} '{}': {}", LOG_VARIABLE, path, why);
+                None
+            }
+        };
+        self.command_log = Some(CommandLog { path: path.clone(), file });
+    }
+
+    /// Appends an executed command with its status to the log, if it is open. Each entry is
+    /// written at once, so that it is in the file even if the shell is killed afterwards.
+    pub(super) fn log_command(&self, command: &str, status: Status) {
+        if let Some(file) = self.command_log.as_ref().and_then(|log| log.file.as_ref()) {
+            let mut file = file;
+            let _ = file.write_all(entry(SystemTime::now(), status, command).as_bytes());
+        }
+    }
+
+    /// Whether the executed commands are logged
+    pub(super) fn logs_commands(&self) -> bool {
+        self.command_log.as_ref().map_or(false, |log| log.file.is_some())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, time::Duration};
+
+    #[test]
+    fn entries() {
+        let time = UNIX_EPOCH + Duration::from_millis(1_600_000_000_042);
+        assert_eq!(entry(time, Status::from_exit_code(3), "echo a"), "1600000000.042\t3\techo a\n");
+    }
+
+    #[test]
+    fn logged_commands() {
+        let temp = mktemp::Temp::new_file().unwrap();
+        let path = temp.as_ref().to_str().unwrap().to_owned();
+        let mut shell = Shell::default();
+        shell.variables_mut().set(LOG_VARIABLE, path.as_str());
+        shell.execute_command("true\nfalse\ndrop ION_DEBUG_COMMANDS\ntrue\n".as_bytes()).unwrap();
+
+        let log = fs::read_to_string(&path).unwrap();
+        let commands = log
+            .lines()
+            .map(|line| line.splitn(2, '\t').nth(1).unwrap().to_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(commands, ["0\ttrue", "1\tfalse", "0\tdrop ION_DEBUG_COMMANDS"]);
+    }
+}
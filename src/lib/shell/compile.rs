@@ -0,0 +1,219 @@
+//! The bytecode cache of scripts. `ion --compile` serializes the statements of a script, so that
+//! `source` can load them instead of parsing the script again.
+
+use super::{flow::BlockError, flow_control::Statement, IonError, Shell};
+use crate::{
+    builtins::Status,
+    parser::{parse_and_validate, StatementSplitter, Terminator},
+};
+use itertools::Itertools;
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+/// The first bytes of every cache
+const MAGIC: &[u8; 4] = b"IONC";
+
+/// The version of ion which wrote a cache, as the layout of the statements may change between
+/// versions
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The extension of the cache of a script
+pub const CACHE_EXTENSION: &str = "ionc";
+
+/// A script could not be compiled to or loaded from its bytecode cache
+#[derive(Debug, Error)]
+pub enum CacheError {
+    /// The cache could not be read or written
+    #[error("could not access the cache: {0}")]
+    Io(#[source] io::Error),
+    /// The statements could not be encoded or decoded
+    #[error("invalid cache: {0}")]
+    Encoding(#[source] bincode::Error),
+    /// The file is not a cache, or was written by another version of ion
+    #[error("not a cache of this version of ion")]
+    Version,
+}
+
+impl From<io::Error> for CacheError {
+    #[must_use]
+    fn from(cause: io::Error) -> Self { Self::Io(cause) }
+}
+
+impl From<bincode::Error> for CacheError {
+    #[must_use]
+    fn from(cause: bincode::Error) -> Self { Self::Encoding(cause) }
+}
+
+/// Serialize a `types::Str` as a string
+pub(crate) mod string {
+    use crate::types;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub(crate) fn serialize<S: Serializer>(
+        string: &types::Str,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        string.as_str().serialize(serializer)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<types::Str, D::Error> {
+        String::deserialize(deserializer).map(Into::into)
+    }
+}
+
+/// Serialize an `Option<types::Str>` as an optional string
+pub(crate) mod optional_string {
+    use crate::types;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub(crate) fn serialize<S: Serializer>(
+        string: &Option<types::Str>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        string.as_ref().map(types::Str::as_str).serialize(serializer)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<types::Str>, D::Error> {
+        Option::<String>::deserialize(deserializer).map(|string| string.map(Into::into))
+    }
+}
+
+/// Serialize any collection of `types::Str`, such as `types::Args`, as a sequence of strings
+pub(crate) mod strings {
+    use crate::types;
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::iter::FromIterator;
+
+    pub(crate) fn serialize<'a, T, S>(strings: &'a T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        &'a T: IntoIterator<Item = &'a types::Str>,
+        S: Serializer,
+    {
+        serializer.collect_seq(strings.into_iter().map(types::Str::as_str))
+    }
+
+    pub(crate) fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: FromIterator<types::Str>,
+        D: Deserializer<'de>,
+    {
+        Vec::<String>::deserialize(deserializer)
+            .map(|strings| strings.into_iter().map(Into::into).collect())
+    }
+}
+
+/// Parse a script into its top-level statements, without executing it
+pub(super) fn parse_script<T: Read>(script: T) -> Result<Vec<Statement>, IonError> {
+    let mut block = Vec::new();
+    let mut statements = Vec::new();
+    let commands = script.bytes().filter_map(Result::ok);
+    for stmt in commands.batching(|cmd| Terminator::new(cmd).terminate()) {
+        for statement in StatementSplitter::new(&stmt) {
+            let statement = parse_and_validate(statement?)?;
+            if let Some(statement) = Shell::insert_statement(&mut block, statement)? {
+                statements.push(statement);
+            }
+        }
+    }
+
+    match block.last() {
+        Some(unclosed) => Err(BlockError::UnclosedBlock(unclosed.to_string()).into()),
+        None => Ok(statements),
+    }
+}
+
+fn write_cache(path: &Path, statements: &[Statement]) -> Result<(), CacheError> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writer.write_all(MAGIC)?;
+    bincode::serialize_into(&mut writer, VERSION)?;
+    bincode::serialize_into(&mut writer, statements)?;
+    writer.flush().map_err(Into::into)
+}
+
+fn read_cache(path: &Path) -> Result<Vec<Statement>, CacheError> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut magic = [0; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC || bincode::deserialize_from::<_, String>(&mut reader)? != VERSION {
+        return Err(CacheError::Version);
+    }
+    bincode::deserialize_from(reader).map_err(Into::into)
+}
+
+/// The cache of `script`, if it was modified after the script
+fn fresh_cache(script: &Path) -> Option<PathBuf> {
+    let modified = |path: &Path| fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+    let cache = script.with_extension(CACHE_EXTENSION);
+    if modified(&cache)? > modified(script)? {
+        Some(cache)
+    } else {
+        None
+    }
+}
+
+impl<'a> Shell<'a> {
+    /// Parse the script at `script` and write its statements to the bytecode cache `output`,
+    /// which is loaded by `execute_cached_file` instead of parsing the script again. The script
+    /// is not executed.
+    pub fn compile_file<P: AsRef<Path>, Q: AsRef<Path>>(
+        script: P,
+        output: Q,
+    ) -> Result<(), IonError> {
+        let script = File::open(script).map_err(CacheError::Io)?;
+        let statements = parse_script(BufReader::new(script))?;
+        write_cache(output.as_ref(), &statements).map_err(Into::into)
+    }
+
+    /// Execute the script at `path` from its bytecode cache, the same path with the `ionc`
+    /// extension, if the cache was modified after the script. `None` is returned when there is
+    /// no such cache, or when it can not be loaded, such as when it was compiled by another
+    /// version of ion: the script must then be executed with `execute_file` instead.
+    pub fn execute_cached_file<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+    ) -> Option<Result<Status, IonError>> {
+        let path = path.as_ref();
+        let statements = read_cache(&fresh_cache(path)?).ok()?;
+
+        self.push_source(path);
+        let result = statements.iter().enumerate().try_for_each(|(i, statement)| {
+            self.execute_operand(statement, statements.get(i + 1)).map(|_| ())
+        });
+        self.sources.pop();
+        Some(result.map(|()| self.previous_status))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cache_round_trip() {
+        let script = "let a = 1\nfn greet name:str\n    echo hello $name > /dev/null &\nend\n\
+                      for i in 1..3\n    if test $i -eq 2; greet @args[1..]; end\nend\n";
+        let statements = parse_script(script.as_bytes()).unwrap();
+        assert_eq!(statements.len(), 3);
+
+        let temp = mktemp::Temp::new_file().unwrap();
+        let path: &Path = temp.as_ref();
+        write_cache(path, &statements).unwrap();
+        assert_eq!(read_cache(path).unwrap(), statements);
+
+        fs::write(path, b"let a = 1").unwrap();
+        assert!(matches!(read_cache(path), Err(CacheError::Version)));
+    }
+
+    #[test]
+    fn unclosed_blocks_are_not_compiled() {
+        assert!(parse_script("if true\n    echo yes\n".as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,193 @@
+//! Programmable completion: generators registered by the embedders of the shell for the arguments
+//! of some commands, and the completion of command names from the builtins, aliases and functions.
+//! The line editor itself is left to the embedder, which queries `Shell::complete`.
+
+use super::Shell;
+use crate::types;
+use std::collections::BTreeMap;
+
+/// A possible completion of the word under the cursor
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Candidate {
+    /// The text replacing the word being completed
+    pub value:       String,
+    /// A short explanation of the candidate, for the line editors which can display it
+    pub description: Option<String>,
+}
+
+impl Candidate {
+    /// A candidate without description
+    pub fn new<T: Into<String>>(value: T) -> Self {
+        Self { value: value.into(), description: None }
+    }
+
+    /// Describe the candidate
+    #[must_use]
+    pub fn with_description<T: Into<String>>(mut self, description: T) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+}
+
+/// Generates the candidates for an argument of a command. It is given the word being completed,
+/// which may be empty, and the arguments before it, without the name of the command. The words
+/// are given as typed, including their quotes.
+pub type CompletionGenerator<'a> = Box<dyn Fn(&str, &[&str]) -> Vec<Candidate> + 'a>;
+
+/// Splits the last command of `line` into its words, returning the complete words and the word
+/// being completed at the end of the line. Commands are separated by `|`, `;` and `&` outside of
+/// quotes.
+fn split_line(line: &str) -> (Vec<&str>, &str) {
+    let mut words = Vec::new();
+    let (mut start, mut quote, mut escaped) = (None, None, false);
+    for (index, character) in line.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match character {
+            '\\' if quote != Some('\'') => escaped = true,
+            _ if quote.is_some() => {
+                if quote == Some(character) {
+                    quote = None;
+                }
+            }
+            '\'' | '"' => quote = Some(character),
+            '|' | ';' | '&' => {
+                words.clear();
+                start = None;
+                continue;
+            }
+            _ if character.is_whitespace() => {
+                if let Some(start) = start.take() {
+                    words.push(&line[start..index]);
+                }
+                continue;
+            }
+            _ => (),
+        }
+        start.get_or_insert(index);
+    }
+    (words, start.map_or("", |start| &line[start..]))
+}
+
+impl<'a> Shell<'a> {
+    /// Registers the generator of the completions for the arguments of `command`, returning the
+    /// one it replaces
+    pub fn register_completion(
+        &mut self,
+        command: &str,
+        generator: CompletionGenerator<'a>,
+    ) -> Option<CompletionGenerator<'a>> {
+        self.completions.insert(command.into(), generator)
+    }
+
+    /// Unregisters the generator of the completions for `command`, returning it
+    pub fn unregister_completion(&mut self, command: &str) -> Option<CompletionGenerator<'a>> {
+        self.completions.remove(command)
+    }
+
+    /// Whether a generator is registered for the arguments of `command`
+    #[must_use]
+    pub fn has_completion(&self, command: &str) -> bool { self.completions.contains_key(command) }
+
+    /// The candidates for the last word of `line`, which is where the cursor is. The name of a
+    /// command is completed from the aliases, functions and builtins, with their descriptions.
+    /// Its arguments are completed by the generator registered for the command, if any: the
+    /// embedder may complete file names otherwise. Only the candidates which start with the word
+    /// are kept, so generators may return all of theirs.
+    #[must_use]
+    pub fn complete(&self, line: &str) -> Vec<Candidate> {
+        let (words, word) = split_line(line);
+        let candidates = match words.split_first() {
+            None => self.command_candidates(),
+            Some((command, args)) => match self.completions.get(*command) {
+                Some(generator) => generator(word, args),
+                None => Vec::new(),
+            },
+        };
+        candidates.into_iter().filter(|candidate| candidate.value.starts_with(word)).collect()
+    }
+
+    /// The names of the commands which do not need a lookup in `$PATH`, sorted. An alias or a
+    /// function shadowing a builtin is described as such.
+    fn command_candidates(&self) -> Vec<Candidate> {
+        let mut commands = BTreeMap::new();
+        for (name, alias) in self.variables.aliases() {
+            commands.entry(name.to_string()).or_insert_with(|| Some(format!("alias to {}", alias)));
+        }
+        for (name, function) in self.variables.functions() {
+            commands
+                .entry(name.to_string())
+                .or_insert_with(|| function.description().map(types::Str::to_string));
+        }
+        for name in self.builtins.keys() {
+            commands
+                .entry(name.to_owned())
+                .or_insert_with(|| self.builtins.get_help(name).map(str::to_owned));
+        }
+        commands.into_iter().map(|(value, description)| Candidate { value, description }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_splitting() {
+        assert_eq!(split_line(""), (vec![], ""));
+        assert_eq!(split_line("git"), (vec![], "git"));
+        assert_eq!(split_line("git "), (vec!["git"], ""));
+        assert_eq!(split_line("git commit -m"), (vec!["git", "commit"], "-m"));
+        assert_eq!(split_line("echo 'a | b' \"c d"), (vec!["echo", "'a | b'"], "\"c d"));
+        assert_eq!(split_line("ls a\\ b"), (vec!["ls"], "a\\ b"));
+        assert_eq!(split_line("ls | grep x; make t"), (vec!["make"], "t"));
+        assert_eq!(split_line("true && gi"), (vec![], "gi"));
+    }
+
+    #[test]
+    fn registered_generators() {
+        let mut shell = Shell::default();
+        let generator: CompletionGenerator<'_> = Box::new(|_: &str, args: &[&str]| match args {
+            [] => vec![
+                Candidate::new("commit").with_description("record changes"),
+                Candidate::new("checkout"),
+                Candidate::new("push"),
+            ],
+            _ => vec![Candidate::new(args.len().to_string())],
+        });
+        assert!(shell.register_completion("git", generator).is_none());
+        assert!(shell.has_completion("git"));
+
+        let values = |candidates: Vec<Candidate>| -> Vec<String> {
+            candidates.into_iter().map(|candidate| candidate.value).collect()
+        };
+        assert_eq!(values(shell.complete("git c")), ["commit", "checkout"]);
+        assert_eq!(
+            shell.complete("git co"),
+            [Candidate::new("commit").with_description("record changes")]
+        );
+        assert_eq!(values(shell.complete("git commit -m ")), ["2"]);
+        assert!(shell.complete("make ").is_empty());
+
+        assert!(shell.unregister_completion("git").is_some());
+        assert!(shell.complete("git c").is_empty());
+    }
+
+    #[test]
+    fn command_names() {
+        let mut shell = Shell::default();
+        shell.execute_command("alias echo-twice = 'echo echo'".as_bytes()).unwrap();
+        let candidates = shell.complete("ech");
+        assert_eq!(
+            candidates[0],
+            Candidate::new("echo").with_description("Display a line of text")
+        );
+        assert_eq!(
+            candidates[1],
+            Candidate::new("echo-twice").with_description("alias to echo echo")
+        );
+        assert_eq!(candidates.len(), 2);
+    }
+}
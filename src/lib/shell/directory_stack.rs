@@ -139,9 +139,19 @@ impl DirectoryStack {
     pub fn switch_to_previous_directory(&mut self) -> Result<(), DirStackError> {
         let prev = self.get_previous_dir().ok_or(DirStackError::NoPreviousDir)?;
 
-        self.popd(0);
+        // Only replace the current directory once the change succeeded
+        self.change_and_push_dir(Path::new(&prev))?;
+        self.popd(1);
         println!("{}", prev);
-        self.change_and_push_dir(Path::new(&prev))
+        Ok(())
+    }
+
+    /// Change to the directory `index` entries back in the stack, `cd -N`, which is pushed again
+    pub fn switch_to_stacked_directory(&mut self, index: usize) -> Result<(), DirStackError> {
+        let dir = self.dirs.get(index).cloned().ok_or(DirStackError::OutOfRange { index })?;
+        self.change_and_push_dir(&dir)?;
+        println!("{}", dir.display());
+        Ok(())
     }
 
     pub fn switch_to_home_directory(&mut self) -> Result<(), DirStackError> {
@@ -1,34 +1,79 @@
 use super::{
     flow_control::{Block, Case, ElseIf, Function, IfMode, Statement},
-    pipe_exec::PipelineError,
-    signals, Shell,
+    pipe_exec::{PipelineError, RedirectError},
+    signals, timing, Shell,
 };
 use crate::{
     assignments::is_array,
     builtins::Status,
     expansion::{
-        pipelines::{PipeItem, Pipeline},
+        self,
+        arithmetic::{self, Number},
+        pipelines::{Input, PipeItem, Pipeline},
         Expander, ForValueExpression,
     },
-    parser::{parse_and_validate, StatementSplitter, Terminator},
+    parser::{
+        lexers::ArgumentSplitter, parse_and_validate, StatementSplitter, StatementVariant,
+        Terminator,
+    },
     shell::{IonError, Job, Value},
     types,
 };
 use itertools::Itertools;
 use nix::unistd::Pid;
-use std::{rc::Rc, time::SystemTime};
+use scopes::Scope;
+use std::{
+    any::Any,
+    env,
+    fs::File,
+    iter, mem,
+    panic::{self, AssertUnwindSafe},
+    rc::Rc,
+    sync::atomic::Ordering,
+    time::{Instant, SystemTime},
+};
 use thiserror::Error;
 
+/// How the execution of a statement affects the statements following it
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum Condition {
+    /// A `continue` skips the rest of the iteration of the enclosing loop
     Continue,
+    /// A `break` exits the enclosing loop
     Break,
+    /// The execution carries on with the next statement
     NoOp,
+    /// A `return` exits the enclosing function
     Return,
 }
 
 type Result = std::result::Result<Condition, IonError>;
 
+/// A for loop iterating over the values yielded by a generator function: `for x in @gen()`
+pub struct Generator {
+    /// The variables of the loop, bound to the values as they are yielded
+    variables:  Vec<types::Str>,
+    /// The `where` clause of the loop
+    filter:     Vec<Statement>,
+    /// The body of the loop
+    statements: Vec<Statement>,
+    /// The depth of the call stack in the body of the generator, where `yield` is allowed
+    depth:      usize,
+    /// The index of the scope in which the generator is defined
+    index:      usize,
+    /// The scopes of the loop, hidden while the generator executes
+    scopes:     Vec<Scope<types::Str, Value<Rc<Function>>>>,
+    /// The values yielded but not yet bound, when the loop has several variables
+    pending:    Vec<types::Str>,
+    /// Set once the loop ended with `break` or `return`, which stops the generator
+    stopped:    Option<Condition>,
+}
+
+/// Whether `next` is the right operand of `&&` or `||`, making the statement before it a condition
+pub(super) const fn precedes_operand(next: Option<&Statement>) -> bool {
+    matches!(next, Some(Statement::And(_)) | Some(Statement::Or(_)))
+}
+
 /// The block order was invalid
 #[derive(Debug, Error, PartialEq, Eq, Hash)]
 pub enum BlockError {
@@ -65,6 +110,12 @@ pub enum BlockError {
     /// Unclosed block
     #[error("expected end block for `{0}`")]
     UnclosedBlock(String),
+    /// A do block was closed without a condition
+    #[error("expected `end while CONDITION` to close a do block")]
+    DoWithoutWhile,
+    /// An end while was found without a do block to close
+    #[error("found `end while` without do block")]
+    UnmatchedEndWhile,
 }
 
 impl<'a> Shell<'a> {
@@ -81,7 +132,10 @@ impl<'a> Shell<'a> {
         match block {
             Statement::Function { ref mut statements, .. }
             | Statement::For { ref mut statements, .. }
-            | Statement::While { ref mut statements, .. } => statements.push(statement),
+            | Statement::CStyleFor { ref mut statements, .. }
+            | Statement::While { ref mut statements, .. }
+            | Statement::DoWhile { ref mut statements, .. }
+            | Statement::WithEnv { ref mut statements, .. } => statements.push(statement),
             Statement::Match { ref mut cases, .. } => {
                 if let Statement::Case(case) = statement {
                     cases.push(case)
@@ -123,20 +177,35 @@ impl<'a> Shell<'a> {
         Ok(())
     }
 
-    fn insert_statement(
+    pub(super) fn insert_statement(
         block: &mut Block,
         statement: Statement,
     ) -> std::result::Result<Option<Statement>, BlockError> {
         match statement {
             // Push new block to stack
             Statement::For { .. }
+            | Statement::CStyleFor { .. }
             | Statement::While { .. }
+            | Statement::DoWhile { .. }
+            | Statement::WithEnv { .. }
             | Statement::Match { .. }
             | Statement::If { .. }
             | Statement::Function { .. } => {
                 block.push(statement);
                 Ok(None)
             }
+            // Give its condition to the do-while block, then close it like any other block
+            Statement::EndWhile(condition) => {
+                let top_block = match block.last_mut() {
+                    Some(Statement::Time(inner)) => Some(&mut **inner),
+                    top_block => top_block,
+                };
+                match top_block {
+                    Some(Statement::DoWhile { ref mut expression, .. }) => *expression = condition,
+                    _ => return Err(BlockError::UnmatchedEndWhile),
+                }
+                Self::insert_statement(block, Statement::End)
+            }
             // Case is special as it should pop back previous Case
             Statement::Case(_) => {
                 match block.last() {
@@ -152,6 +221,11 @@ impl<'a> Shell<'a> {
                 Ok(None)
             }
             Statement::End => {
+                if let Some(Statement::DoWhile { expression, .. }) = block.last() {
+                    if expression.is_empty() {
+                        return Err(BlockError::DoWithoutWhile);
+                    }
+                }
                 match block.len() {
                     0 => Err(BlockError::UnmatchedEnd),
                     // Ready to return the complete block
@@ -256,14 +330,14 @@ impl<'a> Shell<'a> {
         failure: &[Statement],
     ) -> Result {
         // Try execute success branch
-        self.execute_statements(expression)?;
+        self.execute_condition(expression)?;
         if self.previous_status.is_success() {
             return self.execute_statements(success);
         }
 
         // Try to execute else_if branches
         for ElseIf { expression, success } in else_if {
-            self.execute_statements(expression)?;
+            self.execute_condition(expression)?;
 
             if self.previous_status.is_success() {
                 return self.execute_statements(success);
@@ -279,17 +353,17 @@ impl<'a> Shell<'a> {
         &mut self,
         variables: &[types::Str],
         values: &[types::Str],
+        filter: &[Statement],
         statements: &[Statement],
     ) -> Result {
+        for variable in variables.iter().filter(|variable| *variable != "_") {
+            self.ensure_writable(variable)?;
+        }
+
         macro_rules! set_vars_then_exec {
             ($chunk:expr, $def:expr) => {
-                for (key, value) in variables.iter().zip($chunk.chain(::std::iter::repeat($def))) {
-                    if key != "_" {
-                        self.variables_mut().set(key, value.clone());
-                    }
-                }
-
-                match self.execute_statements(statements)? {
+                let values = $chunk.chain(iter::repeat($def));
+                match self.execute_iteration(variables, values, filter, statements)? {
                     Condition::Break => break,
                     Condition::Return => return Ok(Condition::Return),
                     Condition::Continue | Condition::NoOp => (),
@@ -297,36 +371,318 @@ impl<'a> Shell<'a> {
             };
         }
 
+        if let [value] = values {
+            if let Some((function, args)) = self.function_call(value)? {
+                return self.execute_generator(&function, &args, variables, filter, statements);
+            }
+        }
+
         let default = types::Str::new();
 
         match ForValueExpression::new(values, self)? {
-            ForValueExpression::Multiple(values) => {
+            ForValueExpression::Multiple(values) | ForValueExpression::HashMapKeys(values) => {
                 for chunk in &values.iter().chunks(variables.len()) {
                     set_vars_then_exec!(chunk, &default);
                 }
             }
             ForValueExpression::Normal(value) => {
                 if &variables[0] != "_" {
-                    self.variables_mut().set(&variables[0], value);
+                    self.variables.set_str(&variables[0], &value);
                 }
 
-                self.execute_statements(statements)?;
+                if self.filter_accepts(filter)? {
+                    self.execute_statements(statements)?;
+                }
             }
             ForValueExpression::Range(range) => {
                 for chunk in &range.chunks(variables.len()) {
                     set_vars_then_exec!(chunk, default.clone());
                 }
             }
+            ForValueExpression::HashMapPairs(pairs) => {
+                for (key, value) in &pairs {
+                    set_vars_then_exec!([key, value].iter().copied(), &default);
+                }
+            }
         };
 
         Ok(Condition::NoOp)
     }
 
+    /// Binds the variables of a for loop to the next values, then executes the body of the loop
+    /// if the values are accepted by its filter.
+    fn execute_iteration<T: AsRef<str>>(
+        &mut self,
+        variables: &[types::Str],
+        values: impl Iterator<Item = T>,
+        filter: &[Statement],
+        statements: &[Statement],
+    ) -> Result {
+        for (key, value) in variables.iter().zip(values) {
+            if key != "_" {
+                self.variables.set_str(key, value.as_ref());
+            }
+        }
+
+        if self.filter_accepts(filter)? {
+            self.execute_statements(statements)
+        } else {
+            Ok(Condition::NoOp)
+        }
+    }
+
+    /// The function and the expanded arguments of a function call written as `@name(args)`, when
+    /// `name` is a function: a generator iterated by a for loop, or a function whose returned
+    /// value is assigned by `let`.
+    pub(super) fn function_call(
+        &mut self,
+        word: &str,
+    ) -> std::result::Result<Option<(Rc<Function>, types::Args)>, IonError> {
+        let call = match word.strip_prefix('@').and_then(|call| call.strip_suffix(')')) {
+            Some(call) => call,
+            None => return Ok(None),
+        };
+        let (name, args) = match call.find('(') {
+            Some(pos) => (&call[..pos], &call[pos + 1..]),
+            None => return Ok(None),
+        };
+        let function = match self.variables.get(name) {
+            Some(Value::Function(function)) => function.clone(),
+            _ => return Ok(None),
+        };
+
+        let mut expanded = types::Args::new();
+        expanded.push(name.into());
+        for word in ArgumentSplitter::new(args) {
+            expanded.extend(self.expand_string(word)?);
+        }
+        Ok(Some((function, expanded)))
+    }
+
+    /// Executes a for loop over a generator function. The body of the loop is executed each time
+    /// the generator yields values, so that both interleave; once the generator returns, the
+    /// values it returned are iterated as well. Ending the loop with `break` or `return` stops
+    /// the generator.
+    fn execute_generator(
+        &mut self,
+        function: &Function,
+        args: &[types::Str],
+        variables: &[types::Str],
+        filter: &[Statement],
+        statements: &[Statement],
+    ) -> Result {
+        let index = match self.variables.index_scope_for_var(function.name()) {
+            Some(index) => index,
+            None => return Ok(Condition::NoOp),
+        };
+        // The scopes of the loop are hidden from the generator, as they would be by the call
+        let scopes = self.variables.pop_scopes(index).collect();
+        self.generators.push(Generator {
+            variables: variables.to_vec(),
+            filter: filter.to_vec(),
+            statements: statements.to_vec(),
+            depth: self.call_stack.len() + 1,
+            index,
+            scopes,
+            pending: Vec::new(),
+            stopped: None,
+        });
+
+        self.return_value = None;
+        let result = function.execute(self, args);
+        let mut generator = self.generators.pop().expect("the generator was not restored");
+        self.variables.append_scopes(mem::take(&mut generator.scopes));
+        result?;
+
+        if generator.stopped.is_none() {
+            match self.return_value.take() {
+                Some(Value::Array(values)) => {
+                    generator.pending.extend(values.iter().map(|value| value.to_string().into()))
+                }
+                Some(Value::Str(value)) => generator.pending.push(value),
+                _ => (),
+            }
+            self.resume_loop(&mut generator, true)?;
+        }
+        match generator.stopped {
+            Some(Condition::Return) => Ok(Condition::Return),
+            _ => Ok(Condition::NoOp),
+        }
+    }
+
+    /// Executes the body of the loop over a generator for each chunk of the pending values which
+    /// binds every variable, and for the last incomplete chunk once the generator is `done`.
+    fn resume_loop(
+        &mut self,
+        generator: &mut Generator,
+        done: bool,
+    ) -> std::result::Result<(), IonError> {
+        let size = generator.variables.len();
+        let default = types::Str::new();
+        while generator.stopped.is_none()
+            && (generator.pending.len() >= size || done && !generator.pending.is_empty())
+        {
+            let end = size.min(generator.pending.len());
+            let chunk: Vec<_> = generator.pending.drain(..end).collect();
+            let values = chunk.iter().chain(iter::repeat(&default));
+            let Generator { variables, filter, statements, .. } = &*generator;
+            match self.execute_iteration(variables, values, filter, statements)? {
+                Condition::Break => generator.stopped = Some(Condition::Break),
+                Condition::Return => generator.stopped = Some(Condition::Return),
+                Condition::Continue | Condition::NoOp => (),
+            }
+        }
+        Ok(())
+    }
+
+    /// Passes values to the for loop iterating over the generator being executed, whose body is
+    /// executed in the scopes of the loop. Returns `Condition::Return` to unwind the generator
+    /// once the loop ended.
+    fn execute_yield(&mut self, expression: &str) -> Result {
+        let values = self.expand_string(expression)?;
+        let mut generator = match self.generators.pop() {
+            Some(generator) if generator.depth == self.call_stack.len() => generator,
+            generator => {
+                self.generators.extend(generator);
+                self.previous_status = Status::error("ion: yield: not in a generator");
+                return Ok(Condition::NoOp);
+            }
+        };
+        generator.pending.extend(values);
+
+        // Swap the scopes and the call of the generator with those of the loop, and back
+        let scopes = self.variables.pop_scopes(generator.index).collect();
+        self.variables.append_scopes(mem::replace(&mut generator.scopes, scopes));
+        let call = self.call_stack.pop();
+        let result = self.resume_loop(&mut generator, false);
+        self.call_stack.extend(call);
+        let scopes = self.variables.pop_scopes(generator.index).collect();
+        self.variables.append_scopes(mem::replace(&mut generator.scopes, scopes));
+
+        let stopped = generator.stopped.is_some();
+        self.generators.push(generator);
+        result?;
+        if stopped {
+            // Keep the status of the loop, which may have been set by a `return`
+            Ok(Condition::Return)
+        } else {
+            self.previous_status = Status::SUCCESS;
+            Ok(Condition::NoOp)
+        }
+    }
+
+    /// Evaluates the `where` clause of a for loop, with the loop variables bound, the same way
+    /// as the condition of an if statement. An empty filter accepts every value.
+    fn filter_accepts(&mut self, filter: &[Statement]) -> std::result::Result<bool, IonError> {
+        if filter.is_empty() {
+            return Ok(true);
+        }
+        self.execute_condition(filter)?;
+        Ok(self.previous_status.is_success())
+    }
+
+    /// Evaluate an arithmetic expression, as in `$((expression))`
+    fn evaluate_arithmetic(&self, expression: &str) -> std::result::Result<Number, IonError> {
+        arithmetic::evaluate(expression, |name| self.string(name).ok())
+            .map_err(|cause| expansion::Error::<IonError>::from(cause).into())
+    }
+
+    /// Evaluate comma separated arithmetic clauses, assigning the variables of the assignments
+    /// among them: `i = 0, j += 2, k++`
+    fn execute_arithmetic(&mut self, clauses: &str) -> std::result::Result<(), IonError> {
+        for clause in clauses.split(',').map(str::trim).filter(|clause| !clause.is_empty()) {
+            match arithmetic::assignment(clause) {
+                Some((name, expression)) => {
+                    let value = self.evaluate_arithmetic(&expression)?;
+                    self.ensure_writable(name)?;
+                    self.variables.set(name, value.to_string());
+                }
+                None => {
+                    self.evaluate_arithmetic(clause)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Executes a C-style for loop: the init clauses are evaluated once, then the body and the
+    /// update clauses are executed as long as the condition is not 0. An empty condition is
+    /// always true.
+    fn execute_cstyle_for(
+        &mut self,
+        init: &str,
+        condition: &str,
+        update: &str,
+        statements: &[Statement],
+    ) -> Result {
+        self.execute_arithmetic(init)?;
+        loop {
+            if !condition.is_empty() && !self.evaluate_arithmetic(condition)?.is_true() {
+                return Ok(Condition::NoOp);
+            }
+
+            match self.execute_statements(statements)? {
+                Condition::Break => return Ok(Condition::NoOp),
+                Condition::Return => return Ok(Condition::Return),
+                Condition::Continue | Condition::NoOp => (),
+            }
+            self.execute_arithmetic(update)?;
+        }
+    }
+
+    /// Executes the statements with the environment variables of the bindings set to their
+    /// expanded values. Their previous values are restored afterwards, or the variables are
+    /// removed if they were not set, even when the block fails or exits.
+    fn execute_with_env(
+        &mut self,
+        bindings: &[(String, String)],
+        statements: &[Statement],
+    ) -> Result {
+        // All the values are expanded before any variable is set
+        let mut values = Vec::with_capacity(bindings.len());
+        for (name, value) in bindings {
+            values.push((name, self.expand_string(value)?.join(" ")));
+        }
+        let previous =
+            values.iter().map(|(name, _)| (*name, env::var_os(name))).collect::<Vec<_>>();
+        for (name, value) in &values {
+            env::set_var(name, value);
+        }
+
+        let result = self.execute_statements(statements);
+
+        // Restored in reverse, in case a variable is bound twice
+        for (name, value) in previous.into_iter().rev() {
+            match value {
+                Some(value) => env::set_var(name, value),
+                None => env::remove_var(name),
+            }
+        }
+        result
+    }
+
     /// Executes all of the statements within a while block until a certain
     /// condition is met.
     fn execute_while(&mut self, expression: &[Statement], statements: &[Statement]) -> Result {
+        let redirected = self.open_condition_input(expression)?;
+        let (expression, input) = match &redirected {
+            Some((condition, file)) => (condition.as_slice(), Some(file)),
+            None => (expression, None),
+        };
+
         loop {
-            self.execute_statements(expression)?;
+            match input {
+                Some(file) => {
+                    let file = file.try_clone().map_err(PipelineError::ClonePipeFailed)?;
+                    let previous = self.stdin(file);
+                    let result = self.execute_condition(expression);
+                    self.stdin(previous);
+                    result?;
+                }
+                None => {
+                    self.execute_condition(expression)?;
+                }
+            }
             if self.previous_status.is_failure() {
                 return Ok(Condition::NoOp);
             }
@@ -340,16 +696,125 @@ impl<'a> Shell<'a> {
         }
     }
 
-    /// Executes a single statement
-    pub fn execute_statement(&mut self, statement: &Statement) -> Result {
-        match statement {
-            Statement::Let(action) => {
-                self.previous_status = self.local(action);
-                self.variables.set("?", self.previous_status);
+    /// Opens the input file of a loop condition such as `read line < file` once for the whole
+    /// loop, returning the condition without its redirection, so that each iteration continues
+    /// reading where the previous one stopped rather than opening the file again.
+    fn open_condition_input(
+        &mut self,
+        expression: &[Statement],
+    ) -> std::result::Result<Option<(Vec<Statement>, File)>, IonError> {
+        let pipeline = match expression {
+            [Statement::Pipeline(pipeline)] if pipeline.items.len() == 1 => pipeline,
+            _ => return Ok(None),
+        };
+        let name = match pipeline.items[0].inputs.as_slice() {
+            [Input::File(name)] => self.get_string(name)?,
+            _ => return Ok(None),
+        };
+        let file = File::open(name.as_str())
+            .map_err(|why| PipelineError::from(RedirectError::File(name.to_string(), why)))?;
+
+        let mut pipeline = pipeline.clone();
+        pipeline.items[0].inputs.clear();
+        Ok(Some((vec![Statement::Pipeline(pipeline)], file)))
+    }
+
+    /// Executes the body of a do-while loop once, then again as long as the expression succeeds
+    fn execute_do_while(&mut self, statements: &[Statement], expression: &[Statement]) -> Result {
+        loop {
+            match self.execute_statements(statements)? {
+                Condition::Break => return Ok(Condition::NoOp),
+                Condition::Return => return Ok(Condition::Return),
+                Condition::Continue | Condition::NoOp => (),
             }
-            Statement::Export(action) => {
-                self.previous_status = self.export(action);
+
+            self.execute_condition(expression)?;
+            if self.previous_status.is_failure() {
+                return Ok(Condition::NoOp);
+            }
+        }
+    }
+
+    /// Executes a statement given to the shell. With the `recover_panics` option, a panic while
+    /// executing it is caught: the statement fails with a diagnostic, and the function calls and
+    /// scopes it entered are exited, instead of aborting the shell.
+    pub(super) fn execute_recovering(&mut self, statement: &Statement) -> Result {
+        if !self.opts.recover_panics {
+            return self.execute_statement(statement);
+        }
+
+        let (depth, calls, generators) =
+            (self.variables.scope_depth(), self.call_stack.len(), self.generators.len());
+        let (err_exit, pid) = (self.opts.err_exit, Pid::this());
+        match panic::catch_unwind(AssertUnwindSafe(|| self.execute_statement(statement))) {
+            Ok(result) => result,
+            // A forked child must not go on executing the statements of its parent
+            Err(cause) if Pid::this() != pid => panic::resume_unwind(cause),
+            Err(cause) => {
+                while self.variables.scope_depth() > depth {
+                    self.variables.pop_scope();
+                }
+                self.call_stack.truncate(calls);
+                self.generators.truncate(generators);
+                self.return_value = None;
+                self.opts.err_exit = err_exit;
+                self.previous_status =
+                    Status::error(format!("ion: statement panicked: {}", panic_message(&*cause)));
                 self.variables.set("?", self.previous_status);
+                Ok(Condition::NoOp)
+            }
+        }
+    }
+
+    /// Runs `f` with `set -e` disabled, for conditions whose failure is expected
+    pub(super) fn without_err_exit<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        let err_exit = mem::replace(&mut self.opts.err_exit, false);
+        let result = f(self);
+        self.opts.err_exit = err_exit;
+        result
+    }
+
+    /// Executes the expression of an `if` or a loop, which does not exit the shell on failure
+    /// when `set -e` is enabled
+    fn execute_condition(&mut self, expression: &[Statement]) -> Result {
+        self.without_err_exit(|shell| shell.execute_statements(expression))
+    }
+
+    /// Executes a statement followed by `next`. The left operands of `&&` and `||` are
+    /// conditions, which do not exit the shell on failure when `set -e` is enabled.
+    pub(super) fn execute_operand(
+        &mut self,
+        statement: &Statement,
+        next: Option<&Statement>,
+    ) -> Result {
+        if precedes_operand(next) {
+            self.without_err_exit(|shell| shell.execute_statement(statement))
+        } else {
+            self.execute_statement(statement)
+        }
+    }
+
+    /// Executes a single statement. `$?` is updated to the status of the statement, which is the
+    /// status of the last command executed by blocks, and a success for definitions.
+    pub fn execute_statement(&mut self, statement: &Statement) -> Result {
+        if let Some(callback) = self.pre_statement.take() {
+            callback(self, statement);
+            // The callback is kept unless it replaced itself
+            if self.pre_statement.is_none() {
+                self.pre_statement = Some(callback);
+            }
+            if let Some(status) = self.exit_status {
+                return Err(IonError::Exit(status));
+            }
+        }
+        match statement {
+            Statement::Let(action) => self.previous_status = self.local(action),
+            Statement::Export(action) => self.previous_status = self.export(action),
+            Statement::CStyleFor { init, condition, update, statements } => {
+                let condition = self.execute_cstyle_for(init, condition, update, statements)?;
+                if condition != Condition::NoOp {
+                    return Ok(condition);
+                }
             }
             Statement::While { expression, statements } => {
                 let condition = self.execute_while(expression, statements)?;
@@ -357,8 +822,20 @@ impl<'a> Shell<'a> {
                     return Ok(condition);
                 }
             }
-            Statement::For { variables, values, statements } => {
-                let condition = self.execute_for(variables, values, statements)?;
+            Statement::DoWhile { statements, expression } => {
+                let condition = self.execute_do_while(statements, expression)?;
+                if condition != Condition::NoOp {
+                    return Ok(condition);
+                }
+            }
+            Statement::WithEnv { bindings, statements } => {
+                let condition = self.execute_with_env(bindings, statements)?;
+                if condition != Condition::NoOp {
+                    return Ok(condition);
+                }
+            }
+            Statement::For { variables, values, filter, statements } => {
+                let condition = self.execute_for(variables, values, filter, statements)?;
                 if condition != Condition::NoOp {
                     return Ok(condition);
                 }
@@ -371,6 +848,8 @@ impl<'a> Shell<'a> {
                 }
             }
             Statement::Function { name, args, statements, description } => {
+                // The results cached for a previous definition are stale
+                self.clear_memo(name);
                 self.variables.set(
                     name,
                     Value::Function(Rc::new(Function::new(
@@ -380,8 +859,10 @@ impl<'a> Shell<'a> {
                         statements.to_vec(),
                     ))),
                 );
+                self.previous_status = Status::SUCCESS;
             }
             Statement::Pipeline(pipeline) => {
+                let started = if self.profile.is_some() { Some(Instant::now()) } else { None };
                 let (pipeline, statements) = expand_pipeline(self, pipeline)?;
                 if !pipeline.items.is_empty() {
                     let status = self.run_pipeline(&pipeline)?;
@@ -394,21 +875,34 @@ impl<'a> Shell<'a> {
                 if !statements.is_empty() {
                     self.execute_statements(&statements)?;
                 }
+                if let Some(started) = started {
+                    self.record_call(statement, started.elapsed());
+                }
+            }
+            Statement::Coproc { name, pipeline } => {
+                self.previous_status = self.execute_coproc(name, pipeline)?;
+            }
+            Statement::Arithmetic(expression) => {
+                // An invalid expression, such as a division by zero, fails the condition
+                self.previous_status = match self.evaluate_arithmetic(expression) {
+                    Ok(value) => value.is_true().into(),
+                    Err(why) => Status::error(format!("ion: {}", why)),
+                };
             }
             Statement::Time(box_statement) => {
                 let time = std::time::Instant::now();
+                let usage = timing::Usage::now();
 
                 let condition = self.execute_statement(box_statement)?;
 
-                let duration = time.elapsed();
-                let duration = duration.as_secs_f32();
-                let seconds = duration.rem_euclid(60.);
-                let minutes = duration.div_euclid(60.);
-
-                if minutes != 0. {
-                    println!("real    {}m{:.9}s", minutes, seconds);
-                } else {
-                    println!("real    {:.9}s", seconds);
+                let (elapsed, usage) = (time.elapsed(), timing::Usage::now().since(usage));
+                let format = self
+                    .variables
+                    .get_str("TIMEFORMAT")
+                    .unwrap_or_else(|_| timing::DEFAULT_FORMAT.into());
+                if !format.is_empty() {
+                    let report = timing::format(&format, elapsed, usage);
+                    let _ = self.write_error(format_args!("{}\n", report));
                 }
                 if condition != Condition::NoOp {
                     return Ok(condition);
@@ -440,7 +934,11 @@ impl<'a> Shell<'a> {
                 // NOTE: Should the condition be used?
                 let _condition = self.execute_statement(box_statement)?;
                 self.previous_status.toggle();
-                self.variables.set("?", self.previous_status);
+            }
+            Statement::Breakpoint(condition) => self.execute_breakpoint(condition.as_deref())?,
+            Statement::Watch { name, command, body } => {
+                self.add_watch(name, command, body);
+                self.previous_status = Status::SUCCESS;
             }
             Statement::Break => return Ok(Condition::Break),
             Statement::Continue => return Ok(Condition::Continue),
@@ -453,16 +951,45 @@ impl<'a> Shell<'a> {
             }
             Statement::Return(expression) => {
                 if let Some(expression) = expression {
-                    let value = self.expand_string(expression.as_ref())?.join(" ");
-                    if let Ok(status) = value.parse::<i32>() {
-                        self.previous_status = Status::from_exit_code(status);
+                    // `return --value VALUE` hands a value back to the caller, which is kept
+                    // apart from the exit status given by `return STATUS`
+                    match returned_value(expression) {
+                        Some(value) => {
+                            let values = self.expand_string(value)?;
+                            let value = if value.starts_with('@') || is_array(value) {
+                                values.into_iter().map(Value::Str).collect()
+                            } else {
+                                Value::Str(values.join(" ").into())
+                            };
+                            self.return_value = Some(value);
+                        }
+                        None => {
+                            let value = self.expand_string(expression.as_ref())?.join(" ");
+                            if let Ok(status) = value.parse::<i32>() {
+                                self.previous_status = Status::from_exit_code(status);
+                            }
+                        }
                     }
                 }
+                self.variables.set("?", self.previous_status);
                 return Ok(Condition::Return);
             }
+            Statement::Yield(expression) => {
+                if self.execute_yield(expression)? == Condition::Return {
+                    return Ok(Condition::Return);
+                }
+            }
             _ => {}
         }
-        if let Some(signal) = signals::SignalHandler.next() {
+        self.variables.set("?", self.previous_status);
+        // The log is opened or closed as soon as `$ION_DEBUG_COMMANDS` is assigned or dropped
+        self.update_command_log();
+        self.check_watches();
+        if let Some(status) = self.exit_status {
+            Err(IonError::Exit(status))
+        } else if self.cancelled.load(Ordering::SeqCst) {
+            Err(IonError::from(PipelineError::Cancelled))
+        } else if let Some(signal) = signals::SignalHandler.next() {
             let _ = self.handle_signal(signal);
             Err(IonError::from(PipelineError::Interrupted(Pid::this(), signal)))
         } else {
@@ -475,78 +1002,94 @@ impl<'a> Shell<'a> {
         self.variables.new_scope(false);
         let condition = statements
             .iter()
-            .map(|statement| self.execute_statement(statement))
+            .enumerate()
+            .map(|(i, statement)| self.execute_operand(statement, statements.get(i + 1)))
             .find(|condition| !matches!(condition, Ok(Condition::NoOp)))
             .unwrap_or(Ok(Condition::NoOp));
         self.variables.pop_scope();
         condition
     }
 
+    /// Executes the supplied statements until one of them leaves a non-zero status, like a
+    /// `set -e` scoped to the block. Unlike `set -e`, the shell is not exited: the status of the
+    /// failed statement is returned instead. Control flow statements such as `break` or `return`
+    /// also stop the block, returning the current status.
+    pub fn execute_statements_until_error(
+        &mut self,
+        statements: &[Statement],
+    ) -> std::result::Result<Status, IonError> {
+        // The status of a previous command must not abort the block
+        self.previous_status = Status::SUCCESS;
+        self.variables.new_scope(false);
+        let mut result = Ok(Condition::NoOp);
+        for statement in statements {
+            result = self.execute_statement(statement);
+            if !matches!(result, Ok(Condition::NoOp)) || self.previous_status.is_failure() {
+                break;
+            }
+        }
+        self.variables.pop_scope();
+        result.map(|_| self.previous_status)
+    }
+
     /// Expand an expression and run a branch based on the value of the
     /// expanded expression
     fn execute_match<T: AsRef<str>>(&mut self, expression: T, cases: &[Case]) -> Result {
-        use regex::RegexSet;
-        // Logic for determining if the LHS of a match-case construct (the value we are
-        // matching against) matches the RHS of a match-case construct (a value
-        // in a case statement). For example, checking to see if the value
-        // "foo" matches the pattern "bar" would be invoked like so :
-        // ```ignore
-        // matches("foo", "bar")
-        // ```
         let is_array = is_array(expression.as_ref());
-        let value = self.expand_string(expression.as_ref())?;
+        // The expression is only expanded once a case needs its value, so that a command
+        // substitution is not executed when a catch-all case without a binding comes first
+        let mut value = None;
         for case in cases.iter() {
-            let is_match = if let Some(v) = &case.value {
-                let v = self.expand_string(v)?;
-                // Anchor to start and end
-                let v = v.into_iter().map(|v| format!("^{}$", v));
-                RegexSet::new(v).ok().map_or(false, |regex| value.iter().all(|v| regex.is_match(v)))
-            } else {
-                true
+            let is_match = match case.values.as_slice() {
+                [] | [None] => true,
+                [Some(pattern)] => {
+                    let value = self.match_value(&mut value, expression.as_ref())?;
+                    self.case_matches(value, Some(pattern.as_str()))?
+                }
+                // A tuple match: each expression of the match is checked against the pattern in
+                // the same position, and all of them must match.
+                patterns => {
+                    let words = ArgumentSplitter::new(expression.as_ref()).collect::<Vec<_>>();
+                    let mut matched = words.len() == patterns.len();
+                    for (word, pattern) in words.into_iter().zip(patterns) {
+                        if !matched {
+                            break;
+                        }
+                        let value = self.expand_string(word)?;
+                        matched = self.case_matches(&value, pattern.as_deref())?;
+                    }
+                    matched
+                }
             };
 
             if is_match {
-                // let pattern_is_array = is_array(&value);
-                let previous_bind = case.binding.as_ref().and_then(|bind| {
-                    if is_array {
-                        let out = if let Some(Value::Array(array)) = self.variables.get(bind) {
-                            Some(Value::Array(array.clone()))
-                        } else {
-                            None
-                        };
-                        self.variables_mut()
-                            .set(bind, value.iter().cloned().map(Value::Str).collect::<Value<_>>());
-                        out
-                    } else {
-                        let out = if let Some(Value::Str(val)) = self.variables.get(bind) {
-                            Some(Value::Str(val.clone()))
+                let previous_bind = match case.binding.as_ref() {
+                    Some(bind) => {
+                        self.ensure_writable(bind)?;
+                        let value = self.match_value(&mut value, expression.as_ref())?.clone();
+                        let out = self.variables.get(bind).cloned();
+                        if is_array {
+                            self.variables_mut()
+                                .set(bind, value.into_iter().map(Value::Str).collect::<Value<_>>());
                         } else {
-                            None
-                        };
-                        self.variables_mut().set(bind, value.join(" "));
+                            self.variables_mut().set(bind, value.join(" "));
+                        }
                         out
                     }
-                });
+                    None => None,
+                };
 
                 if let Some(statement) = case.conditional.as_ref() {
                     self.on_command(statement.bytes(), true)?;
                     if self.previous_status.is_failure() {
+                        self.restore_binding(case.binding.as_deref(), previous_bind);
                         continue;
                     }
                 }
 
                 let condition = self.execute_statements(&case.statements);
 
-                if let Some(ref bind) = case.binding {
-                    if let Some(value) = previous_bind {
-                        match value {
-                            Value::HashMap(_) | Value::Array(_) | Value::Str(_) => {
-                                self.variables_mut().set(bind, value);
-                            }
-                            _ => (),
-                        }
-                    }
-                }
+                self.restore_binding(case.binding.as_deref(), previous_bind);
 
                 return condition;
             }
@@ -555,7 +1098,64 @@ impl<'a> Shell<'a> {
         Ok(Condition::NoOp)
     }
 
-    /// Receives a command and attempts to execute the contents.
+    /// The value of a match, expanded from its expression the first time it is needed
+    fn match_value<'v>(
+        &mut self,
+        value: &'v mut Option<types::Args>,
+        expression: &str,
+    ) -> std::result::Result<&'v types::Args, IonError> {
+        let expanded = match value.take() {
+            Some(expanded) => expanded,
+            None => self.expand_string(expression)?,
+        };
+        Ok(value.insert(expanded))
+    }
+
+    /// Logic for determining if the LHS of a match-case construct (the value we are matching
+    /// against) matches the RHS of a match-case construct (a value in a case statement). A
+    /// missing pattern, written `_`, matches anything.
+    fn case_matches(
+        &mut self,
+        value: &[types::Str],
+        pattern: Option<&str>,
+    ) -> std::result::Result<bool, IonError> {
+        use regex::RegexSet;
+        let pattern = match pattern {
+            Some(pattern) => pattern,
+            None => return Ok(true),
+        };
+        // A brace range of integers compares numerically, rather than expanding to a regex for
+        // each integer of the range
+        if let Some((start, end)) = integer_range(pattern) {
+            return Ok(value
+                .iter()
+                .filter_map(|v| v.parse::<i64>().ok())
+                .any(|v| start <= v && v <= end));
+        }
+        // Anchor to start and end
+        let pattern = self.expand_string(pattern)?.into_iter().map(|v| format!("^{}$", v));
+        // Both sides may be arrays, such as `case @patterns`, or `case {a,b}` as brace
+        // expansions give a pattern for each alternative: the case matches if any element of the
+        // value matches any of the patterns.
+        Ok(RegexSet::new(pattern).map_or(false, |regex| value.iter().any(|v| regex.is_match(v))))
+    }
+
+    /// Restores the value a match binding had before the case, or removes the binding if it did
+    /// not exist, so that the binding is scoped to the case.
+    fn restore_binding(&mut self, bind: Option<&str>, previous_bind: Option<Value<Rc<Function>>>) {
+        if let Some(bind) = bind {
+            match previous_bind {
+                Some(value) => self.variables_mut().set(bind, value),
+                None => {
+                    self.variables_mut().remove(bind);
+                }
+            }
+        }
+    }
+
+    /// Receives a command and attempts to execute the contents. The blocks left open by the
+    /// command are kept for the next command to complete, unless the command fails: a parse or
+    /// runtime error discards them, so that the next command is parsed afresh.
     pub fn on_command(
         &mut self,
         command_to_execute: impl Iterator<Item = u8>,
@@ -563,15 +1163,9 @@ impl<'a> Shell<'a> {
     ) -> std::result::Result<(), IonError> {
         let command_start_time = if set_cmd_duration { Some(SystemTime::now()) } else { None };
 
-        for stmt in command_to_execute.batching(|cmd| Terminator::new(cmd).terminate()) {
-            // Go through all of the statements and build up the block stack
-            // When block is done return statement for execution.
-            for statement in StatementSplitter::new(&stmt) {
-                let statement = parse_and_validate(statement?)?;
-                if let Some(stm) = Self::insert_statement(&mut self.flow_control, statement)? {
-                    self.execute_statement(&stm)?;
-                }
-            }
+        if let Err(why) = self.execute_lines(command_to_execute) {
+            self.flow_control.clear();
+            return Err(why);
         }
 
         if let Some(start_time) = command_start_time {
@@ -582,6 +1176,51 @@ impl<'a> Shell<'a> {
 
         Ok(())
     }
+
+    /// Builds up the block stack from the statements of the command, executing each statement
+    /// once its block is complete
+    fn execute_lines(
+        &mut self,
+        command_to_execute: impl Iterator<Item = u8>,
+    ) -> std::result::Result<(), IonError> {
+        for stmt in command_to_execute.batching(|cmd| Terminator::new(cmd).terminate()) {
+            let mut statements = StatementSplitter::new(&stmt).peekable();
+            while let Some(statement) = statements.next() {
+                let statement = parse_and_validate(statement?)?;
+                if let Some(stm) = Self::insert_statement(&mut self.flow_control, statement)? {
+                    // The left operand of `&&` or `||` is a condition, which must not exit
+                    if matches!(
+                        statements.peek(),
+                        Some(Ok(StatementVariant::And(_))) | Some(Ok(StatementVariant::Or(_)))
+                    ) {
+                        self.without_err_exit(|shell| shell.execute_recovering(&stm))?;
+                    } else {
+                        self.execute_recovering(&stm)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The message given to a panic, when it is a string
+fn panic_message(cause: &(dyn Any + Send)) -> &str {
+    cause
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| cause.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown cause")
+}
+
+/// The value of a `return --value VALUE` statement, given the expression following `return`
+fn returned_value(expression: &str) -> Option<&str> {
+    let value = expression.strip_prefix("--value")?;
+    if value.is_empty() || value.starts_with(char::is_whitespace) {
+        Some(value.trim_start())
+    } else {
+        None
+    }
 }
 
 /// Expand a pipeline containing aliases. As aliases can split the pipeline by having logical
@@ -659,10 +1298,44 @@ fn expand_pipeline<'a>(
     Ok((Pipeline { items, pipe: pipeline.pipe }, statements))
 }
 
+/// Parses a case pattern which is a brace range of integers, such as `{1..4}`, `{1...3}` or
+/// `{1..=3}`, into the inclusive bounds of the integers it expands to. As with other brace ranges,
+/// `..` excludes the end while `...` and `..=` include it. Stepped and descending ranges are left
+/// to the brace expansion.
+fn integer_range(pattern: &str) -> Option<(i64, i64)> {
+    let pattern = pattern.strip_prefix('{')?.strip_suffix('}')?;
+    let index = pattern.find("..")?;
+    let (start, end) = (&pattern[..index], &pattern[index + 2..]);
+    let (end, inclusive) = match end.strip_prefix(&['.', '='][..]) {
+        Some(end) => (end, true),
+        None => (end, false),
+    };
+    let (start, end) = (start.parse::<i64>().ok()?, end.parse::<i64>().ok()?);
+    let end = if inclusive { end } else { end.checked_sub(1)? };
+    Some((start, end)).filter(|(start, end)| start <= end)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn integer_ranges() {
+        assert_eq!(integer_range("{1..4}"), Some((1, 3)));
+        assert_eq!(integer_range("{1...3}"), Some((1, 3)));
+        assert_eq!(integer_range("{126..=128}"), Some((126, 128)));
+        assert_eq!(integer_range("{-3..-1}"), Some((-3, -2)));
+        // Without braces, the pattern is a regex
+        assert_eq!(integer_range("1..4"), None);
+        assert_eq!(integer_range("{1..3.5}"), None);
+        assert_eq!(integer_range("{a..z}"), None);
+        assert_eq!(integer_range("{..3}"), None);
+        assert_eq!(integer_range("{1..2..9}"), None);
+        assert_eq!(integer_range("{4..1}"), None);
+        assert_eq!(integer_range("{1}"), None);
+        assert_eq!(integer_range("{1,2}"), None);
+    }
+
     fn new_match() -> Statement {
         Statement::Match { expression: types::Str::from(""), cases: Vec::new() }
     }
@@ -677,7 +1350,7 @@ mod tests {
     }
     fn new_case() -> Statement {
         Statement::Case(Case {
-            value:       None,
+            values:      Vec::new(),
             binding:     None,
             conditional: None,
             statements:  Vec::new(),
@@ -756,4 +1429,43 @@ mod tests {
             assert!(Shell::insert_statement(&mut flow_control, err).is_err());
         }
     }
+
+    #[test]
+    fn do_while_needs_condition() {
+        let mut flow_control = Block::default();
+        let do_while = Statement::DoWhile { statements: Vec::new(), expression: Vec::new() };
+
+        assert_eq!(Shell::insert_statement(&mut flow_control, do_while.clone()), Ok(None));
+        assert_eq!(
+            Shell::insert_statement(&mut flow_control, Statement::End),
+            Err(BlockError::DoWithoutWhile)
+        );
+
+        let condition = vec![Statement::Default];
+        let res = Shell::insert_statement(&mut flow_control, Statement::EndWhile(condition));
+        assert_eq!(
+            res,
+            Ok(Some(Statement::DoWhile {
+                statements: Vec::new(),
+                expression: vec![Statement::Default],
+            }))
+        );
+        assert_eq!(
+            Shell::insert_statement(&mut flow_control, Statement::EndWhile(Vec::new())),
+            Err(BlockError::UnmatchedEndWhile)
+        );
+    }
+
+    #[test]
+    fn statements_until_error() {
+        let mut shell = Shell::default();
+        let not = || Statement::Not(Box::new(Statement::Default));
+
+        // The second statement would make the block succeed if it was executed
+        let status = shell.execute_statements_until_error(&[not(), not()]).unwrap();
+        assert_eq!(status, Status::FALSE);
+
+        let status = shell.execute_statements_until_error(&[Statement::Default]).unwrap();
+        assert_eq!(status, Status::SUCCESS);
+    }
 }
@@ -1,12 +1,15 @@
+use super::flow::Condition;
 use crate::{
     assignments::*,
-    expansion::pipelines::Pipeline,
+    builtins::Status,
+    expansion::pipelines::{PipeType, Pipeline, RedirectFrom},
     parser::lexers::assignments::{KeyBuf, Operator, Primitive},
-    shell::{IonError, Job, Shell},
+    shell::{IonError, Job, Shell, Value},
     types,
 };
+use serde::{Deserialize, Serialize};
 use smallvec::SmallVec;
-use std::fmt;
+use std::{fmt, rc::Rc, slice};
 use thiserror::Error;
 
 /// Represents a single branch in a match statement. For example, in the expression
@@ -28,18 +31,20 @@ use thiserror::Error;
 /// would be represented by the Case object:
 /// ```rust,ignore
 /// Case {
-///     value:      Some(value),
+///     values:     vec![Some(value)],
 ///     statements: vec![statement0, statement1, ... statementN],
 /// }
 /// ```
 /// The wildcard branch, a branch that matches any value, is represented as such:
 /// ```rust,ignore
-/// Case { value: None, ... }
+/// Case { values: vec![], ... }
 /// ```
-#[derive(Debug, PartialEq, Clone, Default)]
+/// A case with several values, such as `case "x" _`, matches a match statement with as many
+/// expressions, position by position, and a `_` value matches anything in its position.
+#[derive(Debug, PartialEq, Clone, Default, Serialize, Deserialize)]
 pub struct Case {
-    /// The value to match with
-    pub value:       Option<String>,
+    /// The values to match with, one per matched expression, where `None` is a wildcard
+    pub values:      Vec<Option<String>>,
     /// Set a variable with the exact result
     pub binding:     Option<String>,
     /// An additional statement to test before matching the case statement
@@ -49,7 +54,7 @@ pub struct Case {
 }
 
 /// An elseif case
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct ElseIf {
     /// The block to test
     pub expression: Block,
@@ -58,7 +63,7 @@ pub struct ElseIf {
 }
 
 /// The action to perform on assignment
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum LocalAction {
     /// List all the variables
     List,
@@ -67,7 +72,7 @@ pub enum LocalAction {
 }
 
 /// The action to perform on export
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum ExportAction {
     /// List the environment variables
     List,
@@ -78,7 +83,7 @@ pub enum ExportAction {
 }
 
 /// The mode for the next if block
-#[derive(Debug, PartialEq, Clone, Copy, Hash)]
+#[derive(Debug, PartialEq, Clone, Copy, Hash, Serialize, Deserialize)]
 pub enum IfMode {
     /// Standard if
     Success,
@@ -92,7 +97,7 @@ pub enum IfMode {
 ///
 /// Contains all the possible actions for the shell
 // TODO: Enable statements and expressions to contain &str values.
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum Statement {
     /// Assignment
     Let(LocalAction),
@@ -118,8 +123,10 @@ pub enum Statement {
     /// Create a function
     Function {
         /// the name of the function
+        #[serde(with = "super::compile::string")]
         name:        types::Str,
         /// the description of the function
+        #[serde(with = "super::compile::optional_string")]
         description: Option<types::Str>,
         /// The required arguments of the function, with their types
         args:        Vec<KeyBuf>,
@@ -129,9 +136,27 @@ pub enum Statement {
     /// for loop
     For {
         /// The bounds
+        #[serde(with = "super::compile::strings")]
         variables:  SmallVec<[types::Str; 4]>,
         /// The value to iterator for
+        #[serde(with = "super::compile::strings")]
         values:     Vec<types::Str>,
+        /// The condition to filter the values with, empty if every value is kept
+        filter:     Block,
+        /// The block to execute repetitively
+        statements: Block,
+    },
+    /// C-style for loop: `for (( i = 0; i < 10; i++ ))`
+    CStyleFor {
+        /// The arithmetic assignments run before the first iteration
+        #[serde(with = "super::compile::string")]
+        init:       types::Str,
+        /// The arithmetic expression tested before each iteration, the loop ending when it is 0
+        #[serde(with = "super::compile::string")]
+        condition:  types::Str,
+        /// The arithmetic assignments run after each iteration
+        #[serde(with = "super::compile::string")]
+        update:     types::Str,
         /// The block to execute repetitively
         statements: Block,
     },
@@ -142,9 +167,24 @@ pub enum Statement {
         /// The block to execute repetitively
         statements: Block,
     },
+    /// do-while loop, of which the body is executed before the condition is tested
+    DoWhile {
+        /// The block to execute repetitively
+        statements: Block,
+        /// The block to test after each iteration
+        expression: Block,
+    },
+    /// A block executed with some environment variables set, which are restored afterwards
+    WithEnv {
+        /// The names of the variables, with their values to expand
+        bindings:   Vec<(String, String)>,
+        /// The block to execute
+        statements: Block,
+    },
     /// Match
     Match {
         /// The value to check
+        #[serde(with = "super::compile::string")]
         expression: types::Str,
         /// A list of case to check for
         cases:      Vec<Case>,
@@ -153,14 +193,29 @@ pub enum Statement {
     Else,
     /// End of a block
     End,
+    /// End of a do-while block, with the condition of the loop
+    EndWhile(Block),
     /// Exit loop
     Break,
     /// Next loop
     Continue,
     /// Exit from the current function/script
-    Return(Option<types::Str>),
+    Return(#[serde(with = "super::compile::optional_string")] Option<types::Str>),
+    /// Pass values to the for loop iterating over the current function
+    Yield(#[serde(with = "super::compile::string")] types::Str),
     /// Execute a pipeline
     Pipeline(Pipeline<Job>),
+    /// Execute a pipeline in the background, connected to the shell through pipes
+    Coproc {
+        /// The name of the array holding the file descriptors of the pipes
+        #[serde(with = "super::compile::string")]
+        name:     types::Str,
+        /// The pipeline to spawn
+        pipeline: Pipeline<Job>,
+    },
+    /// Evaluate an arithmetic condition, such as the `(( i < 10 ))` of a while loop, which
+    /// succeeds when its value is not 0
+    Arithmetic(#[serde(with = "super::compile::string")] types::Str),
     /// Time the statement
     Time(Box<Statement>),
     /// Execute the statement if the previous command succeeded
@@ -169,6 +224,18 @@ pub enum Statement {
     Or(Box<Statement>),
     /// Succeed on failure of the inner statement
     Not(Box<Statement>),
+    /// Pause the script with an interactive session, if the condition expands to a true value
+    Breakpoint(#[serde(with = "super::compile::optional_string")] Option<types::Str>),
+    /// Execute the statement each time the value of the variable changes
+    Watch {
+        /// The name of the watched variable
+        #[serde(with = "super::compile::string")]
+        name:    types::Str,
+        /// The statement as written, listed by `watches`
+        #[serde(with = "super::compile::string")]
+        command: types::Str,
+        body:    Box<Statement>,
+    },
     /// An empty statement
     Default,
 }
@@ -186,18 +253,27 @@ impl fmt::Display for Statement {
                 Statement::ElseIf(_) => "ElseIf { .. }",
                 Statement::Function { .. } => "Function { .. }",
                 Statement::For { .. } => "For { .. }",
+                Statement::CStyleFor { .. } => "CStyleFor { .. }",
                 Statement::While { .. } => "While { .. }",
+                Statement::DoWhile { .. } => "DoWhile { .. }",
+                Statement::WithEnv { .. } => "WithEnv { .. }",
                 Statement::Match { .. } => "Match { .. }",
                 Statement::Else => "Else",
                 Statement::End => "End",
+                Statement::EndWhile(_) => "EndWhile { .. }",
                 Statement::Break => "Break",
                 Statement::Continue => "Continue",
                 Statement::Pipeline(_) => "Pipeline { .. }",
+                Statement::Coproc { .. } => "Coproc { .. }",
+                Statement::Arithmetic(_) => "Arithmetic { .. }",
                 Statement::Time(_) => "Time { .. }",
                 Statement::And(_) => "And { .. }",
                 Statement::Or(_) => "Or { .. }",
                 Statement::Not(_) => "Not { .. }",
+                Statement::Breakpoint(_) => "Breakpoint { .. }",
+                Statement::Watch { .. } => "Watch { .. }",
                 Statement::Return(_) => "Return",
+                Statement::Yield(_) => "Yield",
                 Statement::Default => "Default",
             }
         )
@@ -215,7 +291,10 @@ impl Statement {
                 | Statement::ElseIf(_)
                 | Statement::Function { .. }
                 | Statement::For { .. }
+                | Statement::CStyleFor { .. }
                 | Statement::While { .. }
+                | Statement::DoWhile { .. }
+                | Statement::WithEnv { .. }
                 | Statement::Match { .. }
                 | Statement::Else
         )
@@ -225,6 +304,9 @@ impl Statement {
 /// A collection of statement in a block (delimited by braces in most languages)
 pub type Block = Vec<Statement>;
 
+/// The maximum depth of nested function calls when `$ION_MAX_DEPTH` is not set
+const DEFAULT_MAX_DEPTH: usize = 1000;
+
 /// A user-defined function
 #[derive(Clone, Debug, PartialEq, Default)]
 pub struct Function {
@@ -246,18 +328,17 @@ pub enum FunctionError {
 }
 
 impl Function {
-    /// execute the function in the shell
-    pub fn execute<'a, S: AsRef<str>>(
+    /// Check the arguments given to the function against its signature
+    fn arguments<S: AsRef<str>>(
         &self,
-        shell: &mut Shell<'a>,
+        shell: &mut Shell<'_>,
         args: &[S],
-    ) -> Result<(), IonError> {
+    ) -> Result<SmallVec<[(KeyBuf, Value<Rc<Self>>); 8]>, FunctionError> {
         if args.len() - 1 != self.args.len() {
-            return Err(FunctionError::InvalidArgumentCount.into());
+            return Err(FunctionError::InvalidArgumentCount);
         }
 
-        let values = self
-            .args
+        self.args
             .iter()
             .zip(args.iter().skip(1))
             .map(|(type_, value)| {
@@ -270,7 +351,78 @@ impl Function {
                     ))
                 }
             })
-            .collect::<Result<SmallVec<[_; 8]>, _>>()?;
+            .collect()
+    }
+
+    /// If the last statement of the function is a plain call to the function itself, split the
+    /// body into the statements preceding it and that tail call. A function which only calls
+    /// itself is left alone, as it could never be interrupted.
+    fn tail_call(&self) -> Option<(&[Statement], &Statement, &Pipeline<Job>)> {
+        let (last, body) = self.statements.split_last()?;
+        if body.is_empty() {
+            return None;
+        }
+        match last {
+            Statement::Pipeline(pipeline) if pipeline.pipe == PipeType::Normal => {
+                match &pipeline.items[..] {
+                    [item]
+                        if item.inputs.is_empty()
+                            && item.outputs.is_empty()
+                            && item.job.redirection == RedirectFrom::None
+                            && item.job.args[0] == self.name =>
+                    {
+                        Some((body, last, pipeline))
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether the tail call can reuse the scope of the current call. The call must run the
+    /// function, rather than a builtin or an alias of the same name, and nothing may observe the
+    /// statement of the call: the memo cache, the profiler, the command log, the watches and the
+    /// callbacks run before each statement or command would all miss it.
+    fn can_tail_call(&self, shell: &Shell<'_>) -> bool {
+        shell.builtins.get(&self.name).is_none()
+            && matches!(shell.variables.get(&self.name), Some(Value::Function(_)))
+            && !shell.is_memoized(&self.name)
+            && shell.profile.is_none()
+            && !shell.logs_commands()
+            && shell.watches.is_empty()
+            && shell.pre_statement.is_none()
+            && shell.pre_command.is_none()
+    }
+
+    /// execute the function in the shell
+    ///
+    /// A call to the function itself as its last statement reuses the scope of the current call
+    /// instead of recursing, so that tail recursive functions run in constant stack space, unless
+    /// the function is memoized or the statements are profiled, logged, watched or stepped. Calls
+    /// nested deeper than `$ION_MAX_DEPTH` fail instead of overflowing the stack.
+    pub fn execute<'a, S: AsRef<str>>(
+        &self,
+        shell: &mut Shell<'a>,
+        args: &[S],
+    ) -> Result<(), IonError> {
+        let mut values = self.arguments(shell, args)?;
+        if let Some((type_, _)) =
+            values.iter().find(|(type_, _)| shell.variables.is_readonly(&type_.name))
+        {
+            return Err(IonError::ReadonlyVariable(type_.name.as_str().into()));
+        }
+
+        let max_depth = shell
+            .variables
+            .get_str("ION_MAX_DEPTH")
+            .ok()
+            .and_then(|depth| depth.parse::<usize>().ok())
+            .unwrap_or(DEFAULT_MAX_DEPTH);
+        if max_depth != 0 && shell.call_stack.len() >= max_depth {
+            shell.previous_status = Status::error("ion: maximum recursion depth exceeded");
+            return Ok(());
+        }
 
         let index = shell
             .variables
@@ -282,17 +434,55 @@ impl Function {
 
         shell.variables.new_scope(true);
 
-        for (type_, value) in values {
-            shell.variables.set(&type_.name, value);
-        }
+        // Without `set -E`, failing commands do not exit the shell from inside of a function
+        let err_exit = shell.opts.err_exit;
+        shell.opts.err_exit &= shell.opts.err_trace;
+        shell.call_stack.push(self.name.clone());
+        let tail_call = self.tail_call();
+        let res = loop {
+            // The arguments are also available as a whole, in `@argv`
+            let argv = values.iter().map(|(_, value)| value.clone()).collect();
+            shell.variables.set("argv", Value::Array(argv));
+            for (type_, value) in values {
+                shell.variables.set(&type_.name, value);
+            }
 
-        let res = shell.execute_statements(&self.statements);
+            let (body, last, pipeline) = match tail_call {
+                Some(tail_call) => tail_call,
+                None => break shell.execute_statements(&self.statements),
+            };
+            match shell.execute_statements(body) {
+                Ok(Condition::NoOp) => (),
+                condition => break condition,
+            }
+            if !self.can_tail_call(shell) {
+                break shell.execute_statements(slice::from_ref(last));
+            }
+            // The arguments of the tail call are expanded as those of any other call
+            let pipeline = match pipeline.expand(shell) {
+                Ok(pipeline) => pipeline,
+                Err(why) => break Err(why.into()),
+            };
+            values = match self.arguments(shell, &pipeline.items[0].job.args) {
+                Ok(values) => values,
+                Err(why) => break Err(why.into()),
+            };
+            // Start the next call from a fresh scope
+            shell.variables.pop_scope();
+            shell.variables.new_scope(true);
+        };
+        shell.call_stack.pop();
+        shell.opts.err_exit = err_exit;
 
         shell.variables.pop_scope();
         shell.variables.append_scopes(temporary);
         res.map(|_| ())
     }
 
+    /// Get the function's name
+    #[must_use]
+    pub const fn name(&self) -> &types::Str { &self.name }
+
     /// Get the function's description
     #[must_use]
     pub const fn description(&self) -> Option<&types::Str> { self.description.as_ref() }
@@ -0,0 +1,167 @@
+//! The hooks registered with the `hook` builtin: functions called by the shell at some points of
+//! its execution, such as before each prompt or after the directory changes.
+
+use super::{Shell, Value};
+use crate::{builtins::Status, types};
+use std::{
+    fmt::{self, Display, Formatter},
+    str::FromStr,
+};
+use thiserror::Error;
+
+/// A point of the execution of the shell at which the functions of a hook are called
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Hook {
+    /// Before the prompt is displayed by an interactive shell
+    Precmd,
+    /// Before a command line read by an interactive shell is executed, which is given to the
+    /// functions as argument
+    Preexec,
+    /// After the current directory was changed by `cd`, `pushd` or `popd`
+    Chpwd,
+    /// Before the shell exits, with the exit status as argument
+    Exit,
+}
+
+impl Hook {
+    /// Every hook, in the order they are listed
+    pub const ALL: [Self; 4] = [Self::Precmd, Self::Preexec, Self::Chpwd, Self::Exit];
+}
+
+/// The name of a hook did not match any of them
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("unknown hook '{0}': expected precmd, preexec, chpwd or exit")]
+pub struct UnknownHook(String);
+
+impl FromStr for Hook {
+    type Err = UnknownHook;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "precmd" => Ok(Self::Precmd),
+            "preexec" => Ok(Self::Preexec),
+            "chpwd" => Ok(Self::Chpwd),
+            "exit" => Ok(Self::Exit),
+            _ => Err(UnknownHook(name.into())),
+        }
+    }
+}
+
+impl Display for Hook {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Precmd => "precmd",
+            Self::Preexec => "preexec",
+            Self::Chpwd => "chpwd",
+            Self::Exit => "exit",
+        })
+    }
+}
+
+impl<'a> Shell<'a> {
+    /// The names of the functions registered for `hook`, in the order they are called
+    #[must_use]
+    pub fn hooks(&self, hook: Hook) -> &[types::Str] {
+        self.hooks.get(&hook).map_or(&[], Vec::as_slice)
+    }
+
+    /// Registers the function `name` to be called at `hook`, after the functions registered
+    /// before it. Returns `false` if it was already registered, in which case it is not added a
+    /// second time.
+    pub fn add_hook(&mut self, hook: Hook, name: &str) -> bool {
+        let functions = self.hooks.entry(hook).or_default();
+        if functions.iter().any(|function| function == name) {
+            false
+        } else {
+            functions.push(name.into());
+            true
+        }
+    }
+
+    /// Unregisters the function `name` from `hook`, returning `false` if it was not registered
+    pub fn remove_hook(&mut self, hook: Hook, name: &str) -> bool {
+        match self.hooks.get_mut(&hook) {
+            Some(functions) => {
+                let len = functions.len();
+                functions.retain(|function| function != name);
+                functions.len() != len
+            }
+            None => false,
+        }
+    }
+
+    /// Calls the functions registered for `hook` in registration order, with `args` following
+    /// the name of the function. The status of the previous command is restored afterwards, so
+    /// that hooks never change `$?`. A function which no longer exists or which fails to execute
+    /// is reported without stopping the others. A hook is not run again by its own functions,
+    /// such as a `chpwd` function which changes the directory.
+    pub fn run_hook(&mut self, hook: Hook, args: &[&str]) {
+        if self.running_hooks.contains(&hook) || self.hooks(hook).is_empty() {
+            return;
+        }
+        self.running_hooks.push(hook);
+        let previous_status = self.previous_status;
+
+        for name in self.hooks(hook).to_vec() {
+            let function = match self.variables.get(&name) {
+                Some(Value::Function(function)) => function.clone(),
+                _ => {
+                    eprintln!("ion: {} hook: function `{}` does not exist", hook, name);
+                    continue;
+                }
+            };
+            let mut call = Vec::with_capacity(args.len() + 1);
+            call.push(name.as_str());
+            call.extend_from_slice(args);
+            if let Err(why) = function.execute(self, &call) {
+                eprintln!("ion: {} hook: {}: {}", hook, name, why);
+            }
+        }
+
+        self.previous_status = previous_status;
+        self.variables.set("?", previous_status);
+        self.running_hooks.pop();
+    }
+
+    /// Runs the `exit` hook with the exit status as argument. This is done by `exit`, and must be
+    /// done by the embedders of the shell before they exit otherwise, like
+    /// `remove_temporary_paths`. The hook only runs once, even if the shell is exited from several
+    /// places.
+    pub fn run_exit_hook(&mut self, status: Status) {
+        if !self.hooks(Hook::Exit).is_empty() {
+            self.run_hook(Hook::Exit, &[&status.as_os_code().to_string()]);
+            self.hooks.remove(&Hook::Exit);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hook_names() {
+        for &hook in &Hook::ALL {
+            assert_eq!(hook.to_string().parse::<Hook>(), Ok(hook));
+        }
+        assert_eq!("prompt".parse::<Hook>(), Err(UnknownHook("prompt".into())));
+    }
+
+    fn names(shell: &Shell<'_>, hook: Hook) -> Vec<&str> {
+        shell.hooks(hook).iter().map(types::Str::as_str).collect()
+    }
+
+    #[test]
+    fn registration_order() {
+        let mut shell = Shell::default();
+        assert!(shell.add_hook(Hook::Precmd, "first"));
+        assert!(shell.add_hook(Hook::Precmd, "second"));
+        assert!(!shell.add_hook(Hook::Precmd, "first"));
+        assert_eq!(names(&shell, Hook::Precmd), ["first", "second"]);
+        assert!(shell.hooks(Hook::Chpwd).is_empty());
+
+        assert!(shell.remove_hook(Hook::Precmd, "first"));
+        assert!(!shell.remove_hook(Hook::Precmd, "first"));
+        assert_eq!(names(&shell, Hook::Precmd), ["second"]);
+    }
+}
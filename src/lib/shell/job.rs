@@ -4,12 +4,14 @@ use crate::{
     expansion::{self, pipelines::RedirectFrom, Expander},
     types, Value,
 };
+use serde::{Deserialize, Serialize};
 use std::{fmt, fs::File, str};
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 /// A shell job
 pub struct Job {
     /// Job Arguments
+    #[serde(with = "super::compile::strings")]
     pub args:        types::Args,
     /// Redirection of the job (stdout |, stderr ^|, both &|, None)
     pub redirection: RedirectFrom,
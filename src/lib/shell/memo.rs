@@ -0,0 +1,107 @@
+//! The memoization of functions enabled with `@cache NAME` or `fn --memo NAME`.
+
+use super::{flow_control::Function, IonError, Shell};
+use crate::{builtins::Status, types};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, Read, Write},
+    rc::Rc,
+};
+
+/// The results cached for a memoized function, keyed by the arguments of each call
+#[derive(Debug, Default)]
+pub(crate) struct Memo {
+    results: HashMap<Vec<types::Str>, (Vec<u8>, Status)>,
+    hits:    usize,
+    misses:  usize,
+}
+
+impl Memo {
+    /// The number of calls answered from the cache
+    pub(crate) const fn hits(&self) -> usize { self.hits }
+
+    /// The number of calls which executed the function
+    pub(crate) const fn misses(&self) -> usize { self.misses }
+
+    /// The number of distinct argument lists currently cached
+    pub(crate) fn entries(&self) -> usize { self.results.len() }
+
+    /// Forget the cached results, keeping the statistics
+    pub(crate) fn clear(&mut self) { self.results.clear(); }
+}
+
+/// Write the output of a function to the standard output of the shell, which is redirected while
+/// the output of an enclosing memoized call is being captured
+fn replay(stdout: Option<&File>, output: &[u8]) -> io::Result<()> {
+    match stdout {
+        Some(mut file) => file.write_all(output),
+        None => {
+            let stdout = io::stdout();
+            let mut stdout = stdout.lock();
+            stdout.write_all(output)?;
+            stdout.flush()
+        }
+    }
+}
+
+impl<'a> Shell<'a> {
+    /// Memoize the function `name`: its standard output and exit status are cached for each list
+    /// of arguments, and replayed instead of executing it again.
+    pub(crate) fn memoize(&mut self, name: &str) { self.memos.entry(name.into()).or_default(); }
+
+    /// Forget the results cached for the function `name`, returning whether it is memoized
+    pub(crate) fn clear_memo(&mut self, name: &str) -> bool {
+        self.memos.get_mut(name).map(Memo::clear).is_some()
+    }
+
+    /// The memoized functions, with their cache
+    pub(crate) fn memos(&self) -> impl Iterator<Item = (&types::Str, &Memo)> { self.memos.iter() }
+
+    /// Whether the function `name` is memoized
+    pub(crate) fn is_memoized(&self, name: &str) -> bool { self.memos.contains_key(name) }
+
+    /// Execute a memoized function, replaying its cached output if it was already called with
+    /// the same arguments. Only successful calls are cached.
+    pub(super) fn execute_memoized<S: AsRef<str>>(
+        &mut self,
+        function: &Rc<Function>,
+        args: &[S],
+    ) -> Result<Status, IonError> {
+        let name = args[0].as_ref();
+        let key = args[1..].iter().map(|arg| arg.as_ref().into()).collect::<Vec<types::Str>>();
+        let memo = self.memos.get_mut(name).expect("execute_memoized called on a plain function");
+        if let Some(&(ref output, status)) = memo.results.get(&key) {
+            memo.hits += 1;
+            self.previous_status = status;
+            return Ok(match replay(self.stdout.as_ref(), output) {
+                Ok(()) => status,
+                Err(why) => Status::error(format!("ion: {}: {}", name, why)),
+            });
+        }
+        memo.misses += 1;
+
+        let (result, mut reader) =
+            match self.capture_stream(false, |shell| function.execute(shell, args)) {
+                Ok(captured) => captured,
+                Err(why) => return Ok(Status::error(format!("ion: {}: {}", name, why))),
+            };
+        let mut output = Vec::new();
+        if let Err(why) = reader.read_to_end(&mut output) {
+            return Ok(Status::error(format!("ion: {}: {}", name, why)));
+        }
+        let written = replay(self.stdout.as_ref(), &output);
+        result?;
+
+        let status = self.previous_status;
+        if status.is_success() {
+            if let Some(memo) = self.memos.get_mut(name) {
+                memo.results.insert(key, (output, status));
+            }
+        }
+        Ok(match written {
+            Ok(()) => status,
+            Err(why) => Status::error(format!("ion: {}: {}", name, why)),
+        })
+    }
+}
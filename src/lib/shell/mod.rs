@@ -1,31 +1,54 @@
 mod assignments;
+mod breakpoint;
+mod call_graph;
 mod colors;
+mod command_log;
+pub(crate) mod compile;
+mod completion;
 mod directory_stack;
 mod flow;
 /// The various blocks
 pub mod flow_control;
+mod hooks;
 mod job;
+mod memo;
 mod pipe_exec;
+mod profile;
+mod prompt;
 mod shell_expand;
 mod signals;
+mod step;
 pub mod sys;
+mod timing;
 /// Variables for the shell
 pub mod variables;
+mod watches;
 
+pub(crate) use self::colors::Colors;
 use self::{
+    command_log::CommandLog,
     directory_stack::DirectoryStack,
+    flow::Generator,
     flow_control::{Block, Function, FunctionError, Statement},
+    memo::Memo,
     pipe_exec::foreground,
     sys::NULL_PATH,
-    variables::Variables,
+    variables::{Snapshot, Variables},
+    watches::Watch,
 };
 pub use self::{
-    flow::BlockError,
+    call_graph::CallGraph,
+    compile::{CacheError, CACHE_EXTENSION},
+    completion::{Candidate, CompletionGenerator},
+    flow::{BlockError, Condition},
+    hooks::{Hook, UnknownHook},
     job::{Job, RefinedJob},
     pipe_exec::{
         job_control::{BackgroundEvent, BackgroundProcess},
-        PipelineError,
+        Coproc, PipelineError,
     },
+    profile::CommandProfile,
+    step::StatementCursor,
     variables::Value,
 };
 use crate::{
@@ -39,19 +62,31 @@ use crate::{
         lexers::{Key, Primitive},
         Error as ParseError,
     },
+    types,
 };
 use nix::{
     sys::signal::{self, SigHandler},
     unistd::Pid,
 };
 use std::{
+    collections::{BTreeMap, HashMap, HashSet},
     convert::TryFrom,
-    fs::File,
+    env,
+    ffi::OsString,
+    fs::{self, File},
+    io::{self, Write},
     mem,
     ops::{Deref, DerefMut},
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
     rc::Rc,
-    sync::{atomic::Ordering, Arc, Mutex},
-    time::SystemTime,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, RecvTimeoutError},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, SystemTime},
 };
 use thiserror::Error;
 
@@ -76,6 +111,21 @@ pub enum IonError {
     /// Could not properly expand to a pipeline
     #[error("expansion error: {0}")]
     ExpansionError(#[source] ExpansionError<IonError>),
+    /// The namespace given to a script is not a valid variable name
+    #[error("invalid namespace `{0}`: not a valid variable name")]
+    InvalidNamespace(String),
+    /// A loop, a match binding or a function parameter would overwrite a readonly variable
+    #[error("cannot modify readonly variable `{0}`")]
+    ReadonlyVariable(types::Str),
+    /// The execution exceeded the time limit given to `on_command_with_limit`
+    #[error("execution timed out after {0:?}")]
+    TimedOut(Duration),
+    /// A script could not be compiled to its bytecode cache
+    #[error("bytecode cache error: {0}")]
+    Cache(#[source] CacheError),
+    /// The shell exited while an exit handler was set, unwinding the execution
+    #[error("exited with status {}", .0.as_os_code())]
+    Exit(Status),
 }
 
 impl From<ParseError> for IonError {
@@ -98,6 +148,11 @@ impl From<PipelineError> for IonError {
     fn from(cause: PipelineError) -> Self { Self::PipelineExecutionError(cause) }
 }
 
+impl From<CacheError> for IonError {
+    #[must_use]
+    fn from(cause: CacheError) -> Self { Self::Cache(cause) }
+}
+
 impl From<ExpansionError<Self>> for IonError {
     #[must_use]
     fn from(cause: ExpansionError<Self>) -> Self { Self::ExpansionError(cause) }
@@ -107,13 +162,75 @@ impl From<ExpansionError<Self>> for IonError {
 #[derive(Debug, Clone, Hash, Default)]
 pub struct Options {
     /// Exit from the shell on the first error.
-    pub err_exit:  bool,
+    pub err_exit:          bool,
+    /// Also exit on errors inside of functions and on functions returning an error, which are
+    /// otherwise exempt from `err_exit` so that they can be used as predicates.
+    pub err_trace:         bool,
+    /// Exit from the shell when a command can not be found, even if the
+    /// `command_not_found_handler` function handled it.
+    pub not_found_exit:    bool,
     /// Activates the -p option, aka pipefail in bash
-    pub pipe_fail: bool,
+    pub pipe_fail:         bool,
+    /// Refuse assignments with more or less values than keys, instead of distributing the words
+    /// of the values over the keys.
+    pub strict_assignment: bool,
+    /// Set the keys left without a word, when the words of the values are distributed over the
+    /// keys, to an empty string instead of failing.
+    pub pad_assignment:    bool,
     /// Do not execute any commands given to the shell.
-    pub no_exec:   bool,
+    pub no_exec:           bool,
+    /// Catch the panics of the statements, which then fail with a diagnostic instead of aborting
+    /// the shell. The state of the shell may be left partially modified by such a statement.
+    pub recover_panics:    bool,
+    /// Make the builtins printing tables, such as `jobs` or `type`, print them as JSON as if they
+    /// were given `--json`.
+    pub json_output:       bool,
+    /// Expand an index out of the bounds of an array, such as `@array[5]` for an array of two
+    /// elements, to nothing instead of failing.
+    pub lenient_indices:   bool,
     /// If set, denotes that this shell is running as a background job.
-    pub grab_tty:  bool,
+    pub grab_tty:          bool,
+}
+
+impl Options {
+    /// The names of the options which can be set with `set -o NAME` and unset with `set +o NAME`,
+    /// in the order they are listed by `set -o`
+    pub const NAMES: [&'static str; 9] = [
+        "errexit",
+        "exit-on-error-in-functions",
+        "exit-on-command-not-found",
+        "pipefail",
+        "strict-assignment",
+        "pad-assignment",
+        "recover-panics",
+        "json-output",
+        "lenient-indices",
+    ];
+
+    fn named(&mut self, name: &str) -> Option<&mut bool> {
+        match name {
+            "errexit" => Some(&mut self.err_exit),
+            "exit-on-error-in-functions" => Some(&mut self.err_trace),
+            "exit-on-command-not-found" => Some(&mut self.not_found_exit),
+            "pipefail" => Some(&mut self.pipe_fail),
+            "strict-assignment" => Some(&mut self.strict_assignment),
+            "pad-assignment" => Some(&mut self.pad_assignment),
+            "recover-panics" => Some(&mut self.recover_panics),
+            "json-output" => Some(&mut self.json_output),
+            "lenient-indices" => Some(&mut self.lenient_indices),
+            _ => None,
+        }
+    }
+
+    /// Whether the option called `name` is set, or `None` if there is no such option in `NAMES`
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<bool> { self.clone().named(name).map(|set| *set) }
+
+    /// Set or unset the option called `name`, returning false if there is no such option in
+    /// `NAMES`
+    pub fn set(&mut self, name: &str, value: bool) -> bool {
+        self.named(name).map(|set| *set = value).is_some()
+    }
 }
 
 /// The shell structure is a megastructure that manages all of the state of the shell throughout
@@ -133,6 +250,8 @@ pub struct Shell<'a> {
     /// When a command is executed, the final result of that command is stored
     /// here.
     previous_status:    Status,
+    /// The status of the last command substitution, which is the status of a `let` statement.
+    capture_status:     Option<Status>,
     /// The job ID of the previous command sent to the background.
     previous_job:       usize,
     /// Contains all the options relative to the shell
@@ -143,14 +262,57 @@ pub struct Shell<'a> {
     /// When the `fg` command is run, this will be used to communicate with the specified
     /// background process.
     foreground_signals: Arc<foreground::Signals>,
+    /// The coprocesses started with `coproc`, which are connected to the shell through pipes.
+    coprocs:            Vec<Coproc>,
+    /// The canonical paths of the script files being executed, from the outermost script to the
+    /// currently sourced file.
+    sources:            Vec<PathBuf>,
+    /// The canonical paths of every script file executed so far, which `source --once` skips.
+    executed_files:     HashSet<PathBuf>,
+    /// The value given to the last `return` statement, if it was not an exit status.
+    return_value:       Option<Value<Rc<Function>>>,
+    /// The functions memoized with `@cache`, with the output cached for each list of arguments.
+    memos:              HashMap<types::Str, Memo>,
+    /// The names of the functions being executed, from the outermost call to the current one, as
+    /// exposed in reverse by `@FUNCNAME`.
+    call_stack:         Vec<types::Str>,
+    /// Raised by the watchdog of `on_command_with_limit` once the time limit is exceeded, and
+    /// checked between statements like the pending signals.
+    cancelled:          Arc<AtomicBool>,
+    /// The status given to `exit` while an exit handler is set, which unwinds the execution.
+    exit_status:        Option<Status>,
+    /// The for loops over the generator functions being executed, which receive their values.
+    generators:         Vec<Generator>,
+    /// The temporary files and directories to remove on exit, with the process registering them.
+    temporary_paths:    Vec<(Pid, PathBuf)>,
+    /// The names of the functions registered with `hook` for each hook point.
+    hooks:              HashMap<Hook, Vec<types::Str>>,
+    /// The hooks whose functions are being called, which are not run again until they return.
+    running_hooks:      Vec<Hook>,
+    /// The generators of the completions for the arguments of each command.
+    completions:        HashMap<types::Str, CompletionGenerator<'a>>,
+    /// The file logging the executed commands, named by `$ION_DEBUG_COMMANDS`.
+    command_log:        Option<CommandLog>,
+    /// The calls of each command recorded since `start_profiling`, if the shell is profiling.
+    profile:            Option<HashMap<types::Str, CommandProfile>>,
+    /// The environment of the process once the shell was created, compared to by `env-diff`.
+    initial_env:        BTreeMap<OsString, OsString>,
+    /// The variables watched with `watch`, with the statement executed when they change.
+    watches:            Vec<Watch>,
+    /// Whether the statement of a watch is being executed, which does not trigger the watches.
+    running_watches:    bool,
 
     // Callbacks
     /// Custom callback for each command call
     on_command:       Option<OnCommandCallback<'a>>,
     /// Custom callback before each command call
     pre_command:      Option<PreCommandCallback<'a>>,
+    /// Custom callback before each statement, such as the debugger of `ion --step`
+    pre_statement:    Option<PreStatementCallback<'a>>,
     /// Custom callback when a background event occurs
     background_event: Option<BackgroundEventCallback>,
+    /// Custom callback when the shell exits, which prevents the process from exiting
+    exit_handler:     Option<ExitCallback<'a>>,
 
     // Default std pipes
     stdin:  Option<File>,
@@ -162,8 +324,13 @@ pub struct Shell<'a> {
 pub type OnCommandCallback<'a> = Box<dyn Fn(&Shell<'_>, std::time::Duration) + 'a>;
 /// A callback that is executed before each pipeline is run
 pub type PreCommandCallback<'a> = Box<dyn Fn(&Shell<'_>, &Pipeline<RefinedJob<'_>>) + 'a>;
+/// A callback that is executed before each statement is executed, including the statements of
+/// the blocks and of the functions. The statement is not executed if the callback exits the shell.
+pub type PreStatementCallback<'a> = Box<dyn Fn(&mut Shell<'_>, &Statement) + 'a>;
 /// A callback that is executed when a background event occurs
 pub type BackgroundEventCallback = Arc<dyn Fn(usize, Pid, BackgroundEvent) + Send + Sync>;
+/// A callback that is executed when the shell exits, instead of exiting the process
+pub type ExitCallback<'a> = Box<dyn Fn(&Shell<'_>, Status) + 'a>;
 
 impl<'a> Default for Shell<'a> {
     #[must_use]
@@ -208,16 +375,38 @@ impl<'a> Shell<'a> {
         Shell {
             builtins,
             variables: Variables::default(),
+            // Taken after the variables are created, which export some of them
+            initial_env: env::vars_os().collect(),
             flow_control: Block::with_capacity(5),
             directory_stack: DirectoryStack::new(),
             previous_job: !0,
             previous_status: Status::SUCCESS,
+            capture_status: None,
             opts: Options::default(),
             background: Arc::new(Mutex::new(Vec::new())),
             foreground_signals: Arc::new(foreground::Signals::new()),
+            coprocs: Vec::new(),
+            sources: Vec::new(),
+            executed_files: HashSet::new(),
+            return_value: None,
+            memos: HashMap::new(),
+            call_stack: Vec::new(),
+            cancelled: Arc::new(AtomicBool::new(false)),
+            exit_status: None,
+            generators: Vec::new(),
+            temporary_paths: Vec::new(),
+            hooks: HashMap::new(),
+            running_hooks: Vec::new(),
+            completions: HashMap::new(),
+            command_log: None,
+            profile: None,
+            watches: Vec::new(),
+            running_watches: false,
             on_command: None,
             pre_command: None,
+            pre_statement: None,
             background_event: None,
+            exit_handler: None,
 
             stdin: None,
             stdout: None,
@@ -240,6 +429,36 @@ impl<'a> Shell<'a> {
         mem::replace(&mut self.stderr, stderr.into())
     }
 
+    /// Flush the output written so far by the commands, so that it is not interleaved with the
+    /// output the shell writes itself, such as the report of the `time` statement.
+    pub fn flush_output(&mut self) -> io::Result<()> {
+        io::stdout().flush()?;
+        io::stderr().flush()?;
+        if let Some(ref mut stdout) = self.stdout {
+            stdout.flush()?;
+        }
+        if let Some(ref mut stderr) = self.stderr {
+            stderr.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Write a line of the shell's own diagnostics to the configured stderr, after the output of
+    /// the previous commands.
+    pub(crate) fn write_error(&mut self, line: std::fmt::Arguments<'_>) -> io::Result<()> {
+        self.flush_output()?;
+        match self.stderr {
+            Some(ref mut stderr) => stderr.write_fmt(line)?,
+            None => {
+                let stderr = io::stderr();
+                let mut stderr = stderr.lock();
+                stderr.write_fmt(line)?;
+                stderr.flush()?;
+            }
+        }
+        Ok(())
+    }
+
     /// Access the directory stack
     #[must_use]
     pub const fn dir_stack(&self) -> &DirectoryStack { &self.directory_stack }
@@ -260,14 +479,28 @@ impl<'a> Shell<'a> {
     #[must_use]
     pub fn block_len(&self) -> usize { self.flow_control.len() }
 
-    /// A method for executing a function, using `args` as the input.
+    /// Get the subshell nesting level, as exported in `$ION_SUBSHELL`. The top-level shell is at
+    /// level 1.
+    #[must_use]
+    pub fn subshell_level(&self) -> usize {
+        env::var("ION_SUBSHELL").ok().and_then(|level| level.parse().ok()).unwrap_or(1)
+    }
+
+    /// A method for executing a function, using `args` as the input. The output of memoized
+    /// functions is replayed from their cache when they were already called with these arguments.
     pub fn execute_function<S: AsRef<str>>(
         &mut self,
         function: &Rc<Function>,
         args: &[S],
     ) -> Result<Status, IonError> {
-        function.clone().execute(self, args)?;
-        Ok(self.previous_status)
+        let status = if self.is_memoized(args[0].as_ref()) {
+            self.execute_memoized(function, args)
+        } else {
+            function.clone().execute(self, args).map(|_| self.previous_status)
+        };
+        // The value returned by the function is only kept for `let result = call myfn args`
+        self.return_value = None;
+        status
     }
 
     /// A method for executing commands in the Ion shell without capturing. It takes command(s)
@@ -288,6 +521,160 @@ impl<'a> Shell<'a> {
         }
     }
 
+    /// Executes commands like `execute_command`, but stops once they have run for longer than
+    /// `limit`, returning `IonError::TimedOut`. This allows running untrusted scripts with a
+    /// wall-clock budget.
+    ///
+    /// The cancellation is cooperative: a watchdog thread raises a flag once the limit is
+    /// exceeded, which is checked between statements, where pending signals are handled. The
+    /// statements executed before the deadline keep their side effects, such as the variables
+    /// they assigned or the files they wrote, and a command which is already running, such as an
+    /// external process, is waited for before the execution stops.
+    pub fn on_command_with_limit<T: std::io::Read>(
+        &mut self,
+        command: T,
+        limit: Duration,
+    ) -> Result<Status, IonError> {
+        let (done, finished) = mpsc::channel::<()>();
+        let cancelled = self.cancelled.clone();
+        let watchdog = thread::spawn(move || {
+            if finished.recv_timeout(limit) == Err(RecvTimeoutError::Timeout) {
+                cancelled.store(true, Ordering::SeqCst);
+            }
+        });
+
+        let result = self.execute_command(command);
+        drop(done);
+        let _ = watchdog.join();
+        // The watchdog may have fired after the last statement was executed
+        self.cancelled.store(false, Ordering::SeqCst);
+
+        match result {
+            Err(IonError::PipelineExecutionError(PipelineError::Cancelled)) => {
+                self.reset_flow();
+                self.previous_status = Status::TERMINATED;
+                Err(IonError::TimedOut(limit))
+            }
+            result => result,
+        }
+    }
+
+    /// Executes a script in an isolated scope, then promotes the variables and functions it
+    /// defined under a namespace, so that plugins can not collide with each other or with the
+    /// caller.
+    ///
+    /// The script runs in a new namespace scope: it can read the variables of the caller, but any
+    /// variable or function it defines or assigns stays in that scope. Once the script completes,
+    /// each of them is promoted to the current scope as `NAMESPACE_NAME`, keeping its attributes
+    /// and replacing any existing definition of that prefixed name, so that sourcing a plugin
+    /// again updates it. The unprefixed names of the caller are never modified, and functions
+    /// must call each other by their prefixed name once promoted. If the script fails, nothing is
+    /// promoted and the error is returned.
+    pub fn source_string_in_scope<T: AsRef<str>>(
+        &mut self,
+        namespace: &str,
+        script: T,
+    ) -> Result<Status, IonError> {
+        if !Variables::is_valid_name(namespace) {
+            return Err(IonError::InvalidNamespace(namespace.into()));
+        }
+
+        self.variables.new_scope(true);
+        let result = self.on_command(script.as_ref().bytes(), false).and_then(|_| {
+            match self.flow_control.last().map(Statement::to_string) {
+                Some(block) => {
+                    self.flow_control.clear();
+                    Err(IonError::StatementFlowError(BlockError::UnclosedBlock(block)))
+                }
+                None => Ok(self.previous_status),
+            }
+        });
+        let definitions = self.variables.pop_scope_variables();
+
+        if result.is_ok() {
+            // Internal variables such as `?` are not valid names, and are not promoted
+            for (name, value, attributes) in definitions {
+                if Variables::is_valid_name(&name) {
+                    let name = format!("{}_{}", namespace, name);
+                    self.variables.set(&name, value);
+                    self.variables.set_attributes(&name, attributes);
+                }
+            }
+        }
+        result
+    }
+
+    /// Executes the commands read from a script file, the same way as `execute_command`. While it
+    /// runs, the canonical path of the file is available in `$ION_SOURCE`, and in `$ION_SCRIPT` if
+    /// this is the outermost script.
+    pub fn execute_file<P: AsRef<Path>, T: std::io::Read>(
+        &mut self,
+        path: P,
+        script: T,
+    ) -> Result<Status, IonError> {
+        self.push_source(path.as_ref());
+        let result = self.execute_command(script);
+        self.sources.pop();
+        result
+    }
+
+    /// Records that the script file at `path` is being executed, until it is popped from the
+    /// sources once it completes
+    fn push_source(&mut self, path: &Path) {
+        let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_owned());
+        self.executed_files.insert(path.clone());
+        self.sources.push(path);
+    }
+
+    /// Whether the script file at `path` was already executed with `execute_file` or
+    /// `execute_cached_file`, including through the `source` builtin. The paths are compared once
+    /// canonicalized, so that a file reached through a symbolic link or a relative path is still
+    /// recognized.
+    #[must_use]
+    pub fn has_executed_file<P: AsRef<Path>>(&self, path: P) -> bool {
+        fs::canonicalize(path).map_or(false, |path| self.executed_files.contains(&path))
+    }
+
+    /// The default run-commands file: `ion/initrc` in the XDG configuration directories, such as
+    /// `~/.config/ion/initrc`, or `None` if there is no such file.
+    #[must_use]
+    pub fn default_rc_path() -> Option<PathBuf> {
+        xdg::BaseDirectories::with_prefix("ion").ok()?.find_config_file("initrc")
+    }
+
+    /// Sources a run-commands file, such as the one from `default_rc_path`, so that a REPL host
+    /// can initialize the shell before reading any input. Unlike a script, a missing file is
+    /// silently skipped, returning `None`, and errors are not fatal: they are printed, any
+    /// unclosed block is discarded, and the failed status is returned so the host may carry on.
+    pub fn load_rc<P: AsRef<Path>>(&mut self, path: P) -> Option<Status> {
+        let path = path.as_ref();
+        let result = match self.execute_cached_file(path) {
+            Some(result) => result,
+            None => self.execute_file(path, File::open(path).ok()?),
+        };
+        match result {
+            Ok(status) => Some(status),
+            Err(why) => {
+                self.reset_flow();
+                self.previous_status = Status::error(format!("ion: {}: {}", path.display(), why));
+                Some(self.previous_status)
+            }
+        }
+    }
+
+    /// The name exposed as `$0`: the current function, the script being executed as it was given
+    /// in `@args[0]`, or `ion` when no script is executed.
+    fn script_name(&self) -> types::Str {
+        if let Some(function) = self.call_stack.last() {
+            return function.clone();
+        }
+        match (self.variables.get("args"), self.sources.first()) {
+            (Some(Value::Array(args)), Some(_)) if !args.is_empty() => args[0].to_string().into(),
+            (_, Some(script)) => script.to_string_lossy().as_ref().into(),
+            (_, None) => "ion".into(),
+        }
+    }
+
     /// Executes a pipeline and returns the final exit status of the pipeline.
     pub fn run_pipeline(&mut self, pipeline: &Pipeline<Job>) -> Result<Status, IonError> {
         let command_start_time = SystemTime::now();
@@ -317,6 +704,14 @@ impl<'a> Shell<'a> {
         if let Some(ref callback) = self.pre_command {
             callback(self, &pipeline);
         }
+        self.update_command_log();
+        let logged = if self.logs_commands() { Some(pipeline.to_string()) } else { None };
+
+        let function_call = pipeline.items.len() == 1
+            && matches!(
+                self.variables.get(&pipeline.items[0].job.args[0]),
+                Some(Value::Function(_))
+            );
 
         // Don't execute commands when the `-n` flag is passed.
         let exit_status = if self.opts.no_exec {
@@ -332,24 +727,60 @@ impl<'a> Shell<'a> {
         } else if let Some(Value::Function(function)) =
             self.variables.get(&pipeline.items[0].job.args[0]).cloned()
         {
-            function.execute(self, &pipeline.items[0].job.args).map(|_| self.previous_status)
+            self.execute_function(&function, &pipeline.items[0].job.args)
         } else {
             self.execute_pipeline(pipeline).map_err(Into::into)
-        }?;
+        };
+
+        let exit_status = match exit_status {
+            Err(IonError::PipelineExecutionError(PipelineError::CommandNotFound(command))) => {
+                self.command_not_found(command)?
+            }
+            exit_status => exit_status?,
+        };
 
         if let Some(ref callback) = self.on_command {
             if let Ok(elapsed_time) = command_start_time.elapsed() {
                 callback(self, elapsed_time);
             }
         }
+        if let Some(command) = logged {
+            self.log_command(&command, exit_status);
+        }
 
-        if self.opts.err_exit && !exit_status.is_success() {
+        if self.opts.err_exit
+            && !exit_status.is_success()
+            && (self.opts.err_trace || !function_call)
+        {
             return Err(PipelineError::EarlyExit(exit_status).into());
         }
 
         Ok(exit_status)
     }
 
+    /// Give the `command_not_found_handler` function, if defined, a chance to handle a missing
+    /// command. Its status is used in place of the command's, unless the `not_found_exit` option
+    /// is set, in which case the command not found error is returned regardless.
+    fn command_not_found(&mut self, command: types::Str) -> Result<Status, IonError> {
+        let handler = match self.variables.get("command_not_found_handler") {
+            Some(Value::Function(function)) => Some(function.clone()),
+            _ => None,
+        };
+
+        let status = match handler {
+            Some(function) => {
+                self.execute_function(&function, &["command_not_found_handler", &command])?
+            }
+            None => return Err(PipelineError::CommandNotFound(command).into()),
+        };
+
+        if self.opts.not_found_exit {
+            Err(PipelineError::CommandNotFound(command).into())
+        } else {
+            Ok(status)
+        }
+    }
+
     /// Get the pid of the last executed job
     #[must_use]
     pub const fn previous_job(&self) -> Option<usize> {
@@ -382,6 +813,12 @@ impl<'a> Shell<'a> {
         &mut self.pre_command
     }
 
+    /// Set the callback to call before each statement. The blocks, such as loops, are statements
+    /// of their own, executed before the statements they contain.
+    pub fn set_pre_statement(&mut self, callback: Option<PreStatementCallback<'a>>) {
+        self.pre_statement = callback;
+    }
+
     /// Set the callback to call on each command
     pub fn set_on_command(&mut self, callback: Option<OnCommandCallback<'a>>) {
         self.on_command = callback;
@@ -390,6 +827,55 @@ impl<'a> Shell<'a> {
     /// Set the callback to call on each command
     pub fn on_command_mut(&mut self) -> &mut Option<OnCommandCallback<'a>> { &mut self.on_command }
 
+    /// Set the callback to call when the shell exits. While it is set, `exit` no longer exits
+    /// the process, but unwinds the execution back to the caller of the shell.
+    pub fn set_exit_handler(&mut self, callback: Option<ExitCallback<'a>>) {
+        self.exit_handler = callback;
+    }
+
+    /// Exits the shell with `status`, as the `exit` builtin does. By default, the background jobs
+    /// are terminated and the process exits. When an exit handler is set, it is called with the
+    /// status instead, and the execution unwinds: the statements being executed stop, returning
+    /// `IonError::Exit` to the caller of the shell, such as `execute_command`. Any command given
+    /// to the shell afterwards returns the same error, until `take_exit_status` is called.
+    pub fn exit(&mut self, status: Status) -> Status {
+        self.run_exit_hook(status);
+        if let Some(ref handler) = self.exit_handler {
+            handler(self, status);
+            self.exit_status = Some(status);
+            self.previous_status = status;
+            status
+        } else {
+            let _ = self.background_send(signal::Signal::SIGTERM);
+            self.remove_temporary_paths();
+            std::process::exit(status.as_os_code())
+        }
+    }
+
+    /// Registers a file or directory to be removed, with its contents, when the shell exits
+    pub fn remove_on_exit(&mut self, path: PathBuf) {
+        self.temporary_paths.push((Pid::this(), path));
+    }
+
+    /// Removes the files and directories registered with `remove_on_exit`. This is done by the
+    /// `exit` builtin without an exit handler, and must be done by the embedders of the shell
+    /// before they exit otherwise. Only the paths registered by the current process are removed,
+    /// so that a forked child never removes those of its parent.
+    pub fn remove_temporary_paths(&mut self) {
+        let pid = Pid::this();
+        for (_, path) in self.temporary_paths.drain(..).filter(|(owner, _)| *owner == pid) {
+            match fs::symlink_metadata(&path) {
+                Ok(metadata) if metadata.is_dir() => drop(fs::remove_dir_all(&path)),
+                Ok(_) => drop(fs::remove_file(&path)),
+                Err(_) => (),
+            }
+        }
+    }
+
+    /// Get the status the shell exited with through its exit handler, if it did, allowing
+    /// commands to be executed again.
+    pub fn take_exit_status(&mut self) -> Option<Status> { self.exit_status.take() }
+
     /// Get access to the builtins
     #[must_use]
     pub const fn builtins(&self) -> &BuiltinMap<'a> { &self.builtins }
@@ -414,10 +900,49 @@ impl<'a> Shell<'a> {
     #[must_use]
     pub const fn variables(&self) -> &Variables { &self.variables }
 
+    /// The names of the functions being executed, from the outermost to the innermost
+    #[must_use]
+    pub fn call_stack(&self) -> &[types::Str] { &self.call_stack }
+
+    /// The environment of the process when the shell was created, which `env-diff` compares to
+    /// the current one
+    #[must_use]
+    pub fn initial_environment(&self) -> &BTreeMap<OsString, OsString> { &self.initial_env }
+
     /// Mutable access to the variables
     #[must_use]
     pub fn variables_mut(&mut self) -> &mut Variables { &mut self.variables }
 
+    /// Take a snapshot of the variables, to roll back the changes made by some commands with
+    /// `restore_variables`. The snapshot holds the shell variables, functions and aliases of
+    /// every scope, with their attributes. It does not hold the state outside of the variables:
+    /// the environment of the process, as modified by `export`, the current and the stacked
+    /// directories, the shell options and the results of memoized functions are left as they are
+    /// by a restore.
+    #[must_use]
+    pub fn variables_snapshot(&self) -> Snapshot { self.variables.snapshot() }
+
+    /// Restore the variables to a snapshot taken by `variables_snapshot`. The scopes are restored
+    /// as well, so the snapshot should be restored at the same depth as it was taken, such as
+    /// between two top-level commands.
+    pub fn restore_variables(&mut self, snapshot: Snapshot) { self.variables.restore(snapshot) }
+
+    /// Execute `f` in a new scope, which is exited once `f` returns, so that the variables it
+    /// defines, with `variables_mut` or by executing commands, do not outlive it. The variables
+    /// of the outer scopes remain visible and modifiable, as in a function.
+    ///
+    /// The shell is brought back to the scopes it had before the call, even if a command
+    /// executed by `f` left some scopes entered, such as a function interrupted by an error.
+    /// This also holds when `f` panics: the scopes are exited before the panic resumes, so that
+    /// an embedder catching the panic finds the variables as they were before the call.
+    pub fn with_scope<R>(&mut self, f: impl FnOnce(&mut Self) -> R) -> R {
+        let depth = self.variables.scope_depth();
+        self.variables.new_scope(false);
+        let result = panic::catch_unwind(AssertUnwindSafe(|| f(self)));
+        self.variables.pop_scopes(depth - 1).for_each(drop);
+        result.unwrap_or_else(|cause| panic::resume_unwind(cause))
+    }
+
     /// Access to the variables
     #[must_use]
     pub fn background_jobs(&self) -> impl Deref<Target = Vec<BackgroundProcess>> + '_ {
@@ -491,6 +1016,9 @@ impl<'a> Shell<'a> {
             | (_, Value::Array(_))
             | (Primitive::HashMap(_), Value::HashMap(_))
             | (Primitive::BTreeMap(_), Value::BTreeMap(_)) => {
+                if self.variables.attributes(key.name).exported {
+                    env::set_var(key.name, value.to_string());
+                }
                 self.variables.set(key.name, value);
                 Ok(())
             }
@@ -498,3 +1026,185 @@ impl<'a> Shell<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn source_string_in_scope_promotes_definitions() {
+        let mut shell = Shell::default();
+        shell.variables_mut().set("name", "caller");
+
+        let script = "let name = plugin\nfn greet\n    echo hello\nend\n";
+        assert_eq!(shell.source_string_in_scope("plugin", script).unwrap(), Status::SUCCESS);
+
+        let variables = shell.variables();
+        assert_eq!(variables.get_str("name").unwrap(), types::Str::from("caller"));
+        assert_eq!(variables.get_str("plugin_name").unwrap(), types::Str::from("plugin"));
+        assert!(variables.get("plugin_?").is_none());
+        assert!(shell.get_func("plugin_greet").is_some());
+        assert!(shell.get_func("greet").is_none());
+    }
+
+    #[test]
+    fn variables_snapshot_rolls_back() {
+        let mut shell = Shell::default();
+        shell.variables_mut().set("kept", "before");
+        let snapshot = shell.variables_snapshot();
+
+        let script = "let kept = after\nlet added = 1\nfn greet\n    echo hello\nend\n";
+        shell.execute_command(script.as_bytes()).unwrap();
+        assert!(shell.get_func("greet").is_some());
+
+        shell.restore_variables(snapshot);
+        assert_eq!(shell.variables().get_str("kept").unwrap(), types::Str::from("before"));
+        assert!(shell.variables().get("added").is_none());
+        assert!(shell.get_func("greet").is_none());
+    }
+
+    #[test]
+    fn source_string_in_scope_failures() {
+        let mut shell = Shell::default();
+        assert!(shell.source_string_in_scope("not valid", "let a = 1").is_err());
+        assert!(shell.source_string_in_scope("plugin", "let a = 1\nif true\n").is_err());
+        assert!(shell.variables().get("plugin_a").is_none());
+    }
+
+    #[test]
+    fn on_command_with_limit_times_out() {
+        let mut shell = Shell::default();
+        let limit = Duration::from_millis(100);
+        let script = "let count = 0\nwhile true\n    let count += 1\nend\n";
+        assert!(matches!(
+            shell.on_command_with_limit(script.as_bytes(), limit),
+            Err(IonError::TimedOut(_))
+        ));
+        // The statements executed before the deadline keep their side effects
+        assert_ne!(shell.variables().get_str("count").unwrap(), types::Str::from("0"));
+        assert_eq!(shell.previous_status(), Status::TERMINATED);
+
+        let status = shell.on_command_with_limit("let done = 1".as_bytes(), limit).unwrap();
+        assert_eq!(status, Status::SUCCESS);
+        assert_eq!(shell.variables().get_str("done").unwrap(), types::Str::from("1"));
+    }
+
+    #[test]
+    fn failed_commands_discard_open_blocks() {
+        let mut shell = Shell::default();
+        assert!(shell.execute_command("if true\n    echo )\n".as_bytes()).is_err());
+        assert_eq!(shell.block_len(), 0);
+
+        let script = "for i in 1..3\n    echo $missing\nend\nif true\n";
+        assert!(shell.execute_command(script.as_bytes()).is_err());
+        assert_eq!(shell.block_len(), 0);
+        assert_eq!(shell.execute_command("let next = 1".as_bytes()).unwrap(), Status::SUCCESS);
+        assert_eq!(shell.variables().get_str("next").unwrap(), types::Str::from("1"));
+
+        // A command which only opens a block leaves it for the next one to complete
+        shell.on_command("if true".bytes(), false).unwrap();
+        assert_eq!(shell.block_len(), 1);
+        shell.on_command("let inside = 1\nend".bytes(), false).unwrap();
+        assert_eq!(shell.block_len(), 0);
+        assert_eq!(shell.variables().get_str("inside").unwrap(), types::Str::from("1"));
+    }
+
+    #[test]
+    fn load_rc_tolerates_missing_and_failing_files() {
+        let mut shell = Shell::default();
+        // The path is removed with `temp`, and no file exists there until it is written
+        let temp = mktemp::Temp::new_path();
+        let path: &Path = temp.as_ref();
+        assert_eq!(shell.load_rc(path), None);
+
+        fs::write(path, "let loaded = 1\nif true\n").unwrap();
+        assert_eq!(shell.load_rc(path), Some(Status::from_exit_code(1)));
+        assert_eq!(shell.block_len(), 0);
+        assert_eq!(shell.variables().get_str("loaded").unwrap(), types::Str::from("1"));
+    }
+
+    #[test]
+    fn exit_handler_unwinds() {
+        fn exit(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
+            shell.exit(Status::from_exit_code(args[1].parse().unwrap()))
+        }
+
+        let exited = Rc::new(std::cell::Cell::new(None));
+        let mut shell = Shell::default();
+        // The library never exits the process by itself: `exit` is left to the application
+        assert!(shell.builtins().get("exit").is_none());
+        shell.builtins_mut().add("exit", &exit, "Exits the shell");
+        let handler_exited = exited.clone();
+        shell.set_exit_handler(Some(Box::new(move |_, status| handler_exited.set(Some(status)))));
+
+        let script = "fn quit\n    exit 3\nend\nlet before = 1\nquit\nlet after = 1\n";
+        assert!(matches!(
+            shell.execute_command(script.as_bytes()),
+            Err(IonError::Exit(status)) if status == Status::from_exit_code(3)
+        ));
+        assert_eq!(exited.get(), Some(Status::from_exit_code(3)));
+        assert!(shell.variables().get("before").is_some());
+        assert!(shell.variables().get("after").is_none());
+
+        assert_eq!(shell.take_exit_status(), Some(Status::from_exit_code(3)));
+        assert_eq!(shell.execute_command("let after = 1".as_bytes()).unwrap(), Status::SUCCESS);
+    }
+
+    #[test]
+    fn recovers_from_panics() {
+        fn boom(_: &[types::Str], _: &mut Shell<'_>) -> Status { panic!("boom") }
+
+        let mut shell = Shell::default();
+        shell.builtins_mut().add("boom", &boom, "Panics");
+        shell.opts_mut().recover_panics = true;
+
+        let script = "fn inner\n    let local = 1\n    boom\nend\ninner\nlet after = 1\n";
+        assert_eq!(shell.execute_command(script.as_bytes()).unwrap(), Status::SUCCESS);
+        assert!(shell.variables().get("after").is_some());
+        assert!(shell.variables().get("local").is_none());
+        assert!(shell.call_stack.is_empty());
+
+        assert_eq!(shell.execute_command("boom".as_bytes()).unwrap(), Status::from_exit_code(1));
+    }
+
+    #[test]
+    fn named_options() {
+        let mut options = Options::default();
+        for name in &Options::NAMES {
+            assert_eq!(options.get(name), Some(false));
+            assert!(options.set(name, true));
+            assert_eq!(options.get(name), Some(true));
+        }
+        assert!(options.err_exit && options.pipe_fail && options.json_output);
+        assert!(!options.no_exec);
+        assert_eq!(options.get("noexec"), None);
+        assert!(!options.set("noexec", true));
+    }
+
+    #[test]
+    fn scoped_variables() {
+        let mut shell = Shell::default();
+        shell.variables_mut().set("outer", "before");
+        let status = shell.with_scope(|shell| {
+            shell.variables_mut().set("inner", "defined");
+            // A scope left entered by the closure is exited as well
+            shell.variables_mut().new_scope(false);
+            shell.execute_command("let outer = after; let temporary = 1".as_bytes()).unwrap()
+        });
+        assert!(status.is_success());
+        assert_eq!(shell.variables().get_str("outer").unwrap(), types::Str::from("after"));
+        assert!(shell.variables().get_str("inner").is_err());
+        assert!(shell.variables().get_str("temporary").is_err());
+
+        let depth = shell.variables.scope_depth();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            shell.with_scope(|shell| {
+                shell.variables_mut().set("inner", "defined");
+                panic!("boom")
+            })
+        }));
+        assert!(result.is_err());
+        assert_eq!(shell.variables.scope_depth(), depth);
+        assert!(shell.variables().get_str("inner").is_err());
+    }
+}
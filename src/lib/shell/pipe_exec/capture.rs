@@ -0,0 +1,73 @@
+use super::{create_pipe, PipelineError};
+use crate::{
+    expansion::pipelines::{Capture, Redirection},
+    shell::{Shell, Value},
+    types,
+};
+use std::{
+    fs::File,
+    io::Read,
+    thread::{self, JoinHandle},
+};
+
+/// The output of a command being captured in a variable, as with `cmd >$var`
+pub struct CapturedOutput {
+    name:    types::Str,
+    capture: Capture,
+    append:  bool,
+    reader:  JoinHandle<Vec<u8>>,
+}
+
+impl CapturedOutput {
+    /// Starts capturing the output of a redirection, returning the file to write it to. The
+    /// output is read by a thread as it is written, so that the command never blocks on a full
+    /// pipe.
+    pub fn start(
+        redirection: &Redirection,
+        capture: Capture,
+        captures: &mut Vec<Self>,
+    ) -> Result<File, PipelineError> {
+        let (mut reader, writer) = create_pipe()?;
+        let reader = thread::spawn(move || {
+            let mut output = Vec::new();
+            let _ = reader.read_to_end(&mut output);
+            output
+        });
+        captures.push(Self {
+            name: redirection.file.clone(),
+            capture,
+            append: redirection.append,
+            reader,
+        });
+        Ok(writer)
+    }
+
+    /// Waits until every writer of the output is closed, then stores the output in its variable
+    pub fn store(self, shell: &mut Shell<'_>) {
+        let output = self.reader.join().unwrap_or_default();
+        let output = String::from_utf8_lossy(&output);
+        match self.capture {
+            Capture::String => {
+                let mut value = match shell.variables().get_str(&self.name) {
+                    Ok(value) if self.append => value,
+                    _ => types::Str::new(),
+                };
+                let output = output.trim_end_matches('\n');
+                // Appended outputs are kept on lines of their own, as they were printed
+                if !value.is_empty() && !value.ends_with('\n') && !output.is_empty() {
+                    value.push('\n');
+                }
+                value.push_str(output);
+                shell.variables_mut().set(&self.name, value);
+            }
+            Capture::Array => {
+                let mut lines = match shell.variables().get(&self.name) {
+                    Some(Value::Array(items)) if self.append => items.clone(),
+                    _ => Vec::new(),
+                };
+                lines.extend(output.lines().map(|line| Value::Str(line.into())));
+                shell.variables_mut().set(&self.name, lines);
+            }
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use super::job_control::ProcessState;
+use crate::{
+    builtins::Status,
+    expansion::pipelines::Pipeline,
+    shell::{pipe_exec::create_pipe, IonError, Job, Shell, Value},
+    types,
+};
+use std::{fs::File, os::unix::io::AsRawFd};
+
+/// A background job whose stdin and stdout are connected to the shell through pipes
+#[derive(Debug)]
+pub struct Coproc {
+    name:   types::Str,
+    /// Connected to the stdout of the coprocess
+    reader: File,
+    /// Connected to the stdin of the coprocess
+    writer: File,
+}
+
+impl Coproc {
+    /// The name under which the coprocess was registered
+    #[must_use]
+    pub fn name(&self) -> &str { &self.name }
+
+    /// The pipe connected to the stdout of the coprocess
+    #[must_use]
+    pub const fn reader(&self) -> &File { &self.reader }
+
+    /// The pipe connected to the stdin of the coprocess
+    #[must_use]
+    pub const fn writer(&self) -> &File { &self.writer }
+}
+
+impl<'a> Shell<'a> {
+    /// Spawns the pipeline in the background as a coprocess. The file descriptors to read its
+    /// output from and to write its input to are stored in the `name` array, and the pid of the
+    /// job in `name_PID`.
+    pub(crate) fn execute_coproc(
+        &mut self,
+        name: &str,
+        pipeline: &Pipeline<Job>,
+    ) -> Result<Status, IonError> {
+        let mut pipeline = pipeline.expand(self)?;
+
+        let (child_stdin, writer) = create_pipe()?;
+        let (reader, child_stdout) = create_pipe()?;
+        if let Some(first) = pipeline.items.first_mut() {
+            first.job.stdin = Some(child_stdin);
+        }
+        if let Some(last) = pipeline.items.last_mut() {
+            last.job.stdout = Some(child_stdout);
+        }
+
+        // The child ends of the pipes are dropped alongside the pipeline once forked
+        let status = self.fork_pipe(pipeline, ProcessState::Running);
+        if status.is_failure() {
+            return Ok(status);
+        }
+
+        let fds = vec![
+            Value::Str(reader.as_raw_fd().to_string().into()),
+            Value::Str(writer.as_raw_fd().to_string().into()),
+        ];
+        self.variables.set(name, Value::Array(fds));
+        let job = self.previous_job().and_then(|id| self.background_jobs().get(id).cloned());
+        if let Some(job) = job {
+            self.variables.set(&format!("{}_PID", name), job.pid().to_string());
+        }
+
+        // Replacing a coprocess of the same name closes the pipes to the previous one
+        self.coprocs.retain(|coproc| coproc.name() != name);
+        self.coprocs.push(Coproc { name: name.into(), reader, writer });
+        Ok(status)
+    }
+
+    /// Closes the pipes of all the coprocesses, signaling the end of their input.
+    pub fn close_coprocs(&mut self) { self.coprocs.clear(); }
+}
@@ -36,7 +36,7 @@ impl<'a> Shell<'a> {
 
                 // After execution of it's commands, exit with the last command's status.
                 let code = self
-                    .pipe(pipeline)
+                    .pipe(pipeline, &mut Vec::new())
                     .unwrap_or_else(|err| {
                         eprintln!("{}", err);
                         Status::COULD_NOT_EXEC
@@ -75,6 +75,10 @@ impl BackgroundProcess {
     #[must_use]
     pub const fn pid(&self) -> Pid { self.pid }
 
+    /// The command line of the job
+    #[must_use]
+    pub fn name(&self) -> &str { &self.name }
+
     /// Check if the process is still running
     #[must_use]
     pub fn is_running(&self) -> bool { self.state == ProcessState::Running }
@@ -5,14 +5,16 @@
 //! the background, handling pipeline and conditional operators, and
 //! std{in,out,err} redirections.
 
+mod capture;
+mod coproc;
 pub mod foreground;
 mod fork;
 pub mod job_control;
 mod pipes;
 pub mod streams;
 
-pub use self::pipes::create_pipe;
-use self::{job_control::ProcessState, pipes::TeePipe};
+use self::{capture::CapturedOutput, job_control::ProcessState, pipes::TeePipe};
+pub use self::{coproc::Coproc, pipes::create_pipe};
 use super::{
     job::{RefinedJob, TeeItem, Variant},
     signals::{self, SignalHandler},
@@ -23,15 +25,22 @@ use crate::{
     expansion::pipelines::{Input, PipeItem, PipeType, Pipeline, RedirectFrom, Redirection},
     types,
 };
+use mktemp::Temp;
 use nix::{
+    fcntl::{fcntl, FcntlArg},
+    libc::{STDERR_FILENO, STDIN_FILENO, STDOUT_FILENO},
     sys::signal::{self, Signal},
     unistd::{self, ForkResult, Pid},
 };
 use smallvec::SmallVec;
 use std::{
     fs::{File, OpenOptions},
-    io::{self, Write},
-    os::unix::process::CommandExt,
+    io::{self, Seek, SeekFrom, Write},
+    os::unix::{
+        io::{FromRawFd, RawFd},
+        process::CommandExt,
+    },
+    panic::{self, AssertUnwindSafe},
     process::{exit, Command, Stdio},
 };
 use thiserror::Error;
@@ -93,6 +102,9 @@ pub enum PipelineError {
     /// A signal interrupted a child process
     #[error("process ({0}) ended by signal {1}")]
     Interrupted(Pid, Signal),
+    /// The execution was cancelled because it exceeded its time limit
+    #[error("execution cancelled")]
+    Cancelled,
     /// A subprocess had a core dump
     #[error("process ({0}) had a core dump")]
     CoreDump(Pid),
@@ -128,30 +140,38 @@ impl From<RedirectError> for PipelineError {
 /// A file descriptor representing the read end of the pipe.
 pub fn stdin_of<T: AsRef<str>>(input: &T) -> Result<File, PipelineError> {
     let string = input.as_ref();
-    let (reader, mut writer) = create_pipe()?;
-    // Write the contents; make sure to use write_all so that we block until
-    // the entire string is written
-    writer
-        .write_all(string.as_bytes())
-        .map_err(|err| RedirectError::WriteError(string.into(), err))?;
-    if !string.ends_with('\n') {
-        writer.write(b"\n").map_err(|err| RedirectError::WriteError(string.into(), err))?;
+    if string.ends_with('\n') {
+        contents_of(string)
+    } else {
+        contents_of(&format!("{}\n", string))
     }
-    writer.flush().map_err(|err| RedirectError::WriteError(string.into(), err))?;
-    // `infile` currently owns the writer end RawFd. If we just return the reader
-    // end and let `infile` go out of scope, it will be closed, sending EOF to
-    // the reader!
-    Ok(reader)
+}
+
+/// Like `stdin_of`, but without terminating the contents with a newline.
+///
+/// The contents are written to a temporary file rather than to a pipe: nothing reads from the
+/// input before the command is started, so contents larger than the buffer of a pipe would block
+/// the shell forever.
+fn contents_of(string: &str) -> Result<File, PipelineError> {
+    let write_error = |err| RedirectError::WriteError(string.into(), err);
+    let temp = Temp::new_file().map_err(write_error)?;
+    let mut file = OpenOptions::new().read(true).write(true).open(&temp).map_err(write_error)?;
+    file.write_all(string.as_bytes()).map_err(write_error)?;
+    file.seek(SeekFrom::Start(0)).map_err(write_error)?;
+    // The file is removed when `temp` is dropped, but stays readable through its descriptor
+    Ok(file)
 }
 
 impl Input {
     pub(self) fn get_infile(&self) -> Result<File, PipelineError> {
         match self {
-            Self::File(ref filename) => match File::open(filename.as_str()) {
-                Ok(file) => Ok(file),
-                Err(why) => Err(RedirectError::File(filename.to_string(), why).into()),
+            Self::File(ref filename) => match standard_stream(filename) {
+                Some(fd) => duplicate_fd(fd),
+                None => File::open(filename.as_str())
+                    .map_err(|why| RedirectError::File(filename.to_string(), why).into()),
             },
             Self::HereString(ref string) => stdin_of(&string),
+            Self::Variable(ref contents) => contents_of(contents),
         }
     }
 }
@@ -164,14 +184,16 @@ fn need_tee(outs: &[Redirection], redirection: RedirectFrom) -> (bool, bool) {
         RedirectFrom::None => (0, 0),
     };
 
-    for &Redirection { from, .. } in outs {
+    for &Redirection { from, tee, .. } in outs {
+        // A tee redirection also passes the output along, as a second destination
+        let count = if tee { 2 } else { 1 };
         match from {
             RedirectFrom::Both => {
-                stdout_count += 1;
-                stderr_count += 1;
+                stdout_count += count;
+                stderr_count += count;
             }
-            RedirectFrom::Stdout => stdout_count += 1,
-            RedirectFrom::Stderr => stderr_count += 1,
+            RedirectFrom::Stdout => stdout_count += count,
+            RedirectFrom::Stderr => stderr_count += count,
             RedirectFrom::None => (),
         }
         if stdout_count > 1 && stderr_count > 1 {
@@ -184,52 +206,83 @@ fn need_tee(outs: &[Redirection], redirection: RedirectFrom) -> (bool, bool) {
 fn do_tee<'a>(
     outputs: &[Redirection],
     job: &mut RefinedJob<'a>,
+    captures: &mut Vec<CapturedOutput>,
     stdout: &mut dyn FnMut(&mut RefinedJob<'a>, File),
     stderr: &mut dyn FnMut(&mut RefinedJob<'a>, File),
-) -> Result<(), RedirectError> {
+) -> Result<(), PipelineError> {
     // XXX: Possibly add an assertion here for correctness
     for output in outputs {
-        match OpenOptions::new()
-            .create(true)
-            .write(true)
-            .append(output.append)
-            .truncate(!output.append)
-            .open(output.file.as_str())
-        {
-            Ok(file) => match output.from {
-                RedirectFrom::None => (),
-                RedirectFrom::Stdout => stdout(job, file),
-                RedirectFrom::Stderr => stderr(job, file),
-                RedirectFrom::Both => match file.try_clone() {
-                    Ok(f_copy) => {
-                        stdout(job, file);
-                        stderr(job, f_copy);
-                    }
-                    Err(why) => {
-                        return Err(RedirectError::Output {
-                            redirect: output.from,
-                            file: output.file.to_string(),
-                            why,
-                        });
-                    }
-                },
-            },
-            Err(why) => {
-                return Err(RedirectError::Output {
-                    redirect: output.from,
-                    file: output.file.to_string(),
-                    why,
-                });
+        let error = |why| RedirectError::Output {
+            redirect: output.from,
+            file: output.file.to_string(),
+            why,
+        };
+        let file = match (output.capture, standard_stream(&output.file)) {
+            (Some(capture), _) => CapturedOutput::start(output, capture, captures)?,
+            (None, Some(fd)) => duplicate_fd(fd)?,
+            (None, None) => OpenOptions::new()
+                .create(true)
+                .write(true)
+                .append(output.append)
+                .truncate(!output.append)
+                .open(output.file.as_str())
+                .map_err(error)?,
+        };
+        match output.from {
+            RedirectFrom::None => (),
+            RedirectFrom::Stdout => stdout(job, file),
+            RedirectFrom::Stderr => stderr(job, file),
+            RedirectFrom::Both => {
+                let f_copy = file.try_clone().map_err(error)?;
+                stdout(job, file);
+                stderr(job, f_copy);
             }
         }
     }
     Ok(())
 }
 
+/// The shell's own stdout or stderr, for the output of a tee redirection such as `>&$var` to be
+/// passed along to when the stream is not piped to the next command
+fn pass_through(
+    outputs: &[Redirection],
+    redirection: RedirectFrom,
+    stream: RedirectFrom,
+) -> Result<Option<File>, PipelineError> {
+    let piped = |from| from == stream || from == RedirectFrom::Both;
+    if piped(redirection) || !outputs.iter().any(|output| output.tee && piped(output.from)) {
+        return Ok(None);
+    }
+    match stream {
+        RedirectFrom::Stderr => duplicate_fd(STDERR_FILENO).map(Some),
+        _ => duplicate_fd(STDOUT_FILENO).map(Some),
+    }
+}
+
+/// The standard stream of the shell named by `/dev/stdin`, `/dev/stdout` or `/dev/stderr`. These
+/// paths are redirected to by duplicating the stream rather than opening a file, so that they can
+/// be used on the systems lacking them.
+fn standard_stream(path: &str) -> Option<RawFd> {
+    match path {
+        "/dev/stdin" => Some(STDIN_FILENO),
+        "/dev/stdout" => Some(STDOUT_FILENO),
+        "/dev/stderr" => Some(STDERR_FILENO),
+        _ => None,
+    }
+}
+
+/// Duplicates a file descriptor of the shell, to be given to a command
+fn duplicate_fd(fd: RawFd) -> Result<File, PipelineError> {
+    fcntl(fd, FcntlArg::F_DUPFD_CLOEXEC(0))
+        .map(|fd| unsafe { File::from_raw_fd(fd) })
+        .map_err(PipelineError::CloneFdFailed)
+}
+
 /// Insert the multiple redirects as pipelines if necessary. Handle both input and output
 /// redirection if necessary.
 fn prepare<'a>(
     pipeline: Pipeline<RefinedJob<'a>>,
+    captures: &mut Vec<CapturedOutput>,
 ) -> Result<impl IntoIterator<Item = RefinedJob<'a>>, PipelineError> {
     // Real logic begins here
     let mut new_commands =
@@ -254,13 +307,16 @@ fn prepare<'a>(
             match need_tee(&outputs, kind) {
                 // No tees
                 (false, false) => {
-                    do_tee(&outputs, &mut job, &mut RefinedJob::stdout, &mut RefinedJob::stderr)?;
+                    let (stdout, stderr) = (&mut RefinedJob::stdout, &mut RefinedJob::stderr);
+                    do_tee(&outputs, &mut job, captures, stdout, stderr)?;
                     new_commands.push(job);
                 }
                 // tee stderr
                 (false, true) => {
                     let mut tee = TeeItem::new();
-                    do_tee(&outputs, &mut job, &mut RefinedJob::stdout, &mut |_, f| tee.add(f))?;
+                    let stdout = &mut RefinedJob::stdout;
+                    do_tee(&outputs, &mut job, captures, stdout, &mut |_, f| tee.add(f))?;
+                    tee.sinks.extend(pass_through(&outputs, kind, RedirectFrom::Stderr)?);
                     let tee = RefinedJob::tee(None, Some(tee), job.redirection);
                     job.redirection = RedirectFrom::Stderr;
                     new_commands.push(job);
@@ -269,7 +325,9 @@ fn prepare<'a>(
                 // tee stdout
                 (true, false) => {
                     let mut tee = TeeItem::new();
-                    do_tee(&outputs, &mut job, &mut |_, f| tee.add(f), &mut RefinedJob::stderr)?;
+                    let stderr = &mut RefinedJob::stderr;
+                    do_tee(&outputs, &mut job, captures, &mut |_, f| tee.add(f), stderr)?;
+                    tee.sinks.extend(pass_through(&outputs, kind, RedirectFrom::Stdout)?);
                     let tee = RefinedJob::tee(Some(tee), None, job.redirection);
                     job.redirection = RedirectFrom::Stdout;
                     new_commands.push(job);
@@ -279,9 +337,15 @@ fn prepare<'a>(
                 (true, true) => {
                     let mut tee_out = TeeItem::new();
                     let mut tee_err = TeeItem::new();
-                    do_tee(&outputs, &mut job, &mut |_, f| tee_out.add(f), &mut |_, f| {
-                        tee_err.sinks.push(f)
-                    })?;
+                    do_tee(
+                        &outputs,
+                        &mut job,
+                        captures,
+                        &mut |_, f| tee_out.add(f),
+                        &mut |_, f| tee_err.sinks.push(f),
+                    )?;
+                    tee_out.sinks.extend(pass_through(&outputs, kind, RedirectFrom::Stdout)?);
+                    tee_err.sinks.extend(pass_through(&outputs, kind, RedirectFrom::Stderr)?);
                     let tee = RefinedJob::tee(Some(tee_out), Some(tee_err), job.redirection);
                     job.redirection = RedirectFrom::Stdout;
                     new_commands.push(job);
@@ -338,7 +402,7 @@ impl<'b> Shell<'b> {
 
     fn exec_function<S: AsRef<str>>(&mut self, name: &str, args: &[S]) -> Result<Status, IonError> {
         if let Some(Value::Function(function)) = self.variables.get(name).cloned() {
-            function.execute(self, args).map(|_| self.previous_status)
+            self.execute_function(&function, args)
         } else {
             unreachable!()
         }
@@ -353,13 +417,16 @@ impl<'b> Shell<'b> {
         let (stdin_bk, stdout_bk, stderr_bk) =
             streams::duplicate().map_err(PipelineError::CreatePipeError)?;
         streams::redirect(&job.stdin, &job.stdout, &job.stderr)?;
-        let code = match job.var {
+        let recover = self.opts.recover_panics;
+        let run = AssertUnwindSafe(|| match job.var {
             Variant::Builtin { main } => Ok(main(job.args(), self)),
             Variant::Function => self.exec_function(job.command(), job.args()),
             _ => panic!("exec job should not be able to be called on Cat or Tee jobs"),
-        };
+        });
+        // The standard streams of the shell are restored before a panic is recovered from
+        let code = if recover { panic::catch_unwind(run) } else { Ok(run()) };
         streams::redirect(&stdin_bk, &Some(stdout_bk), &Some(stderr_bk))?;
-        code
+        code.unwrap_or_else(|cause| panic::resume_unwind(cause))
     }
 
     /// Given a pipeline, generates commands and executes them.
@@ -391,11 +458,16 @@ impl<'b> Shell<'b> {
             PipeType::Background => Ok(self.fork_pipe(pipeline, ProcessState::Running)),
             // Execute each command in the pipeline, giving each command the foreground.
             PipeType::Normal => {
-                let exit_status = self.pipe(pipeline);
+                let mut captures = Vec::new();
+                let exit_status = self.pipe(pipeline, &mut captures);
                 // Set the shell as the foreground process again to regain the TTY.
                 if self.opts.grab_tty {
                     let _ = unistd::tcsetpgrp(0, Pid::this());
                 }
+                // The outputs captured in variables are complete once the pipeline is done
+                for capture in captures {
+                    capture.store(self);
+                }
                 exit_status
             }
         }
@@ -404,8 +476,12 @@ impl<'b> Shell<'b> {
     /// Executes a piped job `job1 | job2 | job3`
     ///
     /// This function will panic if called with an empty slice
-    fn pipe(&mut self, pipeline: Pipeline<RefinedJob<'b>>) -> Result<Status, IonError> {
-        let mut commands = prepare(pipeline)?.into_iter().peekable();
+    fn pipe(
+        &mut self,
+        pipeline: Pipeline<RefinedJob<'b>>,
+        captures: &mut Vec<CapturedOutput>,
+    ) -> Result<Status, IonError> {
+        let mut commands = prepare(pipeline, captures)?.into_iter().peekable();
 
         if let Some(mut parent) = commands.next() {
             if parent.redirection == RedirectFrom::None && !parent.needs_forking() {
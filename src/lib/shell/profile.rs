@@ -0,0 +1,88 @@
+//! The profiler of the commands executed by the shell, as used by `ion --profile`. Each pipeline
+//! is timed as a whole, and recorded under the names of its commands, so that the calls of a
+//! function are recorded under its name.
+
+use super::{flow_control::Statement, Shell};
+use crate::{expansion::pipelines::Pipeline, shell::Job, types};
+use itertools::Itertools;
+use std::{collections::HashMap, time::Duration};
+
+/// The calls of a command recorded by the profiler
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CommandProfile {
+    /// The number of times the command was executed
+    pub calls: u32,
+    /// The wall time spent executing the command. The time of a function includes the time of
+    /// the commands it executed, which are also recorded on their own.
+    pub total: Duration,
+}
+
+impl CommandProfile {
+    /// The average wall time of a call
+    #[must_use]
+    pub fn average(&self) -> Duration { self.total / self.calls.max(1) }
+}
+
+/// The name under which a pipeline is recorded: the name of its commands, such as `ls | grep`
+fn command_name(pipeline: &Pipeline<Job>) -> types::Str {
+    pipeline.items.iter().filter_map(|item| item.job.args.first()).join(" | ").into()
+}
+
+impl<'a> Shell<'a> {
+    /// Start recording the number of calls and the wall time of each command executed,
+    /// discarding those recorded before
+    pub fn start_profiling(&mut self) { self.profile = Some(HashMap::new()); }
+
+    /// Stop recording the commands, returning what was recorded like `profile`
+    pub fn stop_profiling(&mut self) -> Vec<(types::Str, CommandProfile)> {
+        let profile = self.profile.take().unwrap_or_default();
+        let mut profile = profile.into_iter().collect::<Vec<_>>();
+        profile.sort_by(|(a, first), (b, second)| second.total.cmp(&first.total).then(a.cmp(b)));
+        profile
+    }
+
+    /// The commands recorded since `start_profiling`, sorted by decreasing total time, or `None`
+    /// if the shell is not profiling
+    #[must_use]
+    pub fn profile(&self) -> Option<Vec<(&str, CommandProfile)>> {
+        let profile = self.profile.as_ref()?;
+        let mut profile =
+            profile.iter().map(|(name, calls)| (name.as_str(), *calls)).collect::<Vec<_>>();
+        profile.sort_by(|(a, first), (b, second)| second.total.cmp(&first.total).then(a.cmp(b)));
+        Some(profile)
+    }
+
+    /// Records a call of a command, if the shell is profiling
+    pub(super) fn record_call(&mut self, statement: &Statement, time: Duration) {
+        if let (Some(profile), Statement::Pipeline(pipeline)) = (&mut self.profile, statement) {
+            let calls = profile.entry(command_name(pipeline)).or_default();
+            calls.calls += 1;
+            calls.total += time;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profiled_commands() {
+        let mut shell = Shell::default();
+        assert!(shell.profile().is_none());
+        shell.start_profiling();
+        shell
+            .execute_command("fn nap\n    sleep 0.1\nend\nnap\ntrue | true\ntrue\n".as_bytes())
+            .unwrap();
+
+        let profile = shell.stop_profiling();
+        let names = profile.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>();
+        assert_eq!(&names[..2], ["nap", "sleep"]);
+        assert!(names.contains(&"true | true"));
+        let (_, nap) = profile[0];
+        assert_eq!(nap.calls, 1);
+        assert!(nap.total >= Duration::from_millis(100));
+        assert_eq!(nap.average(), nap.total);
+        assert!(shell.profile().is_none());
+    }
+}
@@ -0,0 +1,89 @@
+//! The rendering of prompt templates such as `$PROMPT`, with the escapes of the prompts of bash.
+
+use super::{IonError, Shell};
+use crate::{expansion::Expander, types};
+use nix::unistd::geteuid;
+use std::env;
+
+/// Escapes the characters of a value inserted in a template, so that it is printed as it is
+/// rather than expanded
+fn literal(value: &str) -> String {
+    let mut literal = String::with_capacity(value.len());
+    for character in value.chars() {
+        if !character.is_alphanumeric() && !"/._-:".contains(character) {
+            literal.push('\\');
+        }
+        literal.push(character);
+    }
+    literal
+}
+
+impl<'a> Shell<'a> {
+    /// Renders a prompt template, expanding it like `$PROMPT`. The escapes of the prompts of bash
+    /// are replaced first: `\u` by the user name, `\h` by the host name up to the first dot, `\w`
+    /// by the current directory with the home directory as `~`, `\$` by `#` for the root user and
+    /// by `$` otherwise, and `\\` by a backslash. The other escapes are left to the expansion.
+    ///
+    /// The status of the previous command, and so `$?`, is left untouched, even when the
+    /// template runs commands.
+    pub fn prompt_expand(&mut self, template: &str) -> Result<types::Str, IonError> {
+        let mut prompt = String::with_capacity(template.len());
+        let mut chars = template.chars();
+        while let Some(character) = chars.next() {
+            if character != '\\' {
+                prompt.push(character);
+                continue;
+            }
+            match chars.next() {
+                Some('u') => prompt.push_str(&literal(&env::var("USER").unwrap_or_default())),
+                Some('h') => {
+                    let host = env::var("HOST").unwrap_or_default();
+                    prompt.push_str(&literal(host.split('.').next().unwrap_or_default()));
+                }
+                Some('w') => prompt.push_str(&literal(&self.variables.get_str("SWD")?)),
+                Some('$') => prompt.push_str(if geteuid().is_root() { "#" } else { "\\$" }),
+                Some(other) => {
+                    prompt.push('\\');
+                    prompt.push(other);
+                }
+                None => prompt.push_str("\\\\"),
+            }
+        }
+
+        let (previous_status, capture_status) = (self.previous_status, self.capture_status);
+        let expanded = self.get_string(&prompt);
+        self.previous_status = previous_status;
+        self.capture_status = capture_status;
+        self.variables.set("?", previous_status);
+        Ok(expanded?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builtins::Status;
+
+    #[test]
+    fn prompt_escapes() {
+        let mut shell = Shell::default();
+        let user = env::var("USER").unwrap_or_default();
+        let swd = shell.variables().get_str("SWD").unwrap();
+        let root = if geteuid().is_root() { "#" } else { "$" };
+        assert_eq!(
+            shell.prompt_expand("\\u:\\w\\$ \\\\ $((1 + 1))").unwrap().as_str(),
+            format!("{}:{}{} \\ 2", user, swd, root)
+        );
+    }
+
+    #[test]
+    fn prompt_keeps_status() {
+        let mut shell = Shell::default();
+        shell.set_previous_status(Status::from_exit_code(3));
+        shell.variables_mut().set("?", Status::from_exit_code(3));
+        assert_eq!(shell.prompt_expand("status $?").unwrap().as_str(), "status 3");
+        assert_eq!(shell.prompt_expand("$(false)").unwrap().as_str(), "");
+        assert_eq!(shell.previous_status(), Status::from_exit_code(3));
+        assert_eq!(shell.variables().get_str("?").unwrap().as_str(), "3");
+    }
+}
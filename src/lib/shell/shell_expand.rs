@@ -8,44 +8,87 @@ use crate::{
 use nix::unistd::{tcsetpgrp, Pid};
 #[cfg(target_os = "redox")]
 use redox_users::All;
-use std::{env, fs::File, io::Read};
+use std::{
+    env,
+    fs::{File, OpenOptions},
+    io::Read,
+    iter,
+};
 #[cfg(not(target_os = "redox"))]
 use users::os::unix::UserExt;
 
-impl<'a, 'b> Expander for Shell<'b> {
-    type Error = IonError;
+impl<'b> Shell<'b> {
+    /// Runs `f` with the standard output of the shell, or its standard error if `stderr` is set,
+    /// redirected to a pipe, returning its result along with the end of the pipe to read what it
+    /// wrote from.
+    pub(super) fn capture_stream<T>(
+        &mut self,
+        stderr: bool,
+        f: impl FnOnce(&mut Self) -> T,
+    ) -> std::result::Result<(T, File), PipelineError> {
+        let (reader, writer) = create_pipe()?;
 
-    /// Uses a subshell to expand a given command.
-    fn command(
+        let previous = if stderr { self.stderr(writer) } else { self.stdout(writer) };
+        let result = f(self);
+        // Dropping the writer closes the pipe, so that the reader ends with what was written
+        if stderr {
+            self.stderr(previous);
+        } else {
+            self.stdout(previous);
+        }
+
+        // Ensure that the parent retains ownership of the terminal before exiting.
+        let _ = tcsetpgrp(nix::libc::STDIN_FILENO, Pid::this());
+
+        Ok((result, reader))
+    }
+
+    /// Executes the command in a subshell and reads what it writes to its standard output, or to
+    /// its standard error if `stderr` is set. The other stream is discarded, and the status of the
+    /// command is kept as the previous status and as the status of the substitution.
+    fn capture(
         &mut self,
         command: &str,
         set_cmd_duration: bool,
-    ) -> Result<types::Str, Self::Error> {
-        let (mut reader, writer) = create_pipe()
-            .map_err(|err| Error::Subprocess(Box::new(IonError::PipelineExecutionError(err))))?;
-        let null_file = File::open(NULL_PATH).map_err(|err| {
+        stderr: bool,
+    ) -> Result<types::Str, IonError> {
+        let null_file = OpenOptions::new().write(true).open(NULL_PATH).map_err(|err| {
             Error::Subprocess(Box::new(IonError::PipelineExecutionError(
                 PipelineError::CaptureFailed(err),
             )))
         })?;
 
-        // Store the previous default redirections
-        let prev_stdout = self.stdout(writer);
-        let prev_stderr = self.stderr(null_file);
+        let (result, mut reader) = self
+            .capture_stream(stderr, |shell| {
+                let previous =
+                    if stderr { shell.stdout(null_file) } else { shell.stderr(null_file) };
 
-        // Execute the command
-        let result = self
-            .on_command(command.bytes(), set_cmd_duration)
-            .map_err(|err| Error::Subprocess(Box::new(err)));
+                // Enter the subshell, exporting the new nesting level for child processes
+                let level = env::var_os("ION_SUBSHELL");
+                env::set_var("ION_SUBSHELL", (shell.subshell_level() + 1).to_string());
 
-        // Reset the pipes, droping the stdout
-        self.stdout(prev_stdout);
-        self.stderr(prev_stderr);
+                // Execute the command
+                let result = shell
+                    .on_command(command.bytes(), set_cmd_duration)
+                    .map_err(|err| Error::Subprocess(Box::new(err)));
 
-        // Ensure that the parent retains ownership of the terminal before exiting.
-        let _ = tcsetpgrp(nix::libc::STDIN_FILENO, Pid::this());
+                // Leave the subshell, restoring the environment as it was, and reset the
+                // discarded stream
+                match level {
+                    Some(level) => env::set_var("ION_SUBSHELL", level),
+                    None => env::remove_var("ION_SUBSHELL"),
+                }
+                if stderr {
+                    shell.stdout(previous);
+                } else {
+                    shell.stderr(previous);
+                }
+                result
+            })
+            .map_err(|err| Error::Subprocess(Box::new(IonError::PipelineExecutionError(err))))?;
 
         result?;
+        self.capture_status = Some(self.previous_status);
 
         let mut string = String::with_capacity(1024);
         match reader.read_to_string(&mut string) {
@@ -53,13 +96,53 @@ impl<'a, 'b> Expander for Shell<'b> {
             Err(why) => Err(Error::Subprocess(Box::new(PipelineError::CaptureFailed(why).into()))),
         }
     }
+}
+
+impl<'a, 'b> Expander for Shell<'b> {
+    type Error = IonError;
+
+    /// Uses a subshell to expand a given command.
+    fn command(
+        &mut self,
+        command: &str,
+        set_cmd_duration: bool,
+    ) -> Result<types::Str, Self::Error> {
+        self.capture(command, set_cmd_duration, false)
+    }
+
+    /// Uses a subshell to expand the standard error of a given command, discarding its output.
+    fn command_stderr(&mut self, command: &str) -> Result<types::Str, Self::Error> {
+        self.capture(command, true, true)
+    }
+
+    /// A condition made of a single expansion is true if it expands to a non-empty value.
+    /// Otherwise, it is executed as a command, like the condition of an if statement.
+    fn condition(&mut self, condition: &str) -> Result<bool, Self::Error> {
+        if (condition.starts_with('$') || condition.starts_with('@'))
+            && !condition.contains(char::is_whitespace)
+        {
+            return Ok(self.expand_string(condition)?.iter().any(|word| !word.is_empty()));
+        }
+        self.command(condition, false)?;
+        Ok(self.previous_status.is_success())
+    }
 
     /// Expand a string variable given if its quoted / unquoted
     fn string(&self, name: &str) -> Result<types::Str, Self::Error> {
-        if name == "?" {
-            Ok(self.previous_status.into())
-        } else {
-            self.variables().get_str(name).map_err(Into::into)
+        match name {
+            "?" => Ok(self.previous_status.into()),
+            // A variable named `status`, such as a function parameter, takes precedence
+            "status" if self.variables.get(name).is_none() => Ok(self.previous_status.into()),
+            "ION_SCRIPT" if !self.sources.is_empty() => {
+                Ok(self.sources[0].to_string_lossy().as_ref().into())
+            }
+            "ION_SOURCE" if !self.sources.is_empty() => {
+                Ok(self.sources[self.sources.len() - 1].to_string_lossy().as_ref().into())
+            }
+            "FUNCNAME" => Ok(self.call_stack.last().cloned().unwrap_or_else(|| "main".into())),
+            "ION_CALL_DEPTH" => Ok(self.call_stack.len().to_string().into()),
+            "0" => Ok(self.script_name()),
+            _ => self.variables().get_str(name).map_err(Into::into),
         }
     }
 
@@ -69,13 +152,31 @@ impl<'a, 'b> Expander for Shell<'b> {
         name: &str,
         selection: &Select<types::Str>,
     ) -> Result<types::Args, Self::Error> {
-        match self.variables.get(name) {
+        let call_stack;
+        let argv;
+        let value = if name == "FUNCNAME" {
+            // The current function comes first, and the top level of the script last
+            let names = self.call_stack.iter().rev().cloned().chain(iter::once("main".into()));
+            call_stack = Value::Array(names.map(Value::Str).collect());
+            Some(&call_stack)
+        } else if name == "argv" && self.call_stack.is_empty() {
+            // Outside of functions, the arguments are those of the script, without its name
+            argv = Value::Array(match self.variables.get("args") {
+                Some(Value::Array(args)) => args.iter().skip(1).cloned().collect(),
+                _ => types::Array::new(),
+            });
+            Some(&argv)
+        } else {
+            self.variables.get(name)
+        };
+        match value {
             Some(Value::Array(array)) => match selection {
                 Select::All => Ok(array.iter().map(|x| format!("{}", x).into()).collect()),
                 Select::Index(ref id) => id
                     .resolve(array.len())
                     .and_then(|n| array.get(n))
                     .map(|x| args![types::Str::from(format!("{}", x))])
+                    .or_else(|| self.opts.lenient_indices.then(types::Args::new))
                     .ok_or(Error::OutOfBound { length: array.len(), index: *id }),
                 Select::Range(ref range) => range
                     .bounds(array.len())
@@ -95,6 +196,7 @@ impl<'a, 'b> Expander for Shell<'b> {
                     })
                     .ok_or(Error::InvalidRange { length: array.len(), range: *range }),
                 Select::Key(_) => Err(Error::InvalidIndex(selection.clone(), "array", name.into())),
+                Select::Length => Ok(args![array.len().to_string()]),
             },
             Some(Value::HashMap(hmap)) => match selection {
                 Select::All => {
@@ -134,6 +236,7 @@ impl<'a, 'b> Expander for Shell<'b> {
                 Select::Range(_) => {
                     Err(Error::InvalidIndex(selection.clone(), "hashmap", name.into()))
                 }
+                Select::Length => Ok(args![hmap.len().to_string()]),
             },
             Some(Value::BTreeMap(bmap)) => match selection {
                 Select::All => {
@@ -173,6 +276,7 @@ impl<'a, 'b> Expander for Shell<'b> {
                 Select::Range(_) => {
                     Err(Error::InvalidIndex(selection.clone(), "btreemap", name.into()))
                 }
+                Select::Length => Ok(args![bmap.len().to_string()]),
             },
             None => Err(Error::VarNotFound(name.into())),
             _ => Err(Error::ScalarAsArray(name.into())),
@@ -205,6 +309,8 @@ impl<'a, 'b> Expander for Shell<'b> {
         }
     }
 
+    fn lenient_indices(&self) -> bool { self.opts.lenient_indices }
+
     fn tilde(&self, input: &str) -> Result<types::Str, Self::Error> {
         // Only if the first character is a tilde character will we perform expansions
         if !input.starts_with('~') {
@@ -0,0 +1,122 @@
+//! Execution of a script one top-level statement at a time, under the control of the embedder,
+//! for tools such as debuggers which inspect the shell between the statements. Breakpoints are
+//! left to the embedder, which decides when to call `Shell::step` again.
+
+use super::{
+    compile,
+    flow::{precedes_operand, Condition},
+    flow_control::Statement,
+    IonError, Shell,
+};
+use crate::builtins::Status;
+use std::io::Read;
+
+/// A cursor over the top-level statements of a script, executed one by one by `Shell::step`.
+///
+/// The blocks of the script, such as loops or function definitions, are assembled into a single
+/// top-level statement, so that a whole block executes atomically in one step.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatementCursor {
+    statements: Vec<Statement>,
+    position:   usize,
+}
+
+impl StatementCursor {
+    /// A cursor at the first of the given statements
+    #[must_use]
+    pub const fn new(statements: Vec<Statement>) -> Self { Self { statements, position: 0 } }
+
+    /// All the statements of the script, including those already executed
+    #[must_use]
+    pub fn statements(&self) -> &[Statement] { &self.statements }
+
+    /// The index of the statement executed by the next step
+    #[must_use]
+    pub const fn position(&self) -> usize { self.position }
+
+    /// The statement executed by the next step, if any is left
+    #[must_use]
+    pub fn peek(&self) -> Option<&Statement> { self.statements.get(self.position) }
+
+    /// Whether all the statements were executed
+    #[must_use]
+    pub fn is_finished(&self) -> bool { self.position >= self.statements.len() }
+
+    /// Moves the cursor to the statement at `position`, so that the next step executes it. The
+    /// statements may be skipped or executed again this way.
+    pub fn seek(&mut self, position: usize) { self.position = position.min(self.statements.len()) }
+}
+
+impl From<Vec<Statement>> for StatementCursor {
+    fn from(statements: Vec<Statement>) -> Self { Self::new(statements) }
+}
+
+impl<'a> Shell<'a> {
+    /// Parse a script into its top-level statements, without executing it. The blocks are
+    /// assembled, so that a block and the statements it contains are a single statement, and a
+    /// block which is not closed is an error.
+    pub fn parse_script<T: Read>(script: T) -> Result<Vec<Statement>, IonError> {
+        compile::parse_script(script)
+    }
+
+    /// Execute the next statement of `cursor`, returning how it affects the flow of the script
+    /// along with its status, or `None` once all the statements were executed.
+    ///
+    /// The statements are executed as they would be by `execute_command`: the left operand of
+    /// `&&` or `||` does not exit the shell on failure when `set -e` is enabled, and a panic is
+    /// recovered from with `set -R`. The cursor moves past the statement even if it fails, so
+    /// that the embedder may carry on with the next one.
+    pub fn step(
+        &mut self,
+        cursor: &mut StatementCursor,
+    ) -> Option<Result<(Condition, Status), IonError>> {
+        let statement = cursor.statements.get(cursor.position)?;
+        let next = cursor.statements.get(cursor.position + 1);
+        let result = if precedes_operand(next) {
+            self.without_err_exit(|shell| shell.execute_recovering(statement))
+        } else {
+            self.execute_recovering(statement)
+        };
+        cursor.position += 1;
+        Some(result.map(|condition| (condition, self.previous_status)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types;
+
+    #[test]
+    fn steps() {
+        let script = "let x = 1\nif true\n    let x = 2\n    true\nend\nfalse && let x = 3\n";
+        let mut cursor = StatementCursor::new(Shell::parse_script(script.as_bytes()).unwrap());
+        assert_eq!(cursor.statements().len(), 4);
+
+        let mut shell = Shell::default();
+        let value = |shell: &Shell<'_>| shell.variables().get_str("x").unwrap();
+        assert_eq!(shell.step(&mut cursor).unwrap().unwrap(), (Condition::NoOp, Status::SUCCESS));
+        assert_eq!(value(&shell), types::Str::from("1"));
+        assert_eq!(cursor.position(), 1);
+
+        // The whole block is executed in a single step
+        assert_eq!(shell.step(&mut cursor).unwrap().unwrap(), (Condition::NoOp, Status::SUCCESS));
+        assert_eq!(value(&shell), types::Str::from("2"));
+
+        assert_eq!(shell.step(&mut cursor).unwrap().unwrap(), (Condition::NoOp, Status::FALSE));
+        assert_eq!(shell.step(&mut cursor).unwrap().unwrap(), (Condition::NoOp, Status::FALSE));
+        assert_eq!(value(&shell), types::Str::from("2"));
+        assert!(cursor.is_finished());
+        assert!(shell.step(&mut cursor).is_none());
+
+        cursor.seek(0);
+        assert!(matches!(cursor.peek(), Some(Statement::Let(_))));
+        assert_eq!(shell.step(&mut cursor).unwrap().unwrap(), (Condition::NoOp, Status::SUCCESS));
+        assert_eq!(value(&shell), types::Str::from("1"));
+    }
+
+    #[test]
+    fn unclosed_blocks_are_not_stepped() {
+        assert!(Shell::parse_script("while true\n    echo yes\n".as_bytes()).is_err());
+    }
+}
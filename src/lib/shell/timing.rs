@@ -0,0 +1,170 @@
+//! The report of the `time` statement, formatted according to `$TIMEFORMAT`.
+
+use nix::libc;
+use std::{mem, time::Duration};
+
+/// The format of the report when `$TIMEFORMAT` is not set, the same as bash
+pub(crate) const DEFAULT_FORMAT: &str = "real\t%3lR\nuser\t%3lU\nsys\t%3lS";
+
+/// The CPU time consumed by the shell and its children
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Usage {
+    user:   Duration,
+    system: Duration,
+}
+
+impl Usage {
+    /// The CPU time consumed so far by the shell and the children it waited for
+    pub(crate) fn now() -> Self {
+        let get = |who: libc::c_int| {
+            let mut usage: libc::rusage = unsafe { mem::zeroed() };
+            unsafe { libc::getrusage(who, &mut usage) };
+            usage
+        };
+        let duration = |time: libc::timeval| {
+            Duration::new(time.tv_sec as u64, time.tv_usec as u32 * 1000)
+        };
+
+        let (shell, children) = (get(libc::RUSAGE_SELF), get(libc::RUSAGE_CHILDREN));
+        Self {
+            user:   duration(shell.ru_utime) + duration(children.ru_utime),
+            system: duration(shell.ru_stime) + duration(children.ru_stime),
+        }
+    }
+
+    /// The CPU time consumed since `start`
+    pub(crate) fn since(self, start: Self) -> Self {
+        Self {
+            user:   self.user.saturating_sub(start.user),
+            system: self.system.saturating_sub(start.system),
+        }
+    }
+}
+
+/// Format a time in seconds, or in minutes and seconds such as `1m2.345s` if `long` is set. The
+/// time is rounded to the precision before being split into minutes, so that `59.9996` seconds
+/// are shown as `1m0.000s` rather than `0m60.000s`.
+fn format_seconds(time: Duration, precision: usize, long: bool) -> String {
+    if long {
+        let scale = 10u128.pow(precision as u32);
+        let units = (time.as_nanos() * scale + 500_000_000) / 1_000_000_000;
+        let (minutes, units) = (units / (60 * scale), units % (60 * scale));
+        let (seconds, fraction) = (units / scale, units % scale);
+        if precision == 0 {
+            format!("{}m{}s", minutes, seconds)
+        } else {
+            format!("{}m{}.{:03$}s", minutes, seconds, fraction, precision)
+        }
+    } else {
+        format!("{:.*}", precision, time.as_secs_f64())
+    }
+}
+
+/// Format the report of a `time` statement. `%R`, `%U` and `%S` are replaced by the real, user
+/// and system times in seconds, `%P` by the CPU percentage and `%%` by a `%`. These may be
+/// preceded by a precision from 0 to 3 digits, and the times by `l` for the `XmY.YYYs` format. A
+/// single `%l` is replaced by the minutes of the real time. `\n` and `\t` are also unescaped.
+pub(crate) fn format(format: &str, real: Duration, usage: Usage) -> String {
+    let mut output = String::with_capacity(format.len());
+    let mut chars = format.chars().peekable();
+    while let Some(character) = chars.next() {
+        match character {
+            '\\' => match chars.next() {
+                Some('n') => output.push('\n'),
+                Some('t') => output.push('\t'),
+                Some(other) => {
+                    output.push('\\');
+                    output.push(other);
+                }
+                None => output.push('\\'),
+            },
+            '%' => {
+                let precision = chars.peek().and_then(|digit| digit.to_digit(10));
+                if precision.is_some() {
+                    chars.next();
+                }
+                let long = chars.peek() == Some(&'l');
+                if long {
+                    chars.next();
+                }
+
+                let digits = precision.unwrap_or(3).min(3) as usize;
+                let time = |time| format_seconds(time, digits, long);
+                match chars.peek() {
+                    Some('R') => output.push_str(&time(real)),
+                    Some('U') => output.push_str(&time(usage.user)),
+                    Some('S') => output.push_str(&time(usage.system)),
+                    Some('P') => {
+                        let cpu = (usage.user + usage.system).as_secs_f64();
+                        let real = real.as_secs_f64();
+                        let percent = if real > 0. { cpu * 100. / real } else { 0. };
+                        let precision = precision.unwrap_or(2).min(3) as usize;
+                        output.push_str(&format!("{:.*}", precision, percent));
+                    }
+                    Some('%') if !long && precision.is_none() => output.push('%'),
+                    _ if long && precision.is_none() => {
+                        output.push_str(&(real.as_secs() / 60).to_string());
+                        continue;
+                    }
+                    _ => {
+                        // Not a format specifier: keep it as is
+                        output.push('%');
+                        output.extend(precision.and_then(|digit| char::from_digit(digit, 10)));
+                        if long {
+                            output.push('l');
+                        }
+                        continue;
+                    }
+                }
+                chars.next();
+            }
+            _ => output.push(character),
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage(user: u64, system: u64) -> Usage {
+        Usage { user: Duration::from_millis(user), system: Duration::from_millis(system) }
+    }
+
+    #[test]
+    fn format_times() {
+        let real = Duration::from_millis(62_500);
+        assert_eq!(format("%R %U %S", real, usage(1_250, 250)), "62.500 1.250 0.250");
+        assert_eq!(format("%1R %0U %lR", real, usage(1_250, 0)), "62.5 1 1m2.500s");
+        let report = format("real\\t%lm\\n%P%%", real, usage(50_000, 12_500));
+        assert_eq!(report, "real\t1m\n100.00%");
+    }
+
+    #[test]
+    fn minutes_rollover() {
+        let long = |millis| format_seconds(Duration::from_millis(millis), 3, true);
+        assert_eq!(long(60_000), "1m0.000s");
+        assert_eq!(long(61_500), "1m1.500s");
+        assert_eq!(long(119_500), "1m59.500s");
+        assert_eq!(long(3_661_000), "61m1.000s");
+        assert_eq!(long(59_999), "0m59.999s");
+        assert_eq!(format_seconds(Duration::from_micros(59_999_600), 3, true), "1m0.000s");
+        assert_eq!(format_seconds(Duration::from_millis(119_600), 0, true), "2m0s");
+        assert_eq!(format_seconds(Duration::from_millis(61_050), 1, true), "1m1.1s");
+        assert_eq!(format_seconds(Duration::from_nanos(1_000_000_001), 3, true), "0m1.000s");
+    }
+
+    #[test]
+    fn default_format() {
+        let report = format(DEFAULT_FORMAT, Duration::from_millis(1_500), usage(1_000, 20));
+        assert_eq!(report, "real\t0m1.500s\nuser\t0m1.000s\nsys\t0m0.020s");
+    }
+
+    #[test]
+    fn keep_unknown_specifiers() {
+        let real = Duration::from_secs(1);
+        assert_eq!(format("%x %2y 100%", real, Usage::default()), "%x %2y 100%");
+        assert_eq!(format("\\q\\", real, Usage::default()), "\\q\\");
+    }
+}
@@ -6,18 +6,91 @@ use crate::{
 };
 use nix::unistd::{geteuid, gethostname, getpid, getuid};
 use scopes::{Namespace, Scope, Scopes};
-use std::{env, ffi::CStr, rc::Rc};
+use std::{
+    collections::HashMap,
+    env,
+    ffi::CStr,
+    rc::Rc,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
 use unicode_segmentation::UnicodeSegmentation;
 
 /// Contain a dynamically-typed variable value
 pub use types_rs::Value;
 /// A structure containing dynamically-typed values organised in scopes
-pub struct Variables(Scopes<types::Str, Value<Rc<Function>>>);
+pub struct Variables {
+    scopes:        Scopes<types::Str, Value<Rc<Function>>>,
+    /// The attributes given to the variables by `declare`
+    attributes:    HashMap<types::Str, Attributes>,
+    /// The instant from which `$SECONDS` counts, and the value it had at that instant
+    seconds:       (Instant, i64),
+    /// The watched variables assigned or dropped in their scope since the watches were checked
+    watch_changes: Vec<types::Str>,
+    /// The watched variables whose scope was exited since the watches were checked
+    watch_ends:    Vec<types::Str>,
+}
+
+/// A copy of the variables of every scope, along with their attributes, which can be restored
+/// later on. Taken by `Variables::snapshot`.
+#[derive(Clone)]
+pub struct Snapshot {
+    scopes:     Scopes<types::Str, Value<Rc<Function>>>,
+    attributes: HashMap<types::Str, Attributes>,
+}
+
+/// The attributes of a variable, given by `declare`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Attributes {
+    /// The variable must hold an integer
+    pub integer:  bool,
+    /// The variable holds an array
+    pub array:    bool,
+    /// The variable can not be modified or dropped
+    pub readonly: bool,
+    /// The variable is exported to the environment of the children processes
+    pub exported: bool,
+}
+
+impl Attributes {
+    /// Returns true if no attribute is set
+    #[must_use]
+    pub fn is_empty(self) -> bool { self == Self::default() }
+
+    /// Returns true if all the attributes of `other` are also set in `self`
+    #[must_use]
+    pub const fn contains(self, other: Self) -> bool {
+        (self.integer || !other.integer)
+            && (self.array || !other.array)
+            && (self.readonly || !other.readonly)
+            && (self.exported || !other.exported)
+    }
+
+    /// Combine the attributes set in either `self` or `other`
+    #[must_use]
+    pub const fn union(self, other: Self) -> Self {
+        Self {
+            integer:  self.integer || other.integer,
+            array:    self.array || other.array,
+            readonly: self.readonly || other.readonly,
+            exported: self.exported || other.exported,
+        }
+    }
+
+    /// The flags of `declare` setting these attributes, such as `-ir`
+    #[must_use]
+    pub fn flags(self) -> String {
+        [(self.integer, 'i'), (self.array, 'a'), (self.readonly, 'r'), (self.exported, 'x')]
+            .iter()
+            .filter(|(set, _)| *set)
+            .map(|(_, flag)| *flag)
+            .collect()
+    }
+}
 
 impl Variables {
     /// Get all strings
     pub fn string_vars(&self) -> impl Iterator<Item = (&types::Str, &types::Str)> {
-        self.0.scopes().flat_map(|map| {
+        self.scopes.scopes().flat_map(|map| {
             map.iter().filter_map(|(key, val)| {
                 if let types_rs::Value::Str(val) = val {
                     Some((key, val))
@@ -30,7 +103,7 @@ impl Variables {
 
     /// Get all aliases
     pub fn aliases(&self) -> impl Iterator<Item = (&types::Str, &types::Str)> {
-        self.0.scopes().rev().flat_map(|map| {
+        self.scopes.scopes().rev().flat_map(|map| {
             map.iter().filter_map(|(key, possible_alias)| {
                 if let types_rs::Value::Alias(alias) = possible_alias {
                     Some((key, &**alias))
@@ -43,7 +116,7 @@ impl Variables {
 
     /// Get all the functions
     pub fn functions(&self) -> impl Iterator<Item = (&types::Str, &Rc<Function>)> {
-        self.0.scopes().rev().flat_map(|map| {
+        self.scopes.scopes().rev().flat_map(|map| {
             map.iter().filter_map(|(key, val)| {
                 if let types_rs::Value::Function(val) = val {
                     Some((key, val))
@@ -56,7 +129,29 @@ impl Variables {
 
     /// Get all the variables
     pub fn variables(&self) -> impl Iterator<Item = (&types::Str, &Value<Rc<Function>>)> {
-        self.0.scopes().rev().flat_map(|map| {
+        self.scopes.scopes().rev().flat_map(|map| {
+            map.iter().filter_map(|(key, val)| match val {
+                val @ Value::Array(_)
+                | val @ Value::Str(_)
+                | val @ Value::HashMap(_)
+                | val @ Value::BTreeMap(_) => Some((key, val)),
+                _ => None,
+            })
+        })
+    }
+
+    /// Get the variables of the current scope: those of the function being executed, including
+    /// its arguments, or every variable outside of a function. Like `variables`, the inner scopes
+    /// come last.
+    pub fn local_variables(&self) -> impl Iterator<Item = (&types::Str, &Value<Rc<Function>>)> {
+        let mut scopes = Vec::new();
+        for scope in self.scopes.scopes() {
+            scopes.push(scope);
+            if scope.is_namespace() {
+                break;
+            }
+        }
+        scopes.into_iter().rev().flat_map(|map| {
             map.iter().filter_map(|(key, val)| match val {
                 val @ Value::Array(_)
                 | val @ Value::Str(_)
@@ -69,7 +164,7 @@ impl Variables {
 
     /// Get all the array values
     pub fn arrays(&self) -> impl Iterator<Item = (&types::Str, &types::Array<Rc<Function>>)> {
-        self.0.scopes().rev().flat_map(|map| {
+        self.scopes.scopes().rev().flat_map(|map| {
             map.iter().filter_map(|(key, val)| {
                 if let types_rs::Value::Array(val) = val {
                     Some((key, val))
@@ -82,25 +177,65 @@ impl Variables {
 
     /// Create a new scope. If namespace is true, variables won't be droppable across the scope
     /// boundary
-    pub fn new_scope(&mut self, namespace: bool) { self.0.new_scope(namespace) }
+    pub fn new_scope(&mut self, namespace: bool) { self.scopes.new_scope(namespace) }
 
     /// Exit the current scope
-    pub fn pop_scope(&mut self) { self.0.pop_scope() }
+    pub fn pop_scope(&mut self) {
+        if let Some(scope) = self.scopes.scopes().next() {
+            self.watch_ends.extend(scope.watched().cloned());
+        }
+        self.scopes.pop_scope()
+    }
+
+    /// The number of scopes currently entered, including the global scope
+    pub(crate) fn scope_depth(&self) -> usize { self.scopes.scopes().count() }
+
+    /// Take a snapshot of the variables, functions and aliases of every scope, and of their
+    /// attributes. Values are cloned, except for functions which are shared.
+    #[must_use]
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot { scopes: self.scopes.clone(), attributes: self.attributes.clone() }
+    }
+
+    /// Restore the variables and their attributes to a snapshot, discarding every change made
+    /// since, including the scopes created or exited since.
+    pub fn restore(&mut self, snapshot: Snapshot) {
+        self.scopes = snapshot.scopes;
+        self.attributes = snapshot.attributes;
+    }
+
+    /// Exit the current scope, returning the variables defined in it along with their attributes
+    pub(crate) fn pop_scope_variables(
+        &mut self,
+    ) -> Vec<(types::Str, Value<Rc<Function>>, Attributes)> {
+        let attributes = &self.attributes;
+        let variables = self.scopes.scopes_mut().next().map_or_else(Vec::new, |scope| {
+            scope
+                .drain()
+                .map(|(name, value)| {
+                    let attributes = attributes.get(&name).copied().unwrap_or_default();
+                    (name, value, attributes)
+                })
+                .collect()
+        });
+        self.pop_scope();
+        variables
+    }
 
     pub(crate) fn pop_scopes(
         &mut self,
         index: usize,
     ) -> impl Iterator<Item = Scope<types::Str, Value<Rc<Function>>>> + '_ {
-        self.0.pop_scopes(index)
+        self.scopes.pop_scopes(index)
     }
 
     pub(crate) fn append_scopes(&mut self, scopes: Vec<Scope<types::Str, Value<Rc<Function>>>>) {
-        self.0.append_scopes(scopes)
+        self.scopes.append_scopes(scopes)
     }
 
     #[must_use]
     pub(crate) fn index_scope_for_var(&self, name: &str) -> Option<usize> {
-        self.0.index_scope_for_var(name)
+        self.scopes.index_scope_for_var(name)
     }
 
     /// Set a variable to a value in the current scope. If a variable already exists in a writable
@@ -108,19 +243,53 @@ impl Variables {
     /// shadowing other variables
     pub fn set<T: Into<Value<Rc<Function>>>>(&mut self, name: &str, value: T) {
         let value = value.into();
-        if let Some(val) = self.0.get_mut(name) {
-            let _ = std::mem::replace(val, value);
-        } else {
-            self.0.set(name, value);
+        if name == "SECONDS" {
+            if let Value::Str(seconds) = &value {
+                if let Ok(seconds) = seconds.parse() {
+                    self.seconds = (Instant::now(), seconds);
+                    return;
+                }
+            }
+        }
+        let scope = self.scopes.scope_to_set_mut(name);
+        let previous = match scope.get_mut(name) {
+            Some(val) => Some(std::mem::replace(val, value)),
+            None => scope.insert(name.into(), value),
+        };
+        if scope.is_watched(name) && previous.as_ref() != scope.get(name) {
+            self.watch_changes.push(name.into());
+        }
+    }
+
+    /// Set a string variable, reusing the storage of the existing value if it is already a
+    /// string in a writable scope. Used for values updated in a tight loop.
+    pub(crate) fn set_str(&mut self, name: &str, value: &str) {
+        if let Some(scope) = self.scopes.writable_scope_mut(name) {
+            if let (false, Some(Value::Str(val))) = (scope.is_watched(name), scope.get_mut(name)) {
+                val.clear();
+                val.push_str(value);
+                return;
+            }
         }
+        self.set(name, value);
     }
 
     /// Set a variable to a value in the top scope.
     /// If a variable already exists in any scope, it is updated and is put in the global scope.
     pub fn set_global<T: Into<Value<Rc<Function>>>>(&mut self, name: &str, value: T) {
         let value = value.into();
-        self.0.remove_variable(name);
-        self.0.set_global(name, value);
+        if let Some(scope) = self.scopes.writable_scope_mut(name) {
+            scope.remove(name);
+            if scope.is_watched(name) {
+                self.watch_changes.push(name.into());
+            }
+        }
+        let previous = self.scopes.set_global(name, value);
+        if let Some(global) = self.scopes.scopes().last() {
+            if global.is_watched(name) && previous.as_ref() != global.get(name) {
+                self.watch_changes.push(name.into());
+            }
+        }
     }
 
     /// Obtains the value for the **MWD** variable.
@@ -181,16 +350,73 @@ impl Variables {
             // Cannot mutate outer namespace
             return None;
         }
-        self.0.remove_variable(name)
+        let scope = self.scopes.writable_scope_mut(name)?;
+        let value = scope.remove(name);
+        if scope.is_watched(name) {
+            self.watch_changes.push(name.into());
+        }
+        if value.is_some() && self.scopes.get(name, Namespace::Any).is_none() {
+            self.attributes.remove(name);
+        }
+        value
+    }
+
+    /// Get the attributes given to a variable by `declare`. The attributes of variables which no
+    /// longer exist, such as the locals of a function which returned, are ignored.
+    #[must_use]
+    pub fn attributes(&self, name: &str) -> Attributes {
+        match self.attributes.get(name) {
+            Some(&attributes) if self.scopes.get(name, Namespace::Any).is_some() => attributes,
+            _ => Attributes::default(),
+        }
+    }
+
+    /// Set the attributes of a variable
+    pub fn set_attributes(&mut self, name: &str, attributes: Attributes) {
+        if attributes.is_empty() {
+            self.attributes.remove(name);
+        } else {
+            self.attributes.insert(name.into(), attributes);
+        }
+    }
+
+    /// Returns true if the variable exists and was declared readonly
+    #[must_use]
+    pub fn is_readonly(&self, name: &str) -> bool { self.attributes(name).readonly }
+
+    /// Obtains the value for the **EPOCHSECONDS** and **EPOCHREALTIME** variables.
+    ///
+    /// The time elapsed since the Unix epoch, in seconds, with or without its microseconds.
+    #[must_use]
+    fn get_epoch_time(realtime: bool) -> types::Str {
+        let elapsed = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        if realtime {
+            format!("{}.{:06}", elapsed.as_secs(), elapsed.subsec_micros()).into()
+        } else {
+            elapsed.as_secs().to_string().into()
+        }
+    }
+
+    /// Obtains the value for the **SECONDS** variable.
+    ///
+    /// The seconds elapsed since the shell started, or since the variable was last assigned, plus
+    /// the value it was assigned.
+    #[must_use]
+    fn get_seconds(&self) -> types::Str {
+        let (since, offset) = self.seconds;
+        (offset + since.elapsed().as_secs() as i64).to_string().into()
     }
 
     /// Get the string value associated with a name on the current scope. This includes fetching
-    /// env vars, colors & hexes and some extra values like MWD and SWD
+    /// env vars, colors & hexes and some extra values like MWD, SWD and the elapsed time
     pub fn get_str(&self, name: &str) -> expansion::Result<types::Str, IonError> {
         use expansion::Error;
         match name {
             "MWD" => return Ok(self.get_minimal_directory()),
             "SWD" => return Ok(self.get_simplified_directory()),
+            "EPOCHSECONDS" => return Ok(Self::get_epoch_time(false)),
+            "EPOCHREALTIME" => return Ok(Self::get_epoch_time(true)),
+            "SECONDS" => return Ok(self.get_seconds()),
             _ => (),
         }
         // If the parsed name contains the '::' pattern, then a namespace was
@@ -209,6 +435,8 @@ impl Variables {
                 // Otherwise, it's just a simple variable name.
                 match self.get(name) {
                     Some(Value::Str(val)) => Ok(val.clone()),
+                    // A function value expands to its name, so that it can be called or passed
+                    Some(Value::Function(function)) => Ok(function.name().clone()),
                     _ => {
                         env::var(name).map(Into::into).map_err(|_| Error::VarNotFound(name.into()))
                     }
@@ -239,7 +467,7 @@ impl Variables {
         } else {
             Namespace::Any
         };
-        self.0.get(name, namespace)
+        self.scopes.get(name, namespace)
     }
 
     /// Get a mutable access to a variable on the current scope
@@ -249,7 +477,30 @@ impl Variables {
             // Cannot mutate outer namespace
             return None;
         }
-        self.0.get_mut(name)
+        let scope = self.scopes.writable_scope_mut(name)?;
+        // The value may be modified through the reference
+        if scope.is_watched(name) {
+            self.watch_changes.push(name.into());
+        }
+        scope.get_mut(name)
+    }
+
+    /// Watch the variable `name` of the nearest scope defining it, or of the current scope. Its
+    /// assignments and removals in that scope are recorded until the scope is exited.
+    pub(crate) fn watch(&mut self, name: &str) { self.scopes.watch(name.into()) }
+
+    /// Stop watching the variable `name`
+    pub(crate) fn unwatch(&mut self, name: &str) { self.scopes.unwatch(name) }
+
+    /// Take the watched variables which were assigned a different value or dropped since the
+    /// last call
+    pub(crate) fn take_watch_changes(&mut self) -> Vec<types::Str> {
+        std::mem::take(&mut self.watch_changes)
+    }
+
+    /// Take the watched variables whose scope was exited since the last call
+    pub(crate) fn take_watch_ends(&mut self) -> Vec<types::Str> {
+        std::mem::take(&mut self.watch_ends)
     }
 }
 
@@ -283,7 +534,13 @@ impl Default for Variables {
                 .as_ref(),
         );
 
-        Self(map)
+        Self {
+            scopes:        map,
+            attributes:    HashMap::new(),
+            seconds:       (Instant::now(), 0),
+            watch_changes: Vec::new(),
+            watch_ends:    Vec::new(),
+        }
     }
 }
 
@@ -319,6 +576,10 @@ pub(crate) mod tests {
             Ok(cmd.into())
         }
 
+        fn command_stderr(&mut self, cmd: &str) -> Result<types::Str, Self::Error> {
+            Ok(cmd.into())
+        }
+
         fn tilde(&self, input: &str) -> Result<types::Str, Self::Error> { Ok(input.into()) }
 
         fn map_keys(&self, name: &str) -> Result<types::Args, Self::Error> {
@@ -365,4 +626,22 @@ pub(crate) mod tests {
             variables.get_str("MWD").expect("no value returned"),
         );
     }
+
+    #[test]
+    fn seconds_var_counts_from_assignment() {
+        let mut variables = Variables::default();
+        assert_eq!(variables.get_str("SECONDS").unwrap(), types::Str::from("0"));
+        variables.set("SECONDS", "42");
+        assert_eq!(variables.get_str("SECONDS").unwrap(), types::Str::from("42"));
+    }
+
+    #[test]
+    fn epoch_time_vars_are_computed() {
+        let variables = Variables::default();
+        let seconds = variables.get_str("EPOCHSECONDS").unwrap().parse::<u64>().unwrap();
+        let realtime = variables.get_str("EPOCHREALTIME").unwrap();
+        let (whole, micros) = realtime.split_at(realtime.find('.').unwrap());
+        assert!(whole.parse::<u64>().unwrap() >= seconds);
+        assert_eq!(micros.len(), 7);
+    }
 }
@@ -0,0 +1,107 @@
+//! The variables watched with the `watch` statement, whose command is executed each time their
+//! value changes. The changes are recorded by `Variables` as they are made to the scope owning
+//! the watched variable, whether by an assignment, a loop variable or a builtin such as `read`,
+//! and their commands are executed after the statement which made them.
+
+use super::{flow_control::Statement, Shell};
+use crate::types;
+
+/// A variable watched with `watch`
+#[derive(Debug)]
+pub(super) struct Watch {
+    name:    types::Str,
+    /// The command as written in the statement, listed by `watches`
+    command: types::Str,
+    body:    Statement,
+}
+
+impl<'a> Shell<'a> {
+    /// The variables being watched, with the command executed when they change, in the order
+    /// they were watched
+    pub fn watches(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.watches.iter().map(|watch| (watch.name.as_str(), watch.command.as_str()))
+    }
+
+    /// Stops watching the variable `name`, returning `false` if it was not watched
+    pub fn unwatch(&mut self, name: &str) -> bool {
+        self.variables.unwatch(name);
+        let len = self.watches.len();
+        self.watches.retain(|watch| watch.name != name);
+        self.watches.len() != len
+    }
+
+    /// Watches the variable `name` of the nearest scope defining it, or of the current scope if it
+    /// is not defined, replacing the command of a previous watch. The watch ends with the scope.
+    pub(super) fn add_watch(&mut self, name: &str, command: &str, body: &Statement) {
+        self.variables.watch(name);
+        let watch = Watch { name: name.into(), command: command.into(), body: body.clone() };
+        match self.watches.iter_mut().find(|watch| watch.name == name) {
+            Some(previous) => *previous = watch,
+            None => self.watches.push(watch),
+        }
+    }
+
+    /// Executes the command of each watched variable which was assigned a different value or
+    /// dropped since the last check, and forgets the watches whose scope was exited. Called after
+    /// each statement, like `update_command_log`. The status of the previous command is restored
+    /// afterwards, and the commands do not trigger the watches themselves, even when they change
+    /// a watched variable: they would run forever otherwise.
+    pub(super) fn check_watches(&mut self) {
+        if self.running_watches {
+            return;
+        }
+        let changes = self.variables.take_watch_changes();
+        let ends = self.variables.take_watch_ends();
+        if !ends.is_empty() {
+            self.watches.retain(|watch| !ends.contains(&watch.name));
+        }
+        if changes.is_empty() {
+            return;
+        }
+        self.running_watches = true;
+        let previous_status = self.previous_status;
+
+        // A command may unwatch variables, so the watches are looked up again after each one
+        let mut i = 0;
+        while let Some(watch) = self.watches.get(i) {
+            i += 1;
+            if !changes.contains(&watch.name) {
+                continue;
+            }
+            let (name, body) = (watch.name.clone(), watch.body.clone());
+            if let Err(why) = self.execute_statement(&body) {
+                eprintln!("ion: watch {}: {}", name, why);
+            }
+        }
+        // The changes made by the commands are not checked again
+        self.variables.take_watch_changes();
+
+        self.previous_status = previous_status;
+        self.variables.set("?", previous_status);
+        self.running_watches = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn watched_variables() {
+        let mut shell = Shell::default();
+        shell
+            .execute_command(
+                "let count = 0\nwatch x { let count += 1 }\nwatch y { echo }\nlet x = 1\nlet x = \
+                 1\nlet x = 2\n"
+                    .as_bytes(),
+            )
+            .unwrap();
+        assert_eq!(shell.variables().get_str("count").unwrap().as_str(), "2");
+        assert_eq!(shell.watches().collect::<Vec<_>>(), [("x", "let count += 1"), ("y", "echo")]);
+
+        assert!(shell.unwatch("x"));
+        assert!(!shell.unwatch("x"));
+        shell.execute_command("let x = 3\n".as_bytes()).unwrap();
+        assert_eq!(shell.variables().get_str("count").unwrap().as_str(), "2");
+    }
+}
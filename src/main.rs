@@ -1,14 +1,24 @@
-use self::binary::{builtins, InteractiveShell};
+use self::binary::{
+    builtins,
+    completions::{self, CompletionShell},
+    debugger::Debugger,
+    manpage, terminal, InteractiveShell,
+};
 use atty::Stream;
-use ion_shell::{BackgroundEvent, BuiltinMap, IonError, PipelineError, Shell, Value};
+use ion_shell::{
+    BackgroundEvent, BuiltinMap, CommandProfile, IonError, PipelineError, Shell, Value,
+    CACHE_EXTENSION,
+};
 use liner::KeyBindings;
 use nix::{
     sys::signal::{self, SaFlags, SigAction, SigHandler, SigSet, Signal},
     unistd,
 };
 use std::{
+    fmt::Write,
     fs,
     io::{stdin, BufReader},
+    path::{Path, PathBuf},
     process,
     sync::Arc,
 };
@@ -29,6 +39,10 @@ struct CommandLineArgs {
     help:             bool,
     /// Print the version, platform and revision of Ion then exit
     version:          bool,
+    /// Print the man page of Ion and its builtins then exit
+    generate_manpage: bool,
+    /// Print the script completing the options of Ion in the given shell then exit
+    completion:       Option<CompletionShell>,
     /// Do not execute any commands, perform only syntax checking
     no_execute:       bool,
     /// Use a fake interactive mode, where errors don't exit the shell
@@ -37,10 +51,22 @@ struct CommandLineArgs {
     interactive:      bool,
     /// Print commands before execution
     print_commands:   bool,
+    /// Make the builtins printing tables print them as JSON
+    json_output:      bool,
+    /// Print the calls and the time of each command once the script completes
+    profile:          bool,
+    /// Pause before each statement of the script
+    step:             bool,
     /// Shortcut layout. Valid options: "vi", "emacs"
     key_bindings:     Option<KeyBindingsWrapper>,
     /// Evaluate given commands instead of reading from the commandline
     command:          Option<String>,
+    /// Compile the given script to a bytecode cache instead of executing it
+    compile:          Option<String>,
+    /// Where to write the bytecode cache of the compiled script
+    output:           Option<String>,
+    /// Print the calls between the functions of the given script instead of executing it
+    call_graph:       Option<String>,
     /// Script arguments (@args). If the -c option is not specified,
     /// the first parameter is taken as a filename to execute
     args:             Vec<String>,
@@ -52,6 +78,8 @@ pub enum ParsingError {
     ArgTwiceSet,
     #[error("invalid keybinding, see --help")]
     InvalidKeybinding,
+    #[error("invalid shell for the completion script, see --help")]
+    InvalidShell,
 }
 
 fn version() -> String { include!(concat!(env!("OUT_DIR"), "/version_string")).to_string() }
@@ -59,15 +87,23 @@ fn version() -> String { include!(concat!(env!("OUT_DIR"), "/version_string")).t
 fn parse_args() -> Result<CommandLineArgs, ParsingError> {
     let mut arg_twice_set = false;
     let mut invalid_keybinding = false;
+    let mut invalid_shell = false;
     let mut args = env::args().skip(1);
     let mut version = false;
     let mut help = false;
+    let mut generate_manpage = false;
+    let mut completion = None;
     let mut no_execute = false;
     let mut fake_interactive = false;
     let mut interactive = false;
     let mut print_commands = false;
-    let mut key_bindings = None;
+    let mut json_output = false;
+    let mut profile = false;
+    let mut step = false;
+    let mut option_o = None;
     let mut command = None;
+    let mut compile = None;
+    let mut call_graph = None;
     let mut additional_arguments = Vec::new();
 
     while let Some(arg) = args.next() {
@@ -84,6 +120,22 @@ fn parse_args() -> Result<CommandLineArgs, ParsingError> {
                 }
                 help = true;
             }
+            "--generate-manpage" => {
+                if generate_manpage {
+                    arg_twice_set = true;
+                }
+                generate_manpage = true;
+            }
+            "--generate-completion" => {
+                if completion.is_some() {
+                    arg_twice_set = true;
+                }
+                completion = args.next().and_then(|shell| shell.parse().ok());
+                if completion.is_none() {
+                    invalid_shell = true;
+                    break;
+                }
+            }
             "-n" | "--no-execute" => {
                 if no_execute {
                     arg_twice_set = true;
@@ -108,24 +160,42 @@ fn parse_args() -> Result<CommandLineArgs, ParsingError> {
                 }
                 print_commands = true;
             }
-            "-o" => {
-                match key_bindings {
-                    Some(KeyBindingsWrapper(KeyBindings::Vi)) => arg_twice_set = true,
-                    Some(KeyBindingsWrapper(KeyBindings::Emacs)) => arg_twice_set = true,
-                    None => (),
+            "--json-output" => {
+                if json_output {
+                    arg_twice_set = true;
+                }
+                json_output = true;
+            }
+            "--profile" => {
+                if profile {
+                    arg_twice_set = true;
                 }
-                key_bindings = match args.next().as_deref() {
-                    Some("vi") => Some(KeyBindingsWrapper(KeyBindings::Vi)),
-                    Some("emacs") => Some(KeyBindingsWrapper(KeyBindings::Emacs)),
-                    Some(_) => {
-                        invalid_keybinding = true;
-                        break;
-                    }
-                    None => {
-                        invalid_keybinding = true;
-                        break;
-                    }
+                profile = true;
+            }
+            "--step" => {
+                if step {
+                    arg_twice_set = true;
                 }
+                step = true;
+            }
+            "--compile" => {
+                if compile.is_some() {
+                    arg_twice_set = true;
+                }
+                compile = args.next();
+            }
+            "--call-graph" => {
+                if call_graph.is_some() {
+                    arg_twice_set = true;
+                }
+                call_graph = args.next();
+            }
+            // The meaning of `-o` depends on `--compile`, which may be given after it
+            "-o" => {
+                if option_o.is_some() {
+                    arg_twice_set = true;
+                }
+                option_o = Some(args.next());
             }
             "-c" => {
                 // convert Option<String< to Option<&str> due to type system limitation
@@ -139,26 +209,67 @@ fn parse_args() -> Result<CommandLineArgs, ParsingError> {
             }
         }
     }
+    // When compiling a script, `-o` names the output instead of the key bindings
+    let (output, key_bindings) = match option_o {
+        Some(output) if compile.is_some() => (output, None),
+        Some(key_bindings) => match key_bindings.as_deref() {
+            Some("vi") => (None, Some(KeyBindingsWrapper(KeyBindings::Vi))),
+            Some("emacs") => (None, Some(KeyBindingsWrapper(KeyBindings::Emacs))),
+            _ => {
+                invalid_keybinding = true;
+                (None, None)
+            }
+        },
+        None => (None, None),
+    };
     if arg_twice_set {
         return Err(ParsingError::ArgTwiceSet);
     }
     if invalid_keybinding {
         return Err(ParsingError::InvalidKeybinding);
     }
+    if invalid_shell {
+        return Err(ParsingError::InvalidShell);
+    }
     // bubble up errors
     Ok(CommandLineArgs {
         help,
         version,
+        generate_manpage,
+        completion,
         no_execute,
         fake_interactive,
         interactive,
         print_commands,
+        json_output,
+        profile,
+        step,
         key_bindings,
         command,
+        compile,
+        output,
+        call_graph,
         args: additional_arguments,
     })
 }
 
+/// The calls of each command recorded by the profiler, as tab-separated lines with a header,
+/// sorted by decreasing total time. The times are in seconds.
+fn profile_report(profile: &[(&str, CommandProfile)]) -> String {
+    let mut report = String::from("command\tcalls\ttotal\taverage\n");
+    for (command, calls) in profile {
+        let _ = writeln!(
+            report,
+            "{}\t{}\t{:.6}\t{:.6}",
+            command,
+            calls.calls,
+            calls.total.as_secs_f64(),
+            calls.average().as_secs_f64()
+        );
+    }
+    report
+}
+
 fn set_unique_pid() -> nix::Result<()> {
     let pgid = unistd::getpid();
     if pgid != unistd::getpgrp() {
@@ -183,6 +294,10 @@ fn main() {
             eprintln!("invalid keybinding, see --help");
             process::exit(1);
         }
+        Err(ParsingError::InvalidShell) => {
+            eprintln!("invalid shell for the completion script, see --help");
+            process::exit(1);
+        }
     };
 
     if command_line_args.help {
@@ -193,6 +308,34 @@ fn main() {
         println!("{}", version());
         return;
     }
+    if let Some(script) = command_line_args.compile {
+        let output = command_line_args.output.map_or_else(
+            || Path::new(&script).with_extension(CACHE_EXTENSION),
+            PathBuf::from,
+        );
+        if let Err(why) = Shell::compile_file(&script, &output) {
+            eprintln!("ion: could not compile '{}': {}", script, why);
+            process::exit(1);
+        }
+        return;
+    }
+    if let Some(script) = command_line_args.call_graph {
+        let graph = match fs::File::open(&script) {
+            Ok(file) => Shell::call_graph(BufReader::new(file)),
+            Err(why) => {
+                eprintln!("ion: could not open '{}': {}", script, why);
+                process::exit(1);
+            }
+        };
+        match graph {
+            Ok(graph) => print!("{}", graph),
+            Err(why) => {
+                eprintln!("ion: could not analyze '{}': {}", script, why);
+                process::exit(1);
+            }
+        }
+        return;
+    }
     if command_line_args.command.is_some() && !command_line_args.args.is_empty() {
         eprintln!("either execute command or file(s)");
         process::exit(1);
@@ -201,10 +344,50 @@ fn main() {
     let mut builtins = BuiltinMap::default();
     builtins
         .with_unsafe()
-        .add("debug", &builtins::builtin_debug, "Toggle debug mode (print commands on exec)")
-        .add("exec", &builtins::builtin_exec, "Replace the shell with the given command.")
-        .add("exit", &builtins::builtin_exit, "Exits the current session")
-        .add("suspend", &builtins::builtin_suspend, "Suspends the shell with a SIGTSTOP signal");
+        .add_with_man(
+            "debug",
+            &builtins::builtin_debug,
+            "Toggle debug mode (print commands on exec)",
+            builtins::MAN_DEBUG,
+        )
+        .add_with_man(
+            "exec",
+            &builtins::builtin_exec,
+            "Replace the shell with the given command.",
+            builtins::MAN_EXEC,
+        )
+        .add_with_man(
+            "exit",
+            &builtins::builtin_exit,
+            "Exits the current session",
+            builtins::MAN_EXIT,
+        )
+        .add_with_man(
+            "suspend",
+            &builtins::builtin_suspend,
+            "Suspends the shell with a SIGTSTOP signal",
+            builtins::MAN_SUSPEND,
+        );
+
+    if command_line_args.generate_manpage {
+        print!("{}", manpage::generate(&builtins));
+        return;
+    }
+    if let Some(shell) = command_line_args.completion {
+        print!("{}", completions::generate(shell, &builtins));
+        return;
+    }
+
+    // Each new shell process sits one level deeper than the shell that started it, and is the
+    // top-level shell until a subshell is entered. This is done before the shell is created, so
+    // that `env-diff` does not report it.
+    let shell_level =
+        env::var("SHLVL").ok().and_then(|level| level.parse::<usize>().ok()).unwrap_or(0) + 1;
+    env::set_var("SHLVL", shell_level.to_string());
+    env::set_var("ION_SUBSHELL", "1");
+
+    // Let scripts know whether they can emit colors
+    terminal::export_capabilities();
 
     let stdin_is_a_tty = atty::is(Stream::Stdin);
     let mut shell = Shell::with_builtins(builtins);
@@ -229,6 +412,22 @@ fn main() {
 
     shell.opts_mut().no_exec = command_line_args.no_execute;
     shell.opts_mut().grab_tty = stdin_is_a_tty;
+    shell.opts_mut().json_output = command_line_args.json_output;
+    if command_line_args.profile {
+        shell.start_profiling();
+        // The report is printed once the script completes, even if it calls `exit`
+        shell.set_exit_handler(Some(Box::new(|shell, _| {
+            if let Some(profile) = shell.profile() {
+                eprint!("{}", profile_report(&profile));
+            }
+        })));
+    }
+    if command_line_args.step {
+        let debugger = Debugger::default();
+        shell.set_pre_statement(Some(Box::new(move |shell, statement| {
+            debugger.pause(shell, statement)
+        })));
+    }
     if command_line_args.print_commands {
         shell.set_pre_command(Some(Box::new(|_shell, pipeline| {
             // A string representing the command is stored here.
@@ -255,7 +454,7 @@ fn main() {
         shell.execute_command(command.as_bytes())
     } else if let Some(path) = script_path {
         match fs::File::open(&path) {
-            Ok(script) => shell.execute_command(std::io::BufReader::new(script)),
+            Ok(script) => shell.execute_file(&path, std::io::BufReader::new(script)),
             Err(cause) => {
                 println!("ion: could not execute '{}': {}", path, cause);
                 process::exit(1);
@@ -278,7 +477,26 @@ fn main() {
     } else {
         shell.execute_command(BufReader::new(stdin()))
     }
-    .and_then(|_| shell.wait_for_background().map_err(Into::into));
+    .and_then(|_| {
+        // Coprocesses only terminate once their input is closed
+        shell.close_coprocs();
+        shell.wait_for_background().map_err(Into::into)
+    });
+    let err = match err {
+        // The exit handler printed the profile, terminate the background jobs as `exit` does
+        Err(IonError::Exit(_)) => {
+            let _ = shell.background_send(Signal::SIGTERM);
+            Ok(())
+        }
+        err => {
+            if let Some(profile) = shell.profile() {
+                eprint!("{}", profile_report(&profile));
+            }
+            err
+        }
+    };
+    shell.run_exit_hook(shell.previous_status());
+    shell.remove_temporary_paths();
     if let Err(IonError::PipelineExecutionError(PipelineError::Interrupted(_, signal))) = err {
         // When the job was aborted because of an interrupt signal, abort with this same signal
         let action = SigAction::new(SigHandler::SigDfl, SaFlags::empty(), SigSet::empty());